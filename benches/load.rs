@@ -0,0 +1,45 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use indexa::database::{Database, DatabaseBuilder, LoadOptions, StatusKind};
+use std::fs;
+
+const NUM_DIRS: usize = 100;
+const NUM_FILES_PER_DIR: usize = 1000;
+
+fn load(c: &mut Criterion) {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let root = tmpdir.path().join("root");
+    for i in 0..NUM_DIRS {
+        let dir = root.join(format!("dir{:03}", i));
+        fs::create_dir_all(&dir).unwrap();
+        for j in 0..NUM_FILES_PER_DIR {
+            fs::write(dir.join(format!("file{:03}_{:04}.txt", i, j)), "").unwrap();
+        }
+    }
+
+    let location = tmpdir.path().join("database.db");
+    DatabaseBuilder::new()
+        .add_dir(&root)
+        .index(StatusKind::Size)
+        .index(StatusKind::Modified)
+        .index(StatusKind::Accessed)
+        .fast_sort(StatusKind::Path)
+        .fast_sort(StatusKind::Size)
+        .fast_sort(StatusKind::Modified)
+        .build()
+        .unwrap()
+        .save(&location)
+        .unwrap();
+
+    let options = LoadOptions::new();
+    let mut group = c.benchmark_group("load");
+    group.bench_function("sequential", |b| {
+        b.iter(|| Database::open_with(&location, &options).unwrap())
+    });
+    group.bench_function("parallel", |b| {
+        b.iter(|| Database::open_parallel(&location, &options).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, load);
+criterion_main!(benches);
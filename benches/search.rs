@@ -0,0 +1,67 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use indexa::{
+    database::{Database, DatabaseBuilder},
+    query::QueryBuilder,
+};
+use std::{fs, path::Path};
+
+const NUM_DIRS: usize = 100;
+const NUM_FILES_PER_DIR: usize = 1000;
+
+fn create_files(root: &Path) {
+    for i in 0..NUM_DIRS {
+        let dir = root.join(format!("dir{:03}", i));
+        fs::create_dir(&dir).unwrap();
+        for j in 0..NUM_FILES_PER_DIR {
+            fs::write(dir.join(format!("file{:03}_{:04}.txt", i, j)), "").unwrap();
+        }
+    }
+}
+
+fn build(root: &Path, basename_bloom: bool) -> Database {
+    DatabaseBuilder::new()
+        .add_dir(root)
+        .basename_bloom(basename_bloom)
+        .build()
+        .unwrap()
+}
+
+fn basename_bloom(c: &mut Criterion) {
+    let tmpdir = tempfile::tempdir().unwrap();
+    create_files(tmpdir.path());
+    let databases = [
+        ("without bloom", build(tmpdir.path(), false)),
+        ("with bloom", build(tmpdir.path(), true)),
+    ];
+
+    let mut group = c.benchmark_group("basename_bloom");
+    for pattern in ["file042_", "0999.txt", "nonexistent"] {
+        let query = QueryBuilder::new(pattern).build().unwrap();
+        for (name, database) in &databases {
+            group.bench_with_input(BenchmarkId::new(*name, pattern), &query, |b, query| {
+                b.iter(|| database.search_all(query).unwrap())
+            });
+        }
+    }
+    group.finish();
+}
+
+/// Visits every entry without bloom filters, which makes the cost of
+/// checking for aborts and deadlines while filtering show up.
+fn filter(c: &mut Criterion) {
+    let tmpdir = tempfile::tempdir().unwrap();
+    create_files(tmpdir.path());
+    let database = build(tmpdir.path(), false);
+
+    let mut group = c.benchmark_group("filter");
+    for pattern in ["nonexistent", "_0999.txt"] {
+        let query = QueryBuilder::new(pattern).build().unwrap();
+        group.bench_with_input(BenchmarkId::from_parameter(pattern), &query, |b, query| {
+            b.iter(|| database.search_all(query).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, basename_bloom, filter);
+criterion_main!(benches);
@@ -1,30 +1,69 @@
-use crate::Opt;
+use crate::{
+    size_spec::SizeRange,
+    tui::key_bindings::{self, Action, KeyBinding, KeySpecs},
+    Opt,
+};
 
 use indexa::{
-    database::StatusKind,
-    query::{CaseSensitivity, MatchPathMode, SortOrder},
+    database::{DatabaseBuilder, StatusKind},
+    query::{CaseSensitivity, MatchPathMode, QueryBuilder, SortOrder},
 };
 
 use anyhow::{anyhow, Context, Result};
+use camino::Utf8Path;
 use itertools::Itertools;
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::{
     borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    fmt,
     fs::{self, File},
     io::{BufWriter, Write},
     path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
+use tui::{
+    layout::Alignment,
+    style::{Color, Modifier, Style},
 };
-use tui::style::Color;
 
-#[derive(Debug, Default, PartialEq, Deserialize)]
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
 #[serde(default, deny_unknown_fields)]
 pub struct Config {
     pub flags: FlagConfig,
     pub database: DatabaseConfig,
     pub ui: UIConfig,
+    pub history: HistoryConfig,
+    pub saved_searches: SavedSearchesConfig,
+    #[serde(
+        serialize_with = "serialize_key_bindings",
+        deserialize_with = "deserialize_key_bindings"
+    )]
+    pub keybindings: HashMap<Action, Vec<KeyBinding>>,
+}
+
+impl Config {
+    /// Returns the config in the format of the config file.
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+}
+
+#[cfg(test)]
+impl Config {
+    /// Returns the default config without history and saved searches,
+    /// so that tests do not read files of the user running them.
+    pub fn for_test() -> Self {
+        let mut config = Self::default();
+        config.history.location = None;
+        config.history.size = 0;
+        config.saved_searches.location = None;
+        config
+    }
 }
 
-#[derive(Debug, PartialEq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(default, deny_unknown_fields)]
 pub struct FlagConfig {
     pub query: Option<String>,
@@ -32,7 +71,32 @@ pub struct FlagConfig {
     pub ignore_case: bool,
     pub match_path: MatchPathMode,
     pub regex: bool,
+    pub invert_match: bool,
+    pub cd: bool,
     pub threads: usize,
+    pub watch: bool,
+    pub print0: bool,
+    pub print_long: bool,
+    pub no_color: bool,
+    /// bounds of statuses given on the command line, which are not
+    /// configurable since times are mostly relative to the current time
+    #[serde(skip)]
+    pub size: SizeRange,
+    #[serde(skip)]
+    pub modified_after: Option<SystemTime>,
+    #[serde(skip)]
+    pub modified_before: Option<SystemTime>,
+    #[serde(skip)]
+    pub created_after: Option<SystemTime>,
+    #[serde(skip)]
+    pub created_before: Option<SystemTime>,
+    #[serde(skip)]
+    pub accessed_after: Option<SystemTime>,
+    #[serde(skip)]
+    pub accessed_before: Option<SystemTime>,
+    /// directory which printed paths are made relative to
+    #[serde(skip)]
+    pub base: Option<PathBuf>,
 }
 
 impl Default for FlagConfig {
@@ -43,7 +107,21 @@ impl Default for FlagConfig {
             ignore_case: false,
             match_path: MatchPathMode::Never,
             regex: false,
+            invert_match: false,
+            cd: false,
             threads: (num_cpus::get() - 1).max(1),
+            watch: false,
+            print0: false,
+            print_long: false,
+            no_color: false,
+            size: SizeRange::default(),
+            modified_after: None,
+            modified_before: None,
+            created_after: None,
+            created_before: None,
+            accessed_after: None,
+            accessed_before: None,
+            base: None,
         }
     }
 }
@@ -71,10 +149,120 @@ impl FlagConfig {
         }
 
         self.regex |= opt.regex;
+        self.invert_match |= opt.invert_match;
+        self.cd |= opt.cd;
+        self.watch |= opt.watch;
+        self.print0 |= opt.print0;
+        self.print_long |= opt.print_long;
+        self.no_color |= opt.no_color;
 
         if let Some(threads) = opt.threads {
             self.threads = threads.min(num_cpus::get() - 1).max(1);
         }
+
+        if let Some(size) = opt.size {
+            self.size = size;
+        }
+
+        // the later of --*-within and --*-after is in effect
+        let now = SystemTime::now();
+        let after = |within: Option<Duration>, after: Option<SystemTime>| {
+            within
+                .and_then(|within| now.checked_sub(within))
+                .into_iter()
+                .chain(after)
+                .max()
+        };
+        self.modified_after = after(opt.modified_within, opt.modified_after);
+        self.modified_before = opt.modified_before;
+        self.created_after = after(opt.created_within, opt.created_after);
+        self.created_before = opt.created_before;
+        self.accessed_after = after(opt.accessed_within, opt.accessed_after);
+        self.accessed_before = opt.accessed_before;
+
+        // paths in databases are canonicalized
+        self.base = opt
+            .base
+            .as_ref()
+            .map(|base| dunce::canonicalize(base).unwrap_or_else(|_| base.clone()));
+    }
+
+    /// Returns the statuses bounded on the command line.
+    pub fn restricted_statuses(&self) -> impl Iterator<Item = StatusKind> {
+        let bounded = vec![
+            (
+                StatusKind::Size,
+                self.size.min.is_some() || self.size.max.is_some(),
+            ),
+            (
+                StatusKind::Modified,
+                self.modified_after.is_some() || self.modified_before.is_some(),
+            ),
+            (
+                StatusKind::Created,
+                self.created_after.is_some() || self.created_before.is_some(),
+            ),
+            (
+                StatusKind::Accessed,
+                self.accessed_after.is_some() || self.accessed_before.is_some(),
+            ),
+        ];
+        bounded
+            .into_iter()
+            .filter(|(_, bounded)| *bounded)
+            .map(|(kind, _)| kind)
+    }
+
+    /// Restricts sizes and times of entries matching the query to the bounds
+    /// given on the command line.
+    pub fn restrict_statuses(&self, builder: &mut QueryBuilder) {
+        if let Some(bytes) = self.size.min {
+            builder.size_min(bytes);
+        }
+        if let Some(bytes) = self.size.max {
+            builder.size_max(bytes);
+        }
+        if let Some(time) = self.modified_after {
+            builder.modified_after(time);
+        }
+        if let Some(time) = self.modified_before {
+            builder.modified_before(time);
+        }
+        if let Some(time) = self.created_after {
+            builder.created_after(time);
+        }
+        if let Some(time) = self.created_before {
+            builder.created_before(time);
+        }
+        if let Some(time) = self.accessed_after {
+            builder.accessed_after(time);
+        }
+        if let Some(time) = self.accessed_before {
+            builder.accessed_before(time);
+        }
+    }
+
+    /// Returns the path to print for `path`, which is relative to `--base`
+    /// if `path` is under it, and `path` itself otherwise.
+    pub fn printed_path<'a>(&self, path: &'a Utf8Path) -> &'a str {
+        let base = match &self.base {
+            Some(base) => base,
+            None => return path.as_str(),
+        };
+        match path.strip_prefix(base) {
+            Ok(relative) if relative.as_str().is_empty() => ".",
+            Ok(relative) => relative.as_str(),
+            Err(_) => path.as_str(),
+        }
+    }
+
+    /// Separator written after each path printed.
+    pub fn separator(&self) -> char {
+        if self.print0 {
+            '\0'
+        } else {
+            '\n'
+        }
     }
 
     pub fn case_sensitivity(&self) -> CaseSensitivity {
@@ -88,14 +276,44 @@ impl FlagConfig {
     }
 }
 
-#[derive(Debug, PartialEq, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 #[serde(default, deny_unknown_fields)]
 pub struct DatabaseConfig {
     pub location: Option<PathBuf>,
+    pub extra_locations: Vec<PathBuf>,
     pub index: Vec<StatusKind>,
     pub fast_sort: Vec<StatusKind>,
     pub dirs: Vec<PathBuf>,
     pub ignore_hidden: bool,
+    pub respect_ignore_files: bool,
+    pub basename_bloom: bool,
+    pub case_fold_index: bool,
+    pub recursive_dir_size: bool,
+    pub lossy_paths: bool,
+    pub record_metadata: bool,
+    pub max_age_days: Option<u64>,
+    pub auto_update: bool,
+}
+
+impl DatabaseConfig {
+    /// Returns a builder with options in config, but without root directories.
+    pub fn database_builder(&self) -> DatabaseBuilder {
+        let mut builder = DatabaseBuilder::new();
+        builder.ignore_hidden(self.ignore_hidden);
+        builder.respect_ignore_files(self.respect_ignore_files);
+        builder.basename_bloom(self.basename_bloom);
+        builder.case_fold_index(self.case_fold_index);
+        builder.recursive_dir_size(self.recursive_dir_size);
+        builder.lossy_paths(self.lossy_paths);
+        builder.record_metadata(self.record_metadata);
+        for kind in &self.index {
+            builder.index(*kind);
+        }
+        for kind in &self.fast_sort {
+            builder.fast_sort(*kind);
+        }
+        builder
+    }
 }
 
 impl Default for DatabaseConfig {
@@ -115,26 +333,50 @@ impl Default for DatabaseConfig {
 
         Self {
             location,
+            extra_locations: Vec::new(),
             index: Vec::new(),
             fast_sort: Vec::new(),
             dirs,
             ignore_hidden: false,
+            respect_ignore_files: false,
+            basename_bloom: false,
+            case_fold_index: false,
+            recursive_dir_size: false,
+            lossy_paths: false,
+            record_metadata: true,
+            max_age_days: None,
+            auto_update: false,
         }
     }
 }
 
-#[derive(Debug, PartialEq, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 #[serde(default, deny_unknown_fields)]
 pub struct UIConfig {
     pub sort_by: StatusKind,
     pub sort_order: SortOrder,
     pub sort_dirs_before_files: bool,
+    pub only_fast_sort: bool,
+    pub empty_query_limit: Option<usize>,
     pub human_readable_size: bool,
+    pub extension_fallback: bool,
+    pub relative_paths: bool,
+    pub dim_hidden: bool,
+    pub on_missing: OnMissing,
     pub datetime_format: String,
+    pub prompt: String,
+    pub status_format: String,
+    pub reveal_command: String,
+    pub action_command: Option<String>,
     pub column_spacing: u16,
+    #[serde(deserialize_with = "deserialize_header_gap")]
+    pub header_gap: u16,
+    pub min_width: u16,
+    pub min_height: u16,
     pub columns: Vec<Column>,
     pub unix: UIConfigUnix,
     pub windows: UIConfigWindows,
+    pub icons: IconConfig,
     pub colors: ColorConfig,
 }
 
@@ -144,35 +386,32 @@ impl Default for UIConfig {
             sort_by: StatusKind::Basename,
             sort_order: SortOrder::Ascending,
             sort_dirs_before_files: false,
+            only_fast_sort: false,
+            empty_query_limit: None,
             human_readable_size: true,
+            extension_fallback: false,
+            relative_paths: false,
+            dim_hidden: false,
+            on_missing: OnMissing::Ignore,
             datetime_format: "%Y-%m-%d %R".to_string(),
+            prompt: "> ".to_string(),
+            status_format: "{matched} / {total}".to_string(),
+            reveal_command: "xdg-open {parent}".to_string(),
+            action_command: None,
             column_spacing: 2,
-            columns: vec![
-                Column {
-                    status: StatusKind::Basename,
-                    width: None,
-                },
-                Column {
-                    status: StatusKind::Size,
-                    width: Some(10),
-                },
-                Column {
-                    status: StatusKind::Modified,
-                    width: Some(16),
-                },
-                Column {
-                    status: StatusKind::Path,
-                    width: None,
-                },
-            ],
+            header_gap: 1,
+            min_width: 10,
+            min_height: 3,
+            columns: default_columns(),
             unix: Default::default(),
             windows: Default::default(),
+            icons: Default::default(),
             colors: Default::default(),
         }
     }
 }
 
-#[derive(Debug, PartialEq, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 #[serde(default, deny_unknown_fields)]
 pub struct UIConfigUnix {
     pub mode_format: ModeFormatUnix,
@@ -186,7 +425,7 @@ impl Default for UIConfigUnix {
     }
 }
 
-#[derive(Debug, PartialEq, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 #[serde(default, deny_unknown_fields)]
 pub struct UIConfigWindows {
     pub mode_format: ModeFormatWindows,
@@ -200,23 +439,108 @@ impl Default for UIConfigWindows {
     }
 }
 
-#[derive(Debug, PartialEq, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IconConfig {
+    pub enabled: bool,
+    pub directory: String,
+    pub symlink: String,
+    pub file: String,
+    #[serde(serialize_with = "serialize_sorted")]
+    pub extensions: HashMap<String, String>,
+}
+
+impl Default for IconConfig {
+    fn default() -> Self {
+        let extensions = [
+            ("7z", "📦"),
+            ("gz", "📦"),
+            ("tar", "📦"),
+            ("zip", "📦"),
+            ("flac", "🎵"),
+            ("mp3", "🎵"),
+            ("wav", "🎵"),
+            ("gif", "🎨"),
+            ("jpeg", "🎨"),
+            ("jpg", "🎨"),
+            ("png", "🎨"),
+            ("svg", "🎨"),
+            ("mkv", "🎬"),
+            ("mp4", "🎬"),
+            ("md", "📝"),
+            ("txt", "📝"),
+            ("pdf", "📕"),
+            ("rs", "🦀"),
+        ]
+        .iter()
+        .map(|(ext, icon)| (ext.to_string(), icon.to_string()))
+        .collect();
+
+        Self {
+            enabled: false,
+            directory: "📁".to_string(),
+            symlink: "🔗".to_string(),
+            file: "📄".to_string(),
+            extensions,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 #[serde(default, deny_unknown_fields)]
 pub struct ColorConfig {
-    #[serde(deserialize_with = "deserialize_color")]
+    #[serde(
+        serialize_with = "serialize_color",
+        deserialize_with = "deserialize_color"
+    )]
     pub selected_fg: Color,
-    #[serde(deserialize_with = "deserialize_color")]
+    #[serde(
+        serialize_with = "serialize_color",
+        deserialize_with = "deserialize_color"
+    )]
     pub selected_bg: Color,
-    #[serde(deserialize_with = "deserialize_color")]
+    #[serde(
+        serialize_with = "serialize_color",
+        deserialize_with = "deserialize_color"
+    )]
     pub matched_fg: Color,
-    #[serde(deserialize_with = "deserialize_color")]
+    #[serde(
+        serialize_with = "serialize_color",
+        deserialize_with = "deserialize_color"
+    )]
     pub matched_bg: Color,
-    #[serde(deserialize_with = "deserialize_color")]
+    pub match_style: MatchStyle,
+    #[serde(
+        serialize_with = "serialize_color",
+        deserialize_with = "deserialize_color"
+    )]
+    pub component_fg: Color,
+    pub component_style: ComponentStyle,
+    #[serde(
+        serialize_with = "serialize_color",
+        deserialize_with = "deserialize_color"
+    )]
     pub error_fg: Color,
-    #[serde(deserialize_with = "deserialize_color")]
+    #[serde(
+        serialize_with = "serialize_color",
+        deserialize_with = "deserialize_color"
+    )]
     pub error_bg: Color,
-    #[serde(deserialize_with = "deserialize_color")]
+    #[serde(
+        serialize_with = "serialize_color",
+        deserialize_with = "deserialize_color"
+    )]
     pub prompt: Color,
+    #[serde(
+        serialize_with = "serialize_color",
+        deserialize_with = "deserialize_color"
+    )]
+    pub alt_row_bg: Color,
+    #[serde(
+        serialize_with = "serialize_color",
+        deserialize_with = "deserialize_color"
+    )]
+    pub hidden_fg: Color,
 }
 
 impl Default for ColorConfig {
@@ -226,27 +550,252 @@ impl Default for ColorConfig {
             selected_bg: Color::Reset,
             matched_fg: Color::Black,
             matched_bg: Color::LightBlue,
+            match_style: MatchStyle::Bg,
+            component_fg: Color::LightBlue,
+            component_style: ComponentStyle::None,
             error_fg: Color::Red,
             error_bg: Color::Reset,
             prompt: Color::LightBlue,
+            alt_row_bg: Color::Reset,
+            hidden_fg: Color::DarkGray,
+        }
+    }
+}
+
+impl ColorConfig {
+    /// Style of matched substrings.
+    pub fn highlight_style(&self) -> Style {
+        match self.match_style {
+            MatchStyle::Bg => Style::default().fg(self.matched_fg).bg(self.matched_bg),
+            MatchStyle::Reverse => Style::default().add_modifier(Modifier::REVERSED),
+            MatchStyle::Underline => Style::default().add_modifier(Modifier::UNDERLINED),
+            MatchStyle::Bold => Style::default().add_modifier(Modifier::BOLD),
+        }
+    }
+
+    /// Style of path components containing matches, if they are marked.
+    pub fn component_highlight_style(&self) -> Option<Style> {
+        match self.component_style {
+            ComponentStyle::None => None,
+            ComponentStyle::Fg => Some(Style::default().fg(self.component_fg)),
+            ComponentStyle::Underline => Some(Style::default().add_modifier(Modifier::UNDERLINED)),
+            ComponentStyle::Bold => Some(Style::default().add_modifier(Modifier::BOLD)),
+        }
+    }
+
+    /// Makes matches visible without colors, which the backend drops
+    /// when colors are disabled.
+    pub fn disable(&mut self) {
+        if self.match_style == MatchStyle::Bg {
+            self.match_style = MatchStyle::Reverse;
+        }
+        if self.component_style == ComponentStyle::Fg {
+            self.component_style = ComponentStyle::Underline;
+        }
+    }
+
+    /// Style of unselected hidden entries with `ui.dim_hidden`.
+    pub fn hidden_style(&self) -> Style {
+        Style::default()
+            .fg(self.hidden_fg)
+            .add_modifier(Modifier::DIM)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchStyle {
+    Bg,
+    Reverse,
+    Underline,
+    Bold,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ComponentStyle {
+    None,
+    Fg,
+    Underline,
+    Bold,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    pub location: Option<PathBuf>,
+    pub size: usize,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        let location = get_config_dir().map(|mut path| {
+            path.push("history");
+            path
+        });
+
+        Self {
+            location,
+            size: 100,
         }
     }
 }
 
-#[derive(Debug, PartialEq, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SavedSearchesConfig {
+    pub location: Option<PathBuf>,
+}
+
+impl Default for SavedSearchesConfig {
+    fn default() -> Self {
+        let location = get_config_dir().map(|mut path| {
+            path.push("searches.toml");
+            path
+        });
+
+        Self { location }
+    }
+}
+
+/// Returns columns suited to the platform, since mode means file attributes
+/// on Windows and owners are only available on Unix.
+fn default_columns() -> Vec<Column> {
+    let column = |status, width: Option<u16>| Column {
+        status,
+        width: width.map(ColumnWidth::Fixed),
+        align: None,
+        color: None,
+    };
+
+    let mut columns = vec![
+        column(StatusKind::Basename, None),
+        column(StatusKind::Size, Some(10)),
+    ];
+    if cfg!(windows) {
+        columns.push(column(StatusKind::Mode, Some(7)));
+    } else {
+        columns.push(column(StatusKind::Mode, Some(10)));
+        columns.push(column(StatusKind::Owner, Some(8)));
+    }
+    columns.push(column(StatusKind::Modified, Some(16)));
+    columns.push(column(StatusKind::Path, None));
+    columns
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Column {
     pub status: StatusKind,
-    pub width: Option<u16>,
+    #[serde(
+        default,
+        serialize_with = "serialize_optional_column_width",
+        deserialize_with = "deserialize_optional_column_width"
+    )]
+    pub width: Option<ColumnWidth>,
+    #[serde(default)]
+    pub align: Option<ColumnAlignment>,
+    #[serde(
+        default,
+        serialize_with = "serialize_optional_color",
+        deserialize_with = "deserialize_optional_color"
+    )]
+    pub color: Option<Color>,
+}
+
+/// Width of a column, written as a number of cells (`16`), a percentage of
+/// the screen width (`"40%"`), or a ratio of the screen width (`"1/3"`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnWidth {
+    Fixed(u16),
+    Percentage(u16),
+    Ratio(u32, u32),
+}
+
+impl ColumnWidth {
+    /// Returns the number of cells the column takes on a screen of `total` cells.
+    pub fn resolve(&self, total: u16) -> u16 {
+        match *self {
+            Self::Fixed(width) => width,
+            Self::Percentage(p) => (u32::from(total) * u32::from(p) / 100) as u16,
+            Self::Ratio(n, d) => (u64::from(total) * u64::from(n) / u64::from(d)) as u16,
+        }
+    }
 }
 
-#[derive(Debug, PartialEq, Deserialize)]
+impl fmt::Display for ColumnWidth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fixed(width) => write!(f, "{}", width),
+            Self::Percentage(p) => write!(f, "{}%", p),
+            Self::Ratio(n, d) => write!(f, "{}/{}", n, d),
+        }
+    }
+}
+
+impl FromStr for ColumnWidth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let invalid = || format!("Invalid column width: {:?}", s);
+
+        if let Some(p) = s.strip_suffix('%') {
+            let p: u16 = p.trim().parse().map_err(|_| invalid())?;
+            if p > 100 {
+                return Err("Percentages should be between 0 and 100 inclusively.".to_string());
+            }
+            return Ok(Self::Percentage(p));
+        }
+        if let Some((n, d)) = s.split_once('/') {
+            let n: u32 = n.trim().parse().map_err(|_| invalid())?;
+            let d: u32 = d.trim().parse().map_err(|_| invalid())?;
+            if d == 0 || n > d {
+                return Err("Ratios should be between 0 and 1 inclusively.".to_string());
+            }
+            return Ok(Self::Ratio(n, d));
+        }
+        s.parse().map(Self::Fixed).map_err(|_| invalid())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnAlignment {
+    Left,
+    Center,
+    Right,
+}
+
+impl From<ColumnAlignment> for Alignment {
+    fn from(alignment: ColumnAlignment) -> Self {
+        match alignment {
+            ColumnAlignment::Left => Alignment::Left,
+            ColumnAlignment::Center => Alignment::Center,
+            ColumnAlignment::Right => Alignment::Right,
+        }
+    }
+}
+
+/// What to do when the accepted entry no longer exists on the file system.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnMissing {
+    /// Print a warning to stderr instead of the path.
+    Warn,
+    /// Print the first existing entry at or below the selected one.
+    Skip,
+    /// Print the path anyway.
+    Ignore,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum ModeFormatUnix {
     Octal,
     Symbolic,
 }
 
-#[derive(Debug, PartialEq, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum ModeFormatWindows {
     Traditional,
@@ -265,17 +814,8 @@ where
 
     let path = if let Some(path) = config_path.as_ref() {
         Cow::Borrowed(path.as_ref())
-    } else if cfg!(windows) {
-        let config_dir = dirs::config_dir().ok_or_else(|| anyhow!(CONFIG_LOCATION_ERROR_MSG))?;
-        let mut path = config_dir;
-        path.push(env!("CARGO_PKG_NAME"));
-        path.push("config.toml");
-        Cow::Owned(path)
     } else {
-        let home_dir = dirs::home_dir().ok_or_else(|| anyhow!(CONFIG_LOCATION_ERROR_MSG))?;
-        let mut path = home_dir;
-        path.push(".config");
-        path.push(env!("CARGO_PKG_NAME"));
+        let mut path = get_config_dir().ok_or_else(|| anyhow!(CONFIG_LOCATION_ERROR_MSG))?;
         path.push("config.toml");
         Cow::Owned(path)
     };
@@ -298,6 +838,40 @@ where
     }
 }
 
+fn serialize_color<S>(color: &Color, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let name = match color {
+        Color::Reset => "reset",
+        Color::Black => "black",
+        Color::Red => "red",
+        Color::Green => "green",
+        Color::Yellow => "yellow",
+        Color::Blue => "blue",
+        Color::Magenta => "magenta",
+        Color::Cyan => "cyan",
+        Color::Gray => "gray",
+        Color::DarkGray => "darkgray",
+        Color::LightRed => "lightred",
+        Color::LightGreen => "lightgreen",
+        Color::LightYellow => "lightyellow",
+        Color::LightBlue => "lightblue",
+        Color::LightMagenta => "lightmagenta",
+        Color::LightCyan => "lightcyan",
+        Color::White => "white",
+        Color::Rgb(r, g, b) => {
+            return serializer.serialize_str(&format!("#{:02x}{:02x}{:02x}", r, g, b))
+        }
+        Color::Indexed(_) => {
+            return Err(serde::ser::Error::custom(
+                "indexed colors cannot be written in config",
+            ))
+        }
+    };
+    serializer.serialize_str(name)
+}
+
 fn deserialize_color<'de, D>(deserializer: D) -> Result<Color, D::Error>
 where
     D: Deserializer<'de>,
@@ -350,6 +924,115 @@ where
     }
 }
 
+const MAX_HEADER_GAP: u16 = 4;
+
+fn deserialize_header_gap<'de, D>(deserializer: D) -> Result<u16, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let gap = u16::deserialize(deserializer)?;
+    if gap > MAX_HEADER_GAP {
+        return Err(serde::de::Error::invalid_value(
+            serde::de::Unexpected::Unsigned(gap.into()),
+            &format!("header gap of at most {}", MAX_HEADER_GAP).as_str(),
+        ));
+    }
+    Ok(gap)
+}
+
+fn serialize_optional_column_width<S>(
+    width: &Option<ColumnWidth>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match width {
+        Some(ColumnWidth::Fixed(width)) => serializer.serialize_u16(*width),
+        Some(width) => serializer.serialize_str(&width.to_string()),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn deserialize_optional_column_width<'de, D>(
+    deserializer: D,
+) -> Result<Option<ColumnWidth>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Width {
+        Fixed(u16),
+        Str(String),
+    }
+
+    match Width::deserialize(deserializer)? {
+        Width::Fixed(width) => Ok(Some(ColumnWidth::Fixed(width))),
+        Width::Str(s) => s.parse().map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
+fn serialize_key_bindings<S>(
+    key_bindings: &HashMap<Action, Vec<KeyBinding>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    key_bindings::key_bindings_to_specs(key_bindings).serialize(serializer)
+}
+
+fn deserialize_key_bindings<'de, D>(
+    deserializer: D,
+) -> Result<HashMap<Action, Vec<KeyBinding>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let config = HashMap::<String, KeySpecs>::deserialize(deserializer)?;
+    key_bindings::parse_key_bindings(config).map_err(serde::de::Error::custom)
+}
+
+fn serialize_optional_color<S>(color: &Option<Color>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match color {
+        Some(color) => serialize_color(color, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn deserialize_optional_color<'de, D>(deserializer: D) -> Result<Option<Color>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_color(deserializer).map(Some)
+}
+
+/// Serializes a map in the order of keys, so that the output is stable.
+fn serialize_sorted<K, V, S>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    K: Ord + Serialize,
+    V: Serialize,
+    S: Serializer,
+{
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
+
+/// Directory where config file and other user files are placed by default.
+fn get_config_dir() -> Option<PathBuf> {
+    let mut path = if cfg!(windows) {
+        dirs::config_dir()?
+    } else {
+        let mut path = dirs::home_dir()?;
+        path.push(".config");
+        path
+    };
+    path.push(env!("CARGO_PKG_NAME"));
+    Some(path)
+}
+
 #[cfg(windows)]
 fn get_default_root_dir() -> Option<PathBuf> {
     if let Ok(homedrive) = std::env::var("HOMEDRIVE") {
@@ -405,6 +1088,52 @@ mod tests {
         assert_eq!(from_str, written);
     }
 
+    #[test]
+    fn to_toml() {
+        let config = Config::default();
+        let written = config.to_toml().unwrap();
+        assert_eq!(toml::from_str::<Config>(&written).unwrap(), config);
+
+        let config: Config = toml::from_str(
+            r##"
+            [flags]
+            query = "foo"
+            match_path = "auto"
+            no_color = true
+
+            [ui]
+            sort_by = "modified"
+            header_gap = 2
+            columns = [
+                { status = "basename", width = "40%", color = "#ff8000" },
+                { status = "size", width = 10, align = "center" },
+                { status = "path", width = "1/3" },
+            ]
+
+            [ui.colors]
+            selected_fg = "lightred"
+            matched_bg = "10, 20, 30"
+
+            [ui.icons]
+            extensions = { rs = "R", md = "M" }
+
+            [keybindings]
+            accept = ["ctrl-j", "enter"]
+            toggle-help = "f1"
+            scroll-path-left = "shift-+"
+            "##,
+        )
+        .unwrap();
+        let written = config.to_toml().unwrap();
+        assert_eq!(toml::from_str::<Config>(&written).unwrap(), config);
+        assert!(written.contains("width = '40%'"));
+        assert!(written.contains("matched_bg = '#0a141e'"));
+        assert!(written.contains("toggle-help = ['F1']"));
+
+        // output is stable regardless of the order of hash maps
+        assert_eq!(config.to_toml().unwrap(), written);
+    }
+
     #[test]
     #[should_panic(expected = "Invalid config file")]
     fn invalid_config() {
@@ -414,6 +1143,235 @@ mod tests {
         read_or_create_config(Some(file.path())).unwrap();
     }
 
+    #[test]
+    fn match_style() {
+        let highlight_style = |match_style| {
+            ColorConfig {
+                match_style,
+                ..Default::default()
+            }
+            .highlight_style()
+        };
+
+        assert_eq!(
+            highlight_style(MatchStyle::Bg),
+            Style::default().fg(Color::Black).bg(Color::LightBlue)
+        );
+        for (match_style, modifier) in [
+            (MatchStyle::Reverse, Modifier::REVERSED),
+            (MatchStyle::Underline, Modifier::UNDERLINED),
+            (MatchStyle::Bold, Modifier::BOLD),
+        ] {
+            let style = highlight_style(match_style);
+            assert!(style.add_modifier.contains(modifier));
+            assert_eq!(style.bg, None);
+        }
+    }
+
+    #[test]
+    fn default_columns_depend_on_platform() {
+        let statuses = Config::default()
+            .ui
+            .columns
+            .iter()
+            .map(|column| column.status)
+            .collect::<Vec<_>>();
+        let expected = if cfg!(windows) {
+            vec![
+                StatusKind::Basename,
+                StatusKind::Size,
+                StatusKind::Mode,
+                StatusKind::Modified,
+                StatusKind::Path,
+            ]
+        } else {
+            vec![
+                StatusKind::Basename,
+                StatusKind::Size,
+                StatusKind::Mode,
+                StatusKind::Owner,
+                StatusKind::Modified,
+                StatusKind::Path,
+            ]
+        };
+        assert_eq!(statuses, expected);
+
+        // columns are not written in default.toml so that the platform's defaults apply
+        let from_str: Config = toml::from_str(DEFAULT_CONFIG_STRING).unwrap();
+        assert_eq!(from_str.ui.columns, default_columns());
+    }
+
+    #[test]
+    fn column() {
+        let config: Config = toml::from_str(
+            r##"
+            [[ui.columns]]
+            status = "basename"
+
+            [[ui.columns]]
+            status = "modified"
+            width = 16
+            align = "right"
+
+            [[ui.columns]]
+            status = "path"
+            align = "center"
+            color = "#fcba03"
+            "##,
+        )
+        .unwrap();
+        assert_eq!(
+            config.ui.columns,
+            [
+                Column {
+                    status: StatusKind::Basename,
+                    width: None,
+                    align: None,
+                    color: None,
+                },
+                Column {
+                    status: StatusKind::Modified,
+                    width: Some(ColumnWidth::Fixed(16)),
+                    align: Some(ColumnAlignment::Right),
+                    color: None,
+                },
+                Column {
+                    status: StatusKind::Path,
+                    width: None,
+                    align: Some(ColumnAlignment::Center),
+                    color: Some(Color::Rgb(252, 186, 3)),
+                },
+            ]
+        );
+
+        assert!(toml::from_str::<Config>(
+            r#"
+            [[ui.columns]]
+            status = "path"
+            align = "justify"
+            "#,
+        )
+        .is_err());
+        assert!(toml::from_str::<Config>(
+            r#"
+            [[ui.columns]]
+            status = "path"
+            color = "nocolor"
+            "#,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn column_width() {
+        let width = |s: &str| {
+            toml::from_str::<Config>(&format!("[[ui.columns]]\nstatus = \"path\"\nwidth = {}", s))
+                .map(|config| config.ui.columns[0].width)
+        };
+
+        assert_eq!(width("16").unwrap(), Some(ColumnWidth::Fixed(16)));
+        assert_eq!(width("\"16\"").unwrap(), Some(ColumnWidth::Fixed(16)));
+        assert_eq!(width("\"40%\"").unwrap(), Some(ColumnWidth::Percentage(40)));
+        assert_eq!(
+            width("\"100%\"").unwrap(),
+            Some(ColumnWidth::Percentage(100))
+        );
+        assert_eq!(width("\"1/3\"").unwrap(), Some(ColumnWidth::Ratio(1, 3)));
+        assert_eq!(
+            width("\" 2 / 5 \"").unwrap(),
+            Some(ColumnWidth::Ratio(2, 5))
+        );
+
+        for invalid in [
+            "-1", "\"101%\"", "\"1/0\"", "\"3/2\"", "\"x\"", "\"%\"", "1.5",
+        ] {
+            assert!(width(invalid).is_err(), "{}", invalid);
+        }
+
+        assert_eq!(ColumnWidth::Fixed(10).resolve(80), 10);
+        assert_eq!(ColumnWidth::Percentage(25).resolve(80), 20);
+        assert_eq!(ColumnWidth::Ratio(1, 3).resolve(90), 30);
+    }
+
+    #[test]
+    fn prompt_and_status_format() {
+        let config: Config = toml::from_str(
+            r#"
+            [ui]
+            prompt = "🔍 "
+            status_format = "{query}: {matched} of {total}"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.ui.prompt, "🔍 ");
+        assert_eq!(config.ui.status_format, "{query}: {matched} of {total}");
+
+        let config: Config = toml::from_str("[ui]\nprompt = \"\"").unwrap();
+        assert_eq!(config.ui.prompt, "");
+        assert_eq!(config.ui.status_format, "{matched} / {total}");
+    }
+
+    #[test]
+    fn header_gap() {
+        let config: Config = toml::from_str("[ui]\nheader_gap = 0").unwrap();
+        assert_eq!(config.ui.header_gap, 0);
+        let config: Config = toml::from_str("[ui]\nheader_gap = 2").unwrap();
+        assert_eq!(config.ui.header_gap, 2);
+
+        assert!(toml::from_str::<Config>("[ui]\nheader_gap = 100").is_err());
+        assert!(toml::from_str::<Config>("[ui]\nheader_gap = -1").is_err());
+    }
+
+    #[test]
+    fn keybindings() {
+        let config: Config = toml::from_str(
+            r#"
+            [keybindings]
+            down = ["down", "ctrl-j"]
+            page-up = "alt-v"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.keybindings.len(), 2);
+        assert_eq!(config.keybindings[&Action::Down][1].to_string(), "Ctrl+J");
+        assert_eq!(config.keybindings[&Action::PageUp][0].to_string(), "Alt+V");
+
+        let err = toml::from_str::<Config>("[keybindings]\nfoo = \"ctrl-f\"").unwrap_err();
+        assert!(err.to_string().contains("unknown variant `foo`"));
+
+        let err = toml::from_str::<Config>("[keybindings]\nup = \"ctrl-foo\"").unwrap_err();
+        assert!(err.to_string().contains("Unknown key"));
+    }
+
+    #[test]
+    fn printed_path() {
+        let root = if cfg!(windows) { r"C:\" } else { "/" };
+        let base = Path::new(root).join("project");
+        let path = |s: &str| Utf8Path::new(root).join(s);
+
+        let flags = FlagConfig::default();
+        assert_eq!(
+            flags.printed_path(&path("project/src")),
+            path("project/src")
+        );
+
+        let flags = FlagConfig {
+            base: Some(base),
+            ..Default::default()
+        };
+        let relative = Utf8Path::new("src").join("main.rs");
+        assert_eq!(
+            flags.printed_path(&path("project").join(&relative)),
+            relative
+        );
+        assert_eq!(flags.printed_path(&path("project")), ".");
+        assert_eq!(flags.printed_path(&path("other/src")), path("other/src"));
+        assert_eq!(
+            flags.printed_path(&path("project2/src")),
+            path("project2/src")
+        );
+    }
+
     #[test]
     fn color() {
         use serde::de::IntoDeserializer;
@@ -0,0 +1,137 @@
+use crate::{
+    config::{Config, FlagConfig},
+    searcher::{self, Hit, SearchOptions},
+};
+
+use indexa::{
+    database::{Database, LoadOptions},
+    query::Query,
+};
+
+use anyhow::{Context, Result};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::{atomic::AtomicBool, Arc},
+};
+
+/// Writes paths of all entries matching the query given in config to `path`,
+/// one per line, returning the number of paths written.
+///
+/// If `path` has the extension `.m3u`, the list starts with the header of
+/// an M3U playlist.
+pub fn export(config: &Config, path: &Path) -> Result<usize> {
+    let pattern = config.flags.query.as_deref().unwrap_or("");
+    let query = SearchOptions::new(config)
+        .query_builder(config, pattern)
+        .build()?;
+    let databases = load_databases(config)?;
+    export_hits(&databases, &query, &config.flags, path)
+}
+
+/// Loads the databases given in config, skipping statuses not needed by
+/// queries built with `SearchOptions::new(config)`.
+pub fn load_databases(config: &Config) -> Result<Vec<Arc<Database>>> {
+    let mut options = LoadOptions::new();
+    options.only(std::iter::once(config.ui.sort_by).chain(config.flags.restricted_statuses()));
+    config
+        .database
+        .location
+        .iter()
+        .chain(&config.database.extra_locations)
+        .map(|location| {
+            Database::open_with(location, &options)
+                .map(Arc::new)
+                .with_context(|| format!("Failed to load database {}", location.display()))
+        })
+        .collect()
+}
+
+fn export_hits(
+    databases: &[Arc<Database>],
+    query: &Query,
+    flags: &FlagConfig,
+    path: &Path,
+) -> Result<usize> {
+    let abort_signal = Arc::new(AtomicBool::new(false));
+    let hits = databases
+        .iter()
+        .map(|database| database.search(query, &abort_signal))
+        .collect::<indexa::Result<Vec<_>>>()?;
+    let hits = searcher::merge_hits(databases, hits, query);
+
+    let is_m3u = path
+        .extension()
+        .map(|ext| ext.eq_ignore_ascii_case("m3u"))
+        .unwrap_or(false);
+    let mut writer = BufWriter::new(
+        File::create(path).with_context(|| format!("Failed to create {}", path.display()))?,
+    );
+    if is_m3u {
+        writeln!(writer, "#EXTM3U")?;
+    }
+    for Hit { database, id } in &hits {
+        let path = databases[*database].entry(*id).path();
+        writeln!(writer, "{}", flags.printed_path(&path))?;
+    }
+    writer.flush()?;
+
+    Ok(hits.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indexa::{database::DatabaseBuilder, query::QueryBuilder};
+    use std::fs;
+
+    #[test]
+    fn export_hits() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let tmpdir = dunce::canonicalize(tmpdir.path()).unwrap();
+        let root = tmpdir.join("music");
+        fs::create_dir(&root).unwrap();
+        for name in ["b.mp3", "a.mp3", "cover.jpg", "c.MP3"] {
+            fs::write(root.join(name), "").unwrap();
+        }
+        let database = DatabaseBuilder::new().add_dir(&root).build().unwrap();
+        let databases = [Arc::new(database)];
+        let query = QueryBuilder::new(".mp3").build().unwrap();
+
+        let expected = ["a.mp3", "b.mp3", "c.MP3"]
+            .iter()
+            .map(|name| format!("{}\n", root.join(name).display()))
+            .collect::<String>();
+
+        let list = tmpdir.join("list.txt");
+        let flags = FlagConfig::default();
+        assert_eq!(
+            super::export_hits(&databases, &query, &flags, &list).unwrap(),
+            3
+        );
+        assert_eq!(fs::read_to_string(&list).unwrap(), expected);
+
+        let playlist = tmpdir.join("list.M3U");
+        assert_eq!(
+            super::export_hits(&databases, &query, &flags, &playlist).unwrap(),
+            3
+        );
+        assert_eq!(
+            fs::read_to_string(&playlist).unwrap(),
+            format!("#EXTM3U\n{}", expected)
+        );
+
+        // paths relative to the base, e.g. for a playlist next to the files
+        let flags = FlagConfig {
+            base: Some(root.clone()),
+            ..Default::default()
+        };
+        let playlist = root.join("list.m3u");
+        super::export_hits(&databases, &query, &flags, &playlist).unwrap();
+        assert_eq!(
+            fs::read_to_string(&playlist).unwrap(),
+            "#EXTM3U\na.mp3\nb.mp3\nc.MP3\n"
+        );
+    }
+}
@@ -0,0 +1,184 @@
+use anyhow::Result;
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// A capped list of accepted queries, oldest first.
+pub struct History {
+    location: Option<PathBuf>,
+    capacity: usize,
+    entries: VecDeque<String>,
+
+    // index of an entry being recalled
+    cursor: Option<usize>,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            location: None,
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+            cursor: None,
+        }
+    }
+
+    /// Loads history from a file. A nonexistent file is treated as empty history.
+    pub fn load<P>(location: P, capacity: usize) -> Result<Self>
+    where
+        P: Into<PathBuf>,
+    {
+        let mut history = Self::new(capacity);
+        let location = location.into();
+
+        match fs::read_to_string(&location) {
+            Ok(content) => {
+                for line in content.lines() {
+                    history.push(line);
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+            Err(err) => return Err(err.into()),
+        }
+
+        history.location = Some(location);
+        Ok(history)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(location) = &self.location {
+            write_lines(location, &self.entries)?;
+        }
+        Ok(())
+    }
+
+    /// Appends a query, discarding the oldest one if the capacity is exceeded.
+    ///
+    /// Empty queries and queries identical to the newest entry are ignored.
+    pub fn push<S>(&mut self, query: S)
+    where
+        S: Into<String>,
+    {
+        self.cursor = None;
+
+        let query = query.into();
+        if self.capacity == 0 || query.is_empty() || self.entries.back() == Some(&query) {
+            return;
+        }
+
+        while self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(query);
+    }
+
+    /// Returns the entry currently recalled, if any.
+    pub fn current(&self) -> Option<&str> {
+        self.cursor.map(|i| self.entries[i].as_str())
+    }
+
+    /// Stops recalling, so that the next `prev()` starts from the newest entry.
+    pub fn reset(&mut self) {
+        self.cursor = None;
+    }
+
+    /// Recalls an older entry.
+    pub fn prev(&mut self) -> Option<&str> {
+        let i = match self.cursor {
+            None => self.entries.len().checked_sub(1)?,
+            Some(i) => i.saturating_sub(1),
+        };
+        self.cursor = Some(i);
+        self.current()
+    }
+
+    /// Recalls a newer entry.
+    ///
+    /// Returns an empty string when moving past the newest entry.
+    pub fn next(&mut self) -> Option<&str> {
+        let i = self.cursor?;
+        if i + 1 < self.entries.len() {
+            self.cursor = Some(i + 1);
+            self.current()
+        } else {
+            self.cursor = None;
+            Some("")
+        }
+    }
+}
+
+fn write_lines<'a, I>(path: &Path, lines: I) -> Result<()>
+where
+    I: IntoIterator<Item = &'a String>,
+{
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    for line in lines {
+        writeln!(writer, "{}", line)?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_buffer() {
+        let mut history = History::new(3);
+        assert_eq!(history.prev(), None);
+        assert_eq!(history.next(), None);
+
+        history.push("a");
+        history.push("b");
+        history.push("b");
+        history.push("");
+        history.push("c");
+        history.push("d");
+        assert_eq!(history.entries, ["b", "c", "d"]);
+
+        assert_eq!(history.prev(), Some("d"));
+        assert_eq!(history.prev(), Some("c"));
+        assert_eq!(history.prev(), Some("b"));
+        assert_eq!(history.prev(), Some("b"));
+        assert_eq!(history.next(), Some("c"));
+        assert_eq!(history.next(), Some("d"));
+        assert_eq!(history.next(), Some(""));
+        assert_eq!(history.next(), None);
+
+        history.prev();
+        history.reset();
+        assert_eq!(history.prev(), Some("d"));
+
+        let mut disabled = History::new(0);
+        disabled.push("a");
+        assert_eq!(disabled.prev(), None);
+    }
+
+    #[test]
+    fn load_and_save() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let location = tmpdir.path().join("dir").join("history");
+
+        let mut history = History::load(&location, 2).unwrap();
+        assert_eq!(history.prev(), None);
+
+        history.push("foo");
+        history.push("bar");
+        history.push("baz");
+        history.save().unwrap();
+
+        let mut history = History::load(&location, 2).unwrap();
+        assert_eq!(history.prev(), Some("baz"));
+        assert_eq!(history.prev(), Some("bar"));
+        assert_eq!(history.prev(), Some("bar"));
+    }
+}
@@ -1,18 +1,31 @@
 mod config;
+mod export;
+mod history;
+mod saved_searches;
 mod searcher;
+mod server;
+mod size_spec;
+mod stats;
+mod time_spec;
+mod timing;
 mod tui;
+mod watcher;
 
-use crate::config::DatabaseConfig;
-use indexa::{database::DatabaseBuilder, query::MatchPathMode};
+use crate::{config::DatabaseConfig, size_spec::SizeRange, stats::Stats};
+use indexa::{
+    database::{Database, Warning},
+    query::MatchPathMode,
+};
 
 use anyhow::{anyhow, Error, Result};
 use dialoguer::Confirm;
 use rayon::ThreadPoolBuilder;
 use std::{
-    fs::File,
-    io::{BufWriter, Write},
-    path::PathBuf,
+    ffi::OsString,
+    io,
+    path::{Path, PathBuf},
     str::FromStr,
+    time::{Duration, SystemTime},
 };
 use structopt::{clap::AppSettings, StructOpt};
 
@@ -77,10 +90,129 @@ pub struct Opt {
     #[structopt(short, long)]
     regex: bool,
 
+    /// Show entries which do not match the query.
+    #[structopt(short = "v", long)]
+    invert_match: bool,
+
+    /// Interpret the query also as a path and show the directory at the path first.
+    #[structopt(long)]
+    cd: bool,
+
+    /// Show only files whose sizes are in <range>.
+    ///
+    /// <range> can be '+10M' (at least), '-1G' (at most), or '500K..2M' (between).
+    /// K, M, G, and T are powers of 1000, and Ki, Mi, Gi, and Ti are powers of 1024.
+    #[structopt(long, value_name = "range", allow_hyphen_values = true)]
+    size: Option<SizeRange>,
+
+    /// Show only entries modified within <duration> (e.g. '30m', '7d', or '1w 2d').
+    #[structopt(long, value_name = "duration", parse(try_from_str = time_spec::parse_duration))]
+    modified_within: Option<Duration>,
+
+    /// Show only entries modified at or after <time>.
+    ///
+    /// <time> can be a date (e.g. '2024-01-31'), a date-time (e.g. '2024-01-31 12:00'),
+    /// 'today', 'yesterday', or a duration meaning that long ago (e.g. '7d').
+    #[structopt(long, value_name = "time", parse(try_from_str = time_spec::parse_time_from_now))]
+    modified_after: Option<SystemTime>,
+
+    /// Show only entries modified before <time>.
+    #[structopt(long, value_name = "time", parse(try_from_str = time_spec::parse_time_from_now))]
+    modified_before: Option<SystemTime>,
+
+    /// Show only entries created within <duration> (e.g. '30m', '7d', or '1w 2d').
+    #[structopt(long, value_name = "duration", parse(try_from_str = time_spec::parse_duration))]
+    created_within: Option<Duration>,
+
+    /// Show only entries created at or after <time>.
+    ///
+    /// <time> can be a date (e.g. '2024-01-31'), a date-time (e.g. '2024-01-31 12:00'),
+    /// 'today', 'yesterday', or a duration meaning that long ago (e.g. '7d').
+    #[structopt(long, value_name = "time", parse(try_from_str = time_spec::parse_time_from_now))]
+    created_after: Option<SystemTime>,
+
+    /// Show only entries created before <time>.
+    #[structopt(long, value_name = "time", parse(try_from_str = time_spec::parse_time_from_now))]
+    created_before: Option<SystemTime>,
+
+    /// Show only entries accessed within <duration> (e.g. '30m', '7d', or '1w 2d').
+    #[structopt(long, value_name = "duration", parse(try_from_str = time_spec::parse_duration))]
+    accessed_within: Option<Duration>,
+
+    /// Show only entries accessed at or after <time>.
+    ///
+    /// <time> can be a date (e.g. '2024-01-31'), a date-time (e.g. '2024-01-31 12:00'),
+    /// 'today', 'yesterday', or a duration meaning that long ago (e.g. '7d').
+    #[structopt(long, value_name = "time", parse(try_from_str = time_spec::parse_time_from_now))]
+    accessed_after: Option<SystemTime>,
+
+    /// Show only entries accessed before <time>.
+    #[structopt(long, value_name = "time", parse(try_from_str = time_spec::parse_time_from_now))]
+    accessed_before: Option<SystemTime>,
+
     /// Update database and exit.
     #[structopt(short, long)]
     update: bool,
 
+    /// Print statistics of the database and exit.
+    #[structopt(long)]
+    stats: bool,
+
+    /// Write paths of all entries matching the query to a file and exit.
+    ///
+    /// If the file has the extension .m3u, it is written as a playlist.
+    #[structopt(long, name = "file")]
+    export_to: Option<PathBuf>,
+
+    /// Answer searches requested on stdin until it is closed, for editor integration.
+    ///
+    /// Each line of stdin is a JSON object like
+    /// {"id": 1, "query": "foo", "limit": 10, "match_path": true}, where all but "query" are
+    /// optional. Each request is answered with a line of stdout holding a JSON object with
+    /// the "id" of the request and either "paths" (the matched paths), "error", or
+    /// "cancelled": true. A new request cancels the previous one if it is still being searched.
+    #[structopt(long)]
+    server: bool,
+
+    /// Print the configuration in effect, after applying command-line options, and exit.
+    #[structopt(long)]
+    print_config: bool,
+
+    /// Do not update the database even if it is older than max_age_days in config.
+    #[structopt(long)]
+    no_auto_update: bool,
+
+    /// Watch root directories and add created files to the results.
+    ///
+    /// Deleted files stay in the results until the database is updated.
+    #[structopt(short, long)]
+    watch: bool,
+
+    /// Separate printed paths with NUL instead of newline.
+    ///
+    /// Useful with `xargs -0` for paths containing spaces or newlines.
+    #[structopt(short = "0", long)]
+    print0: bool,
+
+    /// Also print size, mode, and modified time of the selected entry.
+    ///
+    /// They are printed on a line following the path.
+    #[structopt(long)]
+    print_long: bool,
+
+    /// Print paths relative to <dir> when they are under it.
+    ///
+    /// Paths outside <dir> are printed as they are. This applies to the selected entry,
+    /// --export-to, and --server.
+    #[structopt(long, value_name = "dir")]
+    base: Option<PathBuf>,
+
+    /// Render without colors.
+    ///
+    /// Also enabled by setting the NO_COLOR environment variable.
+    #[structopt(long)]
+    no_color: bool,
+
     /// Number of threads to use.
     ///
     /// Defaults to the number of available CPUs minus 1.
@@ -93,9 +225,26 @@ pub struct Opt {
 }
 
 fn main() -> Result<()> {
-    let opt = Opt::from_args();
+    timing::init();
+
+    let no_color_env = is_no_color_set(std::env::var_os("NO_COLOR"));
+    let mut app = Opt::clap();
+    if no_color_env {
+        app = app.setting(AppSettings::ColorNever);
+    }
+    let opt = Opt::from_clap(&app.get_matches());
+
     let mut config = config::read_or_create_config(opt.config.as_ref())?;
     config.flags.merge_opt(&opt);
+    config.flags.no_color |= no_color_env;
+    if config.flags.no_color {
+        config.ui.colors.disable();
+    }
+
+    if opt.print_config {
+        print!("{}", config.to_toml()?);
+        return Ok(());
+    }
 
     let db_location = if let Some(location) = &config.database.location {
         location
@@ -114,6 +263,23 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
+    if opt.stats {
+        let database = Database::open(db_location)?;
+        let file_size = std::fs::metadata(db_location)?.len();
+        println!("{}", Stats::new(&database, file_size));
+        return Ok(());
+    }
+
+    if let Some(path) = &opt.export_to {
+        let count = export::export(&config, path)?;
+        eprintln!("Exported {} paths to {}", count, path.display());
+        return Ok(());
+    }
+
+    if opt.server {
+        return server::run(&config);
+    }
+
     if !db_location.exists() {
         let yes = Confirm::new()
             .with_prompt("Database is not created yet. Create it now?")
@@ -124,6 +290,21 @@ fn main() -> Result<()> {
         } else {
             return Ok(());
         }
+    } else if let (Some(max_age_days), false) = (config.database.max_age_days, opt.no_auto_update) {
+        let modified = std::fs::metadata(db_location)?.modified()?;
+        if is_older_than(modified, SystemTime::now(), days(max_age_days)) {
+            let yes = config.database.auto_update
+                || Confirm::new()
+                    .with_prompt(format!(
+                        "Database is older than {} days. Update it now?",
+                        max_age_days
+                    ))
+                    .default(true)
+                    .interact()?;
+            if yes {
+                create_database(&config.database)?;
+            }
+        }
     }
 
     tui::run(&config)?;
@@ -131,35 +312,75 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-fn create_database(db_config: &DatabaseConfig) -> Result<()> {
-    let mut builder = DatabaseBuilder::new();
-    builder.ignore_hidden(db_config.ignore_hidden);
-    for dir in &db_config.dirs {
-        builder.add_dir(&dir);
-    }
-    for kind in &db_config.index {
-        builder.index(*kind);
+/// Follows https://no-color.org: NO_COLOR disables colors when it is set
+/// to a non-empty value.
+fn is_no_color_set(value: Option<OsString>) -> bool {
+    matches!(value, Some(value) if !value.is_empty())
+}
+
+/// Saturates instead of overflowing for huge numbers of days.
+fn days(days: u64) -> Duration {
+    Duration::from_secs(days.saturating_mul(24 * 60 * 60))
+}
+
+fn is_older_than(modified: SystemTime, now: SystemTime, max_age: Duration) -> bool {
+    // modified time in the future is treated as fresh
+    now.duration_since(modified)
+        .map(|age| age > max_age)
+        .unwrap_or(false)
+}
+
+fn report_warnings(warnings: &[Warning]) {
+    if warnings.is_empty() {
+        return;
     }
-    for kind in &db_config.fast_sort {
-        builder.fast_sort(*kind);
+
+    let mut counts: Vec<(io::ErrorKind, usize)> = Vec::new();
+    for warning in warnings {
+        let kind = warning.error().kind();
+        match counts.iter_mut().find(|(k, _)| *k == kind) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((kind, 1)),
+        }
     }
 
-    eprintln!("Indexing");
-    let database = builder.build()?;
-    eprintln!("Indexed {} files/directories", database.num_entries());
+    for (kind, count) in counts {
+        eprintln!(
+            "Skipped {} {} ({})",
+            count,
+            if count == 1 {
+                "directory"
+            } else {
+                "directories"
+            },
+            io::Error::from(kind)
+        );
+    }
+}
 
-    eprintln!("Writing");
+fn create_database(db_config: &DatabaseConfig) -> Result<()> {
+    let mut builder = db_config.database_builder();
+    for dir in &db_config.dirs {
+        builder.add_dir(&dir);
+    }
 
     let location = db_config.location.as_ref().unwrap();
     let create = !location.exists();
 
-    if let Some(parent) = location.parent() {
-        std::fs::create_dir_all(parent)?;
+    // the database is written next to the existing one and replaces it only
+    // once complete, so that a failed update leaves the existing one intact
+    let parent = location.parent().unwrap_or_else(|| Path::new("."));
+    std::fs::create_dir_all(parent)?;
+    let file = tempfile::NamedTempFile::new_in(parent)?;
+    if let Ok(metadata) = std::fs::metadata(location) {
+        file.as_file().set_permissions(metadata.permissions())?;
     }
 
-    let mut writer = BufWriter::new(File::create(&location)?);
-    bincode::serialize_into(&mut writer, &database)?;
-    writer.flush()?;
+    eprintln!("Indexing and writing");
+    let warnings = builder.build_to_writer(file.as_file())?;
+    report_warnings(&warnings);
+
+    file.persist(location)?;
 
     if create {
         eprintln!("Created a database at {}", location.display());
@@ -169,3 +390,35 @@ fn create_database(db_config: &DatabaseConfig) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn database_age() {
+        let day = Duration::from_secs(24 * 60 * 60);
+        let now = SystemTime::now();
+
+        assert!(!is_older_than(now, now, day));
+        assert!(!is_older_than(now - day, now, day));
+        assert!(is_older_than(now - day * 2, now, day));
+        assert!(!is_older_than(now + day, now, day));
+        assert!(is_older_than(
+            now - Duration::from_secs(1),
+            now,
+            Duration::ZERO
+        ));
+
+        assert_eq!(days(2), day * 2);
+        assert!(!is_older_than(now - day, now, days(u64::MAX)));
+    }
+
+    #[test]
+    fn no_color_env() {
+        assert!(!is_no_color_set(None));
+        assert!(!is_no_color_set(Some(OsString::new())));
+        assert!(is_no_color_set(Some("1".into())));
+        assert!(is_no_color_set(Some("0".into())));
+    }
+}
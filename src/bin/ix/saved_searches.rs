@@ -0,0 +1,200 @@
+use indexa::{
+    database::StatusKind,
+    query::{CaseSensitivity, MatchPathMode, SortOrder},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// A query stored under a name.
+///
+/// `Query` holds a compiled regex, so what is stored is the inputs of
+/// `QueryBuilder` instead, which are loaded into the TUI.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SavedSearch {
+    pub name: String,
+    pub pattern: String,
+    pub match_path: MatchPathMode,
+    pub case_sensitivity: CaseSensitivity,
+    pub regex: bool,
+    pub sort_by: StatusKind,
+    pub sort_order: SortOrder,
+}
+
+impl Default for SavedSearch {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            pattern: String::new(),
+            match_path: MatchPathMode::Never,
+            case_sensitivity: CaseSensitivity::Smart,
+            regex: false,
+            sort_by: StatusKind::Basename,
+            sort_order: SortOrder::Ascending,
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SearchesFile {
+    #[serde(rename = "search")]
+    searches: Vec<SavedSearch>,
+}
+
+/// Saved searches, in the order they were first saved.
+#[derive(Default)]
+pub struct SavedSearches {
+    location: Option<PathBuf>,
+    searches: Vec<SavedSearch>,
+}
+
+impl SavedSearches {
+    /// Loads searches from a TOML file. A nonexistent file is treated as empty.
+    pub fn load<P>(location: P) -> Result<Self>
+    where
+        P: Into<PathBuf>,
+    {
+        let location = location.into();
+        let searches = match fs::read_to_string(&location) {
+            Ok(content) => toml::from_str::<SearchesFile>(&content)?.searches,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Self {
+            location: Some(location),
+            searches,
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(location) = &self.location {
+            write_searches(location, &self.searches)?;
+        }
+        Ok(())
+    }
+
+    /// Adds a search, replacing the one with the same name if any.
+    pub fn push(&mut self, search: SavedSearch) {
+        match self.searches.iter_mut().find(|s| s.name == search.name) {
+            Some(existing) => *existing = search,
+            None => self.searches.push(search),
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&SavedSearch> {
+        self.searches.get(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.searches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.searches.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &SavedSearch> {
+        self.searches.iter()
+    }
+}
+
+fn write_searches(path: &Path, searches: &[SavedSearch]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let file = SearchesFile {
+        searches: searches.to_vec(),
+    };
+    fs::write(path, toml::to_string(&file)?)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let location = tmpdir.path().join("dir").join("searches.toml");
+
+        let mut searches = SavedSearches::load(&location).unwrap();
+        assert!(searches.is_empty());
+
+        let videos = SavedSearch {
+            name: "large videos".to_string(),
+            pattern: r"\.(mp4|mkv)$".to_string(),
+            match_path: MatchPathMode::Auto,
+            case_sensitivity: CaseSensitivity::Insensitive,
+            regex: true,
+            sort_by: StatusKind::Size,
+            sort_order: SortOrder::Descending,
+        };
+        let notes = SavedSearch {
+            name: "notes".to_string(),
+            pattern: "notes/ \"draft\"".to_string(),
+            ..Default::default()
+        };
+        searches.push(videos.clone());
+        searches.push(notes);
+        let notes = SavedSearch {
+            match_path: MatchPathMode::Always,
+            ..searches.get(1).unwrap().clone()
+        };
+        searches.push(notes.clone());
+        searches.save().unwrap();
+
+        let searches = SavedSearches::load(&location).unwrap();
+        assert_eq!(
+            searches.iter().cloned().collect::<Vec<_>>(),
+            [videos, notes]
+        );
+    }
+
+    #[test]
+    fn parse() {
+        let file: SearchesFile = toml::from_str(
+            r#"
+            [[search]]
+            name = "logs"
+            pattern = "\\.log$"
+            regex = true
+            case_sensitivity = "sensitive"
+            sort_by = "modified"
+            sort_order = "desc"
+
+            [[search]]
+            name = "empty"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            file.searches,
+            [
+                SavedSearch {
+                    name: "logs".to_string(),
+                    pattern: r"\.log$".to_string(),
+                    case_sensitivity: CaseSensitivity::Sensitive,
+                    regex: true,
+                    sort_by: StatusKind::Modified,
+                    sort_order: SortOrder::Descending,
+                    ..Default::default()
+                },
+                SavedSearch {
+                    name: "empty".to_string(),
+                    ..Default::default()
+                }
+            ]
+        );
+        assert!(toml::from_str::<SearchesFile>("[[search]]\nfoo = 1").is_err());
+    }
+}
@@ -1,34 +1,140 @@
+use crate::config::Config;
+
 use indexa::{
-    database::{Database, EntryId},
-    query::Query,
+    database::{Database, EntryId, StatusKind},
+    query::{CaseSensitivity, MatchPathMode, Query, QueryBuilder, SortOrder},
     Error,
 };
 
 use crossbeam_channel::Sender;
+use itertools::Itertools;
 use std::{
+    cmp::Ordering,
     sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
+        atomic::{AtomicBool, Ordering as AtomicOrdering},
+        Arc, Mutex,
     },
     thread,
+    time::Duration,
 };
 
+/// An entry found in one of the searched databases.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct Hit {
+    /// index of the database the entry belongs to
+    pub database: usize,
+    pub id: EntryId,
+}
+
+/// Options of searches which can be changed from the TUI, initialized from config.
+#[derive(Debug, Clone, Copy)]
+pub struct SearchOptions {
+    pub match_path: MatchPathMode,
+    pub case_sensitivity: CaseSensitivity,
+    pub regex: bool,
+    pub sort_by: StatusKind,
+    pub sort_order: SortOrder,
+    pub ignore_hidden: bool,
+}
+
+impl SearchOptions {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            match_path: config.flags.match_path,
+            case_sensitivity: config.flags.case_sensitivity(),
+            regex: config.flags.regex,
+            sort_by: config.ui.sort_by,
+            sort_order: config.ui.sort_order,
+            ignore_hidden: false,
+        }
+    }
+
+    /// Returns a builder of a query for `pattern` with these options and
+    /// the other flags given in config.
+    ///
+    /// The TUI, --export-to, and --server all build queries with this,
+    /// so that they find the same entries for the same options.
+    pub fn query_builder<'a>(&self, config: &Config, pattern: &'a str) -> QueryBuilder<'a> {
+        let mut builder = QueryBuilder::new(pattern);
+        builder
+            .match_path_mode(self.match_path)
+            .case_sensitivity(self.case_sensitivity)
+            .regex(self.regex)
+            .invert(config.flags.invert_match)
+            .prefer_dirs_matching_prefix(config.flags.cd)
+            .sort_by(self.sort_by)
+            .sort_order(self.sort_order)
+            .sort_dirs_before_files(config.ui.sort_dirs_before_files)
+            .ignore_hidden(self.ignore_hidden);
+        config.flags.restrict_statuses(&mut builder);
+        builder
+    }
+}
+
+/// Searches multiple databases, merging their hits.
+///
+/// The first database is the primary one, which is updated with entries
+/// created in watched directories.
 pub struct Searcher {
-    database: Arc<Database>,
-    tx: Sender<Vec<EntryId>>,
+    databases: Vec<Arc<Database>>,
+    tx: Sender<Vec<Hit>>,
     search: Option<Search>,
+    last_results: Arc<Mutex<Option<LastResults>>>,
+    /// Number of loaded databases, which come before the overlay, if any.
+    num_loaded: usize,
 }
 
+/// The last query whose hits in the whole databases are known, and the hits.
+type LastResults = (Query, Arc<Vec<Hit>>);
+
 impl Searcher {
-    pub fn new(database: Arc<Database>, tx: Sender<Vec<EntryId>>) -> Self {
+    pub fn new(databases: Vec<Arc<Database>>, tx: Sender<Vec<Hit>>) -> Self {
         Self {
-            database,
+            num_loaded: databases.len(),
+            databases,
             tx,
             search: None,
+            last_results: Default::default(),
         }
     }
 
+    /// Searches the whole databases.
+    ///
+    /// If the query narrows the last one searched, e.g. "rep" after "re",
+    /// hits of the last query are filtered instead.
     pub fn search(&mut self, query: Query) {
+        let last_results = self.last_results.lock().unwrap().clone();
+        let record = Some(query.clone());
+        match last_results {
+            Some((last_query, hits)) if query.narrows(&last_query) => self
+                .spawn(record, move |databases, abort_signal| {
+                    filter_hits(databases, &hits, &query, abort_signal)
+                }),
+            _ => self.spawn(record, move |databases, abort_signal| {
+                let hits = databases
+                    .iter()
+                    .map(|database| database.search(&query, abort_signal))
+                    .collect::<indexa::Result<Vec<_>>>()?;
+                Ok(merge_hits(databases, hits, &query))
+            }),
+        }
+    }
+
+    /// Filters the given hits instead of searching the whole databases.
+    pub fn refine(&mut self, hits: Arc<Vec<Hit>>, query: Query) {
+        self.spawn(None, move |databases, abort_signal| {
+            filter_hits(databases, &hits, &query, abort_signal)
+        });
+    }
+
+    /// Runs `f` in a new thread, aborting an ongoing search.
+    ///
+    /// With `record`, the hits are remembered as those of the query in the
+    /// whole databases, which later searches may narrow down.
+    fn spawn<F>(&mut self, record: Option<Query>, f: F)
+    where
+        F: FnOnce(&[Arc<Database>], &Arc<AtomicBool>) -> indexa::Result<Vec<Hit>> + Send + 'static,
+    {
         if let Some(search) = &self.search {
             search.abort();
         }
@@ -36,15 +142,20 @@ impl Searcher {
         let abort_signal = Arc::new(AtomicBool::new(false));
 
         {
-            let database = self.database.clone();
+            let databases = self.databases.clone();
             let tx = self.tx.clone();
             let abort_signal = abort_signal.clone();
+            let last_results = self.last_results.clone();
 
             thread::spawn(move || {
-                let hits = database.abortable_search(&query, &abort_signal);
+                let hits = f(&databases, &abort_signal);
                 match hits {
                     Ok(hits) => {
-                        if !abort_signal.load(Ordering::Relaxed) {
+                        if !abort_signal.load(AtomicOrdering::Relaxed) {
+                            if let Some(query) = record {
+                                *last_results.lock().unwrap() =
+                                    Some((query, Arc::new(hits.clone())));
+                            }
                             let _ = tx.send(hits);
                         }
                     }
@@ -58,6 +169,99 @@ impl Searcher {
     }
 }
 
+/// Filters `hits` with `query`, keeping their order.
+fn filter_hits(
+    databases: &[Arc<Database>],
+    hits: &[Hit],
+    query: &Query,
+    abort_signal: &Arc<AtomicBool>,
+) -> indexa::Result<Vec<Hit>> {
+    let filtered = databases
+        .iter()
+        .enumerate()
+        .map(|(i, database)| {
+            let ids = hits
+                .iter()
+                .filter(|hit| hit.database == i)
+                .map(|hit| hit.id)
+                .collect::<Vec<_>>();
+            database.filter_ids(&ids, query, abort_signal)
+        })
+        .collect::<indexa::Result<Vec<_>>>()?;
+
+    // filter_ids keeps the order, so the hits can be picked up in
+    // the original order
+    let mut filtered = filtered
+        .into_iter()
+        .map(|ids| ids.into_iter().peekable())
+        .collect::<Vec<_>>();
+    Ok(hits
+        .iter()
+        .filter(|hit| {
+            let ids = &mut filtered[hit.database];
+            ids.next_if_eq(&hit.id).is_some()
+        })
+        .copied()
+        .collect())
+}
+
+impl Searcher {
+    /// Aborts an ongoing search and adds `created` entries to an overlay
+    /// database, which is searched after the loaded ones.
+    ///
+    /// The loaded databases are left as they are, and ids of entries already
+    /// in the overlay are kept. Returns the databases along with the result
+    /// of appending to the overlay.
+    pub fn add_created(&mut self, created: Database) -> (Vec<Arc<Database>>, indexa::Result<()>) {
+        self.search = None;
+
+        let appended = if self.databases.len() == self.num_loaded {
+            self.databases.push(Arc::new(created));
+            Ok(())
+        } else {
+            // aborted search threads release the overlay shortly
+            loop {
+                if let Some(overlay) = Arc::get_mut(self.databases.last_mut().unwrap()) {
+                    break overlay.append(created);
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+        };
+
+        // hits found before the addition may miss entries
+        *self.last_results.lock().unwrap() = None;
+
+        (self.databases.clone(), appended)
+    }
+}
+
+/// Merges hits of the same query in each of `databases`, keeping them sorted.
+///
+/// Preferred directories of the query, which come first in hits of each
+/// database, also come first in the merged hits.
+pub fn merge_hits(databases: &[Arc<Database>], hits: Vec<Vec<EntryId>>, query: &Query) -> Vec<Hit> {
+    let preferred_dirs: Vec<_> = databases
+        .iter()
+        .map(|database| database.preferred_dir(query))
+        .collect();
+    let is_preferred = |hit: &Hit| preferred_dirs[hit.database] == Some(hit.id);
+
+    hits.into_iter()
+        .enumerate()
+        .map(|(database, ids)| ids.into_iter().map(move |id| Hit { database, id }))
+        .kmerge_by(|a, b| match (is_preferred(a), is_preferred(b)) {
+            (true, false) => true,
+            (false, true) => false,
+            _ => {
+                let a = databases[a.database].entry(a.id);
+                let b = databases[b.database].entry(b.id);
+                query.compare(&a, &b) == Ordering::Less
+            }
+        })
+        .take(query.limit().unwrap_or(usize::MAX))
+        .collect()
+}
+
 struct Search {
     abort_signal: Arc<AtomicBool>,
 }
@@ -70,6 +274,229 @@ impl Drop for Search {
 
 impl Search {
     fn abort(&self) {
-        self.abort_signal.store(true, Ordering::Relaxed);
+        self.abort_signal.store(true, AtomicOrdering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indexa::database::DatabaseBuilder;
+    use std::fs;
+
+    #[test]
+    fn options_from_config() {
+        let mut config = Config::for_test();
+        config.flags.regex = true;
+        config.flags.invert_match = true;
+        config.flags.size.min = Some(10);
+        config.ui.sort_by = StatusKind::Size;
+        config.ui.sort_order = SortOrder::Descending;
+
+        let mut options = SearchOptions::new(&config);
+        let query = options.query_builder(&config, "a.c").build().unwrap();
+        assert!(query.is_regex_enabled());
+        assert!(query.is_inverted());
+        assert_eq!(query.size_min(), Some(10));
+        assert_eq!(query.sort_by(), StatusKind::Size);
+        assert_eq!(query.sort_order(), SortOrder::Descending);
+        assert!(!query.ignores_hidden());
+
+        // options changed from the TUI override config
+        options.regex = false;
+        options.ignore_hidden = true;
+        let query = options.query_builder(&config, "a.c").build().unwrap();
+        assert!(!query.is_regex_enabled());
+        assert!(query.ignores_hidden());
+    }
+
+    #[test]
+    fn merge_ordering() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let root = dunce::canonicalize(tmpdir.path()).unwrap();
+        let files: &[(&str, &[&str])] = &[
+            ("first", &["b.txt", "dd.txt", "e"]),
+            ("second", &["a.txt", "c", "ccc.txt", "dd.txt"]),
+        ];
+
+        let databases = files
+            .iter()
+            .map(|(dir, names)| {
+                let dir = root.join(dir);
+                fs::create_dir_all(dir.join("sub_dir")).unwrap();
+                for name in names.iter() {
+                    fs::write(dir.join(name), name).unwrap();
+                }
+                let database = DatabaseBuilder::new()
+                    .add_dir(dir)
+                    .index(StatusKind::Size)
+                    .fast_sort(StatusKind::Size)
+                    .build()
+                    .unwrap();
+                Arc::new(database)
+            })
+            .collect::<Vec<_>>();
+
+        let abort_signal = Arc::new(AtomicBool::new(false));
+        for sort_by in [StatusKind::Basename, StatusKind::Path, StatusKind::Size] {
+            for sort_order in [SortOrder::Ascending, SortOrder::Descending] {
+                for dirs_first in [false, true] {
+                    let query = QueryBuilder::new("")
+                        .sort_by(sort_by)
+                        .sort_order(sort_order)
+                        .sort_dirs_before_files(dirs_first)
+                        .build()
+                        .unwrap();
+
+                    let hits = databases
+                        .iter()
+                        .map(|database| database.search(&query, &abort_signal).unwrap())
+                        .collect();
+                    let merged = merge_hits(&databases, hits, &query)
+                        .into_iter()
+                        .map(|hit| databases[hit.database].entry(hit.id).path())
+                        .collect::<Vec<_>>();
+
+                    let mut expected = databases
+                        .iter()
+                        .flat_map(|database| database.entries())
+                        .collect::<Vec<_>>();
+                    expected.sort_by(|a, b| query.compare(a, b));
+                    let expected = expected
+                        .into_iter()
+                        .map(|entry| entry.path())
+                        .collect::<Vec<_>>();
+
+                    assert_eq!(
+                        merged, expected,
+                        "{} {:?} {}",
+                        sort_by, sort_order, dirs_first
+                    );
+                }
+            }
+        }
+
+        let query = QueryBuilder::new("txt").build().unwrap();
+        let hits = databases
+            .iter()
+            .map(|database| database.search(&query, &abort_signal).unwrap())
+            .collect();
+        let merged = merge_hits(&databases, hits, &query)
+            .into_iter()
+            .map(|hit| {
+                (
+                    hit.database,
+                    databases[hit.database].entry(hit.id).basename().to_owned(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            merged,
+            [
+                (1, "a.txt".to_string()),
+                (0, "b.txt".to_string()),
+                (1, "ccc.txt".to_string()),
+                (0, "dd.txt".to_string()),
+                (1, "dd.txt".to_string()),
+            ]
+        );
+
+        // "p" matches entries of both databases, but only the root of the
+        // first one is preferred
+        let databases = ["p", "pq"]
+            .iter()
+            .map(|dir| {
+                let dir = root.join(dir);
+                fs::create_dir_all(dir.join("zzz")).unwrap();
+                Arc::new(DatabaseBuilder::new().add_dir(dir).build().unwrap())
+            })
+            .collect::<Vec<_>>();
+        let dir = root.join("p");
+        for sort_order in [SortOrder::Ascending, SortOrder::Descending] {
+            let query = QueryBuilder::new(dir.to_str().unwrap())
+                .match_path_mode(MatchPathMode::Always)
+                .sort_order(sort_order)
+                .prefer_dirs_matching_prefix(true)
+                .build()
+                .unwrap();
+            let hits = databases
+                .iter()
+                .map(|database| database.search(&query, &abort_signal).unwrap())
+                .collect();
+            let merged = merge_hits(&databases, hits, &query)
+                .into_iter()
+                .map(|hit| databases[hit.database].entry(hit.id).path())
+                .collect::<Vec<_>>();
+            assert_eq!(merged.len(), 4);
+            assert_eq!(merged[0], dir);
+        }
+    }
+
+    #[test]
+    fn narrow() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let root = dunce::canonicalize(tmpdir.path()).unwrap().join("root");
+        fs::create_dir_all(root.join("report")).unwrap();
+        for name in [
+            "read.me",
+            "Repo.txt",
+            "rep",
+            "report/rep.rs",
+            "grep",
+            "other",
+        ] {
+            fs::write(root.join(name), "").unwrap();
+        }
+        let database = DatabaseBuilder::new().add_dir(&root).build().unwrap();
+
+        let search = |searcher: &mut Searcher, rx: &crossbeam_channel::Receiver<_>, query| {
+            let query = QueryBuilder::new(query).build().unwrap();
+            searcher.search(query);
+            let hits: Vec<Hit> = rx.recv().unwrap();
+            hits.into_iter()
+                .map(|hit| searcher.databases[hit.database].entry(hit.id).path())
+                .collect::<Vec<_>>()
+        };
+        let last_query = |searcher: &Searcher| {
+            let last_results = searcher.last_results.lock().unwrap();
+            last_results
+                .as_ref()
+                .map(|(query, _)| query.pattern().to_owned())
+        };
+
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let mut searcher = Searcher::new(vec![Arc::new(database)], tx);
+        for query in ["re", "rep", "repo", "rep", "rEp", "rEp.", "p"] {
+            let hits = search(&mut searcher, &rx, query);
+
+            let (fresh_tx, fresh_rx) = crossbeam_channel::unbounded();
+            let mut fresh = Searcher::new(searcher.databases.clone(), fresh_tx);
+            assert_eq!(hits, search(&mut fresh, &fresh_rx, query), "{}", query);
+
+            // hits are recorded once the search finishes
+            for _ in 0..1000 {
+                if last_query(&searcher).as_deref() == Some(&regex::escape(query)) {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+            assert_eq!(last_query(&searcher), Some(regex::escape(query)));
+        }
+
+        // the fresh searchers are gone, so that the overlay can be modified
+        let created_root = dunce::canonicalize(tmpdir.path()).unwrap().join("created");
+        fs::create_dir(&created_root).unwrap();
+        fs::write(created_root.join("rep.new"), "").unwrap();
+        let created = DatabaseBuilder::new()
+            .add_dir(&created_root)
+            .build()
+            .unwrap();
+        let (databases, appended) = searcher.add_created(created);
+        appended.unwrap();
+        assert_eq!(databases.len(), 2);
+        assert!(last_query(&searcher).is_none());
+        assert!(search(&mut searcher, &rx, "rep")
+            .iter()
+            .any(|path| path.ends_with("rep.new")));
     }
 }
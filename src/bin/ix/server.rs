@@ -0,0 +1,377 @@
+use crate::{
+    config::{Config, FlagConfig},
+    export,
+    searcher::{self, Hit, SearchOptions},
+};
+
+use indexa::{
+    database::Database,
+    query::{MatchPathMode, Query},
+    Error,
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    io::{self, BufRead, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+/// A search requested with a line of JSON.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Request {
+    /// echoed in the response, so that the client can tell which request
+    /// it answers
+    id: Option<Value>,
+    query: String,
+    limit: Option<usize>,
+    /// overrides `match_path` in config
+    match_path: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+struct Response {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<Value>,
+    #[serde(flatten)]
+    outcome: Outcome,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum Outcome {
+    Paths { paths: Vec<String> },
+    Error { error: String },
+    Cancelled { cancelled: bool },
+}
+
+impl Outcome {
+    fn error<E: ToString>(err: E) -> Self {
+        Self::Error {
+            error: err.to_string(),
+        }
+    }
+}
+
+/// Loads the databases and answers requests on stdin until it is closed.
+pub fn run(config: &Config) -> Result<()> {
+    let databases = export::load_databases(config)?;
+    let flags = config.flags.clone();
+    let stdin = io::stdin();
+    serve(
+        config,
+        stdin.lock(),
+        io::stdout(),
+        move |query: &Query, abort_signal: &Arc<AtomicBool>| {
+            search(&databases, query, &flags, abort_signal)
+        },
+    )
+}
+
+/// Answers requests read from `reader`, one JSON object per line, with
+/// JSON objects written to `writer`, one per line, using `search` to find
+/// the paths matching a query.
+///
+/// Every request gets exactly one response, which carries the `id` of
+/// the request if it has one, and either `paths`, `error`, or
+/// `"cancelled": true`. A valid request arriving while the previous one is
+/// being searched cancels it. Invalid requests are answered with errors
+/// right away and cancel nothing.
+fn serve<R, W, S>(config: &Config, reader: R, writer: W, search: S) -> Result<()>
+where
+    R: BufRead,
+    W: Write + Send + 'static,
+    S: Fn(&Query, &Arc<AtomicBool>) -> indexa::Result<Vec<String>> + Send + Sync + 'static,
+{
+    let search = Arc::new(search);
+    let writer = Arc::new(Mutex::new(writer));
+    let mut current: Option<(Arc<AtomicBool>, JoinHandle<()>)> = None;
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (id, query) = match parse_request(config, &line) {
+            (id, Ok(query)) => (id, query),
+            (id, Err(err)) => {
+                let response = Response {
+                    id,
+                    outcome: Outcome::error(err),
+                };
+                write_response(&mut *writer.lock().unwrap(), &response)?;
+                continue;
+            }
+        };
+
+        let previous = current.take().map(|(abort_signal, handle)| {
+            abort_signal.store(true, Ordering::Relaxed);
+            handle
+        });
+        let abort_signal = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let search = search.clone();
+            let writer = writer.clone();
+            let abort_signal = abort_signal.clone();
+            thread::spawn(move || {
+                let outcome = match search(&query, &abort_signal) {
+                    Ok(paths) => Outcome::Paths { paths },
+                    Err(Error::SearchAbort) => Outcome::Cancelled { cancelled: true },
+                    Err(err) => Outcome::error(err),
+                };
+
+                // responses are written in the order of requests
+                if let Some(previous) = previous {
+                    let _ = previous.join();
+                }
+                let mut writer = writer.lock().unwrap();
+                let outcome = if abort_signal.load(Ordering::Relaxed) {
+                    Outcome::Cancelled { cancelled: true }
+                } else {
+                    outcome
+                };
+                // the client is gone if the output is closed
+                let _ = write_response(&mut *writer, &Response { id, outcome });
+            })
+        };
+        current = Some((abort_signal, handle));
+    }
+
+    if let Some((_, handle)) = current {
+        let _ = handle.join();
+    }
+
+    Ok(())
+}
+
+/// Parses a request into a query, returning the id of the request as long
+/// as the line is a JSON object.
+fn parse_request(config: &Config, line: &str) -> (Option<Value>, Result<Query>) {
+    let value: Value = match serde_json::from_str(line) {
+        Ok(value) => value,
+        Err(err) => return (None, Err(err.into())),
+    };
+    let id = value.get("id").cloned();
+
+    let request = match Request::deserialize(value) {
+        Ok(request) => request,
+        Err(err) => return (id, Err(err.into())),
+    };
+    let mut options = SearchOptions::new(config);
+    match request.match_path {
+        Some(true) => options.match_path = MatchPathMode::Always,
+        Some(false) => options.match_path = MatchPathMode::Never,
+        None => (),
+    }
+    let mut builder = options.query_builder(config, &request.query);
+    if let Some(limit) = request.limit {
+        builder.limit(limit);
+    }
+    (request.id, builder.build().map_err(Into::into))
+}
+
+fn search(
+    databases: &[Arc<Database>],
+    query: &Query,
+    flags: &FlagConfig,
+    abort_signal: &Arc<AtomicBool>,
+) -> indexa::Result<Vec<String>> {
+    let hits = databases
+        .iter()
+        .map(|database| database.search(query, abort_signal))
+        .collect::<indexa::Result<Vec<_>>>()?;
+    let mut hits = searcher::merge_hits(databases, hits, query);
+    if let Some(limit) = query.limit() {
+        hits.truncate(limit);
+    }
+
+    Ok(hits
+        .into_iter()
+        .map(|Hit { database, id }| {
+            let path = databases[database].entry(id).path();
+            flags.printed_path(&path).to_owned()
+        })
+        .collect())
+}
+
+fn write_response<W: Write>(writer: &mut W, response: &Response) -> io::Result<()> {
+    serde_json::to_writer(&mut *writer, response)?;
+    writeln!(writer)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indexa::database::DatabaseBuilder;
+    use serde_json::json;
+    use std::{collections::VecDeque, fs, io::Read, time::Duration};
+
+    /// Output shared between the server and the test.
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl Output {
+        fn lines(&self) -> Vec<String> {
+            let output = self.0.lock().unwrap();
+            String::from_utf8_lossy(&output)
+                .lines()
+                .map(str::to_owned)
+                .collect()
+        }
+
+        fn responses(&self) -> Vec<Value> {
+            self.lines()
+                .iter()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect()
+        }
+    }
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Sends each request after receiving the response to the previous one.
+    struct Client {
+        requests: VecDeque<&'static str>,
+        sent: usize,
+        pending: Vec<u8>,
+        output: Output,
+    }
+
+    impl Read for Client {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.pending.is_empty() {
+                while self.output.lines().len() < self.sent {
+                    thread::sleep(Duration::from_millis(10));
+                }
+                let request = match self.requests.pop_front() {
+                    Some(request) => request,
+                    None => return Ok(0),
+                };
+                self.pending = format!("{}\n", request).into_bytes();
+                // blank lines are not answered
+                if !request.trim().is_empty() {
+                    self.sent += 1;
+                }
+            }
+
+            let len = self.pending.len().min(buf.len());
+            buf[..len].copy_from_slice(&self.pending[..len]);
+            self.pending.drain(..len);
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn serve_requests() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let root = dunce::canonicalize(tmpdir.path()).unwrap();
+        fs::create_dir(root.join("foo")).unwrap();
+        for name in ["foo/bar", "foobar", "baz"] {
+            fs::write(root.join(name), "").unwrap();
+        }
+        let databases = vec![Arc::new(
+            DatabaseBuilder::new().add_dir(&root).build().unwrap(),
+        )];
+        let path = |name: &str| root.join(name).to_str().unwrap().to_owned();
+        let config = Config::for_test();
+        let flags = config.flags.clone();
+
+        let output = Output::default();
+        let client = Client {
+            requests: VecDeque::from(vec![
+                r#"{"id": 1, "query": "foo"}"#,
+                r#"{"id": "b", "query": "bar", "limit": 1}"#,
+                "",
+                r#"{"query": "foo/b", "match_path": true}"#,
+                r#"{"id": 4, "query": "(", "match_path": false}"#,
+                r#"{"id": 5, "pattern": "foo"}"#,
+                "not json",
+            ]),
+            sent: 0,
+            pending: Vec::new(),
+            output: output.clone(),
+        };
+        serve(
+            &config,
+            io::BufReader::new(client),
+            output.clone(),
+            move |query: &Query, abort_signal: &Arc<AtomicBool>| {
+                search(&databases, query, &flags, abort_signal)
+            },
+        )
+        .unwrap();
+
+        let responses = output.responses();
+        assert_eq!(responses.len(), 6);
+        assert_eq!(
+            responses[0],
+            json!({"id": 1, "paths": [path("foo"), path("foobar")]})
+        );
+        assert_eq!(responses[1], json!({"id": "b", "paths": [path("foo/bar")]}));
+        assert_eq!(responses[2], json!({ "paths": [path("foo/bar")] }));
+        // "(" is a literal unless regex is enabled
+        assert_eq!(responses[3], json!({"id": 4, "paths": []}));
+        assert_eq!(responses[4]["id"], 5);
+        assert!(responses[4]["error"].is_string());
+        assert!(responses[5].get("id").is_none());
+        assert!(responses[5]["error"].is_string());
+    }
+
+    #[test]
+    fn cancel_requests() {
+        // "slow" is searched until it is cancelled
+        let search = |query: &Query, abort_signal: &Arc<AtomicBool>| {
+            if query.pattern() == "slow" {
+                while !abort_signal.load(Ordering::Relaxed) {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                return Err(Error::SearchAbort);
+            }
+            Ok(vec![query.pattern().to_owned()])
+        };
+
+        let output = Output::default();
+        let requests = [
+            r#"{"id": 1, "query": "slow"}"#,
+            "not json",
+            r#"{"id": 2, "query": "fast", "limit": -1}"#,
+            r#"{"id": 3, "query": "fast"}"#,
+        ]
+        .join("\n");
+        serve(
+            &Config::for_test(),
+            requests.as_bytes(),
+            output.clone(),
+            search,
+        )
+        .unwrap();
+
+        // invalid requests are answered right away without cancelling
+        // the one being searched
+        let responses = output.responses();
+        assert_eq!(responses.len(), 4);
+        assert!(responses[0].get("id").is_none());
+        assert!(responses[0]["error"].is_string());
+        assert_eq!(responses[1]["id"], 2);
+        assert!(responses[1]["error"].is_string());
+        assert_eq!(responses[2], json!({"id": 1, "cancelled": true}));
+        assert_eq!(responses[3], json!({"id": 3, "paths": ["fast"]}));
+    }
+}
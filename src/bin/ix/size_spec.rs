@@ -0,0 +1,154 @@
+use anyhow::{anyhow, Result};
+use std::str::FromStr;
+
+/// Bounds of file sizes given as "+10M" (at least), "-1G" (at most),
+/// or "500K..2M" (between, inclusive).
+///
+/// Units are case-insensitive. "k", "m", "g", and "t" (optionally followed
+/// by "b") are powers of 1000, and "ki", "mi", "gi", and "ti" (optionally
+/// followed by "b") are powers of 1024. Sizes without a unit are in bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SizeRange {
+    pub min: Option<u64>,
+    pub max: Option<u64>,
+}
+
+impl FromStr for SizeRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            anyhow!(
+                "Invalid size '{}'. Valid forms are '+10M' (at least), '-1G' (at most), \
+                 and '500K..2M' (between).",
+                s
+            )
+        };
+
+        let trimmed = s.trim();
+        let range = if let Some(min) = trimmed.strip_prefix('+') {
+            Self {
+                min: Some(parse_size(min).ok_or_else(invalid)?),
+                max: None,
+            }
+        } else if let Some(max) = trimmed.strip_prefix('-') {
+            Self {
+                min: None,
+                max: Some(parse_size(max).ok_or_else(invalid)?),
+            }
+        } else if let Some((min, max)) = trimmed.split_once("..") {
+            let parse_bound = |bound: &str| match bound.trim() {
+                "" => Ok(None),
+                bound => parse_size(bound).map(Some).ok_or_else(invalid),
+            };
+            Self {
+                min: parse_bound(min)?,
+                max: parse_bound(max)?,
+            }
+        } else {
+            return Err(invalid());
+        };
+
+        match range {
+            Self {
+                min: Some(min),
+                max: Some(max),
+            } if min > max => Err(anyhow!(
+                "Invalid size '{}'. The lower bound is larger than the upper bound.",
+                s
+            )),
+            Self {
+                min: None,
+                max: None,
+            } => Err(invalid()),
+            range => Ok(range),
+        }
+    }
+}
+
+/// Parses a size such as "10", "1.5M", or "4KiB" into bytes.
+fn parse_size(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let number_len = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(number_len);
+    if !number.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let multiplier: u64 = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1000,
+        "m" | "mb" => 1000u64.pow(2),
+        "g" | "gb" => 1000u64.pow(3),
+        "t" | "tb" => 1000u64.pow(4),
+        "ki" | "kib" => 1 << 10,
+        "mi" | "mib" => 1 << 20,
+        "gi" | "gib" => 1 << 30,
+        "ti" | "tib" => 1 << 40,
+        _ => return None,
+    };
+
+    if let Ok(number) = number.parse::<u64>() {
+        return number.checked_mul(multiplier);
+    }
+    let bytes = number.parse::<f64>().ok()? * multiplier as f64;
+    (bytes < u64::MAX as f64).then(|| bytes.round() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(s: &str) -> (Option<u64>, Option<u64>) {
+        let range: SizeRange = s.parse().unwrap();
+        (range.min, range.max)
+    }
+
+    #[test]
+    fn size() {
+        assert_eq!(parse_size("0"), Some(0));
+        assert_eq!(parse_size("10"), Some(10));
+        assert_eq!(parse_size("10b"), Some(10));
+        assert_eq!(parse_size("500K"), Some(500_000));
+        assert_eq!(parse_size("500kB"), Some(500_000));
+        assert_eq!(parse_size("2M"), Some(2_000_000));
+        assert_eq!(parse_size("1G"), Some(1_000_000_000));
+        assert_eq!(parse_size("1t"), Some(1_000_000_000_000));
+        assert_eq!(parse_size("500Ki"), Some(500 * 1024));
+        assert_eq!(parse_size("2MiB"), Some(2 * 1024 * 1024));
+        assert_eq!(parse_size("1gib"), Some(1024 * 1024 * 1024));
+        assert_eq!(parse_size("1TiB"), Some(1 << 40));
+        assert_eq!(parse_size("1.5M"), Some(1_500_000));
+        assert_eq!(parse_size("1.5KiB"), Some(1536));
+        assert_eq!(parse_size("10 MB"), Some(10_000_000));
+
+        assert_eq!(parse_size(""), None);
+        assert_eq!(parse_size("M"), None);
+        assert_eq!(parse_size(".5M"), None);
+        assert_eq!(parse_size("1..5"), None);
+        assert_eq!(parse_size("10X"), None);
+        assert_eq!(parse_size("10MiBs"), None);
+        assert_eq!(parse_size("99999999999999999999"), None);
+        assert_eq!(parse_size("20000000000T"), None);
+    }
+
+    #[test]
+    fn size_range() {
+        assert_eq!(range("+10M"), (Some(10_000_000), None));
+        assert_eq!(range("-1G"), (None, Some(1_000_000_000)));
+        assert_eq!(range("-1GiB"), (None, Some(1 << 30)));
+        assert_eq!(range("500K..2M"), (Some(500_000), Some(2_000_000)));
+        assert_eq!(range("500KiB..2MiB"), (Some(500 << 10), Some(2 << 20)));
+        assert_eq!(range("1k..1k"), (Some(1000), Some(1000)));
+        assert_eq!(range("1M.."), (Some(1_000_000), None));
+        assert_eq!(range("..1M"), (None, Some(1_000_000)));
+
+        for invalid in [
+            "", "10M", "+", "-", "..", "+-1M", "--1M", "2M..1M", "1M..x", "1M...2M",
+        ] {
+            assert!(invalid.parse::<SizeRange>().is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use indexa::database::{Database, DatabaseMeta, StatusKind};
+
+use chrono::{offset::Local, DateTime};
+use itertools::Itertools;
+use size::{Base, Size, Style};
+use std::{fmt, path::PathBuf};
+
+/// Summary of a database, shown with `ix --stats`.
+#[derive(Debug, PartialEq)]
+pub struct Stats {
+    pub roots: Vec<PathBuf>,
+    pub num_entries: usize,
+    pub num_dirs: usize,
+    pub num_files: usize,
+    pub indexed: Vec<StatusKind>,
+    pub fast_sortable: Vec<StatusKind>,
+    pub name_arena_len: usize,
+    pub file_size: u64,
+    pub metadata: Option<DatabaseMeta>,
+}
+
+impl Stats {
+    pub fn new(database: &Database, file_size: u64) -> Self {
+        Self {
+            roots: database
+                .roots()
+                .map(|(path, _)| path.to_path_buf())
+                .collect(),
+            num_entries: database.num_entries(),
+            num_dirs: database.num_dirs(),
+            num_files: database.num_files(),
+            indexed: database.indexed_statuses().collect(),
+            fast_sortable: database.fast_sortable_statuses().collect(),
+            name_arena_len: database.name_arena_len(),
+            file_size,
+            metadata: database.metadata().cloned(),
+        }
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes = |len| Size::Bytes(len).to_string(Base::Base2, Style::Abbreviated);
+
+        writeln!(
+            f,
+            "Roots:          {}",
+            self.roots.iter().map(|path| path.display()).join(", ")
+        )?;
+        writeln!(f, "Entries:        {}", self.num_entries)?;
+        writeln!(f, "Directories:    {}", self.num_dirs)?;
+        writeln!(f, "Files:          {}", self.num_files)?;
+        writeln!(f, "Indexed:        {}", self.indexed.iter().join(", "))?;
+        writeln!(
+            f,
+            "Fast-sortable:  {}",
+            self.fast_sortable.iter().join(", ")
+        )?;
+        writeln!(f, "Names:          {}", bytes(self.name_arena_len as u64))?;
+        write!(f, "File size:      {}", bytes(self.file_size))?;
+        if let Some(metadata) = &self.metadata {
+            let built_at = DateTime::<Local>::from(metadata.built_at);
+            write!(
+                f,
+                "\nBuilt:          {} on {}",
+                built_at.format("%Y-%m-%d %H:%M:%S"),
+                metadata.hostname.as_deref().unwrap_or("unknown host")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indexa::database::DatabaseBuilder;
+    use std::fs;
+
+    #[test]
+    fn stats() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let root = dunce::canonicalize(tmpdir.path()).unwrap();
+        fs::create_dir_all(root.join("a").join("b")).unwrap();
+        fs::write(root.join("a").join("c.txt"), "foo").unwrap();
+        fs::write(root.join("d"), "bar").unwrap();
+
+        let database = DatabaseBuilder::new()
+            .add_dir(&root)
+            .index(StatusKind::Size)
+            .fast_sort(StatusKind::Size)
+            .build()
+            .unwrap();
+        let location = root.join("database");
+        database.save(&location).unwrap();
+        let file_size = fs::metadata(&location).unwrap().len();
+
+        let root_name_len = root.file_name().unwrap().len();
+        assert_eq!(
+            Stats::new(&database, file_size),
+            Stats {
+                roots: vec![root.clone()],
+                num_entries: 5,
+                num_dirs: 3,
+                num_files: 2,
+                indexed: vec![
+                    StatusKind::Basename,
+                    StatusKind::Path,
+                    StatusKind::Extension,
+                    StatusKind::Size
+                ],
+                fast_sortable: vec![StatusKind::Basename, StatusKind::Size],
+                name_arena_len: root_name_len + "abc.txtd".len(),
+                file_size,
+                metadata: None,
+            }
+        );
+        assert!(!Stats::new(&database, file_size)
+            .to_string()
+            .contains("Built:"));
+
+        let database = DatabaseBuilder::new()
+            .add_dir(&root)
+            .record_metadata(true)
+            .build()
+            .unwrap();
+        let stats = Stats::new(&database, file_size);
+        assert_eq!(stats.metadata.as_ref(), database.metadata());
+        assert!(stats.to_string().contains("\nBuilt:          "));
+    }
+}
@@ -0,0 +1,189 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+use std::time::{Duration, SystemTime};
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+const DATETIME_FORMATS: [&str; 4] = [
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M",
+];
+
+/// Parses a duration such as "7d", "2h30m", or "3 weeks".
+///
+/// Units are s, m, h, d, w, and y, optionally spelled out
+/// (e.g. "min", "hours", "day").
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let invalid = || {
+        anyhow!(
+            "Invalid duration '{}'. Examples of valid durations are '30m', '7d', and '1w 2d'.",
+            s
+        )
+    };
+
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+
+    let mut secs = 0u64;
+    while !rest.is_empty() {
+        let digits_len = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let value: u64 = rest[..digits_len].parse().map_err(|_| invalid())?;
+        rest = rest[digits_len..].trim_start();
+
+        let unit_len = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        let unit_secs = unit_secs(&rest[..unit_len]).ok_or_else(invalid)?;
+        rest = rest[unit_len..].trim_start();
+
+        secs = value
+            .checked_mul(unit_secs)
+            .and_then(|value_secs| secs.checked_add(value_secs))
+            .ok_or_else(invalid)?;
+    }
+
+    Ok(Duration::from_secs(secs))
+}
+
+fn unit_secs(unit: &str) -> Option<u64> {
+    let secs = match unit.to_lowercase().as_str() {
+        "s" | "sec" | "secs" | "second" | "seconds" => 1,
+        "m" | "min" | "mins" | "minute" | "minutes" => 60,
+        "h" | "hr" | "hrs" | "hour" | "hours" => 60 * 60,
+        "d" | "day" | "days" => SECS_PER_DAY,
+        "w" | "week" | "weeks" => 7 * SECS_PER_DAY,
+        "y" | "year" | "years" => 365 * SECS_PER_DAY,
+        _ => return None,
+    };
+    Some(secs)
+}
+
+/// Parses a point in time, which is one of:
+///
+/// - "now", "today", or "yesterday", where the latter two mean the start of the day
+/// - a duration meaning that long before `now`, optionally followed by "ago"
+///   (e.g. "7d" or "2 hours ago")
+/// - a date or date-time in the local time zone (e.g. "2024-01-01" or "2024-01-01 12:00")
+/// - an RFC 3339 date-time (e.g. "2024-01-01T12:00:00+09:00")
+pub fn parse_time(s: &str, now: DateTime<Local>) -> Result<SystemTime> {
+    let trimmed = s.trim();
+    let today = now.naive_local().date();
+    match trimmed.to_lowercase().as_str() {
+        "now" => return Ok(now.into()),
+        "today" => return start_of_day(today),
+        "yesterday" => {
+            let yesterday = today
+                .pred_opt()
+                .ok_or_else(|| anyhow!("Invalid time '{}'", s))?;
+            return start_of_day(yesterday);
+        }
+        _ => (),
+    }
+
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(trimmed) {
+        return Ok(datetime.into());
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(trimmed, "%Y-%m-%d") {
+        return start_of_day(date);
+    }
+    for format in DATETIME_FORMATS.iter() {
+        if let Ok(datetime) = NaiveDateTime::parse_from_str(trimmed, format) {
+            return local_time(datetime);
+        }
+    }
+
+    let duration = trimmed.strip_suffix("ago").unwrap_or(trimmed);
+    let duration = parse_duration(duration).map_err(|_| {
+        anyhow!(
+            "Invalid time '{}'. Examples of valid times are '2024-01-31', '2024-01-31 12:00', \
+             'yesterday', and '7d'.",
+            s
+        )
+    })?;
+    SystemTime::from(now)
+        .checked_sub(duration)
+        .ok_or_else(|| anyhow!("Time '{}' is too far in the past", s))
+}
+
+/// Same as `parse_time`, but relative to the current time.
+pub fn parse_time_from_now(s: &str) -> Result<SystemTime> {
+    parse_time(s, Local::now())
+}
+
+fn start_of_day(date: NaiveDate) -> Result<SystemTime> {
+    local_time(date.and_hms_opt(0, 0, 0).unwrap())
+}
+
+fn local_time(datetime: NaiveDateTime) -> Result<SystemTime> {
+    Local
+        .from_local_datetime(&datetime)
+        .earliest()
+        .map(Into::into)
+        .ok_or_else(|| anyhow!("Time {} does not exist in the local time zone", datetime))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duration() {
+        let secs = |s| parse_duration(s).unwrap().as_secs();
+
+        assert_eq!(secs("30s"), 30);
+        assert_eq!(secs("2h"), 2 * 60 * 60);
+        assert_eq!(secs("7d"), 7 * SECS_PER_DAY);
+        assert_eq!(secs("1w2d"), 9 * SECS_PER_DAY);
+        assert_eq!(secs("1w 2d"), 9 * SECS_PER_DAY);
+        assert_eq!(secs("2h30m"), 150 * 60);
+        assert_eq!(secs("3 weeks"), 21 * SECS_PER_DAY);
+        assert_eq!(secs(" 1 Day "), SECS_PER_DAY);
+        assert_eq!(secs("0s"), 0);
+
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("7").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("7x").is_err());
+        assert!(parse_duration("-7d").is_err());
+        assert!(parse_duration("1.5h").is_err());
+        assert!(parse_duration("99999999999999999999y").is_err());
+    }
+
+    #[test]
+    fn time() {
+        let now = Local.with_ymd_and_hms(2024, 3, 15, 13, 45, 30).unwrap();
+        let local =
+            |y, m, d, h, min| SystemTime::from(Local.with_ymd_and_hms(y, m, d, h, min, 0).unwrap());
+        let parse = |s| parse_time(s, now).unwrap();
+
+        assert_eq!(parse("now"), SystemTime::from(now));
+        assert_eq!(parse("today"), local(2024, 3, 15, 0, 0));
+        assert_eq!(parse("Yesterday"), local(2024, 3, 14, 0, 0));
+        assert_eq!(parse("2024-01-01"), local(2024, 1, 1, 0, 0));
+        assert_eq!(parse("2024-01-01 12:30"), local(2024, 1, 1, 12, 30));
+        assert_eq!(parse("2024-01-01T12:30:00"), local(2024, 1, 1, 12, 30));
+        assert_eq!(
+            parse("2024-01-01T12:30:00Z"),
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1_704_112_200)
+        );
+
+        let day = Duration::from_secs(SECS_PER_DAY);
+        assert_eq!(parse("7d"), SystemTime::from(now) - day * 7);
+        assert_eq!(parse("2 days ago"), SystemTime::from(now) - day * 2);
+        assert_eq!(
+            parse("1h30m"),
+            SystemTime::from(now) - Duration::from_secs(90 * 60)
+        );
+
+        assert!(parse_time("", now).is_err());
+        assert!(parse_time("tomorrow", now).is_err());
+        assert!(parse_time("2024-13-01", now).is_err());
+        assert!(parse_time("01/01/2024", now).is_err());
+    }
+}
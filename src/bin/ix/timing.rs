@@ -0,0 +1,84 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::{env, sync::Mutex};
+
+/// Setting this environment variable to 1 makes ix print how long loading
+/// and searching databases took to stderr.
+const ENV_VAR: &str = "IX_DEBUG_TIMING";
+
+static LOGGER: TimingLogger = TimingLogger {
+    held: Mutex::new(None),
+};
+
+/// Prints timings logged by indexa, or holds them while stderr is taken by the TUI.
+struct TimingLogger {
+    held: Mutex<Option<Vec<String>>>,
+}
+
+impl Log for TimingLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Debug && metadata.target().starts_with("indexa")
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = format!("[{}] {}", ENV_VAR, record.args());
+        match &mut *self.held.lock().unwrap() {
+            Some(held) => held.push(line),
+            None => eprintln!("{}", line),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Starts printing timings if `IX_DEBUG_TIMING` is set to 1.
+pub fn init() {
+    if matches!(env::var(ENV_VAR).as_deref(), Ok("1")) && log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(LevelFilter::Debug);
+    }
+}
+
+/// Holds timings until `release` is called, so that they do not garble the TUI.
+pub fn hold() {
+    LOGGER.held.lock().unwrap().get_or_insert_with(Vec::new);
+}
+
+/// Prints the timings held since `hold`.
+pub fn release() {
+    let held = LOGGER.held.lock().unwrap().take();
+    for line in held.into_iter().flatten() {
+        eprintln!("{}", line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hold_while_tui_is_drawn() {
+        let log = |target| {
+            LOGGER.log(
+                &Record::builder()
+                    .level(Level::Debug)
+                    .target(target)
+                    .args(format_args!("search filter: 1ms"))
+                    .build(),
+            )
+        };
+
+        hold();
+        log("indexa::timing");
+        log("notify");
+        assert_eq!(
+            *LOGGER.held.lock().unwrap(),
+            Some(vec!["[IX_DEBUG_TIMING] search filter: 1ms".to_string()])
+        );
+
+        release();
+        assert!(LOGGER.held.lock().unwrap().is_none());
+    }
+}
@@ -1,28 +1,39 @@
 mod backend;
 mod draw;
 mod handlers;
+pub mod key_bindings;
 mod table;
 mod text_box;
 
 use backend::CustomBackend;
+use key_bindings::KeyBindings;
 use table::TableState;
 use text_box::TextBoxState;
 
-use crate::{config::Config, searcher::Searcher};
+use crate::{
+    config::Config,
+    history::History,
+    saved_searches::SavedSearches,
+    searcher::{Hit, SearchOptions, Searcher},
+    timing,
+    watcher::Watcher,
+};
 
 use indexa::{
-    database::{Database, EntryId},
+    database::{Database, Entry, LoadOptions, StatusKind},
     query::Query,
 };
 
 use anyhow::{Context, Result};
-use bincode::Options;
+use camino::Utf8PathBuf;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture},
+    event::{
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+    },
     terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use std::{io, path::Path, sync::Arc, thread};
-use tui::Terminal;
+use std::{cell::RefCell, collections::HashMap, io, path::Path, sync::Arc, thread, time::Instant};
+use tui::{layout::Rect, Terminal};
 
 pub fn run(config: &Config) -> Result<()> {
     TuiApp::new(config)?.run()
@@ -35,50 +46,111 @@ enum State {
     Ready,
     Searching,
     InvalidQuery(String),
+    Error(String),
     Aborted,
     Accepted,
 }
 
+/// An entry waiting for the user to confirm moving it to the trash.
+struct TrashPrompt {
+    hit: Hit,
+    is_dir: bool,
+    /// true once the extra confirmation for a directory is given
+    confirmed_dir: bool,
+}
+
+/// Results being narrowed down with another query.
+struct Refinement {
+    /// query which produced the results in the first place
+    query: String,
+    hits: Arc<Vec<Hit>>,
+}
+
 struct TuiApp<'a> {
     config: &'a Config,
     status: State,
-    database: Option<Arc<Database>>,
+    // empty until loaded; the first one is at database.location
+    databases: Vec<Arc<Database>>,
     searcher: Option<Searcher>,
     query: Option<Query>,
-    hits: Vec<EntryId>,
+    options: SearchOptions,
+    hits: Vec<Hit>,
+    refinement: Option<Refinement>,
     text_box_state: TextBoxState,
     table_state: TableState,
-    page_scroll_amount: u16,
+    // horizontal offset (in columns) of the selected path, and the entry it is for
+    path_offset: Option<(Hit, usize)>,
+    history: History,
+    saved_searches: SavedSearches,
+    // index of the selected saved search while picking one
+    search_picker: Option<usize>,
+    trash_prompt: Option<TrashPrompt>,
+    // paths moved to the trash; hits at or under them are hidden from later
+    // results since the databases still have them
+    trashed: Vec<Utf8PathBuf>,
+    key_bindings: KeyBindings,
+    rows_area: Rect,
+    last_click: Option<(Instant, usize)>,
+    show_help: bool,
+    // user names resolved from user ids so far
+    owner_names: RefCell<HashMap<u32, String>>,
 }
 
 impl<'a> TuiApp<'a> {
     fn new(config: &'a Config) -> Result<Self> {
+        let history = load_history(config);
+        let saved_searches = match &config.saved_searches.location {
+            Some(location) => {
+                SavedSearches::load(location).context("Failed to load saved searches")?
+            }
+            None => SavedSearches::default(),
+        };
+
         let app = Self {
             config,
             status: State::Loading,
-            database: None,
+            databases: Vec::new(),
             searcher: None,
             query: None,
+            options: SearchOptions::new(config),
             hits: Vec::new(),
-            text_box_state: TextBoxState::with_text(
-                config.flags.query.clone().unwrap_or_else(|| "".to_string()),
-            ),
+            refinement: None,
+            text_box_state: TextBoxState::with_text(config.flags.query.clone().unwrap_or_default()),
             table_state: Default::default(),
-            page_scroll_amount: 0,
+            path_offset: None,
+            history,
+            saved_searches,
+            search_picker: None,
+            trash_prompt: None,
+            trashed: Vec::new(),
+            key_bindings: KeyBindings::new(&config.keybindings),
+            rows_area: Rect::default(),
+            last_click: None,
+            show_help: false,
+            owner_names: RefCell::default(),
         };
 
         Ok(app)
     }
 
     fn run(&mut self) -> Result<()> {
-        let (load_tx, load_rx) = crossbeam_channel::bounded(1);
-        let db_path = self.config.database.location.as_ref().unwrap().clone();
+        let (load_tx, mut load_rx) = crossbeam_channel::bounded(1);
+        let db_paths = std::iter::once(self.config.database.location.as_ref().unwrap())
+            .chain(&self.config.database.extra_locations)
+            .cloned()
+            .collect::<Vec<_>>();
+        let load_options = load_options(self.config);
 
         thread::spawn(move || {
-            load_tx.send(load_database(db_path)).unwrap();
+            let databases = db_paths
+                .iter()
+                .map(|path| load_database(path, &load_options))
+                .collect::<Result<Vec<_>>>();
+            // the receiver is gone if the user quits before loading finishes
+            let _ = load_tx.send(databases);
         });
 
-        let mut terminal = setup_terminal()?;
+        let mut terminal = setup_terminal(!self.config.flags.no_color)?;
 
         let (input_tx, input_rx) = crossbeam_channel::unbounded();
         thread::spawn(move || loop {
@@ -87,14 +159,21 @@ impl<'a> TuiApp<'a> {
             }
         });
 
-        let database = loop {
+        let databases = loop {
             let terminal_width = terminal.size()?.width;
             terminal.draw(|f| self.draw(f, terminal_width))?;
 
             crossbeam_channel::select! {
-                recv(load_rx) -> database => {
-                    self.status = State::Ready;
-                    break Some(database??);
+                recv(load_rx) -> databases => match databases? {
+                    Ok(databases) => {
+                        self.status = State::Ready;
+                        break Some(databases);
+                    }
+                    Err(err) => {
+                        // keep the terminal set up so that the user can read the error
+                        self.status = State::Error(format!("{:#}", err));
+                        load_rx = crossbeam_channel::never();
+                    }
                 },
                 recv(input_rx) -> event => self.handle_input(event?)?,
             }
@@ -108,12 +187,21 @@ impl<'a> TuiApp<'a> {
             }
         };
 
-        if let Some(database) = database {
-            let database = Arc::new(database);
-            self.database = Some(Arc::clone(&database));
+        if let Some(databases) = databases {
+            self.databases = databases.into_iter().map(Arc::new).collect();
 
             let (result_tx, result_rx) = crossbeam_channel::bounded(1);
-            self.searcher = Some(Searcher::new(database, result_tx));
+            self.searcher = Some(Searcher::new(self.databases.clone(), result_tx));
+
+            let (watch_tx, watch_rx) = crossbeam_channel::unbounded();
+            let _watcher = if self.config.flags.watch {
+                Some(
+                    Watcher::new(&self.config.database, self.databases[0].clone(), watch_tx)
+                        .context("Failed to watch directories")?,
+                )
+            } else {
+                None
+            };
 
             self.handle_query_change()?;
 
@@ -124,6 +212,7 @@ impl<'a> TuiApp<'a> {
                 crossbeam_channel::select! {
                     recv(result_rx) -> hits => self.handle_search_result(hits?)?,
                     recv(input_rx) -> event => self.handle_input(event?)?,
+                    recv(watch_rx) -> created => self.handle_created_entries(created?)?,
                 }
 
                 match self.status {
@@ -145,11 +234,29 @@ impl<'a> TuiApp<'a> {
     }
 }
 
-fn setup_terminal() -> Result<Terminal<Backend>> {
+impl TuiApp<'_> {
+    fn entry(&self, hit: Hit) -> Entry<'_> {
+        self.databases[hit.database].entry(hit.id)
+    }
+
+    /// Returns whether hits can be sorted by `sort_by` without sorting them
+    /// on every search.
+    fn is_fast_sortable(&self, sort_by: StatusKind) -> bool {
+        self.databases.iter().all(|db| db.is_fast_sortable(sort_by))
+    }
+}
+
+fn setup_terminal(color: bool) -> Result<Terminal<Backend>> {
     terminal::enable_raw_mode()?;
+    timing::hold();
     let mut stderr = io::stderr();
-    crossterm::execute!(stderr, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CustomBackend::new(stderr);
+    crossterm::execute!(
+        stderr,
+        EnterAlternateScreen,
+        EnableMouseCapture,
+        EnableBracketedPaste
+    )?;
+    let backend = CustomBackend::new(stderr).color(color);
     let mut terminal = Terminal::new(backend)?;
     terminal.hide_cursor()?;
     terminal.clear()?;
@@ -163,18 +270,101 @@ fn cleanup_terminal(terminal: &mut Terminal<Backend>) -> Result<()> {
     crossterm::execute!(
         terminal.backend_mut(),
         LeaveAlternateScreen,
-        DisableMouseCapture
+        DisableMouseCapture,
+        DisableBracketedPaste
     )?;
+    timing::release();
     Ok(())
 }
 
-fn load_database<P>(path: P) -> Result<Database>
+/// Loads query history, starting with empty history if it cannot be loaded.
+///
+/// The history is then not saved, so that the file is left as it is.
+fn load_history(config: &Config) -> History {
+    match &config.history.location {
+        Some(location) if config.history.size > 0 => History::load(location, config.history.size)
+            .unwrap_or_else(|err| {
+                eprintln!(
+                    "Failed to load query history from {}, starting with empty history: {:#}",
+                    location.display(),
+                    err
+                );
+                History::new(config.history.size)
+            }),
+        _ => History::new(0),
+    }
+}
+
+/// Returns options skipping statuses which are neither displayed nor sorted by.
+fn load_options(config: &Config) -> LoadOptions {
+    let statuses = config
+        .ui
+        .columns
+        .iter()
+        .map(|column| column.status)
+        .chain(std::iter::once(config.ui.sort_by))
+        .chain(config.flags.restricted_statuses())
+        // hidden attributes are in mode on Windows, and icons and file type
+        // labels tell file types from mode
+        .chain(
+            (cfg!(windows) || config.ui.icons.enabled || config.ui.extension_fallback)
+                .then(|| StatusKind::Mode),
+        );
+
+    let mut options = LoadOptions::new();
+    options.only(statuses);
+    options
+}
+
+fn load_database<P>(path: P, options: &LoadOptions) -> Result<Database>
 where
     P: AsRef<Path>,
 {
-    bincode::DefaultOptions::new()
-        .with_fixint_encoding()
-        .reject_trailing_bytes()
-        .deserialize(&std::fs::read(path)?)
-        .context("Failed to load database. Try updating the database")
+    let path = path.as_ref();
+    Database::open_with(path, options).with_context(|| {
+        format!(
+            "Failed to load database {}. Try updating the database",
+            path.display()
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corrupt_history() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let location = tmpdir.path().join("history.txt");
+        std::fs::write(&location, b"foo\n\xff\n").unwrap();
+
+        let mut config = Config::for_test();
+        config.history.location = Some(location.clone());
+        config.history.size = 10;
+        let mut history = load_history(&config);
+        assert_eq!(history.prev(), None);
+
+        history.push("bar");
+        history.save().unwrap();
+        assert_eq!(std::fs::read(&location).unwrap(), b"foo\n\xff\n");
+    }
+
+    #[test]
+    fn load_error() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let options = LoadOptions::new();
+
+        let missing = tmpdir.path().join("missing.db");
+        let err = load_database(&missing, &options).unwrap_err();
+        assert!(err.to_string().contains("missing.db"));
+
+        let corrupt = tmpdir.path().join("corrupt.db");
+        std::fs::write(&corrupt, "IXDB").unwrap();
+        let err = load_database(&corrupt, &options).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<indexa::Error>(),
+            Some(indexa::Error::CorruptDatabase)
+        ));
+    }
 }
@@ -39,14 +39,27 @@ use tui::{
     style::{Color, Modifier},
 };
 
-pub struct CustomBackend<W: Write>(CrosstermBackend<W>);
+pub struct CustomBackend<W: Write> {
+    inner: CrosstermBackend<W>,
+    color: bool,
+}
 
 impl<W> CustomBackend<W>
 where
     W: Write,
 {
     pub fn new(buffer: W) -> CustomBackend<W> {
-        Self(CrosstermBackend::new(buffer))
+        Self {
+            inner: CrosstermBackend::new(buffer),
+            color: true,
+        }
+    }
+
+    /// Whether to emit foreground and background colors of cells.
+    /// Modifiers are emitted regardless.
+    pub fn color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
     }
 }
 
@@ -55,11 +68,11 @@ where
     W: Write,
 {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.0.write(buf)
+        self.inner.write(buf)
     }
 
     fn flush(&mut self) -> io::Result<()> {
-        Write::flush(&mut self.0)
+        Write::flush(&mut self.inner)
     }
 }
 
@@ -90,12 +103,12 @@ where
                 diff.queue(&mut buffer)?;
                 modifier = cell.modifier;
             }
-            if cell.fg != fg {
+            if self.color && cell.fg != fg {
                 let color = CColorWrapper::from(cell.fg).0;
                 map_error(queue!(buffer, SetForegroundColor(color)))?;
                 fg = cell.fg;
             }
-            if cell.bg != bg {
+            if self.color && cell.bg != bg {
                 let color = CColorWrapper::from(cell.bg).0;
                 map_error(queue!(buffer, SetBackgroundColor(color)))?;
                 bg = cell.bg;
@@ -105,46 +118,48 @@ where
         }
 
         let string = std::str::from_utf8(&buffer).unwrap();
-        map_error(queue!(
-            self.0,
-            Print(string),
-            SetForegroundColor(CColor::Reset),
-            SetBackgroundColor(CColor::Reset),
-            SetAttribute(CAttribute::Reset)
-        ))
+        map_error(queue!(self.inner, Print(string)))?;
+        if self.color {
+            map_error(queue!(
+                self.inner,
+                SetForegroundColor(CColor::Reset),
+                SetBackgroundColor(CColor::Reset)
+            ))?;
+        }
+        map_error(queue!(self.inner, SetAttribute(CAttribute::Reset)))
     }
 
     fn hide_cursor(&mut self) -> io::Result<()> {
-        self.0.hide_cursor()
+        self.inner.hide_cursor()
     }
 
     fn show_cursor(&mut self) -> io::Result<()> {
-        self.0.show_cursor()
+        self.inner.show_cursor()
     }
 
     fn get_cursor(&mut self) -> io::Result<(u16, u16)> {
-        self.0.get_cursor()
+        self.inner.get_cursor()
     }
 
     fn set_cursor(&mut self, x: u16, y: u16) -> io::Result<()> {
-        self.0.set_cursor(x, y)
+        self.inner.set_cursor(x, y)
     }
 
     fn clear(&mut self) -> io::Result<()> {
-        self.0.clear()
+        self.inner.clear()
     }
 
     fn size(&self) -> io::Result<Rect> {
-        self.0.size()
+        self.inner.size()
     }
 
     fn flush(&mut self) -> io::Result<()> {
-        Backend::flush(&mut self.0)
+        Backend::flush(&mut self.inner)
     }
 }
 
 fn map_error(error: crossterm::Result<()>) -> io::Result<()> {
-    error.map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))
+    error.map_err(|e| io::Error::other(e.to_string()))
 }
 
 struct CColorWrapper(CColor);
@@ -243,3 +258,40 @@ impl ModifierDiff {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tui::{buffer::Buffer, style::Style};
+
+    fn draw(color: bool) -> String {
+        let mut buf = Buffer::empty(Rect::new(0, 0, 4, 2));
+        buf.set_string(0, 0, "ab", Style::default().fg(Color::Red).bg(Color::Blue));
+        buf.set_string(0, 1, "cd", Style::default().add_modifier(Modifier::BOLD));
+
+        let mut output = Vec::new();
+        let mut backend = CustomBackend::new(&mut output).color(color);
+        let empty = Buffer::empty(buf.area);
+        backend.draw(empty.diff(&buf).into_iter()).unwrap();
+        Backend::flush(&mut backend).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn no_color() {
+        let is_color_code = |s: &str| {
+            ["\x1b[38;", "\x1b[48;", "\x1b[39m", "\x1b[49m"]
+                .iter()
+                .any(|code| s.contains(code))
+        };
+
+        let colored = draw(true);
+        assert!(is_color_code(&colored));
+
+        let plain = draw(false);
+        assert!(!is_color_code(&plain));
+        assert!(plain.contains("ab"));
+        assert!(plain.contains("cd"));
+        assert!(plain.contains("\x1b[1m"));
+    }
+}
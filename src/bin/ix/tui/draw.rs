@@ -1,27 +1,54 @@
 use super::{
     table::{HighlightableText, Row, Table},
     text_box::TextBox,
-    Backend, State, TuiApp,
+    State, TuiApp,
+};
+use crate::{
+    config::{Column, ColumnWidth, ComponentStyle},
+    searcher::Hit,
 };
 
 use indexa::{
-    database::{Entry, EntryId, StatusKind},
-    mode::Mode,
+    database::{Entry, StatusKind},
+    mode::{FileType, Mode},
     query::{Query, SortOrder},
 };
 
+use camino::Utf8Path;
 use chrono::{offset::Local, DateTime};
 use std::{ops::Range, time::SystemTime};
 use tui::{
+    backend::Backend,
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
-    text::Span,
-    widgets::Paragraph,
+    text::{Span, Spans},
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
     Frame,
 };
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Drawn in place of the UI when the terminal is smaller than
+/// `ui.min_width` x `ui.min_height`.
+const TOO_SMALL_MESSAGE: &str = "Terminal too small";
+
+/// Shown in place of statuses which are indexed but not loaded.
+const UNLOADED_PLACEHOLDER: &str = "?";
+
+/// Drawn in place of the table when all the loaded databases have no entries.
+const EMPTY_DATABASE_MESSAGE: &str =
+    "Database is empty. Check dirs in the config and run `ix --update`.";
 
 impl<'a> TuiApp<'a> {
-    pub fn draw(&mut self, f: &mut Frame<Backend>, terminal_width: u16) {
+    pub fn draw<B: Backend>(&mut self, f: &mut Frame<B>, terminal_width: u16) {
+        let size = f.size();
+        if size.width < self.config.ui.min_width || size.height < self.config.ui.min_height {
+            self.rows_area = Rect::default();
+            let paragraph = Paragraph::new(Span::raw(TOO_SMALL_MESSAGE)).wrap(Wrap { trim: true });
+            f.render_widget(paragraph, size);
+            return;
+        }
+
         let chunks = Layout::default()
             .constraints([
                 Constraint::Min(1),
@@ -32,34 +59,38 @@ impl<'a> TuiApp<'a> {
             .split(f.size());
 
         // hits table
-        self.draw_table(f, chunks[0], terminal_width);
+        if !self.databases.is_empty() && self.databases.iter().all(|db| db.is_empty()) {
+            self.rows_area = Rect::default();
+            let paragraph =
+                Paragraph::new(Span::raw(EMPTY_DATABASE_MESSAGE)).wrap(Wrap { trim: true });
+            f.render_widget(paragraph, chunks[0]);
+        } else {
+            self.draw_table(f, chunks[0], terminal_width);
+        }
+
+        if self.show_help {
+            self.draw_help(f, chunks[0]);
+        }
+        if let Some(selected) = self.search_picker {
+            self.draw_search_picker(f, chunks[0], selected);
+        }
 
         // status bar
         self.draw_status_bar(f, chunks[1]);
 
         // path of selected row
-        let text = Span::raw(
-            self.hits
-                .get(self.table_state.selected())
-                .map(|id| {
-                    self.database
-                        .as_ref()
-                        .unwrap()
-                        .entry(*id)
-                        .path()
-                        .as_str()
-                        .to_owned()
-                })
-                .unwrap_or_default(),
-        );
-        let paragraph = Paragraph::new(text);
+        let selected = self.hits.get(self.table_state.selected()).copied();
+        let text = selected
+            .map(|hit| self.scrolled_path(hit, chunks[2].width as usize))
+            .unwrap_or_default();
+        let paragraph = Paragraph::new(Span::raw(text));
         f.render_widget(paragraph, chunks[2]);
 
         // input box
         let text_box = TextBox::new()
             .highlight_style(Style::default().fg(Color::Black).bg(Color::White))
             .prompt(Span::styled(
-                "> ",
+                self.config.ui.prompt.as_str(),
                 Style::default()
                     .fg(self.config.ui.colors.prompt)
                     .add_modifier(Modifier::BOLD),
@@ -67,128 +98,297 @@ impl<'a> TuiApp<'a> {
         f.render_stateful_widget(text_box, chunks[3], &mut self.text_box_state);
     }
 
-    fn draw_table(&mut self, f: &mut Frame<Backend>, area: Rect, terminal_width: u16) {
+    /// Returns the part of the path of the entry which fits in `width`.
+    ///
+    /// Unless scrolled by the user, the view follows the first match in the path.
+    fn scrolled_path(&mut self, hit: Hit, width: usize) -> String {
+        let (path, matches) = self.display_path(&self.entry(hit), self.query.as_ref());
+        let max_offset = path.width().saturating_sub(width);
+
+        let offset = match self.path_offset {
+            Some((scrolled_hit, offset)) if scrolled_hit == hit => offset.min(max_offset),
+            _ => matches
+                .first()
+                .map(|range| path[..range.end].width().saturating_sub(width))
+                .unwrap_or(0),
+        };
+        self.path_offset = Some((hit, offset));
+
+        slice_by_width(&path, offset, width).to_owned()
+    }
+
+    /// Returns the path of the entry to display and ranges of matches in it.
+    ///
+    /// With `ui.relative_paths`, the path is relative to the root the entry
+    /// was indexed under.
+    fn display_path(&self, entry: &Entry, query: Option<&Query>) -> (String, Vec<Range<usize>>) {
+        let path = entry.path();
+        let matches = query
+            .map(|query| query.path_matches(entry))
+            .unwrap_or_default();
+        if !self.config.ui.relative_paths {
+            return (path.into_string(), matches);
+        }
+
+        let (relative, stripped_len) = relative_path(&path, &entry.root().path());
+        (relative, shift_ranges(matches, stripped_len))
+    }
+
+    /// Converts ranges in the path of the entry to those in the path
+    /// returned from `display_path`.
+    fn display_ranges(&self, entry: &Entry, ranges: Vec<Range<usize>>) -> Vec<Range<usize>> {
+        if !self.config.ui.relative_paths {
+            return ranges;
+        }
+
+        let (_, stripped_len) = relative_path(&entry.path(), &entry.root().path());
+        shift_ranges(ranges, stripped_len)
+    }
+
+    /// Returns the style of the row of the entry when it is not selected.
+    fn row_style(&self, entry: &Entry) -> Style {
+        if self.config.ui.dim_hidden && entry.is_hidden() {
+            self.config.ui.colors.hidden_style()
+        } else {
+            Style::default()
+        }
+    }
+
+    fn draw_table<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect, terminal_width: u16) {
         let columns = &self.config.ui.columns;
 
+        let is_fast_sortable = self.is_fast_sortable(self.options.sort_by);
         let header = columns.iter().map(|column| {
-            if column.status == self.config.ui.sort_by {
-                match self.config.ui.sort_order {
-                    SortOrder::Ascending => format!("{}▲", column.status),
-                    SortOrder::Descending => format!("{}▼", column.status),
-                }
+            if column.status == self.options.sort_by {
+                format!(
+                    "{}{}",
+                    column.status,
+                    sort_indicator(self.options.sort_order, is_fast_sortable)
+                )
             } else {
                 column.status.to_string()
             }
         });
 
         #[allow(clippy::needless_collect)] // false positive
-        let display_func = |id: &EntryId| {
-            let entry = self.database.as_ref().unwrap().entry(*id);
+        let display_func = |hit: &Hit| {
+            let entry = self.entry(*hit);
             let contents = columns
                 .iter()
                 .map(|column| {
                     self.format_column_content(&column.status, &entry, self.query.as_ref().unwrap())
                 })
                 .collect::<Vec<_>>();
-            Row::new(contents.into_iter())
+            Row::new(contents.into_iter()).style(self.row_style(&entry))
         };
 
-        let (num_fixed, sum_widths) =
-            columns
-                .iter()
-                .fold((0, 0), |(num_fixed, sum_widths), column| {
-                    if let Some(width) = column.width {
-                        (num_fixed + 1, sum_widths + width)
-                    } else {
-                        (num_fixed, sum_widths)
-                    }
-                });
-        let remaining_width = terminal_width - sum_widths;
-        let num_flexible = columns.len() as u16 - num_fixed;
-        let flexible_width = remaining_width.checked_div(num_flexible);
-        let widths = columns
+        let widths = column_constraints(columns, terminal_width);
+
+        let alignments = columns
             .iter()
-            .map(|column| {
-                if let Some(width) = column.width {
-                    Constraint::Length(width)
-                } else {
-                    Constraint::Min(flexible_width.unwrap())
-                }
+            .map(|column| match (column.align, column.status) {
+                (Some(align), _) => align.into(),
+                (None, StatusKind::Size) => Alignment::Right,
+                (None, _) => Alignment::Left,
             })
             .collect::<Vec<_>>();
 
-        let alignments = columns
+        let column_styles = columns
             .iter()
-            .map(|column| match column.status {
-                StatusKind::Size => Alignment::Right,
-                _ => Alignment::Left,
+            .map(|column| match column.color {
+                Some(color) => Style::default().fg(color),
+                None => Style::default(),
             })
             .collect::<Vec<_>>();
 
         let table = Table::new(header, self.hits.iter(), display_func)
             .widths(&widths)
             .alignments(&alignments)
+            .column_styles(&column_styles)
             .selected_style(
                 Style::default()
                     .fg(self.config.ui.colors.selected_fg)
                     .bg(self.config.ui.colors.selected_bg),
             )
-            .highlight_style(
-                Style::default()
-                    .fg(self.config.ui.colors.matched_fg)
-                    .bg(self.config.ui.colors.matched_bg),
-            )
-            .selected_highlight_style(
-                Style::default()
-                    .fg(self.config.ui.colors.matched_fg)
-                    .bg(self.config.ui.colors.matched_bg),
+            .highlight_style(self.config.ui.colors.highlight_style())
+            .selected_highlight_style(self.config.ui.colors.highlight_style())
+            .component_style(
+                self.config
+                    .ui
+                    .colors
+                    .component_highlight_style()
+                    .unwrap_or_default(),
             )
+            .alternate_style(Style::default().bg(self.config.ui.colors.alt_row_bg))
             .selected_symbol("> ")
-            .header_gap(1)
+            .scrollbar(true)
+            .header_gap(self.config.ui.header_gap)
             .column_spacing(self.config.ui.column_spacing);
 
         let mut table_state = self.table_state.clone();
         f.render_stateful_widget(table, area, &mut table_state);
         self.table_state = table_state;
 
-        self.page_scroll_amount = area
-            .height
-            .saturating_sub(
-                // header
-                1 +
-                // header_gap
-                1 +
-                // one less than page height
-                1,
-            )
-            .max(1);
-    }
-
-    fn draw_status_bar(&self, f: &mut Frame<Backend>, area: Rect) {
-        let message = match &self.status {
-            State::Loading => Span::raw("Loading database"),
-            State::Searching => Span::raw("Searching"),
-            State::Ready | State::Aborted | State::Accepted => Span::raw("Ready"),
-            State::InvalidQuery(msg) => Span::styled(
-                msg,
-                Style::default().fg(self.config.ui.colors.error_fg).bg(self
-                    .config
-                    .ui
-                    .colors
-                    .error_bg),
-            ),
+        // area where rows are drawn, excluding header and header_gap
+        let header_height = (1 + self.config.ui.header_gap).min(area.height);
+        self.rows_area = Rect {
+            y: area.y + header_height,
+            height: area.height - header_height,
+            ..area
+        };
+    }
+
+    fn draw_help<B: Backend>(&self, f: &mut Frame<B>, area: Rect) {
+        let keys = self
+            .key_bindings
+            .iter()
+            .map(|(_, bindings)| {
+                bindings
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            })
+            .collect::<Vec<_>>();
+        let keys_width = keys.iter().map(|k| k.len()).max().unwrap_or(0);
+
+        let lines = self
+            .key_bindings
+            .iter()
+            .zip(keys.iter())
+            .map(|((action, _), keys)| {
+                Spans::from(vec![
+                    Span::styled(
+                        format!("{:<width$}  ", keys, width = keys_width),
+                        Style::default().add_modifier(Modifier::BOLD),
+                    ),
+                    Span::raw(action.description()),
+                ])
+            })
+            .collect::<Vec<_>>();
+
+        self.draw_popup(f, area, " Key bindings ", lines);
+    }
+
+    fn draw_search_picker<B: Backend>(&self, f: &mut Frame<B>, area: Rect, selected: usize) {
+        let selected_style = Style::default()
+            .fg(self.config.ui.colors.selected_fg)
+            .bg(self.config.ui.colors.selected_bg);
+        let lines = self
+            .saved_searches
+            .iter()
+            .enumerate()
+            .map(|(i, search)| {
+                let style = if i == selected {
+                    selected_style
+                } else {
+                    Style::default()
+                };
+                let mut spans = vec![Span::styled(search.name.clone(), style)];
+                if search.pattern != search.name {
+                    spans.push(Span::styled(
+                        format!("  {}", search.pattern),
+                        style.add_modifier(Modifier::DIM),
+                    ));
+                }
+                Spans::from(spans)
+            })
+            .collect::<Vec<_>>();
+
+        self.draw_popup(f, area, " Saved searches ", lines);
+    }
+
+    /// Draws `lines` in a bordered box at the center of `area`.
+    fn draw_popup<B: Backend>(&self, f: &mut Frame<B>, area: Rect, title: &str, lines: Vec<Spans>) {
+        let width = lines
+            .iter()
+            .map(|line| line.width() as u16)
+            .chain(std::iter::once(title.width() as u16))
+            .max()
+            .unwrap_or(0)
+            .saturating_add(2) // borders
+            .min(area.width);
+        let height = (lines.len() as u16).saturating_add(2).min(area.height);
+        let popup_area = Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + (area.height - height) / 2,
+            width,
+            height,
         };
 
-        let counter = self
-            .database
-            .as_ref()
-            .map(|db| format!("{} / {}", self.hits.len(), db.num_entries()))
-            .unwrap_or_else(|| "".to_string());
+        let paragraph = Paragraph::new(lines).block(
+            Block::default().borders(Borders::ALL).title(Span::styled(
+                title,
+                Style::default()
+                    .fg(self.config.ui.colors.prompt)
+                    .add_modifier(Modifier::BOLD),
+            )),
+        );
+        f.render_widget(Clear, popup_area);
+        f.render_widget(paragraph, popup_area);
+    }
+
+    fn draw_status_bar<B: Backend>(&self, f: &mut Frame<B>, area: Rect) {
+        let error_style =
+            Style::default()
+                .fg(self.config.ui.colors.error_fg)
+                .bg(self.config.ui.colors.error_bg);
+
+        let message = match (&self.trash_prompt, &self.status) {
+            (Some(prompt), _) => {
+                let path = self.entry(prompt.hit).path();
+                let message = match (prompt.is_dir, prompt.confirmed_dir) {
+                    (false, _) => format!("Move {} to trash? (y/N)", path),
+                    (true, false) => format!("Move directory {} to trash? (y/N)", path),
+                    (true, true) => format!(
+                        "{} and everything in it will be moved to trash. Are you sure? (y/N)",
+                        path
+                    ),
+                };
+                Span::styled(message, error_style)
+            }
+            (None, State::Loading) => Span::raw("Loading database"),
+            (None, State::Searching) => {
+                if self.is_fast_sortable(self.options.sort_by) {
+                    Span::raw("Searching")
+                } else {
+                    Span::raw(format!(
+                        "Searching (sorting by {} may be slow; enable fast_sort for it)",
+                        self.options.sort_by
+                    ))
+                }
+            }
+            (None, State::Ready | State::Aborted | State::Accepted) => match &self.refinement {
+                Some(refinement) => Span::raw(format!(
+                    "Searching within results of \"{}\"",
+                    refinement.query
+                )),
+                None => Span::raw("Ready"),
+            },
+            (None, State::InvalidQuery(msg) | State::Error(msg)) => {
+                Span::styled(msg.as_str(), error_style)
+            }
+        };
+
+        let counter = if self.databases.is_empty() {
+            "".to_string()
+        } else {
+            let total = match &self.refinement {
+                Some(refinement) => refinement.hits.len(),
+                None => self.databases.iter().map(|db| db.num_entries()).sum(),
+            };
+            format_status(
+                &self.config.ui.status_format,
+                self.hits.len(),
+                total,
+                self.text_box_state.text(),
+            )
+        };
 
         let chunks = Layout::default()
             .constraints([
                 Constraint::Min(1),
-                Constraint::Length(counter.len() as u16 + 1),
+                Constraint::Length(counter.width() as u16 + 1),
             ])
             .direction(Direction::Horizontal)
             .split(area);
@@ -207,22 +407,42 @@ impl<'a> TuiApp<'a> {
         entry: &Entry,
         query: &Query,
     ) -> HighlightableText<impl Iterator<Item = Range<usize>>> {
+        // drawing should not look up the file system for every row
+        if entry.database().is_unloaded(*kind) {
+            return UNLOADED_PLACEHOLDER.to_string().into();
+        }
+
         match kind {
-            StatusKind::Basename => HighlightableText::Highlighted(
-                entry.basename().to_owned(),
-                query.basename_matches(entry).into_iter(),
-            ),
-            StatusKind::Path => HighlightableText::Highlighted(
-                entry.path().as_str().to_owned(),
-                query.path_matches(entry).into_iter(),
-            ),
-            StatusKind::Extension => entry
-                .extension()
-                .map(|s| s.to_string().into())
-                .unwrap_or_default(),
+            StatusKind::Basename => {
+                let mut text = entry.basename().to_owned();
+                let mut matches = query.basename_matches(entry);
+                if self.config.ui.icons.enabled {
+                    prepend_icon(self.icon(entry), &mut text, &mut matches);
+                }
+                HighlightableText::Highlighted(text, matches.into_iter())
+            }
+            StatusKind::Path => {
+                let (text, matches) = self.display_path(entry, Some(query));
+                if self.config.ui.colors.component_style == ComponentStyle::None {
+                    return HighlightableText::Highlighted(text, matches.into_iter());
+                }
+                let components = self.display_ranges(entry, query.path_match_components(entry));
+                HighlightableText::HighlightedComponents(
+                    text,
+                    matches.into_iter(),
+                    components.into_iter(),
+                )
+            }
+            StatusKind::Extension => match entry.extension() {
+                Some(ext) => ext.to_string().into(),
+                None if self.config.ui.extension_fallback => file_type_label(entry)
+                    .map(|label| label.to_string().into())
+                    .unwrap_or_default(),
+                None => Default::default(),
+            },
             StatusKind::Size => entry
                 .size()
-                .map(|size| self.format_size(size, entry.is_dir()).into())
+                .map(|size| self.format_size(size, entry.size_is_item_count()).into())
                 .unwrap_or_default(),
             StatusKind::Mode => entry
                 .mode()
@@ -240,11 +460,45 @@ impl<'a> TuiApp<'a> {
                 .accessed()
                 .map(|accessed| self.format_datetime(accessed).into())
                 .unwrap_or_default(),
+            StatusKind::Owner => self.format_owner(entry).into(),
         }
     }
 
-    fn format_size(&self, size: u64, is_dir: bool) -> String {
-        if is_dir {
+    fn icon(&self, entry: &Entry) -> &str {
+        let icons = &self.config.ui.icons;
+        // without indexed modes, telling symlinks would look up every row
+        if entry.database().is_indexed(StatusKind::Mode) && entry.is_symlink().unwrap_or(false) {
+            return &icons.symlink;
+        }
+        if entry.is_dir() {
+            return &icons.directory;
+        }
+        entry
+            .extension()
+            .and_then(|ext| icons.extensions.get(&ext.to_lowercase()))
+            .unwrap_or(&icons.file)
+    }
+
+    /// Formats size, mode, and modified time of the entry in one line,
+    /// the same way as the columns.
+    pub(super) fn format_long(&self, entry: &Entry) -> String {
+        let size = entry
+            .size()
+            .map(|size| self.format_size(size, entry.size_is_item_count()))
+            .unwrap_or_else(|_| "-".to_string());
+        let mode = entry
+            .mode()
+            .map(|mode| self.format_mode(mode))
+            .unwrap_or_else(|_| "-".to_string());
+        let modified = entry
+            .modified()
+            .map(|modified| self.format_datetime(modified))
+            .unwrap_or_else(|_| "-".to_string());
+        format!("{}  {}  {}", size, mode, modified)
+    }
+
+    fn format_size(&self, size: u64, is_item_count: bool) -> String {
+        if is_item_count {
             if size == 1 {
                 format!("{} item", size)
             } else {
@@ -257,6 +511,19 @@ impl<'a> TuiApp<'a> {
         }
     }
 
+    /// Formats the owner as a user name, or "-" if the owner is unavailable.
+    fn format_owner(&self, entry: &Entry) -> String {
+        match entry.owner() {
+            Ok(uid) => self
+                .owner_names
+                .borrow_mut()
+                .entry(uid)
+                .or_insert_with(|| owner_name(uid))
+                .clone(),
+            Err(_) => "-".to_string(),
+        }
+    }
+
     fn format_mode(&self, mode: Mode) -> String {
         #[cfg(unix)]
         {
@@ -284,3 +551,482 @@ impl<'a> TuiApp<'a> {
         datetime.format(&self.config.ui.datetime_format).to_string()
     }
 }
+
+/// Returns the marker appended to the name of the column hits are sorted by.
+///
+/// "~" warns that hits are sorted on every search, which is slow on broad queries.
+fn sort_indicator(sort_order: SortOrder, is_fast_sortable: bool) -> &'static str {
+    match (sort_order, is_fast_sortable) {
+        (SortOrder::Ascending, true) => "▲",
+        (SortOrder::Descending, true) => "▼",
+        (SortOrder::Ascending, false) => "~▲",
+        (SortOrder::Descending, false) => "~▼",
+    }
+}
+
+/// Slices `s` to the graphemes which lie within `width` columns from `offset` columns.
+///
+/// Wide graphemes crossing the boundaries are excluded.
+fn slice_by_width(s: &str, offset: usize, width: usize) -> &str {
+    let mut start = s.len();
+    let mut end = s.len();
+    let mut x = 0;
+    for (i, grapheme) in s.grapheme_indices(true) {
+        if x >= offset && start == s.len() {
+            start = i;
+        }
+        x += grapheme.width();
+        if x > offset + width {
+            end = i;
+            break;
+        }
+    }
+    if start > end {
+        return "";
+    }
+    &s[start..end]
+}
+
+/// Returns `path` relative to `root` and the number of bytes stripped from its front.
+///
+/// The root itself is shown as `.`.
+fn relative_path(path: &Utf8Path, root: &Utf8Path) -> (String, usize) {
+    match path.strip_prefix(root) {
+        Ok(relative) if relative.as_str().is_empty() => (".".to_owned(), path.as_str().len()),
+        Ok(relative) => (
+            relative.as_str().to_owned(),
+            path.as_str().len() - relative.as_str().len(),
+        ),
+        Err(_) => (path.as_str().to_owned(), 0),
+    }
+}
+
+/// Shifts ranges to the left by `offset`, dropping the parts before it.
+fn shift_ranges(ranges: Vec<Range<usize>>, offset: usize) -> Vec<Range<usize>> {
+    ranges
+        .into_iter()
+        .filter(|range| range.end > offset)
+        .map(|range| range.start.saturating_sub(offset)..range.end - offset)
+        .collect()
+}
+
+/// Expands placeholders in `ui.status_format`.
+fn format_status(format: &str, matched: usize, total: usize, query: &str) -> String {
+    format
+        .replace("{matched}", &matched.to_string())
+        .replace("{total}", &total.to_string())
+        .replace("{query}", query)
+}
+
+/// Prepends an icon to the text, shifting the matched ranges accordingly.
+fn prepend_icon(icon: &str, text: &mut String, matches: &mut [Range<usize>]) {
+    let prefix = format!("{} ", icon);
+    text.insert_str(0, &prefix);
+    for range in matches {
+        *range = range.start + prefix.len()..range.end + prefix.len();
+    }
+}
+
+/// Resolves a user name from a user id, falling back to the id itself.
+fn owner_name(uid: u32) -> String {
+    #[cfg(unix)]
+    {
+        if let Some(user) = uzers::get_user_by_uid(uid) {
+            return user.name().to_string_lossy().into_owned();
+        }
+    }
+
+    uid.to_string()
+}
+
+/// Returns a label of the file type for entries other than regular files.
+fn file_type_label(entry: &Entry) -> Option<&'static str> {
+    let mode = if entry.database().is_unloaded(StatusKind::Mode) {
+        None
+    } else {
+        entry.mode().ok()
+    };
+    let file_type = match mode {
+        Some(mode) => mode.file_type(),
+        None if entry.is_dir() => FileType::Directory,
+        None => return None,
+    };
+    (file_type != FileType::Regular).then(|| file_type.label())
+}
+
+/// Returns width constraints of columns on a screen of `terminal_width` cells.
+///
+/// Screen width not taken by columns with widths is evenly distributed
+/// among other columns.
+fn column_constraints(columns: &[Column], terminal_width: u16) -> Vec<Constraint> {
+    let (num_fixed, sum_widths) =
+        columns
+            .iter()
+            .fold((0, 0u16), |(num_fixed, sum_widths), column| {
+                if let Some(width) = column.width {
+                    (
+                        num_fixed + 1,
+                        sum_widths.saturating_add(width.resolve(terminal_width)),
+                    )
+                } else {
+                    (num_fixed, sum_widths)
+                }
+            });
+    let remaining_width = terminal_width.saturating_sub(sum_widths);
+    let num_flexible = columns.len() as u16 - num_fixed;
+    let flexible_width = remaining_width.checked_div(num_flexible);
+    columns
+        .iter()
+        .map(|column| match column.width {
+            Some(ColumnWidth::Fixed(width)) => Constraint::Length(width),
+            Some(ColumnWidth::Percentage(p)) => Constraint::Percentage(p),
+            Some(ColumnWidth::Ratio(n, d)) => Constraint::Ratio(n, d),
+            None => Constraint::Min(flexible_width.unwrap()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sort_indicator_for_order() {
+        assert_eq!(sort_indicator(SortOrder::Ascending, true), "▲");
+        assert_eq!(sort_indicator(SortOrder::Descending, true), "▼");
+        assert_eq!(sort_indicator(SortOrder::Ascending, false), "~▲");
+        assert_eq!(sort_indicator(SortOrder::Descending, false), "~▼");
+    }
+
+    #[test]
+    fn strip_root() {
+        assert_eq!(
+            relative_path(Utf8Path::new("/foo/bar/baz"), Utf8Path::new("/foo")),
+            ("bar/baz".to_owned(), 5)
+        );
+        assert_eq!(
+            relative_path(Utf8Path::new("/foo/bar"), Utf8Path::new("/")),
+            ("foo/bar".to_owned(), 1)
+        );
+        assert_eq!(
+            relative_path(Utf8Path::new("/foo"), Utf8Path::new("/foo")),
+            (".".to_owned(), 4)
+        );
+        assert_eq!(
+            relative_path(Utf8Path::new("/foobar"), Utf8Path::new("/foo")),
+            ("/foobar".to_owned(), 0)
+        );
+    }
+
+    #[test]
+    fn shift_match_ranges() {
+        assert_eq!(shift_ranges(vec![5..8, 10..12], 5), [0..3, 5..7]);
+        assert_eq!(shift_ranges(vec![1..4, 3..8, 9..10], 5), [0..3, 4..5]);
+        assert!(shift_ranges(vec![0..2, 3..5], 5).is_empty());
+        assert_eq!(shift_ranges(vec![0..2, 3..5], 0), [0..2, 3..5]);
+    }
+
+    #[test]
+    fn constraints() {
+        let column = |width| Column {
+            status: StatusKind::Basename,
+            width,
+            align: None,
+            color: None,
+        };
+
+        assert_eq!(
+            column_constraints(
+                &[
+                    column(Some(ColumnWidth::Fixed(10))),
+                    column(Some(ColumnWidth::Percentage(50))),
+                    column(None),
+                    column(Some(ColumnWidth::Ratio(1, 10))),
+                    column(None),
+                ],
+                100
+            ),
+            [
+                Constraint::Length(10),
+                Constraint::Percentage(50),
+                Constraint::Min(15),
+                Constraint::Ratio(1, 10),
+                Constraint::Min(15),
+            ]
+        );
+
+        // columns with widths can take more than the screen
+        assert_eq!(
+            column_constraints(&[column(Some(ColumnWidth::Fixed(200))), column(None)], 100),
+            [Constraint::Length(200), Constraint::Min(0)]
+        );
+    }
+
+    #[test]
+    fn slice_path_by_width() {
+        assert_eq!(slice_by_width("/foo/bar", 0, 4), "/foo");
+        assert_eq!(slice_by_width("/foo/bar", 4, 4), "/bar");
+        assert_eq!(slice_by_width("/foo/bar", 4, 10), "/bar");
+        assert_eq!(slice_by_width("/foo/bar", 8, 4), "");
+        assert_eq!(slice_by_width("/foo/bar", 10, 4), "");
+        assert_eq!(slice_by_width("", 0, 4), "");
+
+        // "あ" and "亜" are 3 bytes long and 2 columns wide
+        assert_eq!(slice_by_width("/あ/亜", 0, 3), "/あ");
+        assert_eq!(slice_by_width("/あ/亜", 0, 2), "/");
+        assert_eq!(slice_by_width("/あ/亜", 1, 3), "あ/");
+        assert_eq!(slice_by_width("/あ/亜", 2, 4), "/亜");
+        assert_eq!(slice_by_width("/あ/亜", 3, 2), "/");
+        assert_eq!(slice_by_width("/あ/亜", 4, 2), "亜");
+
+        // combining characters form a single grapheme
+        assert_eq!(slice_by_width("/e\u{301}/", 1, 1), "e\u{301}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_type_fallback() {
+        use indexa::database::DatabaseBuilder;
+        use std::{fs, os::unix::fs::PermissionsExt};
+
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = dunce::canonicalize(tmpdir.path()).unwrap();
+        fs::create_dir(path.join("dir")).unwrap();
+        fs::write(path.join("run"), "").unwrap();
+        fs::set_permissions(path.join("run"), fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(path.join("plain"), "").unwrap();
+        std::os::unix::fs::symlink(path.join("plain"), path.join("link")).unwrap();
+
+        let label = |database: &indexa::database::Database, name| {
+            let id = database.get_by_path(path.join(name)).unwrap();
+            file_type_label(&database.entry(id))
+        };
+
+        let database = DatabaseBuilder::new()
+            .add_dir(&path)
+            .index(StatusKind::Mode)
+            .build()
+            .unwrap();
+        assert_eq!(label(&database, "dir"), Some("dir"));
+        assert_eq!(label(&database, "link"), Some("link"));
+        assert_eq!(label(&database, "run"), Some("exec"));
+        assert_eq!(label(&database, "plain"), None);
+
+        // directories are told even if mode is unavailable
+        let database = DatabaseBuilder::new().add_dir(&path).build().unwrap();
+        fs::remove_dir(path.join("dir")).unwrap();
+        fs::remove_file(path.join("run")).unwrap();
+        assert_eq!(label(&database, "dir"), Some("dir"));
+        assert_eq!(label(&database, "run"), None);
+
+        // symlinks are told only if mode is indexed
+        let config = crate::config::Config::for_test();
+        let app = TuiApp::new(&config).unwrap();
+        let icon = |database: &indexa::database::Database| {
+            let id = database.get_by_path(path.join("link")).unwrap();
+            app.icon(&database.entry(id)).to_owned()
+        };
+        assert_eq!(icon(&database), config.ui.icons.file);
+        let database = DatabaseBuilder::new()
+            .add_dir(&path)
+            .index(StatusKind::Mode)
+            .build()
+            .unwrap();
+        assert_eq!(icon(&database), config.ui.icons.symlink);
+    }
+
+    #[test]
+    fn unloaded_placeholder() {
+        use crate::config::Config;
+        use indexa::{
+            database::{Database, DatabaseBuilder, LoadOptions},
+            query::QueryBuilder,
+        };
+
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = dunce::canonicalize(tmpdir.path()).unwrap();
+        std::fs::write(path.join("foo"), "foo").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(path.join("foo"), path.join("link")).unwrap();
+        let location = path.join("database.db");
+        DatabaseBuilder::new()
+            .add_dir(&path)
+            .index(StatusKind::Size)
+            .index(StatusKind::Mode)
+            .build()
+            .unwrap()
+            .save(&location)
+            .unwrap();
+        let database =
+            Database::open_with(&location, LoadOptions::new().only([StatusKind::Basename]))
+                .unwrap();
+
+        let config = Config::for_test();
+        let app = TuiApp::new(&config).unwrap();
+        let query = QueryBuilder::new("").build().unwrap();
+        let text = |name, kind| {
+            let entry = database.entry(database.get_by_path(path.join(name)).unwrap());
+            match app.format_column_content(&kind, &entry, &query) {
+                HighlightableText::Raw(text) => text,
+                HighlightableText::Highlighted(text, _)
+                | HighlightableText::HighlightedComponents(text, _, _) => text,
+            }
+        };
+
+        assert_eq!(text("foo", StatusKind::Size), UNLOADED_PLACEHOLDER);
+        assert_eq!(text("foo", StatusKind::Mode), UNLOADED_PLACEHOLDER);
+        assert_eq!(text("foo", StatusKind::Basename), "foo");
+        // not indexed, so looked up from the file system
+        assert_ne!(text("foo", StatusKind::Modified), UNLOADED_PLACEHOLDER);
+
+        // the file type is not looked up either
+        #[cfg(unix)]
+        {
+            let entry = database.entry(database.get_by_path(path.join("link")).unwrap());
+            assert_eq!(file_type_label(&entry), None);
+            assert_eq!(app.icon(&entry), config.ui.icons.file);
+        }
+    }
+
+    #[test]
+    fn too_small() {
+        use crate::config::Config;
+        use tui::{backend::TestBackend, Terminal};
+
+        let config = Config::for_test();
+        let mut app = TuiApp::new(&config).unwrap();
+
+        let mut draw = |width, height| {
+            let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+            terminal.draw(|f| app.draw(f, width)).unwrap();
+            let buffer = terminal.backend().buffer();
+            buffer
+                .content()
+                .iter()
+                .map(|cell| cell.symbol.as_str())
+                .collect::<String>()
+        };
+
+        assert_eq!(draw(1, 1), "T");
+        assert!(draw(9, 10).starts_with("Terminal"));
+        assert!(draw(40, 2).starts_with(TOO_SMALL_MESSAGE));
+        for width in 1..20 {
+            for height in 1..8 {
+                let content = draw(width, height);
+                let too_small = width < config.ui.min_width || height < config.ui.min_height;
+                assert_eq!(content.starts_with('T'), too_small);
+            }
+        }
+    }
+
+    #[test]
+    fn empty_database() {
+        use crate::config::Config;
+        use indexa::database::DatabaseBuilder;
+        use std::sync::Arc;
+        use tui::{backend::TestBackend, Terminal};
+
+        let database = DatabaseBuilder::new().build().unwrap();
+        assert!(database.is_empty());
+
+        let config = Config::for_test();
+        let mut app = TuiApp::new(&config).unwrap();
+        app.databases = vec![Arc::new(database)];
+
+        for (width, height) in [(80, 10), (20, 6)] {
+            let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+            terminal.draw(|f| app.draw(f, width)).unwrap();
+            let content: String = terminal
+                .backend()
+                .buffer()
+                .content()
+                .iter()
+                .map(|cell| cell.symbol.as_str())
+                .collect();
+            assert!(content.starts_with("Database is empty"), "{}", content);
+            assert!(content.contains("0 / 0"));
+        }
+        assert_eq!(app.rows_area, Rect::default());
+    }
+
+    #[test]
+    fn dim_hidden() {
+        use crate::config::Config;
+        use indexa::database::DatabaseBuilder;
+
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = dunce::canonicalize(tmpdir.path()).unwrap();
+        std::fs::write(path.join(".foo"), "").unwrap();
+        std::fs::write(path.join("bar"), "").unwrap();
+        let database = DatabaseBuilder::new().add_dir(&path).build().unwrap();
+        let dotfile = database.entry(database.get_by_path(path.join(".foo")).unwrap());
+        let file = database.entry(database.get_by_path(path.join("bar")).unwrap());
+
+        let mut config = Config::for_test();
+        let app = TuiApp::new(&config).unwrap();
+        assert_eq!(app.row_style(&dotfile), Style::default());
+
+        config.ui.dim_hidden = true;
+        let app = TuiApp::new(&config).unwrap();
+        assert_eq!(app.row_style(&dotfile), config.ui.colors.hidden_style());
+        assert!(app
+            .row_style(&dotfile)
+            .add_modifier
+            .contains(tui::style::Modifier::DIM));
+        assert_eq!(app.row_style(&file), Style::default());
+    }
+
+    #[test]
+    fn owner() {
+        use crate::config::Config;
+        use indexa::database::DatabaseBuilder;
+
+        // no user should have this id
+        assert_eq!(owner_name(u32::MAX - 1), (u32::MAX - 1).to_string());
+
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = dunce::canonicalize(tmpdir.path()).unwrap();
+        std::fs::write(path.join("foo"), "").unwrap();
+        let database = DatabaseBuilder::new().add_dir(&path).build().unwrap();
+        let entry = database.entry(database.get_by_path(path.join("foo")).unwrap());
+
+        let config = Config::for_test();
+        let app = TuiApp::new(&config).unwrap();
+
+        #[cfg(unix)]
+        {
+            let uid = entry.owner().unwrap();
+            assert_eq!(app.format_owner(&entry), owner_name(uid));
+            assert!(app.owner_names.borrow().contains_key(&uid));
+        }
+
+        std::fs::remove_file(path.join("foo")).unwrap();
+        assert_eq!(app.format_owner(&entry), "-");
+    }
+
+    #[test]
+    fn status_format() {
+        assert_eq!(format_status("{matched} / {total}", 3, 10, "foo"), "3 / 10");
+        assert_eq!(
+            format_status("[{query}] {matched}/{total} {matched}", 3, 10, "{total}"),
+            "[{total}] 3/10 3"
+        );
+        assert_eq!(format_status("", 3, 10, "foo"), "");
+    }
+
+    #[test]
+    fn icon_offset() {
+        let mut text = "foo.rs".to_string();
+        let mut matches = vec![0..1, 4..6];
+        prepend_icon("🦀", &mut text, &mut matches);
+        assert_eq!(text, "🦀 foo.rs");
+        assert_eq!(&text[matches[0].clone()], "f");
+        assert_eq!(&text[matches[1].clone()], "rs");
+
+        let mut text = String::new();
+        let mut matches = Vec::new();
+        prepend_icon("", &mut text, &mut matches);
+        assert_eq!(text, " ");
+        assert!(matches.is_empty());
+    }
+}
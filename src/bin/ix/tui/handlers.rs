@@ -1,83 +1,196 @@
-use super::{State, TuiApp};
+use super::{key_bindings::Action, text_box::TextBoxState, Refinement, State, TrashPrompt, TuiApp};
+use crate::{
+    config::{Column, OnMissing},
+    saved_searches::SavedSearch,
+    searcher::Hit,
+};
 
-use indexa::{database::EntryId, query::QueryBuilder};
+use indexa::{
+    database::{Database, StatusKind},
+    query::SortOrder,
+};
 
 use anyhow::Result;
-use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers, MouseEvent, MouseEventKind};
+use camino::{Utf8Path, Utf8PathBuf};
+use crossterm::event::{
+    Event, KeyCode, KeyEvent, KeyEventKind, MouseButton, MouseEvent, MouseEventKind,
+};
+use itertools::Itertools;
+use std::{
+    borrow::Cow,
+    io::{self, Write},
+    path::MAIN_SEPARATOR,
+    process::{Command, Stdio},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tui::layout::Rect;
+
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Number of columns to scroll the selected path by.
+const PATH_SCROLL_AMOUNT: usize = 8;
 
 impl<'a> TuiApp<'a> {
     pub fn handle_input(&mut self, event: Event) -> Result<()> {
         match event {
+            // some terminals report releases of keys as well
+            Event::Key(key) if key.kind == KeyEventKind::Release => (),
             Event::Key(key) => self.handle_key(key)?,
             Event::Mouse(mouse) => self.handle_mouse(mouse)?,
-            Event::Resize(_, _) => (),
+            Event::Paste(text) => self.handle_paste(&text)?,
+            Event::Resize(_, _) | Event::FocusGained | Event::FocusLost => (),
         };
 
         Ok(())
     }
 
     fn handle_key(&mut self, key: KeyEvent) -> Result<()> {
-        match (key.modifiers, key.code) {
-            (_, KeyCode::Esc)
-            | (KeyModifiers::CONTROL, KeyCode::Char('c'))
-            | (KeyModifiers::CONTROL, KeyCode::Char('g')) => self.status = State::Aborted,
-            (_, KeyCode::Enter) => self.status = State::Accepted,
-            (_, KeyCode::Up) | (KeyModifiers::CONTROL, KeyCode::Char('p')) => self.on_up()?,
-            (_, KeyCode::Down) | (KeyModifiers::CONTROL, KeyCode::Char('n')) => self.on_down()?,
-            (_, KeyCode::PageUp) => self.on_pageup()?,
-            (_, KeyCode::PageDown) => self.on_pagedown()?,
-            (KeyModifiers::CONTROL, KeyCode::Home) | (KeyModifiers::SHIFT, KeyCode::Home) => {
-                self.on_scroll_to_top()?;
-            }
-            (KeyModifiers::CONTROL, KeyCode::End) | (KeyModifiers::SHIFT, KeyCode::End) => {
-                self.on_scroll_to_bottom()?;
-            }
-            (_, KeyCode::Backspace) | (KeyModifiers::CONTROL, KeyCode::Char('h')) => {
+        if self.show_help {
+            self.show_help = false;
+            return Ok(());
+        }
+        if self.search_picker.is_some() {
+            return self.handle_search_picker_key(key);
+        }
+        if self.trash_prompt.is_some() {
+            return self.handle_trash_prompt_key(key);
+        }
+
+        let action = match (self.key_bindings.find(&key), key.code) {
+            // "?" is a valid character in queries, so it toggles help only on an empty query
+            (Some(Action::ToggleHelp), KeyCode::Char(_))
+                if !self.text_box_state.text().is_empty() =>
+            {
+                None
+            }
+            (action, _) => action,
+        };
+
+        match action {
+            Some(Action::Abort) if self.refinement.is_some() => self.on_leave_refinement()?,
+            Some(Action::Abort) => self.status = State::Aborted,
+            Some(Action::Accept) => self.status = State::Accepted,
+            Some(Action::Up) => self.on_up()?,
+            Some(Action::Down) => self.on_down()?,
+            Some(Action::PageUp) => self.on_pageup()?,
+            Some(Action::PageDown) => self.on_pagedown()?,
+            Some(Action::ScrollToTop) => self.on_scroll_to_top()?,
+            Some(Action::ScrollToBottom) => self.on_scroll_to_bottom()?,
+            Some(Action::Backspace) => {
                 if self.text_box_state.on_backspace() {
                     self.handle_query_change()?;
                 }
             }
-            (_, KeyCode::Delete) | (KeyModifiers::CONTROL, KeyCode::Char('d')) => {
+            Some(Action::Delete) => {
                 if self.text_box_state.on_delete() {
                     self.handle_query_change()?;
                 }
             }
-            (_, KeyCode::Left) | (KeyModifiers::CONTROL, KeyCode::Char('b')) => {
+            Some(Action::Left) if self.text_box_state.text().is_empty() => {
+                self.on_scroll_path_left()?
+            }
+            Some(Action::Right) if self.text_box_state.text().is_empty() => {
+                self.on_scroll_path_right()?
+            }
+            Some(Action::Left) => {
                 self.text_box_state.on_left();
             }
-            (_, KeyCode::Right) | (KeyModifiers::CONTROL, KeyCode::Char('f')) => {
+            Some(Action::Right) => {
                 self.text_box_state.on_right();
             }
-            (_, KeyCode::Home) | (KeyModifiers::CONTROL, KeyCode::Char('a')) => {
+            Some(Action::Home) => {
                 self.text_box_state.on_home();
             }
-            (_, KeyCode::End) | (KeyModifiers::CONTROL, KeyCode::Char('e')) => {
+            Some(Action::End) => {
                 self.text_box_state.on_end();
             }
-            (KeyModifiers::CONTROL, KeyCode::Char('u')) => {
+            Some(Action::Complete) => self.on_complete()?,
+            Some(Action::Clear) => {
                 self.text_box_state.clear();
                 self.handle_query_change()?;
             }
-            (_, KeyCode::Char(c)) => {
-                self.text_box_state.on_char(c);
+            Some(Action::HistoryPrev) => self.on_history_prev()?,
+            Some(Action::HistoryNext) => self.on_history_next()?,
+            Some(Action::ScrollPathLeft) => self.on_scroll_path_left()?,
+            Some(Action::ScrollPathRight) => self.on_scroll_path_right()?,
+            Some(Action::Reveal) => self.on_reveal()?,
+            Some(Action::RunCommand) => self.on_run_command()?,
+            Some(Action::Refine) => self.on_refine()?,
+            Some(Action::CycleSortBy) => self.on_cycle_sort_by()?,
+            Some(Action::ToggleSortOrder) => self.on_toggle_sort_order()?,
+            Some(Action::ToggleHidden) => {
+                self.options.ignore_hidden = !self.options.ignore_hidden;
                 self.handle_query_change()?;
             }
-            _ => (),
+            Some(Action::SaveSearch) => self.on_save_search()?,
+            Some(Action::LoadSearch) => {
+                if !self.saved_searches.is_empty() {
+                    self.search_picker = Some(0);
+                }
+            }
+            Some(Action::Trash) => self.on_trash(),
+            Some(Action::ToggleHelp) => self.show_help = true,
+            None => {
+                if let KeyCode::Char(c) = key.code {
+                    self.text_box_state.on_char(c);
+                    self.handle_query_change()?;
+                }
+            }
         };
 
         Ok(())
     }
 
+    /// Inserts pasted text into the query at once.
+    fn handle_paste(&mut self, text: &str) -> Result<()> {
+        // help, prompts, and pickers only take keys
+        if self.show_help || self.search_picker.is_some() || self.trash_prompt.is_some() {
+            return Ok(());
+        }
+
+        self.text_box_state.insert_str(text);
+        self.handle_query_change()
+    }
+
     fn handle_mouse(&mut self, mouse: MouseEvent) -> Result<()> {
         match mouse.kind {
             MouseEventKind::ScrollUp => self.on_up()?,
             MouseEventKind::ScrollDown => self.on_down()?,
+            MouseEventKind::Down(MouseButton::Left) => self.on_click(mouse.column, mouse.row)?,
             _ => (),
         };
 
         Ok(())
     }
 
+    fn on_click(&mut self, column: u16, row: u16) -> Result<()> {
+        let index = row_to_index(
+            self.rows_area,
+            self.table_state.offset(),
+            self.hits.len(),
+            column,
+            row,
+        );
+        if let Some(index) = index {
+            let now = Instant::now();
+            let is_double_click = matches!(
+                self.last_click,
+                Some((time, i)) if i == index && now.duration_since(time) < DOUBLE_CLICK_INTERVAL
+            );
+
+            self.table_state.select(index);
+            if is_double_click {
+                self.last_click = None;
+                self.status = State::Accepted;
+            } else {
+                self.last_click = Some((now, index));
+            }
+        }
+
+        Ok(())
+    }
+
     fn on_up(&mut self) -> Result<()> {
         if !self.hits.is_empty() {
             self.table_state
@@ -96,12 +209,18 @@ impl<'a> TuiApp<'a> {
         Ok(())
     }
 
+    /// Returns the number of rows a page up/down moves by, which is the
+    /// number of rows drawn on the screen.
+    fn page_height(&self) -> usize {
+        self.table_state.visible_rows().max(1)
+    }
+
     fn on_pageup(&mut self) -> Result<()> {
         if !self.hits.is_empty() {
             self.table_state.select(
                 self.table_state
                     .selected()
-                    .saturating_sub(self.page_scroll_amount as usize),
+                    .saturating_sub(self.page_height()),
             );
         }
 
@@ -111,8 +230,7 @@ impl<'a> TuiApp<'a> {
     fn on_pagedown(&mut self) -> Result<()> {
         if !self.hits.is_empty() {
             self.table_state.select(
-                (self.table_state.selected() + self.page_scroll_amount as usize)
-                    .min(self.hits.len() - 1),
+                (self.table_state.selected() + self.page_height()).min(self.hits.len() - 1),
             );
         }
 
@@ -135,45 +253,390 @@ impl<'a> TuiApp<'a> {
         Ok(())
     }
 
-    pub fn handle_search_result(&mut self, hits: Vec<EntryId>) -> Result<()> {
+    fn on_history_prev(&mut self) -> Result<()> {
+        // start over if the query was edited after recalling
+        if self.history.current() != Some(self.text_box_state.text()) {
+            self.history.reset();
+        }
+        if let Some(query) = self.history.prev() {
+            self.text_box_state = TextBoxState::with_text(query.to_owned());
+            self.handle_query_change()?;
+        }
+
+        Ok(())
+    }
+
+    fn on_history_next(&mut self) -> Result<()> {
+        if self.history.current() != Some(self.text_box_state.text()) {
+            self.history.reset();
+        }
+        if let Some(query) = self.history.next() {
+            self.text_box_state = TextBoxState::with_text(query.to_owned());
+            self.handle_query_change()?;
+        }
+
+        Ok(())
+    }
+
+    fn handle_search_picker_key(&mut self, key: KeyEvent) -> Result<()> {
+        let selected = match self.search_picker {
+            Some(selected) => selected,
+            None => return Ok(()),
+        };
+
+        match self.key_bindings.find(&key) {
+            Some(Action::Up) => self.search_picker = Some(selected.saturating_sub(1)),
+            Some(Action::Down) => {
+                self.search_picker = Some((selected + 1).min(self.saved_searches.len() - 1))
+            }
+            Some(Action::Accept) => {
+                self.search_picker = None;
+                if let Some(search) = self.saved_searches.get(selected).cloned() {
+                    self.load_search(search)?;
+                }
+            }
+            Some(Action::Abort) | Some(Action::LoadSearch) => self.search_picker = None,
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    /// Saves the current query and its options, named after the query.
+    fn on_save_search(&mut self) -> Result<()> {
+        let pattern = self.text_box_state.text();
+        if pattern.is_empty() {
+            return Ok(());
+        }
+
+        self.saved_searches.push(SavedSearch {
+            name: pattern.to_owned(),
+            pattern: pattern.to_owned(),
+            match_path: self.options.match_path,
+            case_sensitivity: self.options.case_sensitivity,
+            regex: self.options.regex,
+            sort_by: self.options.sort_by,
+            sort_order: self.options.sort_order,
+        });
+        if let Err(err) = self.saved_searches.save() {
+            self.status = State::Error(format!("Failed to save searches: {}", err));
+        }
+
+        Ok(())
+    }
+
+    /// Completes the last component of a query which is a path, looking up
+    /// the directory it is in from the databases.
+    fn on_complete(&mut self) -> Result<()> {
+        let query = self.text_box_state.text();
+        let (dir, partial) = match query.rfind(MAIN_SEPARATOR) {
+            Some(i) => (&query[..=i], &query[i + 1..]),
+            None => return Ok(()),
+        };
+
+        let children: Vec<_> = self
+            .databases
+            .iter()
+            .filter_map(|database| database.get_by_path(dir).map(|id| database.entry(id)))
+            .flat_map(|entry| {
+                entry
+                    .children()
+                    .map(|child| (child.basename().to_owned(), child.is_dir()))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        if let Some(completion) = completion(partial, &children) {
+            let text = format!("{}{}", query, completion);
+            self.text_box_state = TextBoxState::with_text(text);
+            self.handle_query_change()?;
+        }
+
+        Ok(())
+    }
+
+    fn load_search(&mut self, search: SavedSearch) -> Result<()> {
+        self.options.match_path = search.match_path;
+        self.options.case_sensitivity = search.case_sensitivity;
+        self.options.regex = search.regex;
+        self.options.sort_by = search.sort_by;
+        self.options.sort_order = search.sort_order;
+        self.text_box_state = TextBoxState::with_text(search.pattern);
+        self.handle_query_change()
+    }
+
+    fn on_trash(&mut self) {
+        if let Some(hit) = self.hits.get(self.table_state.selected()) {
+            self.trash_prompt = Some(TrashPrompt {
+                hit: *hit,
+                is_dir: self.entry(*hit).is_dir(),
+                confirmed_dir: false,
+            });
+        }
+    }
+
+    /// Answers the trash prompt. Anything other than "y" cancels.
+    fn handle_trash_prompt_key(&mut self, key: KeyEvent) -> Result<()> {
+        let prompt = match self.trash_prompt.take() {
+            Some(prompt) => prompt,
+            None => return Ok(()),
+        };
+        if !matches!(key.code, KeyCode::Char('y') | KeyCode::Char('Y')) {
+            return Ok(());
+        }
+        if prompt.is_dir && !prompt.confirmed_dir {
+            self.trash_prompt = Some(TrashPrompt {
+                confirmed_dir: true,
+                ..prompt
+            });
+            return Ok(());
+        }
+
+        let path = self.entry(prompt.hit).path();
+        if let Err(err) = trash::delete(&path) {
+            self.status = State::Error(format!("Failed to move {} to trash: {}", path, err));
+            return Ok(());
+        }
+        self.hide_trashed(path);
+
+        Ok(())
+    }
+
+    /// Removes hits at or under `path`, which is moved to the trash, and
+    /// keeps them out of later results.
+    fn hide_trashed(&mut self, path: Utf8PathBuf) {
+        self.trashed.push(path);
+
+        let databases = &self.databases;
+        let trashed = &self.trashed;
+        let is_trashed =
+            |hit: &Hit| is_trashed(trashed, &databases[hit.database].entry(hit.id).path());
+        let selected = remove_hits(&mut self.hits, self.table_state.selected(), is_trashed);
+        if let Some(refinement) = &mut self.refinement {
+            Arc::make_mut(&mut refinement.hits).retain(|hit| !is_trashed(hit));
+        }
+        self.table_state.select(selected.unwrap_or(0));
+    }
+
+    fn on_scroll_path_left(&mut self) -> Result<()> {
+        if let Some((_, offset)) = &mut self.path_offset {
+            *offset = offset.saturating_sub(PATH_SCROLL_AMOUNT);
+        }
+
+        Ok(())
+    }
+
+    fn on_scroll_path_right(&mut self) -> Result<()> {
+        // overscrolling is clamped when drawn
+        if let Some((_, offset)) = &mut self.path_offset {
+            *offset += PATH_SCROLL_AMOUNT;
+        }
+
+        Ok(())
+    }
+
+    fn on_reveal(&mut self) -> Result<()> {
+        let hit = match self.hits.get(self.table_state.selected()) {
+            Some(hit) => *hit,
+            None => return Ok(()),
+        };
+        let path = self.entry(hit).path();
+        let (program, args) =
+            reveal_command(std::env::consts::OS, &path, &self.config.ui.reveal_command);
+        self.spawn_detached(&program, &args);
+
+        Ok(())
+    }
+
+    /// Runs `ui.action_command` for the selected entry.
+    fn on_run_command(&mut self) -> Result<()> {
+        let template = match &self.config.ui.action_command {
+            Some(template) => template,
+            None => {
+                self.status = State::Error("ui.action_command is not configured".to_string());
+                return Ok(());
+            }
+        };
+        let hit = match self.hits.get(self.table_state.selected()) {
+            Some(hit) => *hit,
+            None => return Ok(()),
+        };
+
+        let path = self.entry(hit).path();
+        for command in expand_action_command(template, &[path.as_str()]) {
+            if let Some((program, args)) = command.split_first() {
+                self.spawn_detached(program, args);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Spawns a process without waiting for it, reporting failure to the status bar.
+    fn spawn_detached(&mut self, program: &str, args: &[String]) {
+        let result = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        if let Err(err) = result {
+            self.status = State::Error(format!("Failed to run {}: {}", program, err));
+        }
+    }
+
+    fn on_refine(&mut self) -> Result<()> {
+        if self.databases.is_empty() {
+            return Ok(());
+        }
+
+        // refining again narrows down the current results, but leaving goes
+        // back to the original query
+        let query = match self.refinement.take() {
+            Some(refinement) => refinement.query,
+            None => self.text_box_state.text().to_owned(),
+        };
+        self.refinement = Some(Refinement {
+            query,
+            hits: Arc::new(self.hits.clone()),
+        });
+        self.text_box_state.clear();
+        self.handle_query_change()
+    }
+
+    fn on_leave_refinement(&mut self) -> Result<()> {
+        if let Some(refinement) = self.refinement.take() {
+            self.text_box_state = TextBoxState::with_text(refinement.query);
+            self.handle_query_change()?;
+        }
+
+        Ok(())
+    }
+
+    fn on_cycle_sort_by(&mut self) -> Result<()> {
+        self.options.sort_by =
+            next_sort_column(&self.config.ui.columns, self.options.sort_by, |status| {
+                !self.config.ui.only_fast_sort || self.is_fast_sortable(status)
+            });
+        self.handle_query_change()
+    }
+
+    fn on_toggle_sort_order(&mut self) -> Result<()> {
+        self.options.sort_order = match self.options.sort_order {
+            SortOrder::Ascending => SortOrder::Descending,
+            SortOrder::Descending => SortOrder::Ascending,
+        };
+        self.handle_query_change()
+    }
+
+    pub fn handle_search_result(&mut self, mut hits: Vec<Hit>) -> Result<()> {
+        if !self.trashed.is_empty() {
+            let databases = &self.databases;
+            hits.retain(|hit| {
+                !is_trashed(&self.trashed, &databases[hit.database].entry(hit.id).path())
+            });
+        }
+        let selected = reselect(&self.hits, &hits, self.table_state.selected());
+
         self.hits = hits;
         self.status = State::Ready;
 
-        if !self.hits.is_empty() {
-            self.table_state
-                .select(self.table_state.selected().min(self.hits.len() - 1));
+        if let Some(selected) = selected {
+            self.table_state.select(selected);
         }
 
         Ok(())
     }
 
-    pub fn handle_accept(&self) -> Result<()> {
-        if let Some(id) = self.hits.get(self.table_state.selected()) {
-            println!("{}", self.database.as_ref().unwrap().entry(*id).path());
+    pub fn handle_accept(&mut self) -> Result<()> {
+        self.write_accepted(&mut io::stdout().lock(), &mut io::stderr().lock())?;
+
+        self.history.push(self.text_box_state.text());
+        self.history.save()?;
+
+        Ok(())
+    }
+
+    /// Writes the selected entry to `writer`, handling a missing entry
+    /// according to `ui.on_missing` and writing warnings to `warnings`.
+    fn write_accepted<W: Write, E: Write>(
+        &self,
+        writer: &mut W,
+        warnings: &mut E,
+    ) -> io::Result<()> {
+        let selected = self.table_state.selected();
+        let hit = match self.config.ui.on_missing {
+            OnMissing::Ignore => self.hits.get(selected),
+            OnMissing::Warn => match self.hits.get(selected) {
+                Some(hit) if !self.entry(*hit).path().exists() => {
+                    writeln!(
+                        warnings,
+                        "Skipped {} (no longer exists)",
+                        self.entry(*hit).path()
+                    )?;
+                    None
+                }
+                hit => hit,
+            },
+            OnMissing::Skip => self
+                .hits
+                .get(selected..)
+                .unwrap_or_default()
+                .iter()
+                .find(|hit| self.entry(**hit).path().exists()),
+        };
+
+        if let Some(hit) = hit {
+            let entry = self.entry(*hit);
+            let separator = self.config.flags.separator();
+            let path = entry.path();
+            print_path(writer, self.config.flags.printed_path(&path), separator)?;
+            if self.config.flags.print_long {
+                print_path(writer, &self.format_long(&entry), separator)?;
+            }
         }
+
         Ok(())
     }
 
+    pub fn handle_created_entries(&mut self, created: Database) -> Result<()> {
+        // drop our references so that the searcher can modify the overlay
+        self.databases.clear();
+        let (databases, appended) = self.searcher.as_mut().unwrap().add_created(created);
+        self.databases = databases;
+        appended?;
+
+        // existing ids are kept, so the current hits and selection stay valid
+        self.handle_query_change()
+    }
+
     pub fn handle_query_change(&mut self) -> Result<()> {
-        if self.database.is_none() {
+        if self.databases.is_empty() {
             return Ok(());
         }
 
-        let query = self.text_box_state.text();
-        let query = QueryBuilder::new(query)
-            .match_path_mode(self.config.flags.match_path)
-            .case_sensitivity(self.config.flags.case_sensitivity())
-            .regex(self.config.flags.regex)
-            .sort_by(self.config.ui.sort_by)
-            .sort_order(self.config.ui.sort_order)
-            .sort_dirs_before_files(self.config.ui.sort_dirs_before_files)
-            .build();
+        // let the selected path follow the new matches
+        self.path_offset = None;
+
+        let pattern = self.text_box_state.text();
+        let mut builder = self.options.query_builder(self.config, pattern);
+        // only for browsing, unlike the other options
+        if let Some(limit) = self.config.ui.empty_query_limit {
+            if pattern.is_empty() && self.refinement.is_none() {
+                builder.limit(limit);
+            }
+        }
+        let query = builder.build();
 
         match query {
             Ok(query) => {
                 self.query = Some(query.clone());
                 self.status = State::Searching;
-                self.searcher.as_mut().unwrap().search(query);
+                let searcher = self.searcher.as_mut().unwrap();
+                match &self.refinement {
+                    Some(refinement) => searcher.refine(Arc::clone(&refinement.hits), query),
+                    None => searcher.search(query),
+                }
             }
             Err(err) => {
                 let err_str = err.to_string();
@@ -199,3 +662,584 @@ impl<'a> TuiApp<'a> {
         Ok(())
     }
 }
+
+/// Returns text to append to `partial` to complete it to the children
+/// (pairs of basenames and whether they are directories) starting with it.
+///
+/// A unique directory is completed with a trailing separator. With multiple
+/// candidates, the longest common prefix of them is completed.
+fn completion(partial: &str, children: &[(String, bool)]) -> Option<String> {
+    let mut candidates = children
+        .iter()
+        .filter(|(name, _)| name.starts_with(partial));
+    let (first, is_dir) = candidates.next()?;
+
+    let mut common = first.as_str();
+    let mut is_unique = true;
+    for (name, _) in candidates {
+        if name == first {
+            // the same directory can be indexed in multiple databases
+            continue;
+        }
+        is_unique = false;
+        let len = common
+            .char_indices()
+            .zip(name.chars())
+            .find(|((_, a), b)| a != b)
+            .map(|((i, _), _)| i)
+            .unwrap_or_else(|| common.len().min(name.len()));
+        common = &common[..len];
+    }
+
+    let mut completion = common[partial.len()..].to_owned();
+    if is_unique && *is_dir {
+        completion.push(MAIN_SEPARATOR);
+    }
+    (!completion.is_empty()).then(|| completion)
+}
+
+/// Builds a command line which reveals `path` in a file manager of `os`.
+///
+/// `template` is used on platforms other than Windows and macOS.
+fn reveal_command(os: &str, path: &Utf8Path, template: &str) -> (String, Vec<String>) {
+    match os {
+        "windows" => ("explorer".to_string(), vec![format!("/select,{}", path)]),
+        "macos" => ("open".to_string(), vec!["-R".to_string(), path.to_string()]),
+        _ => {
+            let parent = path.parent().unwrap_or(path);
+            // placeholders are replaced after splitting, so that paths
+            // containing spaces are passed as single arguments
+            let mut args = template.split_whitespace().map(|arg| {
+                arg.replace("{path}", path.as_str())
+                    .replace("{parent}", parent.as_str())
+            });
+            let program = args.next().unwrap_or_else(|| "xdg-open".to_string());
+            (program, args.collect())
+        }
+    }
+}
+
+/// Expands `ui.action_command` into command lines to run.
+///
+/// With `{}`, a command is run for each path. With `{+}`, a single command is run
+/// with all the paths, each of which becomes a separate argument if `{+}` stands alone.
+/// Without placeholders, a path is appended to the command for each path.
+///
+/// The template is split into arguments before substitution and no shell is involved,
+/// so paths replacing `{}` or a standalone `{+}` need no quoting. When `{+}` is part of
+/// a larger argument, the paths are quoted as in a POSIX shell and joined with spaces,
+/// so that they can be told apart.
+fn expand_action_command(template: &str, paths: &[&str]) -> Vec<Vec<String>> {
+    let args = template.split_whitespace().collect::<Vec<_>>();
+
+    if args.iter().any(|arg| arg.contains("{+}")) {
+        let command = args
+            .iter()
+            .flat_map(|arg| {
+                if *arg == "{+}" {
+                    paths.iter().map(|path| path.to_string()).collect()
+                } else {
+                    let quoted = paths.iter().map(|path| shell_quote(path)).join(" ");
+                    vec![arg.replace("{+}", &quoted)]
+                }
+            })
+            .collect();
+        return vec![command];
+    }
+
+    paths
+        .iter()
+        .map(|path| {
+            let mut command = args
+                .iter()
+                .map(|arg| arg.replace("{}", path))
+                .collect::<Vec<_>>();
+            if !args.iter().any(|arg| arg.contains("{}")) {
+                command.push(path.to_string());
+            }
+            command
+        })
+        .collect()
+}
+
+/// Quotes `s` with single quotes unless all of its characters are safe
+/// unquoted in a POSIX shell.
+fn shell_quote(s: &str) -> Cow<'_, str> {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "%+,-./:=@_".contains(c);
+    if !s.is_empty() && s.chars().all(is_safe) {
+        Cow::Borrowed(s)
+    } else {
+        Cow::Owned(format!("'{}'", s.replace('\'', r"'\''")))
+    }
+}
+
+/// Writes `path` followed by `separator` and flushes `writer`.
+fn print_path<W: Write>(writer: &mut W, path: &str, separator: char) -> io::Result<()> {
+    write!(writer, "{}{}", path, separator)?;
+    writer.flush()
+}
+
+/// Returns the column to sort by after `current`, skipping columns for which
+/// `is_allowed` returns false.
+fn next_sort_column<F>(columns: &[Column], current: StatusKind, is_allowed: F) -> StatusKind
+where
+    F: Fn(StatusKind) -> bool,
+{
+    let start = columns
+        .iter()
+        .position(|column| column.status == current)
+        .map_or(0, |i| i + 1);
+    columns
+        .iter()
+        .cycle()
+        .skip(start)
+        .take(columns.len())
+        .map(|column| column.status)
+        .find(|status| is_allowed(*status))
+        .unwrap_or(current)
+}
+
+/// Returns an index in `new_hits` to select after hits are replaced.
+///
+/// Previously selected entry stays selected if it is still in `new_hits`.
+/// Otherwise, the index is clamped.
+fn reselect<T: PartialEq>(old_hits: &[T], new_hits: &[T], selected: usize) -> Option<usize> {
+    if new_hits.is_empty() {
+        return None;
+    }
+
+    let index = old_hits
+        .get(selected)
+        .and_then(|id| new_hits.iter().position(|x| x == id))
+        .unwrap_or_else(|| selected.min(new_hits.len() - 1));
+    Some(index)
+}
+
+/// Returns true if `path` is one of `trashed` or is under one of them.
+fn is_trashed(trashed: &[Utf8PathBuf], path: &Utf8Path) -> bool {
+    trashed.iter().any(|trashed| path.starts_with(trashed))
+}
+
+/// Removes hits for which `f` returns true and returns an index to select.
+///
+/// The selection moves to the hit which followed the selected one if it was
+/// removed, or to the last hit if none followed it.
+fn remove_hits<T, F>(hits: &mut Vec<T>, selected: usize, mut f: F) -> Option<usize>
+where
+    F: FnMut(&T) -> bool,
+{
+    let mut num_removed_before = 0;
+    let mut i = 0;
+    hits.retain(|hit| {
+        let remove = f(hit);
+        if remove && i < selected {
+            num_removed_before += 1;
+        }
+        i += 1;
+        !remove
+    });
+
+    if hits.is_empty() {
+        return None;
+    }
+    Some((selected - num_removed_before).min(hits.len() - 1))
+}
+
+/// Maps a clicked position to an index of the hit displayed there.
+fn row_to_index(
+    rows_area: Rect,
+    offset: usize,
+    num_hits: usize,
+    column: u16,
+    row: u16,
+) -> Option<usize> {
+    let is_inside = rows_area.left() <= column
+        && column < rows_area.right()
+        && rows_area.top() <= row
+        && row < rows_area.bottom();
+    if !is_inside {
+        return None;
+    }
+
+    let index = offset + (row - rows_area.top()) as usize;
+    (index < num_hits).then(|| index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns(statuses: &[StatusKind]) -> Vec<Column> {
+        statuses
+            .iter()
+            .map(|status| Column {
+                status: *status,
+                width: None,
+                align: None,
+                color: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn complete() {
+        let children = [
+            ("project".to_string(), true),
+            ("programs".to_string(), true),
+            ("profile.txt".to_string(), false),
+            ("music".to_string(), true),
+            ("notes.txt".to_string(), false),
+            ("notes.txt.bak".to_string(), false),
+            ("\u{e9}t\u{e9}".to_string(), true),
+            ("\u{e9}t\u{e0}".to_string(), true),
+        ];
+        let sep = MAIN_SEPARATOR;
+
+        assert_eq!(completion("mu", &children), Some(format!("sic{}", sep)));
+        assert_eq!(completion("music", &children), Some(sep.to_string()));
+        assert_eq!(completion("pro", &children), None);
+        assert_eq!(completion("proj", &children), Some(format!("ect{}", sep)));
+        assert_eq!(completion("prof", &children), Some("ile.txt".to_string()));
+        assert_eq!(completion("n", &children), Some("otes.txt".to_string()));
+        assert_eq!(completion("notes.txt", &children), None);
+        assert_eq!(completion("\u{e9}", &children), Some("t".to_string()));
+        assert_eq!(completion("x", &children), None);
+        assert_eq!(completion("", &[]), None);
+
+        // duplicates from multiple databases
+        let children = [("music".to_string(), true), ("music".to_string(), true)];
+        assert_eq!(completion("m", &children), Some(format!("usic{}", sep)));
+    }
+
+    #[test]
+    fn reveal() {
+        let path = Utf8Path::new("/foo bar/baz");
+        let template = "xdg-open {parent}";
+
+        assert_eq!(
+            reveal_command("windows", Utf8Path::new(r"C:\foo bar\baz"), template),
+            (
+                "explorer".to_string(),
+                vec![r"/select,C:\foo bar\baz".to_string()]
+            )
+        );
+        assert_eq!(
+            reveal_command("macos", path, template),
+            (
+                "open".to_string(),
+                vec!["-R".to_string(), "/foo bar/baz".to_string()]
+            )
+        );
+        assert_eq!(
+            reveal_command("linux", path, template),
+            ("xdg-open".to_string(), vec!["/foo bar".to_string()])
+        );
+        assert_eq!(
+            reveal_command("freebsd", path, "nautilus --select {path}"),
+            (
+                "nautilus".to_string(),
+                vec!["--select".to_string(), "/foo bar/baz".to_string()]
+            )
+        );
+        assert_eq!(
+            reveal_command("linux", Utf8Path::new("/"), ""),
+            ("xdg-open".to_string(), vec![])
+        );
+    }
+
+    #[test]
+    fn print_long() {
+        use crate::config::Config;
+        use indexa::database::DatabaseBuilder;
+
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = dunce::canonicalize(tmpdir.path()).unwrap();
+        std::fs::write(path.join("foo"), "bar").unwrap();
+        let database = DatabaseBuilder::new()
+            .add_dir(&path)
+            .index(StatusKind::Size)
+            .build()
+            .unwrap();
+        let id = database.get_by_path(path.join("foo")).unwrap();
+        let database = Arc::new(database);
+
+        let mut config = Config::for_test();
+        config.ui.human_readable_size = false;
+        config.ui.datetime_format = "%Y".to_string();
+
+        let accept = |config: &Config| {
+            let mut app = TuiApp::new(config).unwrap();
+            app.databases = vec![Arc::clone(&database)];
+            app.hits = vec![Hit { database: 0, id }];
+            let mut out = Vec::new();
+            app.write_accepted(&mut out, &mut io::sink()).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let expected_path = format!("{}\n", path.join("foo").display());
+        assert_eq!(accept(&config), expected_path);
+
+        config.flags.print_long = true;
+        let output = accept(&config);
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(format!("{}\n", lines[0]), expected_path);
+        assert!(lines[1].starts_with("3  "));
+        assert!(lines[1].ends_with(&chrono::Local::now().format("%Y").to_string()));
+    }
+
+    #[test]
+    fn on_missing() {
+        use crate::config::Config;
+        use indexa::database::DatabaseBuilder;
+
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = dunce::canonicalize(tmpdir.path()).unwrap();
+        for name in ["a", "b", "c"] {
+            std::fs::write(path.join(name), "").unwrap();
+        }
+        let database = DatabaseBuilder::new().add_dir(&path).build().unwrap();
+        let hits: Vec<_> = ["a", "b", "c"]
+            .iter()
+            .map(|name| Hit {
+                database: 0,
+                id: database.get_by_path(path.join(name)).unwrap(),
+            })
+            .collect();
+        let database = Arc::new(database);
+
+        // deleted after indexing
+        std::fs::remove_file(path.join("a")).unwrap();
+        std::fs::remove_file(path.join("b")).unwrap();
+
+        let accept = |on_missing, selected| {
+            let mut config = Config::for_test();
+            config.ui.on_missing = on_missing;
+            let mut app = TuiApp::new(&config).unwrap();
+            app.databases = vec![Arc::clone(&database)];
+            app.hits = hits.clone();
+            app.table_state.select(selected);
+            let (mut out, mut warnings) = (Vec::new(), Vec::new());
+            app.write_accepted(&mut out, &mut warnings).unwrap();
+            (
+                String::from_utf8(out).unwrap(),
+                String::from_utf8(warnings).unwrap(),
+            )
+        };
+        let line = |name| format!("{}\n", path.join(name).display());
+
+        assert_eq!(accept(OnMissing::Ignore, 0), (line("a"), String::new()));
+
+        let (out, warnings) = accept(OnMissing::Warn, 0);
+        assert!(out.is_empty());
+        assert!(warnings.contains(&path.join("a").display().to_string()));
+        assert_eq!(accept(OnMissing::Warn, 2), (line("c"), String::new()));
+
+        assert_eq!(accept(OnMissing::Skip, 0), (line("c"), String::new()));
+        assert_eq!(accept(OnMissing::Skip, 2), (line("c"), String::new()));
+
+        std::fs::remove_file(path.join("c")).unwrap();
+        assert_eq!(accept(OnMissing::Skip, 0), (String::new(), String::new()));
+    }
+
+    #[test]
+    fn action_command() {
+        let paths = ["/foo bar/a.txt", "/b's"];
+
+        assert_eq!(
+            expand_action_command("code {}", &paths[..1]),
+            [["code", "/foo bar/a.txt"]]
+        );
+        assert_eq!(
+            expand_action_command("code -g {}:1", &paths),
+            [["code", "-g", "/foo bar/a.txt:1"], ["code", "-g", "/b's:1"]]
+        );
+        assert_eq!(
+            expand_action_command("code --new-window {+}", &paths),
+            [["code", "--new-window", "/foo bar/a.txt", "/b's"]]
+        );
+        assert_eq!(
+            expand_action_command("echo files={+}", &paths),
+            [["echo", r"files='/foo bar/a.txt' '/b'\''s'"]]
+        );
+        assert_eq!(
+            expand_action_command("echo files={+}", &["/a.txt", "/b/c"]),
+            [["echo", "files=/a.txt /b/c"]]
+        );
+        assert_eq!(
+            expand_action_command("xdg-open", &paths),
+            [["xdg-open", "/foo bar/a.txt"], ["xdg-open", "/b's"]]
+        );
+        assert!(expand_action_command("code {}", &[]).is_empty());
+    }
+
+    #[test]
+    fn print_separated_paths() {
+        let mut out = Vec::new();
+        print_path(&mut out, "/foo bar/baz qux", '\0').unwrap();
+        print_path(&mut out, "/a\nb", '\0').unwrap();
+        assert_eq!(out, b"/foo bar/baz qux\0/a\nb\0");
+
+        let mut out = Vec::new();
+        print_path(&mut out, "/foo bar", '\n').unwrap();
+        assert_eq!(out, b"/foo bar\n");
+    }
+
+    #[test]
+    fn cycle_sort_column() {
+        let columns = columns(&[StatusKind::Basename, StatusKind::Size, StatusKind::Path]);
+
+        assert_eq!(
+            next_sort_column(&columns, StatusKind::Basename, |_| true),
+            StatusKind::Size
+        );
+        assert_eq!(
+            next_sort_column(&columns, StatusKind::Size, |_| true),
+            StatusKind::Path
+        );
+        assert_eq!(
+            next_sort_column(&columns, StatusKind::Path, |_| true),
+            StatusKind::Basename
+        );
+
+        // sorted by a column that is not shown
+        assert_eq!(
+            next_sort_column(&columns, StatusKind::Modified, |_| true),
+            StatusKind::Basename
+        );
+
+        assert_eq!(
+            next_sort_column(&[], StatusKind::Modified, |_| true),
+            StatusKind::Modified
+        );
+
+        // only fast-sortable columns
+        let is_allowed = |status| status != StatusKind::Size;
+        assert_eq!(
+            next_sort_column(&columns, StatusKind::Basename, is_allowed),
+            StatusKind::Path
+        );
+        assert_eq!(
+            next_sort_column(&columns, StatusKind::Path, is_allowed),
+            StatusKind::Basename
+        );
+        assert_eq!(
+            next_sort_column(&columns, StatusKind::Size, |_| false),
+            StatusKind::Size
+        );
+    }
+
+    #[test]
+    fn keep_selection() {
+        let hits1 = [10, 20, 30, 40, 50];
+        let hits2 = [20, 40, 50];
+        let hits3 = [20, 50];
+
+        // 40 stays selected while narrowing down
+        assert_eq!(reselect(&hits1, &hits2, 3), Some(1));
+        // 40 disappears, so the index is clamped
+        assert_eq!(reselect(&hits2, &hits3, 1), Some(1));
+        assert_eq!(reselect(&hits1, &hits3, 4), Some(1));
+        assert_eq!(reselect(&hits1, &hits3, 2), Some(1));
+
+        assert_eq!(reselect(&[], &hits1, 0), Some(0));
+        assert_eq!(reselect(&hits1, &[], 0), None);
+    }
+
+    #[test]
+    fn remove_trashed_hits() {
+        let mut hits = vec![10, 20, 30, 40, 50];
+
+        // the hit following the removed one gets selected
+        assert_eq!(remove_hits(&mut hits, 2, |x| *x == 30), Some(2));
+        assert_eq!(hits, [10, 20, 40, 50]);
+
+        // removing hits before the selection keeps the same hit selected
+        assert_eq!(remove_hits(&mut hits, 2, |x| *x <= 20), Some(0));
+        assert_eq!(hits, [40, 50]);
+
+        // removing the last hit selects the new last one
+        assert_eq!(remove_hits(&mut hits, 1, |x| *x == 50), Some(0));
+        assert_eq!(hits, [40]);
+
+        assert_eq!(remove_hits(&mut hits, 0, |x| *x == 60), Some(0));
+        assert_eq!(hits, [40]);
+        assert_eq!(remove_hits(&mut hits, 0, |_| true), None);
+        assert!(hits.is_empty());
+    }
+
+    #[test]
+    fn hide_trashed_descendants() {
+        use crate::config::Config;
+        use indexa::database::DatabaseBuilder;
+
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = dunce::canonicalize(tmpdir.path()).unwrap();
+        std::fs::create_dir_all(path.join("dir/sub")).unwrap();
+        for name in ["dir/a", "dir/sub/b", "dirty"] {
+            std::fs::write(path.join(name), "").unwrap();
+        }
+        let database = DatabaseBuilder::new().add_dir(&path).build().unwrap();
+        let hit = |name: &str| Hit {
+            database: 0,
+            id: database.get_by_path(path.join(name)).unwrap(),
+        };
+        let (dir, a, b, dirty) = (hit("dir"), hit("dir/a"), hit("dir/sub/b"), hit("dirty"));
+
+        let config = Config::for_test();
+        let mut app = TuiApp::new(&config).unwrap();
+        app.databases = vec![Arc::new(database)];
+
+        // only the directory is in the results when it is trashed
+        app.hits = vec![dir, dirty];
+        app.hide_trashed(app.entry(dir).path());
+        assert_eq!(app.hits, [dirty]);
+
+        // its descendants matching a later query are hidden too
+        app.handle_search_result(vec![a, dirty, b]).unwrap();
+        assert_eq!(app.hits, [dirty]);
+    }
+
+    #[test]
+    fn paste() {
+        use crate::config::Config;
+        use crossterm::event::KeyModifiers;
+
+        let config = Config::for_test();
+        let mut app = TuiApp::new(&config).unwrap();
+
+        app.handle_input(Event::Paste("foo\nbar\r\n".to_string()))
+            .unwrap();
+        assert_eq!(app.text_box_state.text(), "foobar");
+
+        // releasing a key types nothing
+        let mut release = KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE);
+        release.kind = KeyEventKind::Release;
+        app.handle_input(Event::Key(release)).unwrap();
+        assert_eq!(app.text_box_state.text(), "foobar");
+
+        app.show_help = true;
+        app.handle_input(Event::Paste("baz".to_string())).unwrap();
+        assert_eq!(app.text_box_state.text(), "foobar");
+    }
+
+    #[test]
+    fn click_to_index() {
+        // rows start below header and header_gap
+        let rows_area = Rect::new(0, 2, 80, 10);
+
+        assert_eq!(row_to_index(rows_area, 0, 100, 5, 2), Some(0));
+        assert_eq!(row_to_index(rows_area, 0, 100, 5, 11), Some(9));
+        assert_eq!(row_to_index(rows_area, 30, 100, 5, 4), Some(32));
+
+        // header, header_gap, and outside of table
+        assert_eq!(row_to_index(rows_area, 0, 100, 5, 0), None);
+        assert_eq!(row_to_index(rows_area, 0, 100, 5, 1), None);
+        assert_eq!(row_to_index(rows_area, 0, 100, 5, 12), None);
+        assert_eq!(row_to_index(rows_area, 0, 100, 80, 2), None);
+
+        // below the last hit
+        assert_eq!(row_to_index(rows_area, 0, 3, 5, 4), Some(2));
+        assert_eq!(row_to_index(rows_area, 0, 3, 5, 5), None);
+    }
+}
@@ -0,0 +1,540 @@
+use anyhow::{anyhow, bail, Error, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{de::IntoDeserializer, Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Write},
+    str::FromStr,
+};
+use strum_macros::IntoStaticStr;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Deserialize, IntoStaticStr)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum Action {
+    Abort,
+    Accept,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    ScrollToTop,
+    ScrollToBottom,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Home,
+    End,
+    Complete,
+    Clear,
+    HistoryPrev,
+    HistoryNext,
+    ScrollPathLeft,
+    ScrollPathRight,
+    Reveal,
+    RunCommand,
+    Refine,
+    CycleSortBy,
+    ToggleSortOrder,
+    ToggleHidden,
+    SaveSearch,
+    LoadSearch,
+    Trash,
+    ToggleHelp,
+}
+
+impl Action {
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Abort => "Abort",
+            Self::Accept => "Select current line and quit",
+            Self::Up => "Move cursor up",
+            Self::Down => "Move cursor down",
+            Self::PageUp => "Move cursor one page up",
+            Self::PageDown => "Move cursor one page down",
+            Self::ScrollToTop => "Scroll to top of the list",
+            Self::ScrollToBottom => "Scroll to bottom of the list",
+            Self::Backspace => "Delete character before cursor",
+            Self::Delete => "Delete character under cursor",
+            Self::Left => "Move cursor left in query",
+            Self::Right => "Move cursor right in query",
+            Self::Home => "Move cursor to beginning of query",
+            Self::End => "Move cursor to end of query",
+            Self::Complete => "Complete the path component in query",
+            Self::Clear => "Clear the query",
+            Self::HistoryPrev => "Recall previous query from history",
+            Self::HistoryNext => "Recall next query from history",
+            Self::ScrollPathLeft => "Scroll selected path left (also Left on empty query)",
+            Self::ScrollPathRight => "Scroll selected path right (also Right on empty query)",
+            Self::Reveal => "Reveal selected entry in file manager",
+            Self::RunCommand => "Run ui.action_command for selected entry",
+            Self::Refine => "Search within current results (Abort to leave)",
+            Self::CycleSortBy => "Cycle the column to sort by",
+            Self::ToggleSortOrder => "Reverse the sort order",
+            Self::ToggleHidden => "Toggle hiding hidden files and directories",
+            Self::SaveSearch => "Save current query and its options as a named search",
+            Self::LoadSearch => "Pick a saved search to load",
+            Self::Trash => "Move selected entry to the trash (asks for confirmation)",
+            Self::ToggleHelp => "Toggle this help (? only on empty query)",
+        }
+    }
+}
+
+/// A key combination. `modifiers` of `None` matches any modifiers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct KeyBinding {
+    pub modifiers: Option<KeyModifiers>,
+    pub code: KeyCode,
+}
+
+impl KeyBinding {
+    const fn any(code: KeyCode) -> Self {
+        Self {
+            modifiers: None,
+            code,
+        }
+    }
+
+    const fn ctrl(code: KeyCode) -> Self {
+        Self {
+            modifiers: Some(KeyModifiers::CONTROL),
+            code,
+        }
+    }
+
+    const fn alt(code: KeyCode) -> Self {
+        Self {
+            modifiers: Some(KeyModifiers::ALT),
+            code,
+        }
+    }
+
+    const fn shift(code: KeyCode) -> Self {
+        Self {
+            modifiers: Some(KeyModifiers::SHIFT),
+            code,
+        }
+    }
+}
+
+impl FromStr for KeyBinding {
+    type Err = Error;
+
+    /// Parses a key spec such as "ctrl-n", "alt+p", "esc", "f1", or "k".
+    ///
+    /// Without modifiers, the binding matches the key with any modifiers.
+    fn from_str(s: &str) -> Result<Self> {
+        let spec = s.trim();
+        let mut modifiers = KeyModifiers::empty();
+        let mut rest = spec;
+        loop {
+            let (prefix, remainder) = match rest.find(['-', '+']) {
+                // a trailing or lone "-"/"+" is the key itself
+                Some(i) if i > 0 && i + 1 < rest.len() => (&rest[..i], &rest[i + 1..]),
+                _ => break,
+            };
+            modifiers |= match prefix.to_lowercase().as_str() {
+                "ctrl" | "control" | "c" => KeyModifiers::CONTROL,
+                "alt" | "meta" | "m" => KeyModifiers::ALT,
+                "shift" | "s" => KeyModifiers::SHIFT,
+                _ => break,
+            };
+            rest = remainder;
+        }
+
+        let code = match rest.to_lowercase().as_str() {
+            "esc" | "escape" => KeyCode::Esc,
+            "enter" | "return" => KeyCode::Enter,
+            "backspace" | "bs" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "insert" | "ins" => KeyCode::Insert,
+            "tab" => KeyCode::Tab,
+            "space" => KeyCode::Char(' '),
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" | "page-up" | "pgup" => KeyCode::PageUp,
+            "pagedown" | "page-down" | "pgdn" => KeyCode::PageDown,
+            key => {
+                let mut chars = rest.chars();
+                match (chars.next(), chars.next()) {
+                    // terminals report Ctrl/Alt + letter in lowercase
+                    (Some(c), None) if modifiers.is_empty() => KeyCode::Char(c),
+                    (Some(c), None) => KeyCode::Char(c.to_ascii_lowercase()),
+                    _ => match key.strip_prefix('f').map(str::parse::<u8>) {
+                        Some(Ok(n)) if (1..=24).contains(&n) => KeyCode::F(n),
+                        _ => bail!("Unknown key {:?} in key binding {:?}", rest, spec),
+                    },
+                }
+            }
+        };
+
+        Ok(Self {
+            modifiers: if modifiers.is_empty() {
+                None
+            } else {
+                Some(modifiers)
+            },
+            code,
+        })
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let modifiers = self.modifiers.unwrap_or_else(KeyModifiers::empty);
+        if modifiers.contains(KeyModifiers::CONTROL) {
+            f.write_str("Ctrl+")?;
+        }
+        if modifiers.contains(KeyModifiers::ALT) {
+            f.write_str("Alt+")?;
+        }
+        if modifiers.contains(KeyModifiers::SHIFT) {
+            f.write_str("Shift+")?;
+        }
+
+        match self.code {
+            KeyCode::Char(' ') => f.write_str("Space"),
+            KeyCode::Char(c) if modifiers.is_empty() => f.write_char(c),
+            KeyCode::Char(c) => write!(f, "{}", c.to_uppercase()),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            KeyCode::Esc => f.write_str("Esc"),
+            KeyCode::Enter => f.write_str("Enter"),
+            KeyCode::Backspace => f.write_str("Backspace"),
+            KeyCode::Delete => f.write_str("Delete"),
+            KeyCode::Tab => f.write_str("Tab"),
+            KeyCode::Up => f.write_str("Up"),
+            KeyCode::Down => f.write_str("Down"),
+            KeyCode::Left => f.write_str("Left"),
+            KeyCode::Right => f.write_str("Right"),
+            KeyCode::Home => f.write_str("Home"),
+            KeyCode::End => f.write_str("End"),
+            KeyCode::PageUp => f.write_str("PageUp"),
+            KeyCode::PageDown => f.write_str("PageDown"),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+pub const DEFAULT_KEY_BINDINGS: &[(Action, &[KeyBinding])] = &[
+    (Action::Accept, &[KeyBinding::any(KeyCode::Enter)]),
+    (
+        Action::Abort,
+        &[
+            KeyBinding::any(KeyCode::Esc),
+            KeyBinding::ctrl(KeyCode::Char('c')),
+            KeyBinding::ctrl(KeyCode::Char('g')),
+        ],
+    ),
+    (
+        Action::Up,
+        &[
+            KeyBinding::any(KeyCode::Up),
+            KeyBinding::ctrl(KeyCode::Char('p')),
+        ],
+    ),
+    (
+        Action::Down,
+        &[
+            KeyBinding::any(KeyCode::Down),
+            KeyBinding::ctrl(KeyCode::Char('n')),
+        ],
+    ),
+    (Action::PageUp, &[KeyBinding::any(KeyCode::PageUp)]),
+    (Action::PageDown, &[KeyBinding::any(KeyCode::PageDown)]),
+    (
+        Action::ScrollToTop,
+        &[
+            KeyBinding::ctrl(KeyCode::Home),
+            KeyBinding::shift(KeyCode::Home),
+        ],
+    ),
+    (
+        Action::ScrollToBottom,
+        &[
+            KeyBinding::ctrl(KeyCode::End),
+            KeyBinding::shift(KeyCode::End),
+        ],
+    ),
+    (
+        Action::Backspace,
+        &[
+            KeyBinding::any(KeyCode::Backspace),
+            KeyBinding::ctrl(KeyCode::Char('h')),
+        ],
+    ),
+    (
+        Action::Delete,
+        &[
+            KeyBinding::any(KeyCode::Delete),
+            KeyBinding::ctrl(KeyCode::Char('d')),
+        ],
+    ),
+    (
+        Action::Left,
+        &[
+            KeyBinding::any(KeyCode::Left),
+            KeyBinding::ctrl(KeyCode::Char('b')),
+        ],
+    ),
+    (
+        Action::Right,
+        &[
+            KeyBinding::any(KeyCode::Right),
+            KeyBinding::ctrl(KeyCode::Char('f')),
+        ],
+    ),
+    (
+        Action::Home,
+        &[
+            KeyBinding::any(KeyCode::Home),
+            KeyBinding::ctrl(KeyCode::Char('a')),
+        ],
+    ),
+    (
+        Action::End,
+        &[
+            KeyBinding::any(KeyCode::End),
+            KeyBinding::ctrl(KeyCode::Char('e')),
+        ],
+    ),
+    (Action::Complete, &[KeyBinding::any(KeyCode::Tab)]),
+    (Action::Clear, &[KeyBinding::ctrl(KeyCode::Char('u'))]),
+    (Action::HistoryPrev, &[KeyBinding::alt(KeyCode::Char('p'))]),
+    (Action::HistoryNext, &[KeyBinding::alt(KeyCode::Char('n'))]),
+    (Action::ScrollPathLeft, &[KeyBinding::alt(KeyCode::Left)]),
+    (Action::ScrollPathRight, &[KeyBinding::alt(KeyCode::Right)]),
+    (Action::Reveal, &[KeyBinding::alt(KeyCode::Char('r'))]),
+    (Action::RunCommand, &[KeyBinding::ctrl(KeyCode::Char('o'))]),
+    (Action::Refine, &[KeyBinding::alt(KeyCode::Char('f'))]),
+    (Action::CycleSortBy, &[KeyBinding::ctrl(KeyCode::Char('s'))]),
+    (
+        Action::ToggleSortOrder,
+        &[KeyBinding::ctrl(KeyCode::Char('r'))],
+    ),
+    (Action::ToggleHidden, &[KeyBinding::alt(KeyCode::Char('h'))]),
+    (Action::SaveSearch, &[KeyBinding::alt(KeyCode::Char('s'))]),
+    (Action::LoadSearch, &[KeyBinding::alt(KeyCode::Char('l'))]),
+    (Action::Trash, &[KeyBinding::alt(KeyCode::Char('d'))]),
+    (
+        Action::ToggleHelp,
+        &[
+            KeyBinding::any(KeyCode::F(1)),
+            KeyBinding::any(KeyCode::Char('?')),
+        ],
+    ),
+];
+
+/// Key bindings in effect: the defaults, with actions remapped in config replaced.
+pub struct KeyBindings(Vec<(Action, Vec<KeyBinding>)>);
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self::new(&HashMap::new())
+    }
+}
+
+impl KeyBindings {
+    pub fn new(overrides: &HashMap<Action, Vec<KeyBinding>>) -> Self {
+        let bindings = DEFAULT_KEY_BINDINGS
+            .iter()
+            .map(|(action, bindings)| {
+                let bindings = overrides
+                    .get(action)
+                    .cloned()
+                    .unwrap_or_else(|| bindings.to_vec());
+                (*action, bindings)
+            })
+            .collect();
+        Self(bindings)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(Action, Vec<KeyBinding>)> {
+        self.0.iter()
+    }
+
+    /// Finds an action bound to the key.
+    ///
+    /// Bindings with exactly matching modifiers take precedence over
+    /// the ones matching any modifiers.
+    pub fn find(&self, key: &KeyEvent) -> Option<Action> {
+        let find = |exact: bool| {
+            self.0
+                .iter()
+                .find(|(_, bindings)| {
+                    bindings.iter().any(|binding| {
+                        binding.code == key.code
+                            && match binding.modifiers {
+                                Some(modifiers) => exact && modifiers == key.modifiers,
+                                None => !exact,
+                            }
+                    })
+                })
+                .map(|(action, _)| *action)
+        };
+        find(true).or_else(|| find(false))
+    }
+}
+
+/// Parses key bindings in config, which map action names to a key spec or a list of key specs.
+pub fn parse_key_bindings(
+    config: HashMap<String, KeySpecs>,
+) -> Result<HashMap<Action, Vec<KeyBinding>>> {
+    config
+        .into_iter()
+        .map(|(name, specs)| {
+            let action = Action::deserialize(name.as_str().into_deserializer())
+                .map_err(|err: serde::de::value::Error| anyhow!("{}", err))?;
+            let specs = match specs {
+                KeySpecs::One(spec) => vec![spec],
+                KeySpecs::Many(specs) => specs,
+            };
+            let bindings = specs
+                .iter()
+                .map(|spec| spec.parse())
+                .collect::<Result<Vec<_>>>()
+                .map_err(|err| anyhow!("{} (action {:?})", err, action))?;
+            Ok((action, bindings))
+        })
+        .collect()
+}
+
+/// Converts key bindings back to the form in config, which `parse_key_bindings` accepts.
+pub fn key_bindings_to_specs(
+    key_bindings: &HashMap<Action, Vec<KeyBinding>>,
+) -> BTreeMap<String, KeySpecs> {
+    key_bindings
+        .iter()
+        .map(|(action, bindings)| {
+            let name = <&str>::from(action).to_string();
+            let specs = bindings.iter().map(ToString::to_string).collect();
+            (name, KeySpecs::Many(specs))
+        })
+        .collect()
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum KeySpecs {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(modifiers: KeyModifiers, code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn find() {
+        let key_bindings = KeyBindings::default();
+        let find_action = |key| key_bindings.find(&key);
+
+        assert_eq!(
+            find_action(key(KeyModifiers::NONE, KeyCode::Home)),
+            Some(Action::Home)
+        );
+        assert_eq!(
+            find_action(key(KeyModifiers::CONTROL, KeyCode::Home)),
+            Some(Action::ScrollToTop)
+        );
+        assert_eq!(
+            find_action(key(KeyModifiers::CONTROL, KeyCode::Char('c'))),
+            Some(Action::Abort)
+        );
+        assert_eq!(
+            find_action(key(KeyModifiers::NONE, KeyCode::Char('c'))),
+            None
+        );
+        assert_eq!(
+            find_action(key(KeyModifiers::SHIFT, KeyCode::Char('?'))),
+            Some(Action::ToggleHelp)
+        );
+    }
+
+    #[test]
+    fn parse() {
+        let parse = |s: &str| s.parse::<KeyBinding>().unwrap();
+
+        assert_eq!(parse("ctrl-n"), KeyBinding::ctrl(KeyCode::Char('n')));
+        assert_eq!(parse("Ctrl+N"), KeyBinding::ctrl(KeyCode::Char('n')));
+        assert_eq!(parse("alt-p"), KeyBinding::alt(KeyCode::Char('p')));
+        assert_eq!(parse("shift-end"), KeyBinding::shift(KeyCode::End));
+        assert_eq!(parse("esc"), KeyBinding::any(KeyCode::Esc));
+        assert_eq!(parse("Enter"), KeyBinding::any(KeyCode::Enter));
+        assert_eq!(parse("page-up"), KeyBinding::any(KeyCode::PageUp));
+        assert_eq!(parse("f12"), KeyBinding::any(KeyCode::F(12)));
+        assert_eq!(parse("k"), KeyBinding::any(KeyCode::Char('k')));
+        assert_eq!(parse("K"), KeyBinding::any(KeyCode::Char('K')));
+        assert_eq!(parse("-"), KeyBinding::any(KeyCode::Char('-')));
+        assert_eq!(parse("ctrl-+"), KeyBinding::ctrl(KeyCode::Char('+')));
+        assert_eq!(
+            parse("ctrl-alt-x"),
+            KeyBinding {
+                modifiers: Some(KeyModifiers::CONTROL | KeyModifiers::ALT),
+                code: KeyCode::Char('x'),
+            }
+        );
+
+        assert!("ctrl-".parse::<KeyBinding>().is_err());
+        assert!("hyper-x".parse::<KeyBinding>().is_err());
+        assert!("f0".parse::<KeyBinding>().is_err());
+        assert!("foo".parse::<KeyBinding>().is_err());
+    }
+
+    #[test]
+    fn remap() {
+        let mut config = HashMap::new();
+        config.insert(
+            "down".to_string(),
+            KeySpecs::Many(vec!["down".to_string(), "j".to_string()]),
+        );
+        config.insert("accept".to_string(), KeySpecs::One("tab".to_string()));
+        let key_bindings = KeyBindings::new(&parse_key_bindings(config).unwrap());
+
+        assert_eq!(
+            key_bindings.find(&key(KeyModifiers::NONE, KeyCode::Char('j'))),
+            Some(Action::Down)
+        );
+        assert_eq!(
+            key_bindings.find(&key(KeyModifiers::CONTROL, KeyCode::Char('n'))),
+            None
+        );
+        assert_eq!(
+            key_bindings.find(&key(KeyModifiers::NONE, KeyCode::Tab)),
+            Some(Action::Accept)
+        );
+        assert_eq!(
+            key_bindings.find(&key(KeyModifiers::NONE, KeyCode::Enter)),
+            None
+        );
+        assert_eq!(
+            key_bindings.find(&key(KeyModifiers::NONE, KeyCode::Up)),
+            Some(Action::Up)
+        );
+
+        let mut config = HashMap::new();
+        config.insert("up".to_string(), KeySpecs::One("ctrl-".to_string()));
+        assert!(parse_key_bindings(config).is_err());
+
+        let mut config = HashMap::new();
+        config.insert("foo".to_string(), KeySpecs::One("ctrl-f".to_string()));
+        assert!(parse_key_bindings(config).is_err());
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(KeyBinding::any(KeyCode::Esc).to_string(), "Esc");
+        assert_eq!(KeyBinding::ctrl(KeyCode::Char('n')).to_string(), "Ctrl+N");
+        assert_eq!(KeyBinding::shift(KeyCode::End).to_string(), "Shift+End");
+        assert_eq!(KeyBinding::any(KeyCode::F(1)).to_string(), "F1");
+        assert_eq!(KeyBinding::any(KeyCode::Char('?')).to_string(), "?");
+    }
+}
@@ -29,12 +29,7 @@ use cassowary::{
     WeightedRelation::*,
 };
 use itertools::izip;
-use std::{
-    collections::HashMap,
-    fmt::Display,
-    iter::{self, Iterator},
-    ops::Range,
-};
+use std::{collections::HashMap, fmt::Display, iter::Iterator, ops::Range};
 use tui::{
     buffer::Buffer,
     layout::{Alignment, Constraint, Rect},
@@ -48,6 +43,7 @@ use unicode_width::UnicodeWidthStr;
 pub struct TableState {
     offset: usize,
     selected: usize,
+    visible_rows: usize,
 }
 
 impl TableState {
@@ -58,6 +54,16 @@ impl TableState {
     pub fn select(&mut self, index: usize) {
         self.selected = index;
     }
+
+    /// Index of the first visible row.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Number of rows which fit in the area the table was last rendered in.
+    pub fn visible_rows(&self) -> usize {
+        self.visible_rows
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -67,6 +73,9 @@ where
 {
     Raw(String),
     Highlighted(String, M),
+    /// Highlighted text whose ranges in the last iterator are marked
+    /// with the component style underneath the highlights.
+    HighlightedComponents(String, M, M),
 }
 
 impl<M> Default for HighlightableText<M>
@@ -94,6 +103,7 @@ where
     D: Iterator<Item = HighlightableText<M>>,
 {
     data: D,
+    style: Style,
 }
 
 impl<M, D> Row<M, D>
@@ -102,7 +112,16 @@ where
     D: Iterator<Item = HighlightableText<M>>,
 {
     pub fn new(data: D) -> Self {
-        Self { data }
+        Self {
+            data,
+            style: Style::default(),
+        }
+    }
+
+    /// Style applied on top of column styles unless the row is selected.
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
     }
 }
 
@@ -114,12 +133,16 @@ pub struct Table<'a, H, R, F> {
     header_style: Style,
     widths: &'a [Constraint],
     alignments: Option<&'a [Alignment]>,
+    column_styles: Option<&'a [Style]>,
     column_spacing: u16,
     header_gap: u16,
     selected_style: Style,
     highlight_style: Style,
     selected_highlight_style: Style,
+    component_style: Style,
+    alternate_style: Style,
     selected_symbol: Option<&'a str>,
+    scrollbar: bool,
     rows: R,
     display_func: F,
 }
@@ -141,12 +164,16 @@ where
             header_style: Style::default(),
             widths: &[],
             alignments: None,
+            column_styles: None,
             column_spacing: 1,
             header_gap: 1,
             selected_style: Style::default(),
             highlight_style: Style::default(),
             selected_highlight_style: Style::default(),
+            component_style: Style::default(),
+            alternate_style: Style::default(),
             selected_symbol: None,
+            scrollbar: false,
             rows,
             display_func,
         }
@@ -191,6 +218,12 @@ where
         self
     }
 
+    /// Styles patched onto cells of each column, except in the selected row.
+    pub fn column_styles(mut self, column_styles: &'a [Style]) -> Table<'a, H, R, F> {
+        self.column_styles = Some(column_styles);
+        self
+    }
+
     #[allow(dead_code)]
     pub fn rows<II>(mut self, rows: II) -> Table<'a, H, R, F>
     where
@@ -229,6 +262,23 @@ where
         self
     }
 
+    /// Style of the components of `HighlightableText::HighlightedComponents`.
+    pub fn component_style(mut self, component_style: Style) -> Table<'a, H, R, F> {
+        self.component_style = component_style;
+        self
+    }
+
+    /// Style patched onto every other (odd-numbered) row except the selected one.
+    pub fn alternate_style(mut self, alternate_style: Style) -> Table<'a, H, R, F> {
+        self.alternate_style = alternate_style;
+        self
+    }
+
+    pub fn scrollbar(mut self, scrollbar: bool) -> Table<'a, H, R, F> {
+        self.scrollbar = scrollbar;
+        self
+    }
+
     pub fn column_spacing(mut self, spacing: u16) -> Table<'a, H, R, F> {
         self.column_spacing = spacing;
         self
@@ -292,7 +342,10 @@ where
                     .fold(Expression::from_constant(0.), |acc, v| acc + *v)
                     | LE(REQUIRED)
                     | f64::from(
-                        area.width - 2 - (self.column_spacing * (variables.len() as u16 - 1)),
+                        area.width
+                            .saturating_sub(2)
+                            .saturating_sub(self.column_spacing * (variables.len() as u16 - 1))
+                            .saturating_sub(u16::from(self.scrollbar)),
                     ),
             )
             .unwrap();
@@ -311,9 +364,14 @@ where
         let alignments: Vec<_> = if let Some(alignments) = self.alignments {
             alignments.iter().collect()
         } else {
-            iter::repeat(&Alignment::Left)
-                .take(self.widths.len())
-                .collect()
+            vec![&Alignment::Left; self.widths.len()]
+        };
+
+        let default_style = Style::default();
+        let column_styles: Vec<_> = if let Some(column_styles) = self.column_styles {
+            column_styles.iter().collect()
+        } else {
+            vec![&default_style; self.widths.len()]
         };
 
         let mut y = table_area.top();
@@ -326,10 +384,14 @@ where
                 alignments.iter(),
                 self.header.by_ref(),
             ) {
+                // columns may not fit in a narrow area
+                if x >= table_area.right() {
+                    break;
+                }
                 let area = Rect {
                     x,
                     y,
-                    width: *w,
+                    width: (*w).min(table_area.right() - x),
                     height: 1,
                 };
                 let text = Span::styled(t.to_string(), self.header_style);
@@ -344,9 +406,10 @@ where
         let blank_symbol = " ".repeat(selected_symbol.width());
 
         // Draw rows
-        let default_style = Style::default();
+        state.visible_rows = 0;
         if y < table_area.bottom() {
             let remaining = (table_area.bottom() - y) as usize;
+            state.visible_rows = remaining;
 
             state.offset = state.offset.min(self.rows.len().saturating_sub(remaining));
             state.offset = if state.selected >= remaining + state.offset - 1 {
@@ -357,6 +420,19 @@ where
                 state.offset
             };
 
+            if self.scrollbar {
+                let x = table_area.right().saturating_sub(1);
+                let thumb = scrollbar_thumb(state.offset, remaining, self.rows.len());
+                for i in 0..remaining {
+                    let symbol = if thumb.contains(&i) {
+                        SCROLLBAR_THUMB
+                    } else {
+                        SCROLLBAR_TRACK
+                    };
+                    buf.set_string(x, y + i as u16, symbol, self.style);
+                }
+            }
+
             for (i, row) in self
                 .rows
                 .skip(state.offset)
@@ -371,6 +447,12 @@ where
                             self.selected_highlight_style,
                             selected_symbol,
                         )
+                    } else if (state.offset + i) % 2 == 1 {
+                        (
+                            default_style.patch(self.alternate_style),
+                            self.alternate_style.patch(self.highlight_style),
+                            blank_symbol.as_ref(),
+                        )
                     } else {
                         (default_style, self.highlight_style, blank_symbol.as_ref())
                     }
@@ -378,24 +460,48 @@ where
 
                 x = table_area.left();
 
+                // fill the whole row including gaps between columns
+                let row_area = Rect {
+                    x,
+                    y: y + i as u16,
+                    width: table_area.width.saturating_sub(u16::from(self.scrollbar)),
+                    height: 1,
+                };
+                buf.set_style(row_area, style);
+
                 buf.set_stringn(x, y + i as u16, &symbol, symbol.width(), style);
                 x += symbol.width() as u16;
 
-                for (c, (w, &&alignment, elt)) in
-                    izip!(solved_widths.iter(), alignments.iter(), row.data).enumerate()
+                let is_selected = i == state.selected - state.offset;
+                for (c, (w, &&alignment, &&column_style, elt)) in izip!(
+                    solved_widths.iter(),
+                    alignments.iter(),
+                    column_styles.iter(),
+                    row.data
+                )
+                .enumerate()
                 {
                     let width = if c == 0 {
-                        *w - symbol.width() as u16
+                        w.saturating_sub(symbol.width() as u16)
                     } else {
                         *w
                     };
+                    if x >= table_area.right() {
+                        break;
+                    }
                     let area = Rect {
                         x,
                         y: y + i as u16,
-                        width,
+                        width: width.min(table_area.right() - x),
                         height: 1,
                     };
 
+                    let style = if is_selected {
+                        style
+                    } else {
+                        style.patch(column_style).patch(row.style)
+                    };
+
                     match elt {
                         HighlightableText::Raw(text) => {
                             let text = Span::styled(&text, style);
@@ -405,6 +511,17 @@ where
                             let text = build_spans(&text, ranges, &style, &highlight_style);
                             Paragraph::new(text).alignment(alignment).render(area, buf);
                         }
+                        HighlightableText::HighlightedComponents(text, ranges, components) => {
+                            let text = build_component_spans(
+                                &text,
+                                ranges,
+                                components,
+                                &style,
+                                &highlight_style,
+                                &self.component_style,
+                            );
+                            Paragraph::new(text).alignment(alignment).render(area, buf);
+                        }
                     }
 
                     x += width + self.column_spacing;
@@ -429,6 +546,22 @@ where
     }
 }
 
+const SCROLLBAR_TRACK: &str = "│";
+const SCROLLBAR_THUMB: &str = "█";
+
+/// Returns a range of rows occupied by a scrollbar thumb.
+///
+/// The range is empty if all rows fit in the view.
+fn scrollbar_thumb(offset: usize, visible: usize, total: usize) -> Range<usize> {
+    if total <= visible || visible == 0 {
+        return 0..0;
+    }
+
+    let len = (visible * visible / total).max(1);
+    let start = offset.min(total - visible) * (visible - len) / (total - visible);
+    start..start + len
+}
+
 fn build_spans<'t, M>(
     text: &'t str,
     matches: M,
@@ -454,3 +587,304 @@ where
     }
     Spans::from(texts)
 }
+
+/// Same as `build_spans`, but also applies `component_style` to `components`
+/// underneath the highlights of `matches`.
+fn build_component_spans<'t, M>(
+    text: &'t str,
+    matches: M,
+    components: M,
+    style: &Style,
+    highlight_style: &Style,
+    component_style: &Style,
+) -> Spans<'t>
+where
+    M: Iterator<Item = Range<usize>>,
+{
+    let matches = matches.collect::<Vec<_>>();
+    let mut texts = Vec::new();
+    let mut push = |range: Range<usize>, style: &Style, highlight_style: &Style| {
+        // matches in the range, relative to its start
+        let clipped = matches
+            .iter()
+            .filter(|m| m.start < range.end && range.start < m.end)
+            .map(|m| m.start.max(range.start) - range.start..m.end.min(range.end) - range.start);
+        texts.extend(build_spans(&text[range.clone()], clipped, style, highlight_style).0);
+    };
+
+    let in_component_style = style.patch(*component_style);
+    let in_component_highlight_style = component_style.patch(*highlight_style);
+    let mut prev_end = 0;
+    for component in components {
+        if component.start > prev_end {
+            push(prev_end..component.start, style, highlight_style);
+        }
+        if component.end > component.start {
+            push(
+                component.clone(),
+                &in_component_style,
+                &in_component_highlight_style,
+            );
+        }
+        prev_end = component.end;
+    }
+    if prev_end < text.len() {
+        push(prev_end..text.len(), style, highlight_style);
+    }
+    Spans::from(texts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::iter;
+    use tui::style::{Color, Modifier};
+
+    fn render_scrollbar(height: u16, num_rows: usize, selected: usize) -> String {
+        let area = Rect::new(0, 0, 10, height);
+        let mut buf = Buffer::empty(area);
+        let mut state = TableState::default();
+        state.select(selected);
+
+        let widths = [Constraint::Min(1)];
+        let table = Table::new(iter::once("header"), 0..num_rows, |i: usize| {
+            Row::new(iter::once(HighlightableText::<iter::Empty<_>>::Raw(
+                i.to_string(),
+            )))
+        })
+        .widths(&widths)
+        .scrollbar(true);
+        StatefulWidget::render(table, area, &mut buf, &mut state);
+
+        // skip header and header gap
+        (2..height)
+            .map(|y| buf.get(area.right() - 1, y).symbol.clone())
+            .collect()
+    }
+
+    #[test]
+    fn visible_rows() {
+        let visible_rows = |height| {
+            let area = Rect::new(0, 0, 10, height);
+            let mut buf = Buffer::empty(area);
+            let mut state = TableState::default();
+
+            let widths = [Constraint::Min(1)];
+            let table = Table::new(iter::once("header"), 0..100, |i: usize| {
+                Row::new(iter::once(HighlightableText::<iter::Empty<_>>::Raw(
+                    i.to_string(),
+                )))
+            })
+            .widths(&widths)
+            .header_gap(1);
+            StatefulWidget::render(table, area, &mut buf, &mut state);
+            state.visible_rows()
+        };
+
+        // header and header gap take two lines
+        assert_eq!(visible_rows(1), 0);
+        assert_eq!(visible_rows(2), 0);
+        assert_eq!(visible_rows(4), 2);
+        assert_eq!(visible_rows(5), 3);
+        assert_eq!(visible_rows(20), 18);
+    }
+
+    #[test]
+    fn header_gap() {
+        let render = |gap| {
+            let area = Rect::new(0, 0, 10, 5);
+            let mut buf = Buffer::empty(area);
+            let mut state = TableState::default();
+
+            let widths = [Constraint::Min(1)];
+            let table = Table::new(iter::once("header"), 0..10, |i: usize| {
+                Row::new(iter::once(HighlightableText::<iter::Empty<_>>::Raw(
+                    i.to_string(),
+                )))
+            })
+            .widths(&widths)
+            .header_gap(gap);
+            StatefulWidget::render(table, area, &mut buf, &mut state);
+
+            let lines: Vec<_> = (0..area.height)
+                .map(|y| {
+                    (0..area.width)
+                        .map(|x| buf.get(x, y).symbol.clone())
+                        .collect::<String>()
+                        .trim_end()
+                        .to_string()
+                })
+                .collect();
+            (lines, state.visible_rows())
+        };
+
+        assert_eq!(
+            render(0),
+            (
+                vec!["header", "0", "1", "2", "3"]
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+                4
+            )
+        );
+        assert_eq!(
+            render(2),
+            (
+                vec!["header", "", "", "0", "1"]
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+                2
+            )
+        );
+    }
+
+    #[test]
+    fn mixed_widths() {
+        let area = Rect::new(0, 0, 42, 3);
+        let mut buf = Buffer::empty(area);
+        let mut state = TableState::default();
+
+        let widths = [
+            Constraint::Length(4),
+            Constraint::Percentage(50),
+            Constraint::Min(1),
+        ];
+        let table = Table::new(["a", "b", "c"].iter(), 0..1, |_| {
+            Row::new(
+                ["x", "y", "z"]
+                    .iter()
+                    .map(|s| HighlightableText::<iter::Empty<_>>::Raw(s.to_string())),
+            )
+        })
+        .widths(&widths)
+        .column_spacing(1);
+        StatefulWidget::render(table, area, &mut buf, &mut state);
+
+        let header: String = (0..area.width)
+            .map(|x| buf.get(x, 0).symbol.clone())
+            .collect();
+        // 4 cells for the fixed column and 21 (50% of 42) for the percentage
+        // column, separated by spaces
+        assert_eq!(header.find('a'), Some(0));
+        assert_eq!(header.find('b'), Some(4 + 1));
+        assert_eq!(header.find('c'), Some(4 + 1 + 21 + 1));
+    }
+
+    #[test]
+    fn row_style() {
+        let area = Rect::new(0, 0, 10, 5);
+        let mut buf = Buffer::empty(area);
+        let mut state = TableState::default();
+
+        let dim = Style::default().add_modifier(Modifier::DIM);
+        let widths = [Constraint::Min(1)];
+        let column_styles = [Style::default().fg(Color::Red)];
+        let table = Table::new(
+            iter::once("header"),
+            ["foo", ".bar", ".baz"].iter(),
+            |name: &&str| {
+                let row = Row::new(iter::once(HighlightableText::<iter::Empty<_>>::Raw(
+                    name.to_string(),
+                )));
+                if name.starts_with('.') {
+                    row.style(dim)
+                } else {
+                    row
+                }
+            },
+        )
+        .widths(&widths)
+        .selected_symbol("> ")
+        .column_styles(&column_styles);
+        state.select(2);
+        StatefulWidget::render(table, area, &mut buf, &mut state);
+
+        // skip header, header gap, and the selection symbol
+        let cell = |row: u16| buf.get(area.left() + 2, row + 2);
+        assert_eq!(cell(0).symbol, "f");
+        assert!(!cell(0).modifier.contains(Modifier::DIM));
+        assert_eq!(cell(1).symbol, ".");
+        assert!(cell(1).modifier.contains(Modifier::DIM));
+        assert_eq!(cell(1).fg, Color::Red);
+        // selected rows are not dimmed
+        assert!(!cell(2).modifier.contains(Modifier::DIM));
+    }
+
+    #[test]
+    fn alternate_rows() {
+        let area = Rect::new(0, 0, 10, 6);
+        let mut buf = Buffer::empty(area);
+        let mut state = TableState::default();
+        state.select(1);
+
+        let widths = [Constraint::Min(1)];
+        let table = Table::new(iter::once("header"), 0..4, |i: usize| {
+            Row::new(iter::once(HighlightableText::<iter::Empty<_>>::Raw(
+                i.to_string(),
+            )))
+        })
+        .widths(&widths)
+        .selected_style(Style::default().bg(Color::Blue))
+        .alternate_style(Style::default().bg(Color::Gray));
+        StatefulWidget::render(table, area, &mut buf, &mut state);
+
+        // skip header and header gap
+        let bg = |row: u16| buf.get(area.right() - 1, row + 2).bg;
+        assert_eq!(bg(0), Color::Reset);
+        assert_eq!(bg(1), Color::Blue);
+        assert_eq!(bg(2), Color::Reset);
+        assert_eq!(bg(3), Color::Gray);
+    }
+
+    #[test]
+    fn component_spans() {
+        let style = Style::default();
+        let highlight_style = Style::default().bg(Color::Blue);
+        let component_style = Style::default().add_modifier(Modifier::UNDERLINED);
+        let spans = build_component_spans(
+            "/foo/bar/baz",
+            vec![2..3, 10..11].into_iter(),
+            vec![1..4, 9..12].into_iter(),
+            &style,
+            &highlight_style,
+            &component_style,
+        );
+        let underlined = component_style;
+        let both = component_style.bg(Color::Blue);
+        assert_eq!(
+            spans
+                .0
+                .iter()
+                .map(|span| (span.content.as_ref(), span.style))
+                .collect::<Vec<_>>(),
+            vec![
+                ("/", style),
+                ("f", underlined),
+                ("o", both),
+                ("o", underlined),
+                ("/bar/", style),
+                ("b", underlined),
+                ("a", both),
+                ("z", underlined),
+            ]
+        );
+    }
+
+    #[test]
+    fn scrollbar() {
+        assert_eq!(scrollbar_thumb(0, 10, 5), 0..0);
+        assert_eq!(scrollbar_thumb(0, 10, 100), 0..1);
+        assert_eq!(scrollbar_thumb(90, 10, 100), 9..10);
+        assert_eq!(scrollbar_thumb(0, 10, 20), 0..5);
+        assert_eq!(scrollbar_thumb(5, 10, 20), 2..7);
+        assert_eq!(scrollbar_thumb(10, 10, 20), 5..10);
+
+        // 10 visible rows out of 20, scrolled to the middle
+        assert_eq!(render_scrollbar(12, 20, 14), "││█████│││");
+        assert_eq!(render_scrollbar(12, 20, 0), "█████│││││");
+        assert_eq!(render_scrollbar(12, 20, 19), "│││││█████");
+        assert_eq!(render_scrollbar(12, 5, 0), "││││││││││");
+    }
+}
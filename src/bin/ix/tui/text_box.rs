@@ -6,6 +6,7 @@ use tui::{
     widgets::{Paragraph, StatefulWidget, Widget},
 };
 use unicode_segmentation::{GraphemeCursor, UnicodeSegmentation};
+use unicode_width::UnicodeWidthStr;
 
 pub struct TextBox<'b> {
     style: Style,
@@ -43,8 +44,11 @@ impl StatefulWidget for TextBox<'_> {
     type State = TextBoxState;
 
     fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
-        let grapheme_indices = UnicodeSegmentation::grapheme_indices(state.text.as_str(), true);
         let cursor = state.grapheme_cursor.cur_cursor();
+        let width = (area.width as usize).saturating_sub(self.prompt.width());
+        let offset = scroll_offset(&state.text, cursor, width);
+        let grapheme_indices = UnicodeSegmentation::grapheme_indices(&state.text[offset..], true)
+            .map(|(i, grapheme)| (offset + i, grapheme));
 
         let mut text = vec![self.prompt.clone()];
         text.extend(grapheme_indices.map(|(i, grapheme)| {
@@ -63,6 +67,28 @@ impl StatefulWidget for TextBox<'_> {
     }
 }
 
+/// Returns a byte offset of the first grapheme to show,
+/// so that the cursor fits in `width` columns.
+fn scroll_offset(text: &str, cursor: usize, width: usize) -> usize {
+    // cursor at the end of the text occupies a column
+    let cursor_width = text[cursor..]
+        .graphemes(true)
+        .next()
+        .map(|grapheme| grapheme.width())
+        .unwrap_or(1);
+    let mut overflow = (text[..cursor].width() + cursor_width).saturating_sub(width);
+
+    let mut offset = 0;
+    for (i, grapheme) in text[..cursor].grapheme_indices(true) {
+        if overflow == 0 {
+            return i;
+        }
+        overflow = overflow.saturating_sub(grapheme.width());
+        offset = i + grapheme.len();
+    }
+    offset
+}
+
 pub struct TextBoxState {
     text: String,
     grapheme_cursor: GraphemeCursor,
@@ -92,12 +118,27 @@ impl TextBoxState {
     }
 
     pub fn on_char(&mut self, ch: char) {
+        self.insert_str(ch.encode_utf8(&mut [0; 4]));
+    }
+
+    /// Inserts `s` at the cursor at once, moving the cursor to the end of it.
+    ///
+    /// Line breaks are stripped, as a query is a single line.
+    pub fn insert_str(&mut self, s: &str) {
         let cursor = self.grapheme_cursor.cur_cursor();
-        self.text.insert(cursor, ch);
+        let mut end = cursor;
+        for line in s.split(['\r', '\n']) {
+            self.text.insert_str(end, line);
+            end += line.len();
+        }
+
+        // the end of the inserted text may be in the middle of a grapheme
         self.grapheme_cursor = GraphemeCursor::new(cursor, self.text.len(), true);
-        self.grapheme_cursor
-            .next_boundary(&self.text[cursor..], cursor)
-            .unwrap();
+        while self.grapheme_cursor.cur_cursor() < end {
+            self.grapheme_cursor
+                .next_boundary(&self.text[cursor..], cursor)
+                .unwrap();
+        }
     }
 
     pub fn on_backspace(&mut self) -> bool {
@@ -210,4 +251,49 @@ mod tests {
         state.clear();
         assert_eq!("", state.text());
     }
+
+    #[test]
+    fn insert_str() {
+        let mut state = TextBoxState::with_text("ab".to_string());
+        state.on_left();
+        state.insert_str("/tmp/x y\n");
+        assert_eq!("a/tmp/x yb", state.text());
+        assert_eq!(state.grapheme_cursor.cur_cursor(), 9);
+
+        state.insert_str("\r\nfoo\nbar\r\n");
+        assert_eq!("a/tmp/x yfoobarb", state.text());
+        assert_eq!(state.grapheme_cursor.cur_cursor(), 15);
+
+        state.insert_str("\n");
+        assert_eq!("a/tmp/x yfoobarb", state.text());
+        assert_eq!(state.grapheme_cursor.cur_cursor(), 15);
+
+        // the cursor stays at a grapheme boundary
+        let mut state = TextBoxState::with_text("\u{301}".to_string());
+        state.on_home();
+        state.insert_str("e");
+        assert_eq!(state.grapheme_cursor.cur_cursor(), 3);
+        state.insert_str("あ𠮷");
+        assert_eq!("e\u{301}あ𠮷", state.text());
+        assert_eq!(state.grapheme_cursor.cur_cursor(), state.text().len());
+    }
+
+    #[test]
+    fn scroll() {
+        assert_eq!(scroll_offset("", 0, 10), 0);
+        assert_eq!(scroll_offset("abc", 3, 4), 0);
+        assert_eq!(scroll_offset("abc", 3, 3), 1);
+        assert_eq!(scroll_offset("abc", 1, 2), 0);
+        assert_eq!(scroll_offset("abc", 2, 2), 1);
+
+        // "あ" and "亜" are 3 bytes long and 2 columns wide
+        assert_eq!(scroll_offset("あ亜x", 7, 4), 3);
+        assert_eq!(scroll_offset("あ亜x", 7, 3), 6);
+        assert_eq!(scroll_offset("あ亜x", 3, 3), 3);
+        assert_eq!(scroll_offset("xあ亜", 4, 3), 4);
+        assert_eq!(scroll_offset("xあ亜", 4, 4), 1);
+
+        // cursor itself doesn't fit
+        assert_eq!(scroll_offset("abc", 2, 0), 2);
+    }
 }
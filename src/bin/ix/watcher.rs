@@ -0,0 +1,225 @@
+use crate::config::DatabaseConfig;
+
+use indexa::database::{Database, DatabaseBuilder};
+
+use anyhow::Result;
+use crossbeam_channel::Sender;
+use notify::{
+    event::{EventKind, ModifyKind, RenameMode},
+    Event, RecommendedWatcher, RecursiveMode, Watcher as _,
+};
+use std::{
+    collections::HashSet,
+    path::PathBuf,
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Events arriving within this interval are indexed together.
+const DEBOUNCE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Events are indexed at most this long after the first of them arrived,
+/// even if more keep arriving.
+const MAX_DEBOUNCE_DELAY: Duration = Duration::from_secs(2);
+
+/// Watches root directories and indexes files created in them.
+///
+/// The consistency model is append-only: created files and directories, and
+/// destinations of renames are indexed and sent as databases to be searched
+/// alongside the loaded database. Paths already in `primary`, and those that
+/// indexing the root directories would skip, are not sent. Deleted files and
+/// sources of renames stay in the database until it is updated with
+/// `ix --update`.
+pub struct Watcher {
+    _watcher: RecommendedWatcher,
+}
+
+impl Watcher {
+    pub fn new(
+        db_config: &DatabaseConfig,
+        primary: Arc<Database>,
+        tx: Sender<Database>,
+    ) -> Result<Self> {
+        let (event_tx, event_rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = event_tx.send(event);
+        })?;
+        for dir in &db_config.dirs {
+            watcher.watch(dir, RecursiveMode::Recursive)?;
+        }
+
+        let mut builder = db_config.database_builder();
+        builder.skip_missing_dirs(true);
+        let roots = db_config
+            .dirs
+            .iter()
+            .filter_map(|dir| dunce::canonicalize(dir).ok())
+            .collect::<Vec<_>>();
+
+        thread::spawn(move || {
+            // paths already sent, so that files in a directory indexed as a whole
+            // are not indexed again when their own events arrive
+            let mut indexed = HashSet::new();
+
+            while let Ok(event) = event_rx.recv() {
+                let mut paths = Vec::new();
+                collect_created_paths(event, &mut paths);
+                let deadline = Instant::now() + MAX_DEBOUNCE_DELAY;
+                loop {
+                    let timeout = deadline
+                        .saturating_duration_since(Instant::now())
+                        .min(DEBOUNCE_INTERVAL);
+                    match event_rx.recv_timeout(timeout) {
+                        Ok(event) => collect_created_paths(event, &mut paths),
+                        Err(RecvTimeoutError::Timeout) => break,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+
+                let paths = filter_new_paths(paths, &primary, &indexed, &builder, &roots);
+                if paths.is_empty() {
+                    continue;
+                }
+
+                let mut builder = builder.clone();
+                for path in paths {
+                    builder.add_dir(path);
+                }
+                let database = match builder.build() {
+                    Ok(database) if database.num_entries() > 0 => database,
+                    _ => continue,
+                };
+
+                indexed.extend(
+                    database
+                        .entries()
+                        .map(|entry| entry.path().into_std_path_buf()),
+                );
+                if tx.send(database).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Ok(Self { _watcher: watcher })
+    }
+}
+
+/// Canonicalizes `paths` and keeps those which are neither in `primary` nor
+/// already `indexed`, and which indexing the root directories would index.
+fn filter_new_paths(
+    paths: Vec<PathBuf>,
+    primary: &Database,
+    indexed: &HashSet<PathBuf>,
+    builder: &DatabaseBuilder,
+    roots: &[PathBuf],
+) -> Vec<PathBuf> {
+    paths
+        .into_iter()
+        .filter_map(|path| dunce::canonicalize(path).ok())
+        .filter(|path| {
+            !indexed.contains(path)
+                && primary.get_by_path(path).is_none()
+                && roots
+                    .iter()
+                    .any(|root| path.starts_with(root) && !builder.excludes(root, path))
+        })
+        .collect()
+}
+
+fn collect_created_paths(event: notify::Result<Event>, paths: &mut Vec<PathBuf>) {
+    let mut event = match event {
+        Ok(event) => event,
+        Err(_) => return,
+    };
+    match event.kind {
+        EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+            paths.append(&mut event.paths)
+        }
+        // paths are [from, to]
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => paths.extend(event.paths.pop()),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{CreateKind, RemoveKind};
+    use std::{fs, path::Path};
+
+    #[test]
+    fn created_paths() {
+        let event = |kind, paths: &[&str]| {
+            Ok(paths
+                .iter()
+                .fold(Event::new(kind), |event, path| event.add_path(path.into())))
+        };
+
+        let mut paths = Vec::new();
+        collect_created_paths(
+            event(EventKind::Create(CreateKind::File), &["/a"]),
+            &mut paths,
+        );
+        collect_created_paths(
+            event(EventKind::Remove(RemoveKind::File), &["/b"]),
+            &mut paths,
+        );
+        collect_created_paths(
+            event(
+                EventKind::Modify(ModifyKind::Name(RenameMode::Both)),
+                &["/c", "/d"],
+            ),
+            &mut paths,
+        );
+        collect_created_paths(
+            event(
+                EventKind::Modify(ModifyKind::Name(RenameMode::From)),
+                &["/e"],
+            ),
+            &mut paths,
+        );
+        collect_created_paths(
+            event(EventKind::Modify(ModifyKind::Name(RenameMode::To)), &["/f"]),
+            &mut paths,
+        );
+        assert_eq!(paths, [Path::new("/a"), Path::new("/d"), Path::new("/f")]);
+    }
+
+    #[test]
+    fn new_paths() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let root = dunce::canonicalize(tmpdir.path()).unwrap();
+        fs::write(root.join("existing"), "").unwrap();
+        fs::create_dir(root.join(".hidden")).unwrap();
+
+        let mut builder = DatabaseBuilder::new();
+        builder.ignore_hidden(true).skip_missing_dirs(true);
+        let primary = builder.clone().add_dir(&root).build().unwrap();
+        let roots = [root.clone()];
+
+        // saving atomically renames a temporary file onto the existing one
+        fs::write(root.join("tmp"), "").unwrap();
+        fs::rename(root.join("tmp"), root.join("existing")).unwrap();
+        fs::write(root.join(".hidden").join("a"), "").unwrap();
+        fs::write(root.join("new"), "").unwrap();
+        fs::write(root.join("indexed"), "").unwrap();
+
+        let indexed = std::iter::once(root.join("indexed")).collect();
+        let paths = filter_new_paths(
+            ["existing", ".hidden/a", "new", "indexed", "missing"]
+                .iter()
+                .map(|path| root.join(path))
+                .collect(),
+            &primary,
+            &indexed,
+            &builder,
+            &roots,
+        );
+        assert_eq!(paths, [root.join("new")]);
+    }
+}
@@ -1,47 +1,361 @@
+mod bloom;
 mod builder;
+mod file;
+mod fold;
+mod ignore_rules;
 mod indexer;
 mod search;
+mod timing;
 mod util;
 
-pub use builder::DatabaseBuilder;
+pub(crate) use bloom::query_trigrams;
+pub use builder::{DatabaseBuilder, Warning};
+pub use file::LoadOptions;
+pub(crate) use fold::fold_case;
+pub use indexer::EntryFilter;
+pub use search::PartialHits;
+pub(crate) use util::get_compare_func;
 
-use crate::{mode::Mode, Result};
+use crate::{
+    mode::{FileType, Mode},
+    Error, Result,
+};
 
 use camino::Utf8PathBuf;
 use enum_map::{Enum, EnumMap};
-use fxhash::FxHashMap;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::{cmp::Ordering, time::SystemTime};
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    time::SystemTime,
+};
+use strum::IntoEnumIterator;
 use strum_macros::{Display, EnumIter};
 
 // Database can have multiple "root" entries, which correspond to directories
 // specified in "dirs" in config.
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug)]
 pub struct Database {
     /// names of all entries concatenated
     name_arena: String,
     nodes: Vec<EntryNode>,
-    root_paths: FxHashMap<u32, Utf8PathBuf>,
+    /// number of directories, which is counted when indexed
+    num_dirs: usize,
+    /// sorted by id, so that entries of a root lie between it and the next root
+    root_paths: BTreeMap<u32, Utf8PathBuf>,
     size: Option<Vec<u64>>,
+    /// whether sizes of directories are total sizes of files under them
+    /// instead of numbers of their children
+    recursive_dir_size: bool,
     mode: Option<Vec<Mode>>,
     created: Option<Vec<SystemTime>>,
     modified: Option<Vec<SystemTime>>,
     accessed: Option<Vec<SystemTime>>,
+    owner: Option<Vec<u32>>,
+    xattr: Option<XattrColumn>,
+    /// sorted ids of entries whose names were converted lossily from non-UTF-8 names
+    lossy_ids: Vec<u32>,
+    basename_bloom: Option<bloom::BasenameBloom>,
+    folded_names: Option<fold::FoldedNames>,
+    metadata: Option<DatabaseMeta>,
     sorted_ids: EnumMap<StatusKind, Option<Vec<u32>>>,
+    /// ids sorted in the composite orders declared with `DatabaseBuilder::fast_sort_by`
+    composite_sorted_ids: BTreeMap<SortKey, Vec<u32>>,
+    /// statuses indexed in the file but skipped by `LoadOptions`
+    unloaded: EnumMap<StatusKind, bool>,
 }
 
 impl Database {
+    /// Loads a database saved with `save`.
+    pub fn open<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::open_with(path, &LoadOptions::new())
+    }
+
+    /// Loads a database saved with `save`, skipping parts not needed by `options`.
+    pub fn open_with<P>(path: P, options: &LoadOptions) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        timing::time("load", || file::read(path.as_ref(), options))
+    }
+
+    /// Same as `open_with`, but deserializes parts of the database in parallel.
+    ///
+    /// This is faster for large databases with many indexed statuses,
+    /// at the cost of temporarily holding the loaded parts of the file in memory.
+    pub fn open_parallel<P>(path: P, options: &LoadOptions) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        timing::time("load", || file::read_parallel(path.as_ref(), options))
+    }
+
+    /// Writes the database to a file, creating parent directories if needed.
+    pub fn save<P>(&self, path: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        file::write(self, &mut writer)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Combines databases into one, as if their root directories were indexed together.
+    ///
+    /// A status is indexed (or fast-sortable) in the result only if it is
+    /// indexed (or fast-sortable) in all of the databases.
+    /// Overlapping root directories are not deduplicated.
+    /// If any of the databases has recursive directory sizes, those of
+    /// the result are recomputed as recursive ones.
+    ///
+    /// Fails with `Error::TooManyNames` if names of the entries exceed
+    /// `u32::MAX` bytes in total.
+    pub fn merge(databases: Vec<Database>) -> Result<Self> {
+        let mut databases = databases.into_iter();
+        let mut merged = databases.next().unwrap_or_else(|| Self {
+            name_arena: String::new(),
+            nodes: Vec::new(),
+            num_dirs: 0,
+            root_paths: BTreeMap::new(),
+            size: None,
+            recursive_dir_size: false,
+            mode: None,
+            created: None,
+            modified: None,
+            accessed: None,
+            owner: None,
+            xattr: None,
+            lossy_ids: Vec::new(),
+            basename_bloom: None,
+            folded_names: None,
+            metadata: None,
+            sorted_ids: EnumMap::default(),
+            composite_sorted_ids: BTreeMap::new(),
+            unloaded: EnumMap::default(),
+        });
+
+        let mut fast_sort_flags = merged.fast_sort_flags();
+        let mut sort_keys = merged.fast_sort_keys().collect::<Vec<_>>();
+        for database in databases {
+            for (kind, enabled) in fast_sort_flags.iter_mut() {
+                *enabled &= database.is_fast_sortable(kind);
+            }
+            sort_keys.retain(|key| database.is_fast_sortable_by(*key));
+            merged.extend(database)?;
+        }
+        merged.sort_ids(fast_sort_flags, &sort_keys);
+
+        Ok(merged)
+    }
+
+    /// Adds entries of another database, keeping ids of the existing entries.
+    ///
+    /// Same as `merge`, a status stays indexed (or fast-sortable) only if
+    /// it is indexed (or fast-sortable) in both of the databases.
+    ///
+    /// Fails with `Error::TooManyNames`, leaving the database unchanged, if
+    /// names of the entries exceed `u32::MAX` bytes in total.
+    pub fn append(&mut self, other: Database) -> Result<()> {
+        let mut fast_sort_flags = self.fast_sort_flags();
+        for (kind, enabled) in fast_sort_flags.iter_mut() {
+            *enabled &= other.is_fast_sortable(kind);
+        }
+        let sort_keys = self
+            .fast_sort_keys()
+            .filter(|key| other.is_fast_sortable_by(*key))
+            .collect::<Vec<_>>();
+        self.extend(other)?;
+        self.sort_ids(fast_sort_flags, &sort_keys);
+        Ok(())
+    }
+
+    /// Fails without modifying the database if names would exceed `u32::MAX`
+    /// bytes in total.
+    fn extend(&mut self, other: Database) -> Result<()> {
+        fn concat<T>(a: &mut Option<Vec<T>>, b: Option<Vec<T>>) {
+            match (a.as_mut(), b) {
+                (Some(a), Some(mut b)) => a.append(&mut b),
+                _ => *a = None,
+            }
+        }
+
+        if self.name_arena.len() + other.name_arena.len() > u32::MAX as usize {
+            return Err(Error::TooManyNames);
+        }
+        let name_offset = self.name_arena.len() as u32;
+        let id_offset = self.nodes.len() as u32;
+
+        // u32::MAX denotes absence of children
+        let offset_child = |id| {
+            if id == u32::MAX {
+                id
+            } else {
+                id + id_offset
+            }
+        };
+
+        self.name_arena.push_str(&other.name_arena);
+        self.nodes
+            .extend(other.nodes.into_iter().map(|node| EntryNode {
+                name_start: node.name_start + name_offset,
+                parent: node.parent + id_offset,
+                child_start: offset_child(node.child_start),
+                child_end: offset_child(node.child_end),
+                ..node
+            }));
+        self.num_dirs += other.num_dirs;
+        self.root_paths.extend(
+            other
+                .root_paths
+                .into_iter()
+                .map(|(id, path)| (id + id_offset, path)),
+        );
+        concat(&mut self.size, other.size);
+        if self.recursive_dir_size || other.recursive_dir_size {
+            self.sum_dir_sizes();
+        }
+        concat(&mut self.mode, other.mode);
+        concat(&mut self.created, other.created);
+        concat(&mut self.modified, other.modified);
+        concat(&mut self.accessed, other.accessed);
+        concat(&mut self.owner, other.owner);
+        match (self.xattr.as_mut(), other.xattr) {
+            (Some(a), Some(mut b)) if a.name == b.name => a.values.append(&mut b.values),
+            _ => self.xattr = None,
+        }
+        self.lossy_ids
+            .extend(other.lossy_ids.into_iter().map(|id| id + id_offset));
+        if self.basename_bloom.is_some() && other.basename_bloom.is_some() {
+            self.basename_bloom = Some(bloom::BasenameBloom::build(self));
+        } else {
+            self.basename_bloom = None;
+        }
+        // the combined database was not built at once on a single host
+        self.metadata = None;
+        if self.folded_names.is_some() && other.folded_names.is_some() {
+            self.folded_names = Some(fold::FoldedNames::build(self));
+        } else {
+            self.folded_names = None;
+        }
+
+        Ok(())
+    }
+
+    /// Replaces sizes of directories with total sizes of files under them.
+    fn sum_dir_sizes(&mut self) {
+        if let Some(size) = &mut self.size {
+            // children have larger ids than their parents
+            for (id, node) in self.nodes.iter().enumerate().rev() {
+                if node.is_dir {
+                    size[id] = if node.has_any_child() {
+                        size[node.child_start as usize..node.child_end as usize]
+                            .iter()
+                            .sum()
+                    } else {
+                        0
+                    };
+                }
+            }
+        }
+        self.recursive_dir_size = true;
+    }
+
+    fn fast_sort_flags(&self) -> StatusFlags {
+        enum_map::enum_map! {
+            kind => self.is_fast_sortable(kind),
+        }
+    }
+
+    fn sort_ids(&mut self, fast_sort_flags: StatusFlags, sort_keys: &[SortKey]) {
+        for (kind, enabled) in fast_sort_flags {
+            self.sorted_ids[kind] = None;
+            if enabled {
+                self.sorted_ids[kind] = Some(builder::sort_ids(self, kind));
+            }
+        }
+        self.composite_sorted_ids.clear();
+        for key in sort_keys {
+            let ids = builder::sort_ids_by(self, *key);
+            self.composite_sorted_ids.insert(*key, ids);
+        }
+    }
+
+    /// Regenerates ids sorted by each of `kinds`, making them fast-sortable.
+    ///
+    /// This brings the precomputed orders up to date after entries are
+    /// added to a loaded database outside of `DatabaseBuilder`.
+    /// Composite orders declared with `DatabaseBuilder::fast_sort_by` are
+    /// regenerated as well, while orders of statuses not in `kinds` are
+    /// left as they are.
+    pub fn rebuild_sorted_ids(&mut self, kinds: &[StatusKind]) -> Result<()> {
+        if kinds.iter().any(|kind| !self.is_indexed(*kind)) {
+            return Err(Error::InvalidOption(
+                "Fast sorting cannot be enabled for a non-indexed status.".to_string(),
+            ));
+        }
+
+        for kind in kinds {
+            self.sorted_ids[*kind] = Some(builder::sort_ids(self, *kind));
+        }
+        let sort_keys = self.fast_sort_keys().collect::<Vec<_>>();
+        for key in sort_keys {
+            let ids = builder::sort_ids_by(self, key);
+            self.composite_sorted_ids.insert(key, ids);
+        }
+
+        Ok(())
+    }
+
     #[inline]
     pub fn num_entries(&self) -> usize {
         self.nodes.len()
     }
 
+    /// Returns true if the database has no entries, e.g. when no directory
+    /// was given to the builder.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    #[inline]
+    pub fn num_dirs(&self) -> usize {
+        self.num_dirs
+    }
+
+    #[inline]
+    pub fn num_files(&self) -> usize {
+        self.nodes.len() - self.num_dirs
+    }
+
     #[inline]
     pub fn entries(&self) -> impl ExactSizeIterator<Item = Entry<'_>> {
         (0..self.nodes.len() as u32).map(move |id| self.entry(EntryId(id)))
     }
 
+    /// Parallel version of `entries`.
+    #[inline]
+    pub fn entries_par(&self) -> impl IndexedParallelIterator<Item = Entry<'_>> {
+        (0..self.nodes.len() as u32)
+            .into_par_iter()
+            .map(move |id| self.entry(EntryId(id)))
+    }
+
     #[inline]
     pub fn root_entries(&self) -> impl ExactSizeIterator<Item = Entry<'_>> {
         self.root_paths
@@ -49,6 +363,14 @@ impl Database {
             .map(move |id| self.entry(EntryId(*id)))
     }
 
+    /// Returns root directories (or files) the database covers with their entries,
+    /// in the order they were indexed.
+    pub fn roots(&self) -> impl Iterator<Item = (&Path, Entry<'_>)> {
+        self.root_paths
+            .iter()
+            .map(move |(id, path)| (path.as_std_path(), self.entry(EntryId(*id))))
+    }
+
     #[inline]
     pub fn is_indexed(&self, kind: StatusKind) -> bool {
         match kind {
@@ -58,22 +380,148 @@ impl Database {
             StatusKind::Created => self.created.is_some(),
             StatusKind::Modified => self.modified.is_some(),
             StatusKind::Accessed => self.accessed.is_some(),
+            StatusKind::Owner => self.owner.is_some(),
         }
     }
 
+    /// Returns true if the status was indexed but not loaded because of
+    /// `LoadOptions`.
+    ///
+    /// Accessing such a status looks up the file system, as if it were not indexed.
+    #[inline]
+    pub fn is_unloaded(&self, kind: StatusKind) -> bool {
+        self.unloaded[kind]
+    }
+
+    /// Returns true if sizes of directories are total sizes of files under
+    /// them rather than numbers of their children.
+    ///
+    /// See `DatabaseBuilder::recursive_dir_size`.
+    #[inline]
+    pub fn is_dir_size_recursive(&self) -> bool {
+        self.recursive_dir_size && self.size.is_some()
+    }
+
     #[inline]
     pub fn is_fast_sortable(&self, kind: StatusKind) -> bool {
         self.sorted_ids[kind].is_some()
     }
 
+    /// Returns true if ids sorted in the composite order are precomputed.
+    #[inline]
+    pub fn is_fast_sortable_by(&self, key: SortKey) -> bool {
+        self.composite_sorted_ids.contains_key(&key)
+    }
+
+    /// Returns composite orders which are fast-sortable.
+    pub fn fast_sort_keys(&self) -> impl Iterator<Item = SortKey> + '_ {
+        self.composite_sorted_ids.keys().copied()
+    }
+
+    /// Returns statuses which are indexed.
+    pub fn indexed_statuses(&self) -> impl Iterator<Item = StatusKind> + '_ {
+        StatusKind::iter().filter(move |kind| self.is_indexed(*kind))
+    }
+
+    /// Returns statuses which are fast-sortable.
+    pub fn fast_sortable_statuses(&self) -> impl Iterator<Item = StatusKind> + '_ {
+        StatusKind::iter().filter(move |kind| self.is_fast_sortable(*kind))
+    }
+
+    /// Returns the name of the extended attribute whose presence is indexed.
+    #[inline]
+    pub fn indexed_xattr(&self) -> Option<&str> {
+        self.xattr.as_ref().map(|xattr| xattr.name.as_str())
+    }
+
+    /// Returns when, where, and how the database was built, if recorded with
+    /// `DatabaseBuilder::record_metadata`.
+    ///
+    /// Databases combined with `merge` or `append` have no metadata.
+    #[inline]
+    pub fn metadata(&self) -> Option<&DatabaseMeta> {
+        self.metadata.as_ref()
+    }
+
+    /// Returns the number of bytes used to store names of all entries.
+    #[inline]
+    pub fn name_arena_len(&self) -> usize {
+        self.name_arena.len()
+    }
+
     #[inline]
     pub fn entry(&self, id: EntryId) -> Entry<'_> {
         Entry { database: self, id }
     }
 
+    /// Looks up an entry by its path.
+    ///
+    /// Paths are compared as they are, so `path` should be canonicalized
+    /// just as root directories are. On Windows, they are compared case-insensitively.
+    pub fn get_by_path<P>(&self, path: P) -> Option<EntryId>
+    where
+        P: AsRef<Path>,
+    {
+        let path = util::comparison_key(path.as_ref().to_str()?);
+        self.root_paths.iter().find_map(|(&root_id, root_path)| {
+            let root_path = util::comparison_key(root_path.as_str());
+            if !util::is_same_or_subdir(&path, &root_path) {
+                return None;
+            }
+
+            let mut id = root_id;
+            for name in path[root_path.len()..]
+                .split(std::path::MAIN_SEPARATOR)
+                .filter(|name| !name.is_empty())
+            {
+                let node = &self.nodes[id as usize];
+                if !node.has_any_child() {
+                    return None;
+                }
+                id = (node.child_start..node.child_end).find(|child| {
+                    util::comparison_key(self.basename_from_node(&self.nodes[*child as usize]))
+                        == name
+                })?;
+            }
+            Some(EntryId(id))
+        })
+    }
+
+    /// Returns true if the entry or any of its ancestors is hidden.
+    ///
+    /// Root entries are never considered hidden, as they are indexed explicitly.
+    fn is_in_hidden_tree(&self, mut id: u32) -> bool {
+        loop {
+            let node = &self.nodes[id as usize];
+            if node.parent == id {
+                return false;
+            }
+            if self.is_hidden_node(id) {
+                return true;
+            }
+            id = node.parent;
+        }
+    }
+
+    #[inline]
+    fn is_hidden_node(&self, id: u32) -> bool {
+        #[cfg(windows)]
+        {
+            if let Some(mode) = &self.mode {
+                if mode[id as usize].is_hidden() {
+                    return true;
+                }
+            }
+        }
+
+        self.basename_from_node(&self.nodes[id as usize])
+            .starts_with('.')
+    }
+
     #[inline]
     fn basename_from_node(&self, node: &EntryNode) -> &str {
-        &self.name_arena[node.name_start..node.name_start + node.name_len as usize]
+        let start = node.name_start as usize;
+        &self.name_arena[start..start + node.name_len as usize]
     }
 
     #[inline]
@@ -150,10 +598,17 @@ impl Database {
                 (None, Some(_)) => return Ordering::Less, // /foo vs. /foo/bar
                 (Some(_), None) => return Ordering::Greater, // /foo/bar vs. /foo
                 (Some(a), Some(b)) => {
+                    let node_a = &self.nodes[a as usize];
+                    let node_b = &self.nodes[b as usize];
+                    if node_a.parent == a && node_b.parent == b {
+                        // different roots e.g. /foo/bar vs. /baz.txt
+                        return Ord::cmp(&self.root_paths[&a], &self.root_paths[&b]);
+                    }
+
                     // /foo/bar vs. /foo/baz
                     return Ord::cmp(
-                        self.basename_from_node(&self.nodes[a as usize]),
-                        self.basename_from_node(&self.nodes[b as usize]),
+                        self.basename_from_node(node_a),
+                        self.basename_from_node(node_b),
                     );
                 }
             }
@@ -161,7 +616,20 @@ impl Database {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize, Enum, Display, EnumIter)]
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    Enum,
+    Display,
+    EnumIter,
+)]
 #[serde(rename_all = "lowercase")]
 pub enum StatusKind {
     #[serde(alias = "name")]
@@ -183,6 +651,30 @@ pub enum StatusKind {
     Modified,
     #[serde(alias = "atime")]
     Accessed,
+    #[serde(alias = "user", alias = "uid")]
+    Owner,
+}
+
+/// Composite sort order which can be precomputed with
+/// `DatabaseBuilder::fast_sort_by`, in addition to the orders by a single
+/// status precomputed with `DatabaseBuilder::fast_sort`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum SortKey {
+    /// sorted by the first status, with ties broken by the second
+    Then(StatusKind, StatusKind),
+    /// directories before files, each sorted by the status with ties broken by basename
+    DirsFirst(StatusKind),
+}
+
+impl SortKey {
+    /// Returns statuses the order depends on.
+    pub(crate) fn statuses(self) -> impl Iterator<Item = StatusKind> {
+        let (primary, secondary) = match self {
+            Self::Then(primary, secondary) => (primary, Some(secondary)),
+            Self::DirsFirst(primary) => (primary, None),
+        };
+        std::iter::once(primary).chain(secondary)
+    }
 }
 
 type StatusFlags = EnumMap<StatusKind, bool>;
@@ -201,17 +693,99 @@ pub struct Entry<'a> {
 }
 
 impl<'a> Entry<'a> {
+    /// Returns the database the entry belongs to.
+    #[inline]
+    pub fn database(&self) -> &'a Database {
+        self.database
+    }
+
+    /// Returns true if `size` of the entry is the number of its children
+    /// rather than bytes, which is the case for directories unless the
+    /// database has recursive directory sizes.
+    #[inline]
+    pub fn size_is_item_count(&self) -> bool {
+        self.is_dir() && !self.database.is_dir_size_recursive()
+    }
+
+    /// Returns the root entry the entry was indexed under, which is the
+    /// entry itself for a root.
+    pub fn root(&self) -> Entry<'a> {
+        let mut id = self.id.0;
+        loop {
+            let parent = self.database.nodes[id as usize].parent;
+            if parent == id {
+                return self.database.entry(EntryId(id));
+            }
+            id = parent;
+        }
+    }
+
     #[inline]
     pub fn is_dir(&self) -> bool {
         self.node().is_dir
     }
 
+    /// Returns true if the entry itself is hidden, regardless of its ancestors.
+    ///
+    /// An entry is hidden if its basename starts with a dot. On Windows, an
+    /// entry with the hidden attribute is also hidden if `Mode` is indexed.
+    #[inline]
+    pub fn is_hidden(&self) -> bool {
+        self.database.is_hidden_node(self.id.0)
+    }
+
+    /// Returns true if the name of the entry or any of its ancestors was not
+    /// valid UTF-8 and was indexed lossily with `DatabaseBuilder::lossy_paths`.
+    ///
+    /// The path of such an entry only approximates the real path, so it may
+    /// not be usable to access the file.
+    pub fn is_lossy(&self) -> bool {
+        let lossy_ids = &self.database.lossy_ids;
+        if lossy_ids.is_empty() {
+            return false;
+        }
+
+        let mut id = self.id.0;
+        loop {
+            if lossy_ids.binary_search(&id).is_ok() {
+                return true;
+            }
+            let parent = self.database.nodes[id as usize].parent;
+            if parent == id {
+                return false;
+            }
+            id = parent;
+        }
+    }
+
     #[inline]
     pub fn children(&self) -> impl ExactSizeIterator<Item = Entry<'_>> {
         let node = &self.node();
         (node.child_start..node.child_end).map(move |id| self.database.entry(EntryId(id)))
     }
 
+    /// Returns all entries under the entry in depth-first order.
+    pub fn descendants(&self) -> impl Iterator<Item = Entry<'_>> {
+        // children of each directory are contiguous, but its subtree is not
+        let node = self.node();
+        let mut stack = Vec::new();
+        stack.push(node.child_start..node.child_end);
+        std::iter::from_fn(move || loop {
+            let id = match stack.last_mut()?.next() {
+                Some(id) => id,
+                None => {
+                    stack.pop();
+                    continue;
+                }
+            };
+            let node = &self.database.nodes[id as usize];
+            if node.has_any_child() {
+                stack.push(node.child_start..node.child_end);
+            }
+            return Some(self.database.entry(EntryId(id)));
+        })
+    }
+
     #[inline]
     pub fn basename(&self) -> &str {
         self.database.basename_from_node(self.node())
@@ -264,6 +838,21 @@ impl<'a> Entry<'a> {
             .map_err(Into::into)
     }
 
+    /// Returns true if the entry is a symbolic link.
+    ///
+    /// The indexed mode is used if available, which avoids accessing the file system.
+    #[inline]
+    pub fn is_symlink(&self) -> Result<bool> {
+        if let Some(mode) = &self.database.mode {
+            return Ok(mode[self.id.0 as usize].file_type() == FileType::Symlink);
+        }
+
+        self.path()
+            .symlink_metadata()
+            .map(|metadata| metadata.file_type().is_symlink())
+            .map_err(Into::into)
+    }
+
     #[inline]
     pub fn created(&self) -> Result<SystemTime> {
         if let Some(created) = &self.database.created {
@@ -303,6 +892,51 @@ impl<'a> Entry<'a> {
             .map_err(Into::into)
     }
 
+    /// Returns the user id of the owner. Not supported on Windows.
+    #[inline]
+    pub fn owner(&self) -> Result<u32> {
+        if let Some(owner) = &self.database.owner {
+            return Ok(owner[self.id.0 as usize]);
+        }
+
+        self.path()
+            .symlink_metadata()
+            .and_then(|metadata| util::owner(&metadata))
+            .map_err(Into::into)
+    }
+
+    /// Returns true if the entry has the extended attribute `name`.
+    ///
+    /// Unless `name` is the indexed one, the file system is queried.
+    #[inline]
+    pub fn has_xattr(&self, name: &str) -> Result<bool> {
+        if let Some(xattr) = &self.database.xattr {
+            if xattr.name == name {
+                return Ok(xattr.values[self.id.0 as usize]);
+            }
+        }
+
+        util::has_xattr(self.path(), name).map_err(Into::into)
+    }
+
+    /// Copies the entry into an owned record.
+    ///
+    /// Only indexed statuses are filled in, so that no file system access happens.
+    pub fn to_record(&self) -> EntryRecord {
+        let i = self.id.0 as usize;
+        EntryRecord {
+            path: self.path(),
+            basename: self.basename().to_string(),
+            is_dir: self.is_dir(),
+            size: self.database.size.as_ref().map(|size| size[i]),
+            mode: self.database.mode.as_ref().map(|mode| mode[i]),
+            created: self.database.created.as_ref().map(|created| created[i]),
+            modified: self.database.modified.as_ref().map(|modified| modified[i]),
+            accessed: self.database.accessed.as_ref().map(|accessed| accessed[i]),
+            owner: self.database.owner.as_ref().map(|owner| owner[i]),
+        }
+    }
+
     #[inline]
     fn node(&self) -> &EntryNode {
         &self.database.nodes[self.id.0 as usize]
@@ -310,6 +944,10 @@ impl<'a> Entry<'a> {
 
     #[inline]
     fn cmp_by_path(&self, other: &Self) -> Ordering {
+        if !std::ptr::eq(self.database, other.database) {
+            // ids of different databases are unrelated
+            return Ord::cmp(&self.path(), &other.path());
+        }
         self.database.cmp_by_path(self.id.0, other.id.0)
     }
 
@@ -322,9 +960,44 @@ impl<'a> Entry<'a> {
     }
 }
 
+/// Presence of an extended attribute for each entry.
+#[derive(Debug, Serialize, Deserialize)]
+struct XattrColumn {
+    name: String,
+    values: Vec<bool>,
+}
+
+/// An owned snapshot of an entry, which can be serialized and sent elsewhere.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntryRecord {
+    pub path: Utf8PathBuf,
+    pub basename: String,
+    pub is_dir: bool,
+    pub size: Option<u64>,
+    pub mode: Option<Mode>,
+    pub created: Option<SystemTime>,
+    pub modified: Option<SystemTime>,
+    pub accessed: Option<SystemTime>,
+    pub owner: Option<u32>,
+}
+
+/// Information about how a database was built.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DatabaseMeta {
+    pub built_at: SystemTime,
+    /// host name of the machine which built the database, if known
+    pub hostname: Option<String>,
+    pub roots: Vec<Utf8PathBuf>,
+    pub indexed: Vec<StatusKind>,
+}
+
+/// A node of the tree of entries.
+///
+/// Names are stored in a shared arena, whose length is limited to `u32::MAX`
+/// bytes so that nodes stay small in memory and in database files.
 #[derive(Debug, Serialize, Deserialize)]
 struct EntryNode {
-    name_start: usize,
+    name_start: u32,
     parent: u32,
     child_start: u32,
     child_end: u32,
@@ -0,0 +1,92 @@
+use super::Database;
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Number of consecutive entries covered by a single filter.
+pub(crate) const CHUNK_SIZE: usize = 2048;
+
+/// Number of bits in a single filter. Must be a power of two.
+const FILTER_BITS: usize = 1 << 16;
+const FILTER_WORDS: usize = FILTER_BITS / 64;
+
+/// Bloom filters of trigrams in basenames, one per chunk of entries.
+///
+/// Trigrams are taken over bytes with ASCII letters lowercased,
+/// so that the filters can be used for case-insensitive queries too.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct BasenameBloom {
+    words: Vec<u64>,
+}
+
+impl BasenameBloom {
+    pub fn build(database: &Database) -> Self {
+        let words = database
+            .nodes
+            .par_chunks(CHUNK_SIZE)
+            .flat_map_iter(|nodes| {
+                let mut filter = vec![0u64; FILTER_WORDS];
+                for node in nodes {
+                    for trigram in trigrams(database.basename_from_node(node)) {
+                        for bit in bit_positions(&trigram) {
+                            filter[bit / 64] |= 1 << (bit % 64);
+                        }
+                    }
+                }
+                filter
+            })
+            .collect();
+        Self { words }
+    }
+
+    /// Returns, for each chunk, whether it can contain a basename having
+    /// all of `trigrams`.
+    pub fn candidate_chunks(&self, trigrams: &[[u8; 3]]) -> Vec<bool> {
+        let positions: Vec<_> = trigrams.iter().flat_map(bit_positions).collect();
+        self.words
+            .chunks(FILTER_WORDS)
+            .map(|filter| {
+                positions
+                    .iter()
+                    .all(|bit| filter[bit / 64] & (1 << (bit % 64)) != 0)
+            })
+            .collect()
+    }
+}
+
+/// Returns trigrams which all basenames containing `literal` have.
+///
+/// If the query is case-insensitive, trigrams that could match differently
+/// cased strings other than by ASCII case folding are left out.
+pub(crate) fn query_trigrams(literal: &str, case_sensitive: bool) -> Vec<[u8; 3]> {
+    let mut trigrams: Vec<_> = literal
+        .as_bytes()
+        .windows(3)
+        .filter(|window| {
+            // Unicode simple case folding maps "k" and "s" to non-ASCII
+            // characters (KELVIN SIGN and LATIN SMALL LETTER LONG S)
+            case_sensitive
+                || window
+                    .iter()
+                    .all(|b| b.is_ascii() && !matches!(b.to_ascii_lowercase(), b'k' | b's'))
+        })
+        .map(|window| [window[0], window[1], window[2]].map(|b| b.to_ascii_lowercase()))
+        .collect();
+    trigrams.sort_unstable();
+    trigrams.dedup();
+    trigrams
+}
+
+fn trigrams(s: &str) -> impl Iterator<Item = [u8; 3]> + '_ {
+    s.as_bytes()
+        .windows(3)
+        .map(|window| [window[0], window[1], window[2]].map(|b| b.to_ascii_lowercase()))
+}
+
+fn bit_positions(trigram: &[u8; 3]) -> [usize; 2] {
+    let hash = fxhash::hash64(trigram);
+    [
+        hash as usize & (FILTER_BITS - 1),
+        (hash >> 32) as usize & (FILTER_BITS - 1),
+    ]
+}
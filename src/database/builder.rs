@@ -1,18 +1,29 @@
 use super::{
-    indexer::{IndexOptions, Indexer},
-    util, Database, EntryId, StatusFlags, StatusKind,
+    file,
+    indexer::{EntryFilter, IndexOptions, Indexer},
+    util, Database, DatabaseMeta, EntryId, SortKey, StatusFlags, StatusKind,
 };
 use crate::{Error, Result};
 
 use enum_map::{enum_map, EnumMap};
-use rayon::prelude::*;
-use std::path::PathBuf;
+use rayon::{prelude::*, ThreadPoolBuilder};
+use std::{
+    fmt,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct DatabaseBuilder {
     dirs: Vec<PathBuf>,
     index_options: IndexOptions,
     fast_sort_flags: StatusFlags,
+    fast_sort_keys: Vec<SortKey>,
+    skip_missing_dirs: bool,
+    record_metadata: bool,
+    threads: usize,
 }
 
 impl DatabaseBuilder {
@@ -29,10 +40,18 @@ impl DatabaseBuilder {
                 StatusKind::Created => false,
                 StatusKind::Modified => false,
                 StatusKind::Accessed => false,
+                StatusKind::Owner => false,
             },
+            fast_sort_keys: Vec::new(),
+            skip_missing_dirs: false,
+            record_metadata: false,
+            threads: 0,
         }
     }
 
+    /// Adds a root directory to index.
+    ///
+    /// If `path` points to a file, the file alone is indexed as a root entry.
     pub fn add_dir<P: Into<PathBuf>>(&mut self, path: P) -> &mut Self {
         self.dirs.push(path.into());
         self
@@ -48,12 +67,215 @@ impl DatabaseBuilder {
         self
     }
 
+    /// Precomputes ids sorted in a composite order, so that queries sorting
+    /// in the order skip sorting as with `fast_sort`.
+    ///
+    /// `SortKey::Then(kind, StatusKind::Basename)` is the same as
+    /// `fast_sort(kind)`, since ties are broken by basename by default.
+    pub fn fast_sort_by(&mut self, key: SortKey) -> &mut Self {
+        match key {
+            SortKey::Then(kind, StatusKind::Basename) => {
+                self.fast_sort_flags[kind] = true;
+            }
+            _ if !self.fast_sort_keys.contains(&key) => self.fast_sort_keys.push(key),
+            _ => (),
+        }
+        self
+    }
+
     pub fn ignore_hidden(&mut self, yes: bool) -> &mut Self {
         self.index_options.ignore_hidden = yes;
         self
     }
 
+    /// Records whether each entry has the extended attribute `name`.
+    ///
+    /// On platforms without extended attributes, this is a no-op and a warning
+    /// is returned from `build_with_warnings`.
+    pub fn index_xattr<S: Into<String>>(&mut self, name: S) -> &mut Self {
+        self.index_options.xattr = Some(name.into());
+        self
+    }
+
+    /// Indexes only entries for which `filter` returns true.
+    ///
+    /// The filter is called with a path and a file type of each entry found
+    /// while walking directories. If it returns false for a directory, the whole
+    /// directory is skipped. Root directories themselves are always indexed.
+    /// This is applied in addition to `ignore_hidden`.
+    pub fn filter_entry(&mut self, filter: Box<EntryFilter>) -> &mut Self {
+        self.index_options.filter_entry = Some(Arc::from(filter));
+        self
+    }
+
+    /// Stores bloom filters of basenames so that searches for literal patterns
+    /// can skip chunks of entries that cannot match.
+    ///
+    /// This makes the database slightly larger.
+    pub fn basename_bloom(&mut self, yes: bool) -> &mut Self {
+        self.index_options.basename_bloom = yes;
+        self
+    }
+
+    /// Stores case-folded copies of basenames so that case-insensitive
+    /// non-regex queries matching basenames can be searched without regexes.
+    ///
+    /// This roughly doubles the size of names in the database.
+    pub fn case_fold_index(&mut self, yes: bool) -> &mut Self {
+        self.index_options.case_fold_index = yes;
+        self
+    }
+
+    /// Stores total sizes of files under directories as their sizes,
+    /// instead of numbers of their children, so that directories can be
+    /// sorted by disk usage.
+    ///
+    /// Sizes of files are summed as they are, so files with multiple hard
+    /// links are counted multiple times. Requires sizes to be indexed.
+    pub fn recursive_dir_size(&mut self, yes: bool) -> &mut Self {
+        self.index_options.recursive_dir_size = yes;
+        self
+    }
+
+    /// Indexes entries with non-UTF-8 names by replacing invalid sequences with
+    /// U+FFFD REPLACEMENT CHARACTER, instead of skipping them.
+    ///
+    /// Paths of such entries do not round-trip to the real files, so opening
+    /// them or fetching non-indexed statuses of them may fail.
+    /// `Entry::is_lossy` tells whether a path is approximate.
+    /// Root directories still have to be valid UTF-8.
+    pub fn lossy_paths(&mut self, yes: bool) -> &mut Self {
+        self.index_options.lossy_paths = yes;
+        self
+    }
+
+    /// Skips entries matching patterns in `.ignore` and `.fdignore` files,
+    /// which are also respected by ripgrep and fd.
+    ///
+    /// The files use the syntax of gitignore. Patterns in `.fdignore` take
+    /// precedence over those in `.ignore`, and patterns in deeper directories
+    /// take precedence over those in shallower ones.
+    /// Only ignore files in root directories and their descendants are read.
+    /// `ignore_hidden` and `filter_entry` are applied regardless of the files.
+    pub fn respect_ignore_files(&mut self, yes: bool) -> &mut Self {
+        self.index_options.respect_ignore_files = yes;
+        self
+    }
+
+    /// Skips nonexistent root directories with a warning instead of failing.
+    pub fn skip_missing_dirs(&mut self, yes: bool) -> &mut Self {
+        self.skip_missing_dirs = yes;
+        self
+    }
+
+    /// Returns true if indexing `root` would skip `path`, because `path` or
+    /// one of its ancestors below `root` is hidden, filtered out, or ignored
+    /// by ignore files.
+    ///
+    /// This lets files found later, e.g. by watching `root`, be indexed as
+    /// if they had been found when `root` was indexed.
+    /// Paths which are not under `root` or do not exist are excluded.
+    pub fn excludes<P, Q>(&self, root: P, path: Q) -> bool
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        self.index_options.excludes(root.as_ref(), path.as_ref())
+    }
+
+    /// Records the time of the build, the host name, the root directories,
+    /// and the indexed statuses in the database, which are returned from
+    /// `Database::metadata`.
+    pub fn record_metadata(&mut self, yes: bool) -> &mut Self {
+        self.record_metadata = yes;
+        self
+    }
+
+    /// Sets the number of threads to build a database with.
+    ///
+    /// The threads are separate from rayon's global thread pool.
+    /// 0 (default) means the global thread pool is used.
+    pub fn threads(&mut self, threads: usize) -> &mut Self {
+        self.threads = threads;
+        self
+    }
+
     pub fn build(&self) -> Result<Database> {
+        self.build_with_warnings().map(|(database, _)| database)
+    }
+
+    /// Builds a database, also returning the problems that did not abort
+    /// indexing, such as directories that could not be read.
+    pub fn build_with_warnings(&self) -> Result<(Database, Vec<Warning>)> {
+        self.install(|| {
+            let (mut database, warnings) = self.build_unsorted()?;
+
+            let mut sorted_ids = EnumMap::default();
+            for (kind, ids) in sorted_ids.iter_mut() {
+                if self.fast_sort_flags[kind] {
+                    *ids = Some(sort_ids(&database, kind));
+                }
+            }
+            database.sorted_ids = sorted_ids;
+            for key in &self.fast_sort_keys {
+                let ids = sort_ids_by(&database, *key);
+                database.composite_sorted_ids.insert(*key, ids);
+            }
+
+            Ok((database, warnings))
+        })
+    }
+
+    /// Builds a database and writes it to `writer` in the format of
+    /// `Database::save`, returning warnings as in `build_with_warnings`.
+    ///
+    /// Fast-sorted ids are computed and written one status at a time, and each
+    /// status column is freed as soon as it is written, so the peak memory
+    /// usage is lower than that of `build` followed by `Database::save` when
+    /// many statuses are fast-sortable.
+    /// Names and nodes of the whole tree are still held in memory during
+    /// the build, because a node can only be written once all of its children
+    /// have been assigned ids.
+    ///
+    /// The output is the same as `Database::save` writes for the database
+    /// `build` returns. `writer` is buffered internally.
+    pub fn build_to_writer<W: Write + Send>(&self, writer: W) -> Result<Vec<Warning>> {
+        self.install(|| {
+            let (database, warnings) = self.build_unsorted()?;
+            let fast_sort_kinds: Vec<_> = self
+                .fast_sort_flags
+                .iter()
+                .filter(|(_, enabled)| **enabled)
+                .map(|(kind, _)| kind)
+                .collect();
+            let mut writer = BufWriter::new(writer);
+            file::write_streaming(
+                database,
+                &fast_sort_kinds,
+                &self.fast_sort_keys,
+                &mut writer,
+            )?;
+            writer.flush()?;
+            Ok(warnings)
+        })
+    }
+
+    /// Runs `f` in the thread pool specified with `threads`.
+    fn install<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send,
+        F: FnOnce() -> Result<T> + Send,
+    {
+        if self.threads > 0 {
+            let pool = ThreadPoolBuilder::new().num_threads(self.threads).build()?;
+            pool.install(f)
+        } else {
+            f()
+        }
+    }
+
+    /// Builds a database without fast-sorted ids.
+    fn build_unsorted(&self) -> Result<(Database, Vec<Warning>)> {
         for (kind, enabled) in self.fast_sort_flags {
             if enabled && !self.index_options.index_flags[kind] {
                 return Err(Error::InvalidOption(
@@ -61,30 +283,86 @@ impl DatabaseBuilder {
                 ));
             }
         }
+        for key in &self.fast_sort_keys {
+            if key
+                .statuses()
+                .any(|kind| !self.index_options.index_flags[kind])
+            {
+                return Err(Error::InvalidOption(
+                    "Fast sorting cannot be enabled for a non-indexed status.".to_string(),
+                ));
+            }
+        }
+
+        if self.index_options.recursive_dir_size
+            && !self.index_options.index_flags[StatusKind::Size]
+        {
+            return Err(Error::InvalidOption(
+                "Recursive directory sizes cannot be enabled without indexing sizes.".to_string(),
+            ));
+        }
 
-        let dirs = util::canonicalize_dirs(&self.dirs)?;
+        let mut warnings = Vec::new();
+        let dirs = util::canonicalize_dirs(&self.dirs, self.skip_missing_dirs, &mut warnings)?;
+        let unsupported = (self.index_options.xattr.is_some() && !util::XATTR_SUPPORTED)
+            || (self.index_options.index_flags[StatusKind::Owner] && !util::OWNER_SUPPORTED);
+        if unsupported {
+            for dir in &dirs {
+                warnings.push(Warning::new(dir.clone(), io::ErrorKind::Unsupported.into()));
+            }
+        }
         let mut indexer = Indexer::new(&self.index_options);
 
         for path in dirs {
             indexer = indexer.index(path)?;
         }
 
-        let mut database = indexer.finish();
+        let (mut database, index_warnings) = indexer.finish();
+        warnings.extend(index_warnings);
 
-        let mut sorted_ids = EnumMap::default();
-        for (kind, ids) in sorted_ids.iter_mut() {
-            if self.fast_sort_flags[kind] {
-                *ids = Some(sort_ids(&database, kind));
-            }
+        if self.record_metadata {
+            database.metadata = Some(DatabaseMeta {
+                built_at: SystemTime::now(),
+                hostname: util::hostname(),
+                roots: database.root_paths.values().cloned().collect(),
+                indexed: database.indexed_statuses().collect(),
+            });
         }
-        database.sorted_ids = sorted_ids;
 
-        Ok(database)
+        Ok((database, warnings))
+    }
+}
+
+/// A non-fatal problem encountered while building a database.
+#[derive(Debug)]
+pub struct Warning {
+    path: PathBuf,
+    error: io::Error,
+}
+
+impl Warning {
+    pub(crate) fn new(path: PathBuf, error: io::Error) -> Self {
+        Self { path, error }
+    }
+
+    /// Path of the entry that was skipped.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn error(&self) -> &io::Error {
+        &self.error
     }
 }
 
-fn sort_ids(database: &Database, sort_by: StatusKind) -> Vec<u32> {
-    let compare_func = util::get_compare_func(sort_by);
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.error)
+    }
+}
+
+pub(super) fn sort_ids(database: &Database, sort_by: StatusKind) -> Vec<u32> {
+    let compare_func = util::get_compare_func(sort_by, StatusKind::Basename, false);
 
     let mut ids = (0..database.nodes.len() as u32).collect::<Vec<_>>();
     ids.as_parallel_slice_mut().par_sort_unstable_by(|a, b| {
@@ -94,11 +372,38 @@ fn sort_ids(database: &Database, sort_by: StatusKind) -> Vec<u32> {
     ids
 }
 
+pub(super) fn sort_ids_by(database: &Database, key: SortKey) -> Vec<u32> {
+    let mut ids = (0..database.nodes.len() as u32).collect::<Vec<_>>();
+    let slice = ids.as_parallel_slice_mut();
+    match key {
+        SortKey::Then(kind, tiebreak) => {
+            let compare_func = util::get_compare_func(kind, tiebreak, false);
+            slice.par_sort_unstable_by(|a, b| {
+                compare_func(&database.entry(EntryId(*a)), &database.entry(EntryId(*b)))
+            });
+        }
+        SortKey::DirsFirst(kind) => {
+            let compare_func = util::get_compare_func(kind, StatusKind::Basename, false);
+            slice.par_sort_unstable_by(|a, b| {
+                let (node_a, node_b) = (&database.nodes[*a as usize], &database.nodes[*b as usize]);
+                Ord::cmp(&node_b.is_dir, &node_a.is_dir).then_with(|| {
+                    compare_func(&database.entry(EntryId(*a)), &database.entry(EntryId(*b)))
+                })
+            });
+        }
+    }
+
+    ids
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::database::*;
+    use crate::{database::*, query::*};
     use itertools::Itertools;
-    use std::{fs, path::Path};
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
     use strum::IntoEnumIterator;
     use tempfile::TempDir;
 
@@ -185,10 +490,594 @@ mod tests {
         );
     }
 
+    #[test]
+    fn file_as_root() {
+        let tmpdir = create_dir_structure(&[Path::new("a/b")]);
+        let path = tmpdir.path();
+        fs::write(path.join("afoo.txt"), "").unwrap();
+        fs::write(path.join("a/b/foo.txt"), "").unwrap();
+        let file = dunce::canonicalize(path.join("afoo.txt")).unwrap();
+
+        let database = DatabaseBuilder::new()
+            .add_dir(&file)
+            .add_dir(path.join("a/b"))
+            .build()
+            .unwrap();
+
+        let root = database
+            .root_entries()
+            .find(|entry| !entry.is_dir())
+            .unwrap();
+        assert_eq!(root.basename(), "afoo.txt");
+        assert_eq!(root.path(), file);
+        assert_eq!(root.children().len(), 0);
+
+        let query = QueryBuilder::new("")
+            .sort_by(StatusKind::Path)
+            .build()
+            .unwrap();
+        let paths = database
+            .search_all(&query)
+            .unwrap()
+            .into_iter()
+            .map(|id| database.entry(id).path())
+            .collect::<Vec<_>>();
+        let mut sorted_paths = paths.clone();
+        sorted_paths.sort_unstable();
+        assert_eq!(paths, sorted_paths);
+
+        for match_path_mode in [MatchPathMode::Never, MatchPathMode::Always] {
+            for pattern in ["foo", "foo.txt", "o.t"] {
+                for regex in [false, true] {
+                    let query = QueryBuilder::new(pattern)
+                        .match_path_mode(match_path_mode)
+                        .regex(regex)
+                        .build()
+                        .unwrap();
+                    let paths = database
+                        .search_all(&query)
+                        .unwrap()
+                        .into_iter()
+                        .map(|id| database.entry(id).path())
+                        .sorted()
+                        .collect::<Vec<_>>();
+                    assert_eq!(
+                        paths,
+                        vec![
+                            dunce::canonicalize(path.join("a/b/foo.txt")).unwrap(),
+                            file.clone()
+                        ]
+                    );
+                }
+            }
+        }
+
+        let query = QueryBuilder::new(file.to_str().unwrap())
+            .match_path_mode(MatchPathMode::Always)
+            .build()
+            .unwrap();
+        let hits = database.search_all(&query).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(database.entry(hits[0]).path(), file);
+    }
+
+    #[test]
+    fn to_record() {
+        let tmpdir = tmpdir();
+        let file = dunce::canonicalize(tmpdir.path()).unwrap().join("foo.txt");
+        fs::write(&file, "hello").unwrap();
+
+        let database = DatabaseBuilder::new()
+            .add_dir(&file)
+            .index(StatusKind::Size)
+            .build()
+            .unwrap();
+        let record = database.root_entries().next().unwrap().to_record();
+        assert_eq!(record.path, file);
+        assert_eq!(record.basename, "foo.txt");
+        assert!(!record.is_dir);
+        assert_eq!(record.size, Some(5));
+        assert_eq!(record.mode, None);
+        assert_eq!(record.modified, None);
+
+        let json = serde_json::to_value(&record).unwrap();
+        assert_eq!(json["path"], file.to_str().unwrap());
+        assert_eq!(json["basename"], "foo.txt");
+        assert_eq!(json["size"], 5);
+        assert!(json["mode"].is_null());
+        assert_eq!(serde_json::from_value::<EntryRecord>(json).unwrap(), record);
+
+        // every status survives a round trip through JSON text
+        let database = DatabaseBuilder::new()
+            .add_dir(&file)
+            .index(StatusKind::Mode)
+            .index(StatusKind::Modified)
+            .build()
+            .unwrap();
+        let record = database.root_entries().next().unwrap().to_record();
+        assert!(record.mode.is_some() && record.modified.is_some());
+        let json = serde_json::to_string(&record).unwrap();
+        assert_eq!(serde_json::from_str::<EntryRecord>(&json).unwrap(), record);
+    }
+
+    #[test]
+    fn threads() {
+        let tmpdir = create_dir_structure(&[
+            Path::new("a/b/c"),
+            Path::new("a/d"),
+            Path::new("e/f/g/h"),
+            Path::new("i"),
+        ]);
+
+        let mut builder = DatabaseBuilder::new();
+        builder
+            .add_dir(tmpdir.path())
+            .index(StatusKind::Size)
+            .fast_sort(StatusKind::Size);
+        let database = builder.build().unwrap();
+        let single_threaded = builder.threads(1).build().unwrap();
+
+        assert_eq!(
+            collect_paths(single_threaded.root_entries()),
+            collect_paths(database.root_entries())
+        );
+        for kind in [StatusKind::Basename, StatusKind::Size] {
+            let ids = |database: &Database| {
+                database.sorted_ids[kind]
+                    .as_ref()
+                    .unwrap()
+                    .iter()
+                    .map(|id| database.entry(EntryId(*id)).path())
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(ids(&single_threaded), ids(&database));
+        }
+    }
+
+    #[test]
+    fn num_dirs_and_files() {
+        let tmpdir = create_dir_structure(&[Path::new("a/b"), Path::new("c")]);
+        let path = tmpdir.path();
+        fs::write(path.join("d"), "").unwrap();
+        fs::write(path.join("a").join("e"), "").unwrap();
+        fs::write(path.join("a").join("b").join("f"), "").unwrap();
+
+        let database = DatabaseBuilder::new().add_dir(path).build().unwrap();
+        assert_eq!(database.num_dirs(), 4);
+        assert_eq!(database.num_files(), 3);
+        assert_eq!(
+            database.num_dirs(),
+            database.entries().filter(|entry| entry.is_dir()).count()
+        );
+
+        let file = DatabaseBuilder::new()
+            .add_dir(path.join("d"))
+            .build()
+            .unwrap();
+        assert_eq!(file.num_dirs(), 0);
+        assert_eq!(file.num_files(), 1);
+    }
+
+    #[test]
+    fn entries() {
+        let tmpdir = create_dir_structure(&[Path::new("a/b"), Path::new("c/d/e")]);
+        let tmpdir2 = create_dir_structure(&[Path::new("f")]);
+
+        let database = DatabaseBuilder::new()
+            .add_dir(tmpdir.path())
+            .add_dir(tmpdir2.path())
+            .build()
+            .unwrap();
+
+        let mut expected = collect_paths(database.root_entries());
+        expected.sort_unstable();
+
+        assert_eq!(database.entries().len(), database.num_entries());
+        let mut paths = database.entries().map(|e| e.path()).collect::<Vec<_>>();
+        paths.sort_unstable();
+        assert_eq!(paths, expected);
+
+        assert_eq!(database.entries_par().len(), database.num_entries());
+        let mut paths = database.entries_par().map(|e| e.path()).collect::<Vec<_>>();
+        paths.sort_unstable();
+        assert_eq!(paths, expected);
+    }
+
+    #[test]
+    fn get_by_path() {
+        let tmpdir = create_dir_structure(&[Path::new("a/b/c"), Path::new("ab/b")]);
+        let path = dunce::canonicalize(tmpdir.path()).unwrap();
+        fs::write(path.join("a").join("d"), "").unwrap();
+
+        let database = DatabaseBuilder::new().add_dir(&path).build().unwrap();
+        for entry in database.entries() {
+            assert_eq!(database.get_by_path(entry.path()), Some(entry.id));
+        }
+
+        let id = database.get_by_path(path.join("a").join("b")).unwrap();
+        let entry = database.entry(id);
+        assert_eq!(entry.basename(), "b");
+        assert_eq!(entry.path(), path.join("a").join("b"));
+        assert_eq!(entry.children().next().unwrap().basename(), "c");
+
+        assert_eq!(database.get_by_path(path.join("a").join("c")), None);
+        assert_eq!(
+            database.get_by_path(path.join("a").join("d").join("e")),
+            None
+        );
+        assert_eq!(database.get_by_path(path.join("b")), None);
+        assert_eq!(database.get_by_path(format!("{}b", path.display())), None);
+        assert_eq!(database.get_by_path(path.parent().unwrap()), None);
+    }
+
+    #[test]
+    fn descendants() {
+        let tmpdir =
+            create_dir_structure(&[Path::new("a/b"), Path::new("e/a/b"), Path::new("b/c/d")]);
+        let tmpdir2 = create_dir_structure(&[Path::new("f/g")]);
+        let path = tmpdir.path();
+        fs::write(path.join("b/c/foo.txt"), "").unwrap();
+
+        let database = DatabaseBuilder::new()
+            .add_dir(path)
+            .add_dir(tmpdir2.path())
+            .build()
+            .unwrap();
+        for root in database.root_entries() {
+            let mut paths: Vec<_> = root.descendants().map(|entry| entry.path()).collect();
+            paths.sort_unstable();
+            let mut expected = collect_paths(std::iter::once(root.clone()));
+            expected.sort_unstable();
+            expected.retain(|p| *p != root.path());
+            assert_eq!(paths, expected);
+        }
+
+        let root = dunce::canonicalize(path).unwrap();
+        let entry = database.entry(database.get_by_path(root.join("b")).unwrap());
+        assert_eq!(
+            entry
+                .descendants()
+                .map(|e| e.path())
+                .sorted()
+                .collect::<Vec<_>>(),
+            vec![
+                root.join("b/c"),
+                root.join("b/c/d"),
+                root.join("b/c/foo.txt")
+            ]
+        );
+        let leaf = database.entry(database.get_by_path(root.join("b/c/d")).unwrap());
+        assert_eq!(leaf.descendants().count(), 0);
+    }
+
+    #[test]
+    fn recursive_dir_size() {
+        let tmpdir = create_dir_structure(&[Path::new("a/b"), Path::new("c")]);
+        let path = dunce::canonicalize(tmpdir.path()).unwrap();
+        fs::write(path.join("a/x"), "foo").unwrap();
+        fs::write(path.join("a/b/y"), "hello").unwrap();
+        fs::write(path.join("a/b/w"), "!").unwrap();
+        fs::write(path.join("z"), "1234567").unwrap();
+
+        let mut builder = DatabaseBuilder::new();
+        builder.add_dir(&path).index(StatusKind::Size);
+        let size_of = |database: &Database, name| {
+            let entry = database.entry(database.get_by_path(path.join(name)).unwrap());
+            (entry.size().unwrap(), entry.size_is_item_count())
+        };
+
+        let database = builder.build().unwrap();
+        assert!(!database.is_dir_size_recursive());
+        assert_eq!(size_of(&database, ""), (3, true));
+        assert_eq!(size_of(&database, "a"), (2, true));
+        assert_eq!(size_of(&database, "z"), (7, false));
+
+        let database = builder
+            .recursive_dir_size(true)
+            .fast_sort(StatusKind::Size)
+            .build()
+            .unwrap();
+        assert!(database.is_dir_size_recursive());
+        assert_eq!(size_of(&database, ""), (16, false));
+        assert_eq!(size_of(&database, "a"), (9, false));
+        assert_eq!(size_of(&database, "a/b"), (6, false));
+        assert_eq!(size_of(&database, "a/x"), (3, false));
+        assert_eq!(size_of(&database, "c"), (0, false));
+
+        let query = QueryBuilder::new("")
+            .sort_by(StatusKind::Size)
+            .sort_order(SortOrder::Descending)
+            .build()
+            .unwrap();
+        let paths = database
+            .search_all(&query)
+            .unwrap()
+            .into_iter()
+            .map(|id| database.entry(id).path())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![
+                path.clone(),
+                path.join("a"),
+                path.join("z"),
+                path.join("a/b"),
+                path.join("a/b/y"),
+                path.join("a/x"),
+                path.join("a/b/w"),
+                path.join("c"),
+            ]
+        );
+
+        // directories are not excluded by size bounds
+        let query = QueryBuilder::new("").size_min(6).build().unwrap();
+        assert_eq!(database.search_all(&query).unwrap().len(), 4);
+
+        // sizes of appended directories are recomputed as recursive ones
+        let tmpdir2 = create_dir_structure(&[Path::new("d")]);
+        let path2 = dunce::canonicalize(tmpdir2.path()).unwrap();
+        fs::write(path2.join("d/w"), "12").unwrap();
+        let mut appended = database;
+        appended
+            .append(
+                DatabaseBuilder::new()
+                    .add_dir(&path2)
+                    .index(StatusKind::Size)
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap();
+        assert!(appended.is_dir_size_recursive());
+        let id = appended.get_by_path(&path2).unwrap();
+        assert_eq!(appended.entry(id).size().unwrap(), 2);
+        assert_eq!(size_of(&appended, "a"), (9, false));
+
+        assert!(matches!(
+            DatabaseBuilder::new()
+                .add_dir(&path)
+                .recursive_dir_size(true)
+                .build(),
+            Err(crate::Error::InvalidOption(_))
+        ));
+    }
+
+    #[test]
+    fn rebuild_sorted_ids() {
+        let tmpdir = create_dir_structure(&[Path::new("b"), Path::new("c/d"), Path::new("empty")]);
+        let path = dunce::canonicalize(tmpdir.path()).unwrap();
+        let key = SortKey::DirsFirst(StatusKind::Extension);
+        let mut database = DatabaseBuilder::new()
+            .add_dir(&path)
+            .fast_sort(StatusKind::Path)
+            .fast_sort_by(key)
+            .build()
+            .unwrap();
+
+        // add a file "a" under the empty directory
+        let parent = database.get_by_path(path.join("empty")).unwrap().0;
+        let id = database.nodes.len() as u32;
+        let name_start = database.name_arena.len() as u32;
+        database.name_arena.push('a');
+        database.nodes[parent as usize].child_start = id;
+        database.nodes[parent as usize].child_end = id + 1;
+        database.nodes.push(EntryNode {
+            name_start,
+            parent,
+            child_start: u32::MAX,
+            child_end: u32::MAX,
+            name_len: 1,
+            is_dir: false,
+        });
+        assert_eq!(
+            database.sorted_ids[StatusKind::Path]
+                .as_ref()
+                .unwrap()
+                .len(),
+            5
+        );
+
+        database
+            .rebuild_sorted_ids(&[StatusKind::Basename, StatusKind::Path])
+            .unwrap();
+        assert!(!database.is_fast_sortable(StatusKind::Extension));
+
+        let basenames = |ids: &[u32]| {
+            ids.iter()
+                .map(|id| database.entry(EntryId(*id)).basename().to_owned())
+                .collect::<Vec<_>>()
+        };
+        let sorted_ids = &database.sorted_ids;
+        let names = basenames(sorted_ids[StatusKind::Basename].as_ref().unwrap());
+        assert_eq!(names.len(), 6);
+        assert!(names.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(
+            basenames(sorted_ids[StatusKind::Path].as_ref().unwrap())[4..],
+            ["empty", "a"]
+        );
+        let composite = basenames(&database.composite_sorted_ids[&key]);
+        assert_eq!(composite.last().map(String::as_str), Some("a"));
+
+        let query = QueryBuilder::new("a")
+            .sort_by(StatusKind::Path)
+            .sort_order(SortOrder::Descending)
+            .build()
+            .unwrap();
+        let hits = database.search_all(&query).unwrap();
+        assert_eq!(database.entry(hits[0]).path(), path.join("empty/a"));
+
+        assert!(matches!(
+            database.rebuild_sorted_ids(&[StatusKind::Size]),
+            Err(crate::Error::InvalidOption(_))
+        ));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn get_by_path_case_insensitive() {
+        let tmpdir = create_dir_structure(&[Path::new("Foo/Bar")]);
+        let path = dunce::canonicalize(tmpdir.path()).unwrap();
+
+        let database = DatabaseBuilder::new().add_dir(&path).build().unwrap();
+        let id = database.get_by_path(path.join("foo").join("BAR")).unwrap();
+        assert_eq!(database.entry(id).path(), path.join("Foo").join("Bar"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn extended_length_root() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = dunce::canonicalize(tmpdir.path()).unwrap();
+        let long_dir = (0..20).fold(path.clone(), |dir, i| {
+            dir.join(format!("long_directory_name{}", i))
+        });
+        fs::create_dir_all(&long_dir).unwrap();
+        fs::write(long_dir.join("foo.txt"), "").unwrap();
+        let file = long_dir.join("foo.txt");
+        assert!(file.as_os_str().len() > 260);
+
+        let verbatim = |path: &Path| PathBuf::from(format!(r"\\?\{}", path.display()));
+        for (root, root_path) in vec![
+            (verbatim(&path), &path),
+            (verbatim(&long_dir), &long_dir),
+            (long_dir.clone(), &long_dir),
+        ] {
+            let database = DatabaseBuilder::new().add_dir(&root).build().unwrap();
+            let (indexed_root, root_entry) = database.roots().next().unwrap();
+            assert_eq!(indexed_root, root_path);
+            assert_eq!(root_entry.path(), *root_path);
+            assert_eq!(
+                root_entry.basename(),
+                root_path.file_name().unwrap().to_str().unwrap()
+            );
+
+            let id = database.get_by_path(&file).unwrap();
+            let entry = database.entry(id);
+            assert_eq!(entry.basename(), "foo.txt");
+            assert_eq!(entry.path(), file);
+            assert_eq!(database.get_by_path(verbatim(&file)), Some(id));
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn unc_root() {
+        let tmpdir = create_dir_structure(&[Path::new("a/b")]);
+        let path = dunce::canonicalize(tmpdir.path()).unwrap();
+        let path = path.to_str().unwrap();
+
+        // the temporary directory seen through the administrative share
+        let unc = PathBuf::from(format!(r"\\localhost\{}${}", &path[..1], &path[2..]));
+        if !unc.exists() {
+            // the share is not available
+            return;
+        }
+
+        let verbatim = PathBuf::from(format!(r"\\?\UNC\{}", &unc.to_str().unwrap()[2..]));
+        for root in vec![unc.clone(), verbatim] {
+            let database = DatabaseBuilder::new().add_dir(&root).build().unwrap();
+            let root_entry = database.root_entries().next().unwrap();
+            assert_eq!(root_entry.path(), unc);
+
+            let id = database.get_by_path(unc.join("a").join("b")).unwrap();
+            let entry = database.entry(id);
+            assert_eq!(entry.basename(), "b");
+            assert_eq!(entry.path(), unc.join("a").join("b"));
+        }
+    }
+
+    #[test]
+    fn merge() {
+        let tmpdir = create_dir_structure(&[Path::new("a/b"), Path::new("c")]);
+        let tmpdir2 = create_dir_structure(&[Path::new("a/d"), Path::new("e/f")]);
+        let path = tmpdir.path();
+        let path2 = tmpdir2.path();
+
+        let database1 = DatabaseBuilder::new()
+            .add_dir(path)
+            .index(StatusKind::Size)
+            .index(StatusKind::Modified)
+            .fast_sort(StatusKind::Size)
+            .fast_sort(StatusKind::Modified)
+            .build()
+            .unwrap();
+        let database2 = DatabaseBuilder::new()
+            .add_dir(path2)
+            .index(StatusKind::Size)
+            .fast_sort(StatusKind::Size)
+            .build()
+            .unwrap();
+
+        let mut expected = collect_paths(database1.root_entries());
+        expected.append(&mut collect_paths(database2.root_entries()));
+        expected.sort_unstable();
+
+        let merged = Database::merge(vec![database1, database2]).unwrap();
+        assert_eq!(merged.num_entries(), expected.len());
+        assert_eq!(merged.root_entries().len(), 2);
+        assert_eq!(merged.num_dirs(), 9);
+
+        let mut paths = collect_paths(merged.root_entries());
+        paths.sort_unstable();
+        assert_eq!(paths, expected);
+
+        assert!(merged.is_indexed(StatusKind::Size));
+        assert!(!merged.is_indexed(StatusKind::Modified));
+        assert!(merged.is_fast_sortable(StatusKind::Size));
+        assert!(merged.is_fast_sortable(StatusKind::Basename));
+        assert!(!merged.is_fast_sortable(StatusKind::Modified));
+
+        let query = QueryBuilder::new("^a$")
+            .regex(true)
+            .sort_by(StatusKind::Size)
+            .build()
+            .unwrap();
+        let hits = merged.search_all(&query).unwrap();
+        assert_eq!(hits.len(), 2);
+        for id in hits {
+            let entry = merged.entry(id);
+            assert_eq!(entry.basename(), "a");
+            assert_eq!(entry.size().unwrap(), 1);
+        }
+    }
+
+    #[test]
+    fn append() {
+        let tmpdir = create_dir_structure(&[Path::new("a/b")]);
+        let path = tmpdir.path();
+
+        let mut database = DatabaseBuilder::new().add_dir(path).build().unwrap();
+        let paths = database.entries().map(|e| e.path()).collect::<Vec<_>>();
+
+        fs::create_dir(path.join("a/c")).unwrap();
+        let created = DatabaseBuilder::new()
+            .add_dir(path.join("a/c"))
+            .build()
+            .unwrap();
+        database.append(created).unwrap();
+
+        assert_eq!(database.num_entries(), paths.len() + 1);
+        for (entry, path) in database.entries().zip(paths.iter()) {
+            assert_eq!(&entry.path(), path);
+        }
+
+        let query = QueryBuilder::new("^c$").regex(true).build().unwrap();
+        let hits = database.search_all(&query).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(
+            database.entry(hits[0]).path(),
+            dunce::canonicalize(path.join("a/c")).unwrap()
+        );
+    }
+
     #[test]
     fn empty_database() {
         let database = DatabaseBuilder::new().build().unwrap();
         assert_eq!(database.num_entries(), 0);
+        assert!(database.is_empty());
+        assert!(database
+            .search_all(&QueryBuilder::new("").build().unwrap())
+            .unwrap()
+            .is_empty());
     }
 
     #[test]
@@ -199,6 +1088,458 @@ mod tests {
         DatabaseBuilder::new().add_dir(dir).build().unwrap();
     }
 
+    #[test]
+    fn skip_missing_dirs() {
+        let tmpdir = create_dir_structure(&[Path::new("a/b")]);
+        let missing = tmpdir.path().join("xxxx");
+
+        let (database, warnings) = DatabaseBuilder::new()
+            .add_dir(tmpdir.path().join("a"))
+            .add_dir(&missing)
+            .skip_missing_dirs(true)
+            .build_with_warnings()
+            .unwrap();
+
+        assert_eq!(
+            collect_paths(database.root_entries()),
+            vec![
+                dunce::canonicalize(tmpdir.path().join("a")).unwrap(),
+                dunce::canonicalize(tmpdir.path().join("a/b")).unwrap(),
+            ]
+        );
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].path(), missing);
+    }
+
+    #[test]
+    fn roots() {
+        let tmpdir = create_dir_structure(&[Path::new("a/b"), Path::new("c"), Path::new("d")]);
+        let path = tmpdir.path();
+
+        let database = DatabaseBuilder::new()
+            .add_dir(path.join("d"))
+            .add_dir(path.join("a"))
+            .add_dir(path.join("a/b"))
+            .add_dir(path.join("c/../a"))
+            .add_dir(path.join("c"))
+            .build()
+            .unwrap();
+
+        let roots = database
+            .roots()
+            .map(|(root_path, entry)| {
+                assert_eq!(entry.path(), root_path);
+                root_path.to_path_buf()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            roots,
+            ["a", "c", "d"]
+                .iter()
+                .map(|dir| dunce::canonicalize(path.join(dir)).unwrap())
+                .collect::<Vec<_>>()
+        );
+
+        let root_of = |path: PathBuf| {
+            let entry = database.entry(database.get_by_path(path).unwrap());
+            entry.root().path()
+        };
+        let root = dunce::canonicalize(path).unwrap();
+        assert_eq!(root_of(root.join("a/b")), root.join("a"));
+        assert_eq!(root_of(root.join("a")), root.join("a"));
+        assert_eq!(root_of(root.join("d")), root.join("d"));
+    }
+
+    #[test]
+    fn filter_entry() {
+        let tmpdir = create_dir_structure(&[Path::new("a/b"), Path::new("target/c")]);
+        let path = tmpdir.path();
+        for file in [
+            "main.rs",
+            "a/lib.rs",
+            "a/b/README.md",
+            "target/c/d.rs",
+            ".hidden.md",
+        ] {
+            fs::write(path.join(file), "").unwrap();
+        }
+
+        let database = DatabaseBuilder::new()
+            .add_dir(path)
+            .ignore_hidden(true)
+            .filter_entry(Box::new(|path, file_type| {
+                if file_type.is_dir() {
+                    path.file_name().unwrap() != "target"
+                } else {
+                    path.extension() != Some("rs".as_ref())
+                }
+            }))
+            .build()
+            .unwrap();
+
+        let root = dunce::canonicalize(path).unwrap();
+        assert_eq!(
+            collect_paths(database.root_entries()),
+            vec![
+                root.clone(),
+                root.join("a"),
+                root.join("a/b"),
+                root.join("a/b/README.md"),
+            ]
+        );
+    }
+
+    #[test]
+    fn respect_ignore_files() {
+        let tmpdir = create_dir_structure(&[
+            Path::new("a/build/x"),
+            Path::new("a/keep"),
+            Path::new("b/build"),
+            Path::new("c"),
+        ]);
+        let path = tmpdir.path();
+        for file in [
+            "a/main.log",
+            "a/keep/x.log",
+            "b/y.log",
+            "c/z.log",
+            "c/w.txt",
+        ] {
+            fs::write(path.join(file), "").unwrap();
+        }
+        fs::write(path.join(".ignore"), "build/\n*.log\n").unwrap();
+        fs::write(path.join(".fdignore"), "!a/main.log\n").unwrap();
+        fs::write(path.join("a/keep/.ignore"), "!*.log\n").unwrap();
+        fs::write(path.join("c/.fdignore"), "*\n!w.txt\n").unwrap();
+
+        let root = dunce::canonicalize(path).unwrap();
+        let database = DatabaseBuilder::new()
+            .add_dir(path)
+            .respect_ignore_files(true)
+            .filter_entry(Box::new(|path, _| path.file_name().unwrap() != "keep"))
+            .build()
+            .unwrap();
+        assert_eq!(
+            collect_paths(database.root_entries())
+                .into_iter()
+                .sorted()
+                .collect::<Vec<_>>(),
+            vec![
+                root.clone(),
+                root.join(".fdignore"),
+                root.join(".ignore"),
+                root.join("a"),
+                root.join("a/main.log"),
+                root.join("b"),
+                root.join("c"),
+                root.join("c/w.txt"),
+            ]
+        );
+
+        let database = DatabaseBuilder::new()
+            .add_dir(path)
+            .respect_ignore_files(true)
+            .build()
+            .unwrap();
+        let paths: Vec<PathBuf> = collect_paths(database.root_entries())
+            .into_iter()
+            .map(PathBuf::from)
+            .collect();
+        assert!(paths.contains(&root.join("a/keep/x.log")));
+        assert!(!paths.contains(&root.join("a/build")));
+        assert!(!paths.contains(&root.join("b/build")));
+        assert!(!paths.contains(&root.join("b/y.log")));
+
+        let database = DatabaseBuilder::new().add_dir(path).build().unwrap();
+        let paths: Vec<PathBuf> = collect_paths(database.root_entries())
+            .into_iter()
+            .map(PathBuf::from)
+            .collect();
+        assert!(paths.contains(&root.join("a/build/x")));
+        assert!(paths.contains(&root.join("c/z.log")));
+    }
+
+    #[test]
+    fn excludes() {
+        let tmpdir = create_dir_structure(&[
+            Path::new("a/build/x"),
+            Path::new(".hidden/y"),
+            Path::new("b"),
+        ]);
+        let path = tmpdir.path();
+        for file in [
+            "a/main.log",
+            "a/build/x/z.txt",
+            ".hidden/y/w.txt",
+            "b/v.txt",
+        ] {
+            fs::write(path.join(file), "").unwrap();
+        }
+        fs::write(path.join(".ignore"), "build/\n*.log\n").unwrap();
+        let root = dunce::canonicalize(path).unwrap();
+
+        // every path is excluded exactly when indexing the root skips it
+        let all = collect_paths(
+            DatabaseBuilder::new()
+                .add_dir(path)
+                .build()
+                .unwrap()
+                .root_entries(),
+        );
+        let mut builder = DatabaseBuilder::new();
+        builder
+            .add_dir(path)
+            .ignore_hidden(true)
+            .respect_ignore_files(true);
+        let indexed = collect_paths(builder.build().unwrap().root_entries());
+        assert!(indexed.len() < all.len());
+        for path in &all {
+            assert_eq!(
+                builder.excludes(&root, path),
+                !indexed.contains(path),
+                "{}",
+                path
+            );
+        }
+
+        assert!(builder.excludes(&root, root.join("nonexistent")));
+        assert!(builder.excludes(root.join("b"), root.join("a")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn xattr() {
+        const NAME: &str = "user.indexa.test";
+
+        let tmpdir = create_dir_structure(&[Path::new("a/b")]);
+        let path = tmpdir.path();
+        fs::write(path.join("c"), "").unwrap();
+        fs::write(path.join("d"), "").unwrap();
+
+        // file systems such as tmpfs may not support user attributes
+        if xattr::set(path.join("c"), NAME, b"1").is_err() {
+            return;
+        }
+        xattr::set(path.join("a").join("b"), NAME, b"").unwrap();
+
+        let database = DatabaseBuilder::new()
+            .add_dir(path)
+            .index_xattr(NAME)
+            .build()
+            .unwrap();
+        assert_eq!(database.indexed_xattr(), Some(NAME));
+
+        for entry in database.entries() {
+            let expected = matches!(entry.basename(), "b" | "c");
+            assert_eq!(entry.has_xattr(NAME).unwrap(), expected);
+            assert!(!entry.has_xattr("user.indexa.other").unwrap());
+        }
+
+        let query = QueryBuilder::new("").require_xattr(true).build().unwrap();
+        let hits = database.search_all(&query).unwrap();
+        assert_eq!(
+            hits.iter()
+                .map(|id| database.entry(*id).basename().to_string())
+                .collect::<Vec<_>>(),
+            ["b", "c"]
+        );
+
+        let without_xattr = DatabaseBuilder::new().add_dir(path).build().unwrap();
+        assert_eq!(without_xattr.indexed_xattr(), None);
+        assert!(without_xattr.search_all(&query).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn is_symlink() {
+        let tmpdir = create_dir_structure(&[Path::new("dir")]);
+        let path = tmpdir.path();
+        fs::write(path.join("file"), "").unwrap();
+        std::os::unix::fs::symlink(path.join("file"), path.join("file_link")).unwrap();
+        std::os::unix::fs::symlink(path.join("dir"), path.join("dir_link")).unwrap();
+
+        let is_symlink = |database: &Database| {
+            database
+                .entries()
+                .filter(|entry| entry.is_symlink().unwrap())
+                .map(|entry| entry.basename().to_owned())
+                .sorted()
+                .collect::<Vec<_>>()
+        };
+
+        let database = DatabaseBuilder::new()
+            .add_dir(path)
+            .index(StatusKind::Mode)
+            .build()
+            .unwrap();
+
+        // the indexed mode is used even after the links are gone
+        fs::remove_file(path.join("file_link")).unwrap();
+        assert_eq!(is_symlink(&database), ["dir_link", "file_link"]);
+
+        let database = DatabaseBuilder::new().add_dir(path).build().unwrap();
+        assert_eq!(is_symlink(&database), ["dir_link"]);
+    }
+
+    #[test]
+    fn is_hidden() {
+        let tmpdir = create_dir_structure(&[Path::new(".dir"), Path::new("dir")]);
+        let path = tmpdir.path();
+        for file in [".foo", "foo", ".dir/bar", "dir/.baz", "dir/a.b"] {
+            fs::write(path.join(file), "").unwrap();
+        }
+        #[cfg(windows)]
+        {
+            fs::write(path.join("attr"), "").unwrap();
+            let status = std::process::Command::new("attrib")
+                .arg("+h")
+                .arg(path.join("attr"))
+                .status()
+                .unwrap();
+            assert!(status.success());
+        }
+
+        let hidden = |database: &Database| {
+            database
+                .entries()
+                .filter(|entry| entry.path() != entry.root().path() && entry.is_hidden())
+                .map(|entry| entry.basename().to_owned())
+                .sorted()
+                .collect::<Vec<_>>()
+        };
+
+        let database = DatabaseBuilder::new().add_dir(path).build().unwrap();
+        assert_eq!(hidden(&database), [".baz", ".dir", ".foo"]);
+
+        let database = DatabaseBuilder::new()
+            .add_dir(path)
+            .index(StatusKind::Mode)
+            .build()
+            .unwrap();
+        if cfg!(windows) {
+            assert_eq!(hidden(&database), [".baz", ".dir", ".foo", "attr"]);
+        } else {
+            assert_eq!(hidden(&database), [".baz", ".dir", ".foo"]);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn lossy_paths() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let tmpdir = create_dir_structure(&[Path::new("a")]);
+        let path = tmpdir.path();
+        let invalid_dir = path.join(OsStr::from_bytes(b"b\xff"));
+        // some file systems reject names which are not valid UTF-8
+        if fs::create_dir(&invalid_dir).is_err() {
+            return;
+        }
+        fs::write(invalid_dir.join("c"), "").unwrap();
+        fs::write(path.join(OsStr::from_bytes(b"d\xfe\xfd.txt")), "").unwrap();
+
+        let basenames = |database: &Database| {
+            database
+                .entries()
+                .map(|entry| (entry.basename().to_owned(), entry.is_lossy()))
+                .sorted()
+                .collect::<Vec<_>>()
+        };
+
+        let database = DatabaseBuilder::new().add_dir(path).build().unwrap();
+        assert_eq!(basenames(&database)[1..], [("a".to_owned(), false)]);
+
+        let database = DatabaseBuilder::new()
+            .add_dir(path)
+            .lossy_paths(true)
+            .build()
+            .unwrap();
+        assert_eq!(
+            basenames(&database)[1..],
+            [
+                ("a".to_owned(), false),
+                ("b\u{fffd}".to_owned(), true),
+                ("c".to_owned(), true),
+                ("d\u{fffd}\u{fffd}.txt".to_owned(), true),
+            ]
+        );
+
+        let location = path.join("database");
+        database.save(&location).unwrap();
+        let database = Database::open(&location).unwrap();
+        let entry = database
+            .entries()
+            .find(|entry| entry.basename() == "c")
+            .unwrap();
+        assert!(entry.is_lossy());
+        assert!(entry.path().ends_with("b\u{fffd}/c"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unreadable_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmpdir = create_dir_structure(&[Path::new("a"), Path::new("b/c")]);
+        let locked = tmpdir.path().join("b");
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+        // permissions are not enforced for privileged users
+        if fs::read_dir(&locked).is_ok() {
+            fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+            return;
+        }
+
+        let result = DatabaseBuilder::new()
+            .add_dir(tmpdir.path())
+            .build_with_warnings();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let (database, warnings) = result.unwrap();
+        assert_eq!(database.num_entries(), 3);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            dunce::canonicalize(warnings[0].path()).unwrap(),
+            dunce::canonicalize(&locked).unwrap()
+        );
+        assert_eq!(
+            warnings[0].error().kind(),
+            std::io::ErrorKind::PermissionDenied
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unreadable_metadata() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmpdir = create_dir_structure(&[Path::new("a"), Path::new("b/c")]);
+        let locked = tmpdir.path().join("b");
+        // children can be listed but not looked up
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o644)).unwrap();
+
+        // permissions are not enforced for privileged users
+        if fs::symlink_metadata(locked.join("c")).is_ok() {
+            fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+            return;
+        }
+
+        let result = DatabaseBuilder::new()
+            .add_dir(tmpdir.path())
+            .index(StatusKind::Mode)
+            .build_with_warnings();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let (database, warnings) = result.unwrap();
+        assert_eq!(database.num_entries(), 3);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].path(), locked.join("c"));
+        assert_eq!(
+            warnings[0].error().kind(),
+            std::io::ErrorKind::PermissionDenied
+        );
+    }
+
     #[test]
     #[should_panic(expected = "Fast sorting cannot be enabled for a non-indexed status")]
     fn fast_sort_for_non_indexed_status() {
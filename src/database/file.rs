@@ -0,0 +1,1020 @@
+use super::{
+    bloom::BasenameBloom, builder, fold::FoldedNames, Database, DatabaseMeta, EntryNode, SortKey,
+    StatusKind, XattrColumn,
+};
+use crate::{mode::Mode, Error, Result};
+
+use bincode::Options;
+use camino::Utf8PathBuf;
+use enum_map::EnumMap;
+use rayon::prelude::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufReader, Read, Seek, Write},
+    path::Path,
+    time::SystemTime,
+};
+
+// A database file consists of the magic, a table of sections, and the sections.
+// Each status column and each list of fast-sorted ids is a separate section,
+// so that loading can skip them. Names and nodes are also separate sections
+// so that large parts can be deserialized in parallel.
+// The table (prefixed with its length) and each section are followed by
+// CRC-32 checksums of them, so that corruption of loaded parts is detected.
+
+const MAGIC: &[u8; 4] = b"IXDB";
+const CHECKSUM_LEN: u64 = 4;
+
+/// Part of a database file which can be loaded independently of others.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+enum Section {
+    /// everything needed for basename and path queries other than names and nodes
+    Core,
+    Names,
+    Nodes,
+    Column(StatusKind),
+    SortedIds(StatusKind),
+    /// case-folded basenames, stored with `DatabaseBuilder::case_fold_index`
+    FoldedNames,
+    Metadata,
+    /// ids sorted in a composite order, stored with `DatabaseBuilder::fast_sort_by`
+    CompositeSortedIds(SortKey),
+    /// present if sizes of directories are recursive, stored with
+    /// `DatabaseBuilder::recursive_dir_size`
+    RecursiveDirSize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SectionHeader {
+    section: Section,
+    len: u64,
+}
+
+#[derive(Serialize)]
+struct CoreRef<'a> {
+    num_dirs: usize,
+    root_paths: &'a BTreeMap<u32, Utf8PathBuf>,
+    xattr: &'a Option<XattrColumn>,
+    lossy_ids: &'a Vec<u32>,
+    basename_bloom: &'a Option<BasenameBloom>,
+}
+
+#[derive(Deserialize)]
+struct Core {
+    num_dirs: usize,
+    root_paths: BTreeMap<u32, Utf8PathBuf>,
+    xattr: Option<XattrColumn>,
+    lossy_ids: Vec<u32>,
+    basename_bloom: Option<BasenameBloom>,
+}
+
+/// Options specifying which parts of a database file to load.
+///
+/// Statuses whose columns are not loaded are treated as not indexed, so
+/// accessing them falls back to looking up the file system. They can be told
+/// apart with `Database::is_unloaded`.
+#[derive(Debug, Clone)]
+pub struct LoadOptions {
+    statuses: EnumMap<StatusKind, bool>,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LoadOptions {
+    /// Returns options loading everything.
+    pub fn new() -> Self {
+        Self {
+            statuses: enum_map::enum_map! { _ => true },
+        }
+    }
+
+    /// Sets whether to load the column of the status and ids sorted by it.
+    pub fn status(&mut self, kind: StatusKind, yes: bool) -> &mut Self {
+        self.statuses[kind] = yes;
+        self
+    }
+
+    /// Loads only the columns of the given statuses and ids sorted by them.
+    pub fn only<I>(&mut self, kinds: I) -> &mut Self
+    where
+        I: IntoIterator<Item = StatusKind>,
+    {
+        self.statuses = EnumMap::default();
+        for kind in kinds {
+            self.statuses[kind] = true;
+        }
+        self
+    }
+
+    fn should_load(&self, section: Section) -> bool {
+        match section {
+            Section::Core
+            | Section::Names
+            | Section::Nodes
+            | Section::FoldedNames
+            | Section::Metadata => true,
+            Section::Column(kind) | Section::SortedIds(kind) => self.statuses[kind],
+            Section::RecursiveDirSize => self.statuses[StatusKind::Size],
+            Section::CompositeSortedIds(key) => key.statuses().all(|kind| self.statuses[kind]),
+        }
+    }
+}
+
+fn bincode_options() -> impl Options {
+    bincode::DefaultOptions::new().with_fixint_encoding()
+}
+
+pub(super) fn write<W: Write>(database: &Database, mut writer: W) -> Result<()> {
+    let core = CoreRef {
+        num_dirs: database.num_dirs,
+        root_paths: &database.root_paths,
+        xattr: &database.xattr,
+        lossy_ids: &database.lossy_ids,
+        basename_bloom: &database.basename_bloom,
+    };
+
+    let mut sections: Vec<(Section, &dyn erased::Serialize)> = vec![
+        (Section::Core, &core),
+        (Section::Names, &database.name_arena),
+        (Section::Nodes, &database.nodes),
+    ];
+    for (kind, ids) in &database.sorted_ids {
+        if let Some(ids) = ids {
+            sections.push((Section::SortedIds(kind), ids));
+        }
+    }
+    for (key, ids) in &database.composite_sorted_ids {
+        sections.push((Section::CompositeSortedIds(*key), ids));
+    }
+    for (kind, column) in columns(database) {
+        if let Some(column) = column {
+            sections.push((Section::Column(kind), column));
+        }
+    }
+    if let Some(folded_names) = &database.folded_names {
+        sections.push((Section::FoldedNames, folded_names));
+    }
+    if let Some(metadata) = &database.metadata {
+        sections.push((Section::Metadata, metadata));
+    }
+    if database.recursive_dir_size {
+        sections.push((Section::RecursiveDirSize, &database.recursive_dir_size));
+    }
+
+    let headers = sections
+        .iter()
+        .map(|(section, value)| {
+            Ok(SectionHeader {
+                section: *section,
+                len: value.serialized_size()?,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&bincode_options().serialized_size(&headers)?.to_le_bytes())?;
+    write_checked(&mut writer, &headers)?;
+    for (_, value) in sections {
+        write_checked(&mut writer, value)?;
+    }
+
+    Ok(())
+}
+
+/// Writes a database without fast-sorted ids, sorting ids by each of
+/// `fast_sort_kinds` and `sort_keys` and writing them one at a time.
+///
+/// Sorted ids are written before status columns, since sorting needs the
+/// columns, and each column is dropped right after it is written.
+/// The result is byte for byte the same as `write` with the sorted ids.
+pub(super) fn write_streaming<W: Write>(
+    mut database: Database,
+    fast_sort_kinds: &[StatusKind],
+    sort_keys: &[SortKey],
+    mut writer: W,
+) -> Result<()> {
+    // in the order in which composite_sorted_ids are written by `write`
+    let mut sort_keys = sort_keys.to_vec();
+    sort_keys.sort_unstable();
+
+    let core = CoreRef {
+        num_dirs: database.num_dirs,
+        root_paths: &database.root_paths,
+        xattr: &database.xattr,
+        lossy_ids: &database.lossy_ids,
+        basename_bloom: &database.basename_bloom,
+    };
+
+    // sizes of sorted ids are known without sorting, as they are permutations
+    let sorted_ids_len = bincode_options().serialized_size(&Vec::<u32>::new())?
+        + database.nodes.len() as u64 * bincode_options().serialized_size(&0u32)?;
+
+    let mut headers = vec![
+        SectionHeader {
+            section: Section::Core,
+            len: bincode_options().serialized_size(&core)?,
+        },
+        SectionHeader {
+            section: Section::Names,
+            len: bincode_options().serialized_size(&database.name_arena)?,
+        },
+        SectionHeader {
+            section: Section::Nodes,
+            len: bincode_options().serialized_size(&database.nodes)?,
+        },
+    ];
+    for kind in fast_sort_kinds {
+        headers.push(SectionHeader {
+            section: Section::SortedIds(*kind),
+            len: sorted_ids_len,
+        });
+    }
+    for key in &sort_keys {
+        headers.push(SectionHeader {
+            section: Section::CompositeSortedIds(*key),
+            len: sorted_ids_len,
+        });
+    }
+    for (kind, column) in columns(&database) {
+        if let Some(column) = column {
+            headers.push(SectionHeader {
+                section: Section::Column(kind),
+                len: column.serialized_size()?,
+            });
+        }
+    }
+    if let Some(folded_names) = &database.folded_names {
+        headers.push(SectionHeader {
+            section: Section::FoldedNames,
+            len: bincode_options().serialized_size(folded_names)?,
+        });
+    }
+    if let Some(metadata) = &database.metadata {
+        headers.push(SectionHeader {
+            section: Section::Metadata,
+            len: bincode_options().serialized_size(metadata)?,
+        });
+    }
+    if database.recursive_dir_size {
+        headers.push(SectionHeader {
+            section: Section::RecursiveDirSize,
+            len: bincode_options().serialized_size(&database.recursive_dir_size)?,
+        });
+    }
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&bincode_options().serialized_size(&headers)?.to_le_bytes())?;
+    write_checked(&mut writer, &headers)?;
+    write_checked(&mut writer, &core)?;
+    write_checked(&mut writer, &database.name_arena)?;
+    write_checked(&mut writer, &database.nodes)?;
+
+    for kind in fast_sort_kinds {
+        let ids = builder::sort_ids(&database, *kind);
+        write_checked(&mut writer, &ids)?;
+    }
+    for key in &sort_keys {
+        let ids = builder::sort_ids_by(&database, *key);
+        write_checked(&mut writer, &ids)?;
+    }
+
+    if let Some(size) = database.size.take() {
+        write_checked(&mut writer, &size)?;
+    }
+    if let Some(mode) = database.mode.take() {
+        write_checked(&mut writer, &mode)?;
+    }
+    if let Some(created) = database.created.take() {
+        write_checked(&mut writer, &created)?;
+    }
+    if let Some(modified) = database.modified.take() {
+        write_checked(&mut writer, &modified)?;
+    }
+    if let Some(accessed) = database.accessed.take() {
+        write_checked(&mut writer, &accessed)?;
+    }
+    if let Some(owner) = database.owner.take() {
+        write_checked(&mut writer, &owner)?;
+    }
+    if let Some(folded_names) = &database.folded_names {
+        write_checked(&mut writer, folded_names)?;
+    }
+    if let Some(metadata) = &database.metadata {
+        write_checked(&mut writer, metadata)?;
+    }
+    if database.recursive_dir_size {
+        write_checked(&mut writer, &database.recursive_dir_size)?;
+    }
+
+    Ok(())
+}
+
+/// Returns status columns of the database in the order they are written.
+fn columns(database: &Database) -> [(StatusKind, Option<&dyn erased::Serialize>); 6] {
+    [
+        (StatusKind::Size, database.size.as_ref().map(|x| x as _)),
+        (StatusKind::Mode, database.mode.as_ref().map(|x| x as _)),
+        (
+            StatusKind::Created,
+            database.created.as_ref().map(|x| x as _),
+        ),
+        (
+            StatusKind::Modified,
+            database.modified.as_ref().map(|x| x as _),
+        ),
+        (
+            StatusKind::Accessed,
+            database.accessed.as_ref().map(|x| x as _),
+        ),
+        (StatusKind::Owner, database.owner.as_ref().map(|x| x as _)),
+    ]
+}
+
+/// Writes a value followed by its checksum.
+fn write_checked<W: Write>(writer: &mut W, value: &dyn erased::Serialize) -> Result<()> {
+    let mut checksum_writer = ChecksumWriter {
+        inner: &mut *writer,
+        hasher: crc32fast::Hasher::new(),
+    };
+    value.serialize_into(&mut checksum_writer)?;
+    let checksum = checksum_writer.hasher.finalize();
+    writer.write_all(&checksum.to_le_bytes())?;
+    Ok(())
+}
+
+/// Passes bytes through to `inner`, computing their checksum.
+struct ChecksumWriter<W> {
+    inner: W,
+    hasher: crc32fast::Hasher,
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.hasher.update(&buf[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reads `len` bytes followed by their checksum into `buf`, verifying them.
+fn read_checked<R: Read>(reader: &mut R, len: u64, buf: &mut Vec<u8>) -> Result<()> {
+    read_unchecked(reader, len, buf)?;
+    verify_checksum(buf, len)
+}
+
+/// Reads `len` bytes followed by their checksum into `buf` without verifying them.
+fn read_unchecked<R: Read>(reader: &mut R, len: u64, buf: &mut Vec<u8>) -> Result<()> {
+    let checked_len = len
+        .checked_add(CHECKSUM_LEN)
+        .ok_or(Error::CorruptDatabase)?;
+
+    // reading through take() avoids allocating a bogus length up front
+    buf.clear();
+    reader.take(checked_len).read_to_end(buf)?;
+    if buf.len() as u64 != checked_len {
+        return Err(Error::CorruptDatabase);
+    }
+    Ok(())
+}
+
+/// Returns the length of a section of `len` bytes followed by its checksum,
+/// failing if it does not fit in the rest of the file read by `reader`.
+fn section_len(reader: &mut BufReader<File>, file_len: u64, len: u64) -> Result<u64> {
+    let remaining = file_len.saturating_sub(reader.stream_position()?);
+    len.checked_add(CHECKSUM_LEN)
+        .filter(|section_len| *section_len <= remaining)
+        .ok_or(Error::CorruptDatabase)
+}
+
+/// Verifies `len` bytes followed by their checksum in `buf`, removing the checksum.
+fn verify_checksum(buf: &mut Vec<u8>, len: u64) -> Result<()> {
+    let checksum = buf.split_off(len as usize);
+    if crc32fast::hash(buf).to_le_bytes() != checksum[..] {
+        return Err(Error::CorruptDatabase);
+    }
+    Ok(())
+}
+
+/// Reads the magic and the table of sections, returning the reader
+/// positioned at the first section and the length of the file.
+fn read_headers(path: &Path) -> Result<(BufReader<File>, Vec<SectionHeader>, u64)> {
+    let file = File::open(path)?;
+    let file_len = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+
+    let mut magic = [0; MAGIC.len()];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(Error::UnknownDatabaseFormat);
+    }
+
+    let mut buf = Vec::new();
+    let mut table_len = [0; 8];
+    reader
+        .read_exact(&mut table_len)
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::UnexpectedEof => Error::CorruptDatabase,
+            _ => err.into(),
+        })?;
+    let table_len = u64::from_le_bytes(table_len);
+    section_len(&mut reader, file_len, table_len)?;
+    read_checked(&mut reader, table_len, &mut buf)?;
+    let headers = deserialize(&buf)?;
+
+    Ok((reader, headers, file_len))
+}
+
+pub(super) fn read(path: &Path, options: &LoadOptions) -> Result<Database> {
+    let (mut reader, headers, file_len) = read_headers(path)?;
+
+    let mut buf = Vec::new();
+    let mut decoded = Vec::new();
+    let mut unloaded = EnumMap::default();
+    for SectionHeader { section, len } in headers {
+        let section_len = section_len(&mut reader, file_len, len)?;
+        if !options.should_load(section) {
+            if let Section::Column(kind) = section {
+                unloaded[kind] = true;
+            }
+            reader.seek_relative(section_len as i64)?;
+            continue;
+        }
+
+        read_checked(&mut reader, len, &mut buf)?;
+        decoded.push(Decoded::new(section, &buf)?);
+    }
+
+    assemble(decoded, unloaded)
+}
+
+/// Same as `read`, but verifies and deserializes sections in parallel.
+///
+/// Sections are read into memory first, so this needs as much extra memory
+/// as the size of the loaded sections.
+pub(super) fn read_parallel(path: &Path, options: &LoadOptions) -> Result<Database> {
+    let (mut reader, headers, file_len) = read_headers(path)?;
+
+    let mut sections = Vec::new();
+    let mut unloaded = EnumMap::default();
+    for SectionHeader { section, len } in headers {
+        // a length exceeding the file is caught without allocating it
+        let section_len = section_len(&mut reader, file_len, len)?;
+        if !options.should_load(section) {
+            if let Section::Column(kind) = section {
+                unloaded[kind] = true;
+            }
+            reader.seek_relative(section_len as i64)?;
+            continue;
+        }
+
+        let mut buf = Vec::with_capacity(section_len as usize);
+        read_unchecked(&mut reader, len, &mut buf)?;
+        sections.push((section, len, buf));
+    }
+
+    let decoded = sections
+        .into_par_iter()
+        .map(|(section, len, mut buf)| {
+            verify_checksum(&mut buf, len)?;
+            Decoded::new(section, &buf)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    assemble(decoded, unloaded)
+}
+
+/// A deserialized section, which is yet to be put into a database.
+enum Decoded {
+    Core(Core),
+    Names(String),
+    Nodes(Vec<EntryNode>),
+    Size(Vec<u64>),
+    Mode(Vec<Mode>),
+    Created(Vec<SystemTime>),
+    Modified(Vec<SystemTime>),
+    Accessed(Vec<SystemTime>),
+    Owner(Vec<u32>),
+    SortedIds(StatusKind, Vec<u32>),
+    CompositeSortedIds(SortKey, Vec<u32>),
+    FoldedNames(FoldedNames),
+    Metadata(DatabaseMeta),
+    RecursiveDirSize(bool),
+}
+
+impl Decoded {
+    fn new(section: Section, bytes: &[u8]) -> Result<Self> {
+        let decoded = match section {
+            Section::Core => Self::Core(deserialize(bytes)?),
+            Section::Names => Self::Names(deserialize(bytes)?),
+            Section::Nodes => Self::Nodes(deserialize(bytes)?),
+            Section::Column(kind) => match kind {
+                StatusKind::Size => Self::Size(deserialize(bytes)?),
+                StatusKind::Mode => Self::Mode(deserialize(bytes)?),
+                StatusKind::Created => Self::Created(deserialize(bytes)?),
+                StatusKind::Modified => Self::Modified(deserialize(bytes)?),
+                StatusKind::Accessed => Self::Accessed(deserialize(bytes)?),
+                StatusKind::Owner => Self::Owner(deserialize(bytes)?),
+                StatusKind::Basename | StatusKind::Path | StatusKind::Extension => {
+                    return Err(Error::UnknownDatabaseFormat)
+                }
+            },
+            Section::SortedIds(kind) => Self::SortedIds(kind, deserialize(bytes)?),
+            Section::FoldedNames => Self::FoldedNames(deserialize(bytes)?),
+            Section::Metadata => Self::Metadata(deserialize(bytes)?),
+            Section::CompositeSortedIds(key) => Self::CompositeSortedIds(key, deserialize(bytes)?),
+            Section::RecursiveDirSize => Self::RecursiveDirSize(deserialize(bytes)?),
+        };
+        Ok(decoded)
+    }
+}
+
+/// Builds a database from sections, which must include the core, names, and nodes.
+///
+/// `unloaded` marks the statuses whose columns were skipped.
+fn assemble(sections: Vec<Decoded>, unloaded: EnumMap<StatusKind, bool>) -> Result<Database> {
+    let mut database = Database {
+        name_arena: String::new(),
+        nodes: Vec::new(),
+        num_dirs: 0,
+        root_paths: BTreeMap::new(),
+        size: None,
+        recursive_dir_size: false,
+        mode: None,
+        created: None,
+        modified: None,
+        accessed: None,
+        owner: None,
+        xattr: None,
+        lossy_ids: Vec::new(),
+        basename_bloom: None,
+        folded_names: None,
+        metadata: None,
+        sorted_ids: EnumMap::default(),
+        composite_sorted_ids: BTreeMap::new(),
+        unloaded,
+    };
+    let (mut has_core, mut has_names, mut has_nodes) = (false, false, false);
+
+    for section in sections {
+        match section {
+            Decoded::Core(core) => {
+                database.num_dirs = core.num_dirs;
+                database.root_paths = core.root_paths;
+                database.xattr = core.xattr;
+                database.lossy_ids = core.lossy_ids;
+                database.basename_bloom = core.basename_bloom;
+                has_core = true;
+            }
+            Decoded::Names(name_arena) => {
+                database.name_arena = name_arena;
+                has_names = true;
+            }
+            Decoded::Nodes(nodes) => {
+                database.nodes = nodes;
+                has_nodes = true;
+            }
+            Decoded::Size(size) => database.size = Some(size),
+            Decoded::Mode(mode) => database.mode = Some(mode),
+            Decoded::Created(created) => database.created = Some(created),
+            Decoded::Modified(modified) => database.modified = Some(modified),
+            Decoded::Accessed(accessed) => database.accessed = Some(accessed),
+            Decoded::Owner(owner) => database.owner = Some(owner),
+            Decoded::SortedIds(kind, ids) => database.sorted_ids[kind] = Some(ids),
+            Decoded::CompositeSortedIds(key, ids) => {
+                database.composite_sorted_ids.insert(key, ids);
+            }
+            Decoded::FoldedNames(folded_names) => database.folded_names = Some(folded_names),
+            Decoded::Metadata(metadata) => database.metadata = Some(metadata),
+            Decoded::RecursiveDirSize(recursive) => database.recursive_dir_size = recursive,
+        }
+    }
+
+    if !(has_core && has_names && has_nodes) {
+        return Err(Error::UnknownDatabaseFormat);
+    }
+    Ok(database)
+}
+
+fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    let value = bincode_options()
+        .reject_trailing_bytes()
+        .deserialize(bytes)?;
+    Ok(value)
+}
+
+/// Object-safe serialization, so that sections of different types can be
+/// handled together.
+mod erased {
+    use super::bincode_options;
+    use crate::Result;
+
+    use bincode::Options;
+    use std::io::Write;
+
+    pub trait Serialize {
+        fn serialized_size(&self) -> Result<u64>;
+        fn serialize_into(&self, writer: &mut dyn Write) -> Result<()>;
+    }
+
+    impl<T: serde::Serialize> Serialize for T {
+        fn serialized_size(&self) -> Result<u64> {
+            Ok(bincode_options().serialized_size(self)?)
+        }
+
+        fn serialize_into(&self, writer: &mut dyn Write) -> Result<()> {
+            bincode_options().serialize_into(writer, self)?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{database::DatabaseBuilder, query::QueryBuilder};
+    use std::{fs, time::SystemTime};
+
+    fn create_database(root: &Path) -> Database {
+        fs::create_dir_all(root.join("dir").join("sub")).unwrap();
+        fs::write(root.join("dir").join("foo.txt"), "foo").unwrap();
+        fs::write(root.join("dir").join("sub").join("bar.txt"), "barbar").unwrap();
+        fs::write(root.join("baz"), "").unwrap();
+
+        DatabaseBuilder::new()
+            .add_dir(root)
+            .index(StatusKind::Size)
+            .index(StatusKind::Modified)
+            .fast_sort(StatusKind::Size)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let root = dunce::canonicalize(tmpdir.path()).unwrap();
+        let database = create_database(&root);
+        let location = root.join("database.db");
+        database.save(&location).unwrap();
+
+        let loaded = Database::open(&location).unwrap();
+        assert_eq!(
+            loaded.indexed_statuses().collect::<Vec<_>>(),
+            database.indexed_statuses().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            loaded.fast_sortable_statuses().collect::<Vec<_>>(),
+            database.fast_sortable_statuses().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            loaded.entries().map(|e| e.to_record()).collect::<Vec<_>>(),
+            database
+                .entries()
+                .map(|e| e.to_record())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn compact_nodes() {
+        assert!(std::mem::size_of::<EntryNode>() <= 20);
+
+        let tmpdir = tempfile::tempdir().unwrap();
+        let database = create_database(&tmpdir.path().join("root"));
+        let node_len = bincode_options()
+            .serialized_size(&database.nodes[0])
+            .unwrap();
+        assert_eq!(node_len, 19);
+
+        let location = tmpdir.path().join("database.db");
+        database.save(&location).unwrap();
+        let loaded = Database::open(&location).unwrap();
+        assert_eq!(
+            bincode_options().serialize(&loaded.nodes).unwrap(),
+            bincode_options().serialize(&database.nodes).unwrap()
+        );
+    }
+
+    #[test]
+    fn build_to_writer() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let tmpdir = dunce::canonicalize(tmpdir.path()).unwrap();
+        let root = tmpdir.join("root");
+        create_database(&root);
+
+        let mut builder = DatabaseBuilder::new();
+        builder
+            .add_dir(&root)
+            .index(StatusKind::Size)
+            .index(StatusKind::Mode)
+            .index(StatusKind::Modified)
+            .fast_sort(StatusKind::Path)
+            .fast_sort(StatusKind::Size)
+            .fast_sort(StatusKind::Modified)
+            .fast_sort_by(SortKey::DirsFirst(StatusKind::Size))
+            .fast_sort_by(SortKey::Then(StatusKind::Mode, StatusKind::Modified))
+            .case_fold_index(true);
+        let database = builder.build().unwrap();
+
+        // outside of the root, so that the files are not indexed
+        let location = tmpdir.join("database.db");
+        let warnings = builder
+            .build_to_writer(File::create(&location).unwrap())
+            .unwrap();
+        assert!(warnings.is_empty());
+        let saved = tmpdir.join("saved.db");
+        database.save(&saved).unwrap();
+        assert_eq!(fs::read(&location).unwrap(), fs::read(&saved).unwrap());
+        let loaded = Database::open(&location).unwrap();
+
+        assert_eq!(
+            loaded.indexed_statuses().collect::<Vec<_>>(),
+            database.indexed_statuses().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            loaded.fast_sortable_statuses().collect::<Vec<_>>(),
+            database.fast_sortable_statuses().collect::<Vec<_>>()
+        );
+        assert_eq!(loaded.sorted_ids, database.sorted_ids);
+        assert_eq!(loaded.composite_sorted_ids, database.composite_sorted_ids);
+        assert!(loaded.folded_names.is_some());
+
+        // composite orders are skipped unless all of their statuses are loaded
+        let mut options = LoadOptions::new();
+        options.status(StatusKind::Modified, false);
+        let partial = Database::open_with(&location, &options).unwrap();
+        assert_eq!(
+            partial.fast_sort_keys().collect::<Vec<_>>(),
+            [SortKey::DirsFirst(StatusKind::Size)]
+        );
+        assert_eq!(
+            loaded.entries().map(|e| e.to_record()).collect::<Vec<_>>(),
+            database
+                .entries()
+                .map(|e| e.to_record())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn metadata() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let root = dunce::canonicalize(tmpdir.path()).unwrap();
+        let database = create_database(&root);
+        assert!(database.metadata().is_none());
+
+        let before = SystemTime::now();
+        let database = DatabaseBuilder::new()
+            .add_dir(&root)
+            .index(StatusKind::Size)
+            .record_metadata(true)
+            .build()
+            .unwrap();
+        let metadata = database.metadata().unwrap();
+        assert!(metadata.built_at >= before);
+        assert!(metadata.built_at <= SystemTime::now());
+        assert_eq!(metadata.roots, [root.to_str().unwrap()]);
+        assert_eq!(
+            metadata.indexed,
+            [
+                StatusKind::Basename,
+                StatusKind::Path,
+                StatusKind::Extension,
+                StatusKind::Size
+            ]
+        );
+
+        let location = root.join("database.db");
+        database.save(&location).unwrap();
+        let loaded = Database::open(&location).unwrap();
+        assert_eq!(loaded.metadata(), Some(metadata));
+
+        let mut merged = Database::merge(vec![loaded]).unwrap();
+        assert!(merged.metadata().is_some());
+        merged.append(create_database(&root.join("other"))).unwrap();
+        assert!(merged.metadata().is_none());
+    }
+
+    #[test]
+    fn parallel_load() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let root = dunce::canonicalize(tmpdir.path()).unwrap();
+        create_database(&root);
+        let database = DatabaseBuilder::new()
+            .add_dir(&root)
+            .index(StatusKind::Size)
+            .index(StatusKind::Mode)
+            .index(StatusKind::Created)
+            .index(StatusKind::Modified)
+            .index(StatusKind::Accessed)
+            .fast_sort(StatusKind::Size)
+            .fast_sort(StatusKind::Modified)
+            .case_fold_index(true)
+            .record_metadata(true)
+            .build()
+            .unwrap();
+        let location = root.join("database.db");
+        database.save(&location).unwrap();
+
+        let records = |database: &Database| {
+            database
+                .entries()
+                .map(|e| e.to_record())
+                .collect::<Vec<_>>()
+        };
+        for options in [
+            LoadOptions::new(),
+            LoadOptions::new().only([StatusKind::Modified]).clone(),
+        ] {
+            let sequential = Database::open_with(&location, &options).unwrap();
+            let parallel = Database::open_parallel(&location, &options).unwrap();
+            assert_eq!(records(&parallel), records(&sequential));
+            assert_eq!(
+                parallel.indexed_statuses().collect::<Vec<_>>(),
+                sequential.indexed_statuses().collect::<Vec<_>>()
+            );
+            assert_eq!(
+                parallel.fast_sortable_statuses().collect::<Vec<_>>(),
+                sequential.fast_sortable_statuses().collect::<Vec<_>>()
+            );
+            assert_eq!(parallel.sorted_ids, sequential.sorted_ids);
+            assert_eq!(parallel.metadata(), sequential.metadata());
+            assert!(parallel.folded_names.is_some());
+        }
+
+        let mut bytes = fs::read(&location).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        fs::write(&location, bytes).unwrap();
+        assert!(matches!(
+            Database::open_parallel(&location, &LoadOptions::new()),
+            Err(Error::CorruptDatabase)
+        ));
+    }
+
+    #[test]
+    fn recursive_dir_size() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let root = dunce::canonicalize(tmpdir.path()).unwrap();
+        create_database(&root);
+        let database = DatabaseBuilder::new()
+            .add_dir(&root)
+            .index(StatusKind::Size)
+            .recursive_dir_size(true)
+            .build()
+            .unwrap();
+        let location = root.join("database.db");
+        database.save(&location).unwrap();
+
+        let loaded = Database::open(&location).unwrap();
+        assert!(loaded.is_dir_size_recursive());
+        let id = loaded.get_by_path(root.join("dir")).unwrap();
+        assert_eq!(loaded.entry(id).size().unwrap(), 9);
+
+        let loaded = Database::open_with(
+            &location,
+            LoadOptions::new().status(StatusKind::Size, false),
+        )
+        .unwrap();
+        assert!(!loaded.is_dir_size_recursive());
+
+        let location = root.join("streamed.db");
+        DatabaseBuilder::new()
+            .add_dir(&root)
+            .index(StatusKind::Size)
+            .recursive_dir_size(true)
+            .build_to_writer(fs::File::create(&location).unwrap())
+            .unwrap();
+        assert!(Database::open(&location).unwrap().is_dir_size_recursive());
+    }
+
+    #[test]
+    fn partial_load() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let root = dunce::canonicalize(tmpdir.path()).unwrap();
+        let database = create_database(&root);
+        let location = root.join("database.db");
+        database.save(&location).unwrap();
+
+        let loaded = Database::open_with(
+            &location,
+            LoadOptions::new().status(StatusKind::Size, false),
+        )
+        .unwrap();
+        assert!(!loaded.is_indexed(StatusKind::Size));
+        assert!(!loaded.is_fast_sortable(StatusKind::Size));
+        assert!(loaded.is_indexed(StatusKind::Modified));
+        assert!(loaded.is_unloaded(StatusKind::Size));
+        assert!(!loaded.is_unloaded(StatusKind::Modified));
+        // never indexed
+        assert!(!loaded.is_unloaded(StatusKind::Mode));
+        let parallel = Database::open_parallel(
+            &location,
+            LoadOptions::new().status(StatusKind::Size, false),
+        )
+        .unwrap();
+        assert!(parallel.is_unloaded(StatusKind::Size));
+
+        let loaded =
+            Database::open_with(&location, LoadOptions::new().only([StatusKind::Basename]))
+                .unwrap();
+        assert_eq!(
+            loaded.indexed_statuses().collect::<Vec<_>>(),
+            [
+                StatusKind::Basename,
+                StatusKind::Path,
+                StatusKind::Extension
+            ]
+        );
+        assert_eq!(
+            loaded.fast_sortable_statuses().collect::<Vec<_>>(),
+            [StatusKind::Basename]
+        );
+
+        for pattern in ["txt", "dir/", "^b"] {
+            let query = QueryBuilder::new(pattern).build().unwrap();
+            let paths = |database: &Database| {
+                database
+                    .search_all(&query)
+                    .unwrap()
+                    .into_iter()
+                    .map(|id| database.entry(id).path())
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(paths(&loaded), paths(&database));
+        }
+
+        // statuses which are not loaded are looked up from the file system
+        fs::write(root.join("baz"), "changed").unwrap();
+        let id = loaded.get_by_path(root.join("baz")).unwrap();
+        assert_eq!(loaded.entry(id).size().unwrap(), 7);
+        let id = database.get_by_path(root.join("baz")).unwrap();
+        assert_eq!(database.entry(id).size().unwrap(), 0);
+    }
+
+    #[test]
+    fn unknown_format() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let location = tmpdir.path().join("database.db");
+        fs::write(&location, "not a database").unwrap();
+        assert!(matches!(
+            Database::open(&location),
+            Err(Error::UnknownDatabaseFormat)
+        ));
+    }
+
+    #[test]
+    fn corruption() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let root = dunce::canonicalize(tmpdir.path()).unwrap();
+        let database = create_database(&root);
+        let location = root.join("database.db");
+        database.save(&location).unwrap();
+        let bytes = fs::read(&location).unwrap();
+
+        // any byte after the magic is covered by a checksum or is a length
+        // which the checksums would disagree with
+        for i in MAGIC.len()..bytes.len() {
+            let mut corrupted = bytes.clone();
+            corrupted[i] ^= 0x10;
+            fs::write(&location, &corrupted).unwrap();
+            assert!(
+                matches!(Database::open(&location), Err(Error::CorruptDatabase)),
+                "{}",
+                i
+            );
+        }
+
+        fs::write(&location, &bytes[..bytes.len() - 1]).unwrap();
+        assert!(matches!(
+            Database::open(&location),
+            Err(Error::CorruptDatabase)
+        ));
+
+        // lengths which overflow or exceed the file are not trusted
+        for table_len in [u64::MAX, u64::MAX - CHECKSUM_LEN, bytes.len() as u64] {
+            let mut corrupted = MAGIC.to_vec();
+            corrupted.extend_from_slice(&table_len.to_le_bytes());
+            corrupted.extend_from_slice(&bytes[MAGIC.len() + 8..]);
+            fs::write(&location, &corrupted).unwrap();
+            assert!(matches!(
+                Database::open(&location),
+                Err(Error::CorruptDatabase)
+            ));
+            assert!(matches!(
+                Database::open_parallel(&location, &LoadOptions::new()),
+                Err(Error::CorruptDatabase)
+            ));
+        }
+    }
+}
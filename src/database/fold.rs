@@ -0,0 +1,66 @@
+use super::Database;
+
+use rayon::prelude::*;
+use regex_syntax::hir::{ClassUnicode, ClassUnicodeRange};
+use serde::{Deserialize, Serialize};
+
+/// Case-folded copies of basenames, so that case-insensitive literal queries
+/// can be matched by byte search instead of case-insensitive regexes.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct FoldedNames {
+    /// folded basenames of all entries concatenated in the order of ids
+    arena: String,
+    /// `arena[offsets[id]..offsets[id + 1]]` is the folded basename of entry `id`
+    offsets: Vec<u32>,
+}
+
+impl FoldedNames {
+    pub fn build(database: &Database) -> Self {
+        let names: Vec<_> = database
+            .nodes
+            .par_iter()
+            .map(|node| fold_case(database.basename_from_node(node)))
+            .collect();
+
+        let mut arena = String::with_capacity(names.iter().map(String::len).sum());
+        let mut offsets = Vec::with_capacity(names.len() + 1);
+        offsets.push(0);
+        for name in names {
+            arena.push_str(&name);
+            // folding never lengthens names, whose total length fits in u32
+            offsets.push(arena.len() as u32);
+        }
+
+        Self { arena, offsets }
+    }
+
+    #[inline]
+    pub fn get(&self, id: u32) -> &[u8] {
+        let id = id as usize;
+        let start = self.offsets[id] as usize;
+        let end = self.offsets[id + 1] as usize;
+        &self.arena.as_bytes()[start..end]
+    }
+}
+
+/// Maps each character of `s` to a single representative of the characters
+/// it matches case-insensitively.
+///
+/// This uses the same Unicode simple case folding as case-insensitive regexes,
+/// so `fold_case(a).contains(&fold_case(b))` agrees with matching `b`
+/// case-insensitively against `a`.
+pub(crate) fn fold_case(s: &str) -> String {
+    s.chars().map(fold_char).collect()
+}
+
+fn fold_char(c: char) -> char {
+    // KELVIN SIGN and LATIN SMALL LETTER LONG S are the only non-ASCII
+    // characters folding to ASCII letters
+    if c.is_ascii() && !matches!(c, 'K' | 'S' | 'k' | 's') {
+        return c.to_ascii_lowercase();
+    }
+    let mut class = ClassUnicode::new([ClassUnicodeRange::new(c, c)]);
+    class.case_fold_simple();
+    // the smallest character in the orbit
+    class.ranges()[0].start()
+}
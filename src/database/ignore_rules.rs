@@ -0,0 +1,193 @@
+use crate::glob;
+
+use regex::Regex;
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+/// Names of ignore files in increasing order of precedence, as in fd.
+const IGNORE_FILE_NAMES: [&str; 2] = [".ignore", ".fdignore"];
+
+/// Patterns of ignore files found in a directory and its ancestors.
+///
+/// Patterns in deeper directories take precedence over those in shallower
+/// ones, and later patterns take precedence over earlier ones in the same
+/// directory, following the rules of gitignore.
+#[derive(Default)]
+pub(super) struct IgnoreRules {
+    parent: Option<Arc<IgnoreRules>>,
+    dir: PathBuf,
+    patterns: Vec<Pattern>,
+}
+
+impl IgnoreRules {
+    /// Returns rules applying to children of `dir`, which is a child of the
+    /// directory the rules apply to.
+    ///
+    /// Unreadable ignore files are treated as empty.
+    pub fn child(self: &Arc<Self>, dir: &Path) -> Arc<Self> {
+        let patterns: Vec<_> = IGNORE_FILE_NAMES
+            .iter()
+            .filter_map(|name| fs::read_to_string(dir.join(name)).ok())
+            .flat_map(|content| {
+                content
+                    .lines()
+                    .filter_map(Pattern::parse)
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        if patterns.is_empty() {
+            return Arc::clone(self);
+        }
+
+        Arc::new(Self {
+            parent: Some(Arc::clone(self)),
+            dir: dir.to_path_buf(),
+            patterns,
+        })
+    }
+
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let mut rules = Some(self);
+        while let Some(IgnoreRules {
+            parent,
+            dir,
+            patterns,
+        }) = rules
+        {
+            if let Some(relative) = relative_path(path, dir) {
+                let matched = patterns
+                    .iter()
+                    .rev()
+                    .find(|pattern| pattern.is_match(&relative, is_dir));
+                if let Some(pattern) = matched {
+                    return !pattern.negated;
+                }
+            }
+            rules = parent.as_deref();
+        }
+        false
+    }
+}
+
+struct Pattern {
+    regex: Regex,
+    negated: bool,
+    dir_only: bool,
+}
+
+impl Pattern {
+    /// Parses a line of an ignore file. Returns None for blank lines,
+    /// comments and invalid patterns.
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        // leading "\!" and "\#" are handled as escapes by glob::to_regex
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        if line.is_empty() {
+            return None;
+        }
+
+        // patterns without a slash (except a trailing one) match at any depth
+        let (anchored, glob) = match line.strip_prefix('/') {
+            Some(rest) => (true, rest),
+            None => (line.contains('/'), line),
+        };
+        let prefix = if anchored { "^" } else { "^(?:.*/)?" };
+        let regex = Regex::new(&format!("{}{}$", prefix, glob::to_regex(glob, '/'))).ok()?;
+
+        Some(Self {
+            regex,
+            negated,
+            dir_only,
+        })
+    }
+
+    fn is_match(&self, relative_path: &str, is_dir: bool) -> bool {
+        (is_dir || !self.dir_only) && self.regex.is_match(relative_path)
+    }
+}
+
+/// Returns `path` relative to `dir`, separated by "/".
+fn relative_path(path: &Path, dir: &Path) -> Option<String> {
+    let relative = path.strip_prefix(dir).ok()?;
+    let components: Vec<_> = relative
+        .components()
+        .map(|component| match component {
+            Component::Normal(name) => name.to_string_lossy(),
+            _ => component.as_os_str().to_string_lossy(),
+        })
+        .collect();
+    Some(components.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_match(pattern: &str, path: &str, is_dir: bool) -> bool {
+        Pattern::parse(pattern).unwrap().is_match(path, is_dir)
+    }
+
+    #[test]
+    fn parse() {
+        assert!(Pattern::parse("").is_none());
+        assert!(Pattern::parse("   ").is_none());
+        assert!(Pattern::parse("# comment").is_none());
+        assert!(Pattern::parse("/").is_none());
+        assert!(Pattern::parse("!foo").unwrap().negated);
+        assert!(!Pattern::parse(r"\!foo").unwrap().negated);
+        assert!(Pattern::parse("foo/").unwrap().dir_only);
+    }
+
+    #[test]
+    fn glob() {
+        assert!(is_match("foo", "foo", false));
+        assert!(is_match("foo", "a/b/foo", true));
+        assert!(!is_match("foo", "foobar", false));
+        assert!(!is_match("foo", "foo/bar", false));
+
+        assert!(is_match("/foo", "foo", false));
+        assert!(!is_match("/foo", "a/foo", false));
+        assert!(is_match("a/foo", "a/foo", false));
+        assert!(!is_match("a/foo", "b/a/foo", false));
+
+        assert!(is_match("build/", "a/build", true));
+        assert!(!is_match("build/", "a/build", false));
+
+        assert!(is_match("*.log", "a.log", false));
+        assert!(is_match("*.log", "x/y/a.log", false));
+        assert!(!is_match("*.log", "a.log.txt", false));
+        assert!(is_match("a/*.log", "a/b.log", false));
+        assert!(!is_match("a/*.log", "a/b/c.log", false));
+        assert!(is_match("file?.txt", "file1.txt", false));
+        assert!(!is_match("file?.txt", "file10.txt", false));
+
+        assert!(is_match("**/foo", "foo", false));
+        assert!(is_match("**/foo", "a/b/foo", false));
+        assert!(is_match("a/**/b", "a/b", false));
+        assert!(is_match("a/**/b", "a/x/y/b", false));
+        assert!(is_match("a/**", "a/x/y", false));
+        assert!(!is_match("a/**", "b/x", false));
+
+        assert!(is_match("[abc].txt", "b.txt", false));
+        assert!(!is_match("[abc].txt", "d.txt", false));
+        assert!(is_match("[!abc].txt", "d.txt", false));
+        assert!(is_match("[a-c].txt", "b.txt", false));
+        assert!(is_match(r"\*.txt", "*.txt", false));
+        assert!(!is_match(r"\*.txt", "a.txt", false));
+        assert!(is_match("a+b(c).txt", "a+b(c).txt", false));
+    }
+}
@@ -1,20 +1,37 @@
-use super::{util, Database, EntryNode, StatusFlags, StatusKind};
+use super::{
+    bloom::BasenameBloom, fold::FoldedNames, ignore_rules::IgnoreRules, util, Database, EntryNode,
+    StatusFlags, StatusKind, Warning, XattrColumn,
+};
 use crate::{mode::Mode, Error, Result};
 
 use camino::{Utf8Path, Utf8PathBuf};
 use enum_map::{enum_map, EnumMap};
-use fxhash::FxHashMap;
 use hashbrown::{hash_map::RawEntryMut, HashMap};
 use parking_lot::Mutex;
 use rayon::prelude::*;
 use std::{
+    collections::BTreeMap,
+    fs::FileType,
     path::{Path, PathBuf},
+    sync::Arc,
     time::SystemTime,
 };
 
+/// Predicate deciding whether an entry (and its descendants) should be indexed.
+pub type EntryFilter = dyn Fn(&Path, &FileType) -> bool + Send + Sync;
+
+#[derive(Clone)]
 pub struct IndexOptions {
     pub index_flags: StatusFlags,
     pub ignore_hidden: bool,
+    /// name of an extended attribute to record presence of
+    pub xattr: Option<String>,
+    pub basename_bloom: bool,
+    pub case_fold_index: bool,
+    pub recursive_dir_size: bool,
+    pub lossy_paths: bool,
+    pub respect_ignore_files: bool,
+    pub filter_entry: Option<Arc<EntryFilter>>,
 }
 
 impl Default for IndexOptions {
@@ -29,8 +46,16 @@ impl Default for IndexOptions {
                 StatusKind::Created => false,
                 StatusKind::Modified => false,
                 StatusKind::Accessed => false,
+                StatusKind::Owner => false,
             },
             ignore_hidden: false,
+            xattr: None,
+            basename_bloom: false,
+            case_fold_index: false,
+            recursive_dir_size: false,
+            lossy_paths: false,
+            respect_ignore_files: false,
+            filter_entry: None,
         }
     }
 }
@@ -44,6 +69,45 @@ impl IndexOptions {
             || flags[StatusKind::Created]
             || flags[StatusKind::Modified]
             || flags[StatusKind::Accessed]
+            || flags[StatusKind::Owner]
+    }
+
+    /// Returns true if indexing `root` would skip `path`, because `path` or
+    /// one of its ancestors below `root` is hidden, filtered out, or ignored.
+    ///
+    /// Paths which are not under `root` or do not exist are excluded.
+    pub fn excludes(&self, root: &Path, path: &Path) -> bool {
+        let relative = match path.strip_prefix(root) {
+            Ok(relative) => relative,
+            Err(_) => return true,
+        };
+
+        let mut ignore_rules = Arc::new(IgnoreRules::default());
+        let mut dir = root.to_path_buf();
+        for component in relative.components() {
+            if self.respect_ignore_files {
+                ignore_rules = ignore_rules.child(&dir);
+            }
+            let child = dir.join(component);
+            let metadata = match child.symlink_metadata() {
+                Ok(metadata) => metadata,
+                Err(_) => return true,
+            };
+            if self.ignore_hidden && util::is_hidden_path(&child, &metadata) {
+                return true;
+            }
+            if let Some(filter) = &self.filter_entry {
+                if !filter(&child, &metadata.file_type()) {
+                    return true;
+                }
+            }
+            if self.respect_ignore_files && ignore_rules.is_ignored(&child, metadata.is_dir()) {
+                return true;
+            }
+            dir = child;
+        }
+
+        false
     }
 }
 
@@ -57,13 +121,30 @@ impl<'a> Indexer<'a> {
         let database = Database {
             name_arena: String::new(),
             nodes: Vec::new(),
-            root_paths: FxHashMap::default(),
+            num_dirs: 0,
+            root_paths: BTreeMap::new(),
             size: options.index_flags[StatusKind::Size].then(Vec::new),
+            recursive_dir_size: false,
             mode: options.index_flags[StatusKind::Mode].then(Vec::new),
             created: options.index_flags[StatusKind::Created].then(Vec::new),
             modified: options.index_flags[StatusKind::Modified].then(Vec::new),
             accessed: options.index_flags[StatusKind::Accessed].then(Vec::new),
+            owner: (options.index_flags[StatusKind::Owner] && util::OWNER_SUPPORTED).then(Vec::new),
+            xattr: options
+                .xattr
+                .clone()
+                .filter(|_| util::XATTR_SUPPORTED)
+                .map(|name| XattrColumn {
+                    name,
+                    values: Vec::new(),
+                }),
+            lossy_ids: Vec::new(),
+            basename_bloom: None,
+            folded_names: None,
+            metadata: None,
             sorted_ids: EnumMap::default(),
+            composite_sorted_ids: BTreeMap::new(),
+            unloaded: EnumMap::default(),
         };
 
         Self {
@@ -75,7 +156,8 @@ impl<'a> Indexer<'a> {
     pub fn index<P: Into<PathBuf>>(mut self, path: P) -> Result<Self> {
         let path = Utf8PathBuf::from_path_buf(path.into()).map_err(|_| Error::NonUtf8Path)?;
 
-        let root_entry = LeafOrInternalEntry::from_path(&path, self.options)?;
+        let root_entry =
+            LeafOrInternalEntry::from_path(&path, self.options, &mut self.ctx.warnings)?;
         let root_node_id = self.ctx.database.nodes.len() as u32;
         self.ctx.database.root_paths.insert(root_node_id, path);
 
@@ -91,22 +173,36 @@ impl<'a> Indexer<'a> {
                     self.options,
                     root_node_id,
                     entry.child_dir_entries.into(),
+                    &entry.ignore_rules,
                 );
                 self.ctx = ctx.into_inner();
             }
         }
 
+        if self.ctx.names_overflowed {
+            return Err(Error::TooManyNames);
+        }
         Ok(self)
     }
 
-    pub fn finish(self) -> Database {
-        self.ctx.into_inner()
+    pub fn finish(self) -> (Database, Vec<Warning>) {
+        let (mut database, warnings) = self.ctx.into_inner();
+        if self.options.basename_bloom {
+            database.basename_bloom = Some(BasenameBloom::build(&database));
+        }
+        if self.options.case_fold_index {
+            database.folded_names = Some(FoldedNames::build(&database));
+        }
+        if self.options.recursive_dir_size {
+            database.sum_dir_sizes();
+        }
+        (database, warnings)
     }
 }
 
 /// Span in name_arena
 struct NameSpan {
-    start: usize,
+    start: u32,
     len: u16,
 }
 
@@ -118,6 +214,12 @@ struct WalkContext {
     // Also, () is specified as HashBuilder since we don't use the default hasher.
     // Each hash value is caluculated from a string NameSpan represents.
     name_spans: HashMap<NameSpan, (), ()>,
+
+    // Set when name_arena would exceed u32::MAX bytes, in which case
+    // the database is invalid.
+    names_overflowed: bool,
+
+    warnings: Vec<Warning>,
 }
 
 impl WalkContext {
@@ -125,27 +227,48 @@ impl WalkContext {
         Self {
             database,
             name_spans: HashMap::with_hasher(()),
+            names_overflowed: false,
+            warnings: Vec::new(),
         }
     }
 
-    fn into_inner(self) -> Database {
-        self.database
+    fn into_inner(self) -> (Database, Vec<Warning>) {
+        (self.database, self.warnings)
     }
 
     fn push_leaf_entry(&mut self, entry: &LeafEntry, parent_id: u32) {
-        self.push_entry(&entry.name, &entry.metadata, entry.is_dir, parent_id);
+        self.push_entry(
+            &entry.name,
+            &entry.metadata,
+            entry.is_dir,
+            entry.is_lossy,
+            parent_id,
+        );
     }
 
     fn push_internal_entry(&mut self, entry: &InternalEntry, parent_id: u32) {
-        self.push_entry(&entry.name, &entry.metadata, true, parent_id);
+        self.push_entry(
+            &entry.name,
+            &entry.metadata,
+            true,
+            entry.is_lossy,
+            parent_id,
+        );
     }
 
-    fn push_entry(&mut self, name: &str, metadata: &Metadata, is_dir: bool, parent_id: u32) {
+    fn push_entry(
+        &mut self,
+        name: &str,
+        metadata: &Metadata,
+        is_dir: bool,
+        is_lossy: bool,
+        parent_id: u32,
+    ) {
         let hash = fxhash::hash64(name);
         let hash_entry = {
             let name_arena = &self.database.name_arena;
             self.name_spans.raw_entry_mut().from_hash(hash, |span| {
-                &name_arena[span.start..][..span.len as usize] == name
+                &name_arena[span.start as usize..][..span.len as usize] == name
             })
         };
 
@@ -156,9 +279,15 @@ impl WalkContext {
                 debug_assert_eq!(len, name_len);
                 start
             }
+            RawEntryMut::Vacant(_)
+                if self.database.name_arena.len() + name.len() > u32::MAX as usize =>
+            {
+                self.names_overflowed = true;
+                0
+            }
             RawEntryMut::Vacant(entry) => {
                 let name_arena = &mut self.database.name_arena;
-                let start = name_arena.len();
+                let start = name_arena.len() as u32;
                 name_arena.push_str(name);
                 entry.insert_with_hasher(
                     hash,
@@ -167,13 +296,21 @@ impl WalkContext {
                         len: name_len,
                     },
                     (),
-                    |span| fxhash::hash64(&name_arena[span.start..][..span.len as usize]),
+                    |span| fxhash::hash64(&name_arena[span.start as usize..][..span.len as usize]),
                 );
                 start
             }
         };
-        debug_assert_eq!(&self.database.name_arena[name_start..][..name.len()], name);
-
+        debug_assert!(
+            self.names_overflowed
+                || &self.database.name_arena[name_start as usize..][..name.len()] == name
+        );
+
+        if is_lossy {
+            // ids are assigned in increasing order, so lossy_ids stays sorted
+            let id = self.database.nodes.len() as u32;
+            self.database.lossy_ids.push(id);
+        }
         self.database.nodes.push(EntryNode {
             name_start,
             name_len,
@@ -182,6 +319,9 @@ impl WalkContext {
             child_end: u32::MAX,
             is_dir,
         });
+        if is_dir {
+            self.database.num_dirs += 1;
+        }
 
         if let Some(size) = &mut self.database.size {
             size.push(metadata.size);
@@ -198,6 +338,12 @@ impl WalkContext {
         if let Some(accessed) = &mut self.database.accessed {
             accessed.push(metadata.accessed);
         }
+        if let Some(owner) = &mut self.database.owner {
+            owner.push(metadata.owner);
+        }
+        if let Some(xattr) = &mut self.database.xattr {
+            xattr.values.push(metadata.has_xattr);
+        }
     }
 }
 
@@ -206,11 +352,13 @@ fn walk_file_system(
     options: &IndexOptions,
     parent_id: u32,
     dir_entries: Vec<DirEntry>,
+    ignore_rules: &Arc<IgnoreRules>,
 ) {
     let mut child_leaf_entries = Vec::new();
     let mut child_internal_entries = Vec::new();
+    let mut warnings = Vec::new();
     for dent in dir_entries {
-        match LeafOrInternalEntry::from_dir_entry(dent, options) {
+        match LeafOrInternalEntry::from_dir_entry(dent, options, ignore_rules, &mut warnings) {
             LeafOrInternalEntry::Leaf(entry) => {
                 child_leaf_entries.push(entry);
             }
@@ -222,6 +370,7 @@ fn walk_file_system(
 
     let (internal_start, internal_end) = {
         let mut ctx = ctx.lock();
+        ctx.warnings.append(&mut warnings);
 
         let child_start = ctx.database.nodes.len() as u32;
         let internal_end = child_start + child_internal_entries.len() as u32;
@@ -244,11 +393,34 @@ fn walk_file_system(
     (internal_start..internal_end)
         .into_par_iter()
         .zip(child_internal_entries.into_par_iter())
-        .for_each(|(id, entry)| walk_file_system(ctx, options, id, entry.child_dir_entries.into()));
+        .for_each(|(id, entry)| {
+            walk_file_system(
+                ctx,
+                options,
+                id,
+                entry.child_dir_entries.into(),
+                &entry.ignore_rules,
+            )
+        });
 }
 
-fn list_dir<P: AsRef<Path>>(path: P, options: &IndexOptions) -> Result<(Vec<DirEntry>, u64)> {
-    let rd = path.as_ref().read_dir()?;
+/// Lists entries in the directory, returning them with the number of all
+/// children and ignore rules applying to the children.
+///
+/// Children whose statuses cannot be read are skipped with warnings.
+fn list_dir<P: AsRef<Path>>(
+    path: P,
+    options: &IndexOptions,
+    ignore_rules: &Arc<IgnoreRules>,
+    warnings: &mut Vec<Warning>,
+) -> std::io::Result<(Vec<DirEntry>, u64, Arc<IgnoreRules>)> {
+    let path = path.as_ref();
+    let rd = path.read_dir()?;
+    let ignore_rules = if options.respect_ignore_files {
+        ignore_rules.child(path)
+    } else {
+        Arc::clone(ignore_rules)
+    };
 
     let mut dir_entries = Vec::new();
     let mut num_children = 0;
@@ -260,13 +432,29 @@ fn list_dir<P: AsRef<Path>>(path: P, options: &IndexOptions) -> Result<(Vec<DirE
             if options.ignore_hidden && util::is_hidden(&dent) {
                 continue;
             }
-            if let Ok(dir_entry) = DirEntry::from_std_dir_entry(dent, options) {
-                dir_entries.push(dir_entry);
+            if let Some(filter) = &options.filter_entry {
+                match dent.file_type() {
+                    Ok(file_type) if filter(&dent.path(), &file_type) => (),
+                    _ => continue,
+                }
+            }
+            if options.respect_ignore_files {
+                let is_dir = dent.file_type().map(|t| t.is_dir()).unwrap_or(false);
+                if ignore_rules.is_ignored(&dent.path(), is_dir) {
+                    continue;
+                }
+            }
+            let dent_path = dent.path();
+            match DirEntry::from_std_dir_entry(dent, options) {
+                Ok(dir_entry) => dir_entries.push(dir_entry),
+                Err(Error::Io(err)) => warnings.push(Warning::new(dent_path, err)),
+                // non-UTF-8 names are skipped unless lossy_paths is enabled
+                Err(_) => (),
             }
         }
     }
 
-    Ok((dir_entries, num_children))
+    Ok((dir_entries, num_children, ignore_rules))
 }
 
 /// Our version of DirEntry.
@@ -276,6 +464,8 @@ fn list_dir<P: AsRef<Path>>(path: P, options: &IndexOptions) -> Result<(Vec<DirE
 // discarding std::fs::DirEntry.
 struct DirEntry {
     name: Box<str>,
+    /// whether `name` was converted lossily from a non-UTF-8 name
+    is_lossy: bool,
     path: Box<Path>,
     is_dir: bool,
     metadata: Metadata,
@@ -284,15 +474,24 @@ struct DirEntry {
 impl DirEntry {
     fn from_std_dir_entry(dent: std::fs::DirEntry, options: &IndexOptions) -> Result<Self> {
         let is_dir = dent.file_type()?.is_dir();
+        let path = dent.path();
+        let metadata = if options.needs_metadata(is_dir) {
+            Metadata::from_std_metadata(&dent.metadata()?, options)?
+        } else {
+            Metadata::default()
+        };
+        let file_name = dent.file_name();
+        let (name, is_lossy) = match file_name.to_str() {
+            Some(name) => (name.into(), false),
+            None if options.lossy_paths => (file_name.to_string_lossy().into(), true),
+            None => return Err(Error::NonUtf8Path),
+        };
         Ok(Self {
-            name: dent.file_name().to_str().ok_or(Error::NonUtf8Path)?.into(),
-            path: dent.path().into(),
+            name,
+            is_lossy,
+            metadata: metadata.with_xattr(&path, options),
+            path: path.into(),
             is_dir,
-            metadata: if options.needs_metadata(is_dir) {
-                Metadata::from_std_metadata(&dent.metadata()?, options)?
-            } else {
-                Metadata::default()
-            },
         })
     }
 }
@@ -307,6 +506,8 @@ struct Metadata {
     created: SystemTime,
     modified: SystemTime,
     accessed: SystemTime,
+    owner: u32,
+    has_xattr: bool,
 }
 
 impl Default for Metadata {
@@ -317,6 +518,8 @@ impl Default for Metadata {
             created: SystemTime::UNIX_EPOCH,
             modified: SystemTime::UNIX_EPOCH,
             accessed: SystemTime::UNIX_EPOCH,
+            owner: 0,
+            has_xattr: false,
         }
     }
 }
@@ -349,8 +552,24 @@ impl Metadata {
             } else {
                 SystemTime::UNIX_EPOCH
             },
+            owner: if options.index_flags[StatusKind::Owner] && util::OWNER_SUPPORTED {
+                util::owner(metadata)?
+            } else {
+                0
+            },
+            has_xattr: false,
         })
     }
+
+    /// Records presence of the extended attribute specified in options.
+    fn with_xattr(self, path: &Path, options: &IndexOptions) -> Self {
+        let has_xattr = match &options.xattr {
+            // unreadable attributes are treated as absent
+            Some(name) if util::XATTR_SUPPORTED => util::has_xattr(path, name).unwrap_or(false),
+            _ => false,
+        };
+        Self { has_xattr, ..self }
+    }
 }
 
 /// An entry that has no children.
@@ -358,6 +577,7 @@ impl Metadata {
 /// This can be a file or a directory.
 struct LeafEntry {
     name: Box<str>,
+    is_lossy: bool,
     is_dir: bool,
     metadata: Metadata,
 }
@@ -367,8 +587,11 @@ struct LeafEntry {
 /// All internal entries are, by definition, directories.
 struct InternalEntry {
     name: Box<str>,
+    is_lossy: bool,
     metadata: Metadata,
     child_dir_entries: Box<[DirEntry]>,
+    /// rules to filter children of `child_dir_entries` with
+    ignore_rules: Arc<IgnoreRules>,
 }
 
 enum LeafOrInternalEntry {
@@ -377,16 +600,29 @@ enum LeafOrInternalEntry {
 }
 
 impl LeafOrInternalEntry {
-    fn from_dir_entry(dent: DirEntry, options: &IndexOptions) -> Self {
+    fn from_dir_entry(
+        dent: DirEntry,
+        options: &IndexOptions,
+        ignore_rules: &Arc<IgnoreRules>,
+        warnings: &mut Vec<Warning>,
+    ) -> Self {
         if !dent.is_dir {
             return Self::Leaf(LeafEntry {
                 name: dent.name,
+                is_lossy: dent.is_lossy,
                 is_dir: false,
                 metadata: dent.metadata,
             });
         }
 
-        let (dir_entries, num_children) = list_dir(&dent.path, options).unwrap_or_default();
+        let (dir_entries, num_children, ignore_rules) =
+            match list_dir(&dent.path, options, ignore_rules, warnings) {
+                Ok(x) => x,
+                Err(err) => {
+                    warnings.push(Warning::new(dent.path.clone().into(), err));
+                    (Vec::new(), 0, Arc::clone(ignore_rules))
+                }
+            };
         let metadata = Metadata {
             size: num_children,
             ..dent.metadata
@@ -394,34 +630,44 @@ impl LeafOrInternalEntry {
         if dir_entries.is_empty() {
             Self::Leaf(LeafEntry {
                 name: dent.name,
+                is_lossy: dent.is_lossy,
                 is_dir: true,
                 metadata,
             })
         } else {
             Self::Internal(InternalEntry {
                 name: dent.name,
+                is_lossy: dent.is_lossy,
                 metadata,
                 child_dir_entries: dir_entries.into(),
+                ignore_rules,
             })
         }
     }
 
-    fn from_path<P: AsRef<Utf8Path>>(path: P, options: &IndexOptions) -> Result<Self> {
+    fn from_path<P: AsRef<Utf8Path>>(
+        path: P,
+        options: &IndexOptions,
+        warnings: &mut Vec<Warning>,
+    ) -> Result<Self> {
         let path = path.as_ref();
         let metadata = path.symlink_metadata()?;
         let is_dir = metadata.is_dir();
 
         let dent = DirEntry {
             name: util::get_basename(path).into(),
+            is_lossy: false,
             path: path.into(),
             is_dir,
             metadata: options
                 .needs_metadata(is_dir)
                 .then(|| Metadata::from_std_metadata(&metadata, options))
                 .transpose()?
-                .unwrap_or_default(),
+                .unwrap_or_default()
+                .with_xattr(path.as_std_path(), options),
         };
 
-        Ok(Self::from_dir_entry(dent, options))
+        let ignore_rules = Arc::new(IgnoreRules::default());
+        Ok(Self::from_dir_entry(dent, options, &ignore_rules, warnings))
     }
 }
@@ -1,64 +1,413 @@
 mod filters;
 
-use super::{util, Database, EntryId};
+use super::{
+    fold::FoldedNames, timing::Stopwatch, util, Database, Entry, EntryId, SortKey, StatusKind,
+};
 use crate::{
-    query::{Query, SortOrder},
+    query::{Query, QueryBuilder, SortOrder},
     Error, Result,
 };
 use filters::{Filter, FilterContext};
 
 use rayon::prelude::*;
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc,
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc,
+    },
+    thread,
+    time::Instant,
 };
 
 impl Database {
-    pub fn search(&self, query: &Query) -> Result<Vec<EntryId>> {
+    /// Searches for entries matching the query.
+    ///
+    /// Setting `abort_signal` from another thread makes the search return
+    /// `Error::SearchAbort` as soon as possible.
+    ///
+    /// ```
+    /// use indexa::{database::DatabaseBuilder, query::QueryBuilder};
+    /// use std::sync::{atomic::AtomicBool, Arc};
+    ///
+    /// let dir = tempfile::tempdir()?;
+    /// std::fs::create_dir(dir.path().join("foo"))?;
+    ///
+    /// let database = DatabaseBuilder::new().add_dir(dir.path()).build()?;
+    /// let query = QueryBuilder::new("foo").build()?;
+    /// let abort_signal = Arc::new(AtomicBool::new(false));
+    ///
+    /// let hits = database.search(&query, &abort_signal)?;
+    /// assert_eq!(hits.len(), 1);
+    /// assert_eq!(database.entry(hits[0]).basename(), "foo");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn search(&self, query: &Query, abort_signal: &Arc<AtomicBool>) -> Result<Vec<EntryId>> {
+        self.search_until(query, abort_signal, None)
+            .map(|partial| partial.hits)
+    }
+
+    /// Same as `search`, but returns a parallel iterator over the matching
+    /// entries, so that they can be post-processed in parallel without
+    /// looking up each id.
+    ///
+    /// The iterator yields entries in the order of the query.
+    ///
+    /// ```
+    /// use indexa::{database::DatabaseBuilder, query::QueryBuilder};
+    /// use rayon::prelude::*;
+    /// use std::sync::{atomic::AtomicBool, Arc};
+    ///
+    /// let dir = tempfile::tempdir()?;
+    /// std::fs::write(dir.path().join("foo.txt"), "foo")?;
+    /// std::fs::write(dir.path().join("bar.txt"), "bar")?;
+    ///
+    /// let database = DatabaseBuilder::new().add_dir(dir.path()).build()?;
+    /// let query = QueryBuilder::new(".txt").build()?;
+    /// let abort_signal = Arc::new(AtomicBool::new(false));
+    ///
+    /// let total_len: usize = database
+    ///     .search_par(&query, &abort_signal)?
+    ///     .map(|entry| entry.basename().len())
+    ///     .sum();
+    /// assert_eq!(total_len, 14);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn search_par(
+        &self,
+        query: &Query,
+        abort_signal: &Arc<AtomicBool>,
+    ) -> Result<impl IndexedParallelIterator<Item = Entry<'_>> + '_> {
+        let hits = self.search(query, abort_signal)?;
+        Ok(hits.into_par_iter().map(move |id| self.entry(id)))
+    }
+
+    /// Searches for entries matching the query, giving up at `deadline`.
+    ///
+    /// If the deadline passes, the remaining entries are skipped and the hits
+    /// found so far are returned with `truncated` set, instead of failing.
+    /// Inverted queries are never truncated, since their hits are known only
+    /// after all entries are examined.
+    pub fn search_with_deadline(&self, query: &Query, deadline: Instant) -> Result<PartialHits> {
         let abort_signal = Arc::new(AtomicBool::new(false));
-        self.abortable_search(query, &abort_signal)
+        let deadline_signal = Arc::new(AtomicBool::new(false));
+
+        // the watchdog sets deadline_signal unless the search finishes in time
+        let (done_tx, done_rx) = mpsc::channel::<()>();
+        let watchdog = {
+            let deadline_signal = Arc::clone(&deadline_signal);
+            thread::spawn(move || {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                if let Err(RecvTimeoutError::Timeout) = done_rx.recv_timeout(timeout) {
+                    deadline_signal.store(true, Ordering::Relaxed);
+                }
+            })
+        };
+
+        let result = self.search_until(query, &abort_signal, Some(&deadline_signal));
+        drop(done_tx);
+        let _ = watchdog.join();
+
+        result
     }
 
-    pub fn abortable_search(
+    /// Returns precomputed ids in the order of the query, and whether they
+    /// already put directories before files.
+    ///
+    /// Fast-sorted ids are compared case-sensitively.
+    fn fast_sorted_ids(&self, query: &Query) -> Option<(&Vec<u32>, bool)> {
+        if query.case_insensitive_sort() {
+            return None;
+        }
+
+        if query.sort_dirs_before_files() && query.tiebreak() == StatusKind::Basename {
+            let key = SortKey::DirsFirst(query.sort_by());
+            if let Some(ids) = self.composite_sorted_ids.get(&key) {
+                return Some((ids, true));
+            }
+        }
+
+        let ids = if query.tiebreak() == StatusKind::Basename {
+            self.sorted_ids[query.sort_by()].as_ref()
+        } else {
+            let key = SortKey::Then(query.sort_by(), query.tiebreak());
+            self.composite_sorted_ids.get(&key)
+        };
+        ids.map(|ids| (ids, false))
+    }
+
+    fn search_until(
         &self,
         query: &Query,
         abort_signal: &Arc<AtomicBool>,
-    ) -> Result<Vec<EntryId>> {
+        deadline_signal: Option<&AtomicBool>,
+    ) -> Result<PartialHits> {
         if query.is_empty() {
-            return self.filter_and_sort::<filters::PassthroughFilter>(query, abort_signal);
+            if let Some(hits) = self.first_sorted_ids(query) {
+                return Ok(PartialHits {
+                    hits,
+                    truncated: false,
+                });
+            }
+            return self.filter_and_sort::<filters::PassthroughFilter>(
+                query,
+                abort_signal,
+                deadline_signal,
+            );
+        }
+        if query.match_extension() {
+            return self.filter_and_sort::<filters::ExtensionFilter>(
+                query,
+                abort_signal,
+                deadline_signal,
+            );
         }
         if !query.match_path() {
-            return self.filter_and_sort::<filters::BasenameFilter>(query, abort_signal);
+            return self.filter_and_sort::<filters::BasenameFilter>(
+                query,
+                abort_signal,
+                deadline_signal,
+            );
         }
         if !query.is_literal() {
-            return self.filter_and_sort::<filters::RegexPathFilter>(query, abort_signal);
+            return self.filter_and_sort::<filters::RegexPathFilter>(
+                query,
+                abort_signal,
+                deadline_signal,
+            );
         }
         if !query.has_path_separator() {
-            return self.filter_and_sort::<filters::ComponentWisePathFilter>(query, abort_signal);
+            return self.filter_and_sort::<filters::ComponentWisePathFilter>(
+                query,
+                abort_signal,
+                deadline_signal,
+            );
         }
-        self.filter_and_sort::<filters::FullPathFilter>(query, abort_signal)
+        self.filter_and_sort::<filters::FullPathFilter>(query, abort_signal, deadline_signal)
     }
 
-    fn filter_and_sort<F: Filter>(
+    /// Returns the first `query.limit()` fast-sorted ids if they are exactly
+    /// the hits of the empty query, skipping the filtering of all entries.
+    fn first_sorted_ids(&self, query: &Query) -> Option<Vec<EntryId>> {
+        let limit = query.limit()?;
+        if query.requires_xattr()
+            || query.ignores_hidden()
+            || query.limits_basename_len()
+            || query.limits_statuses()
+        {
+            return None;
+        }
+        let (ids, dirs_sorted) = self.fast_sorted_ids(query)?;
+        if query.sort_dirs_before_files() && !dirs_sorted {
+            return None;
+        }
+
+        let ids = ids.iter().copied().map(EntryId);
+        let hits = match query.sort_order() {
+            SortOrder::Ascending => ids.take(limit).collect(),
+            SortOrder::Descending => ids.rev().take(limit).collect(),
+        };
+        Some(hits)
+    }
+
+    /// Same as `search`, but cannot be aborted.
+    pub fn search_all(&self, query: &Query) -> Result<Vec<EntryId>> {
+        let abort_signal = Arc::new(AtomicBool::new(false));
+        self.search(query, &abort_signal)
+    }
+
+    /// Searches for entries matching `pattern` with the default query options.
+    ///
+    /// ```
+    /// use indexa::database::{Database, DatabaseBuilder};
+    ///
+    /// let dir = tempfile::tempdir()?;
+    /// std::fs::write(dir.path().join("foo.txt"), "")?;
+    ///
+    /// let location = dir.path().join("database");
+    /// DatabaseBuilder::new().add_dir(dir.path()).build()?.save(&location)?;
+    ///
+    /// let database = Database::open(&location)?;
+    /// let entries = database.query("foo")?;
+    /// assert_eq!(entries.len(), 1);
+    /// assert_eq!(entries[0].basename(), "foo.txt");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn query(&self, pattern: &str) -> Result<Vec<Entry<'_>>> {
+        let query = QueryBuilder::new(pattern).build()?;
+        let hits = self.search_all(&query)?;
+        Ok(hits.into_iter().map(|id| self.entry(id)).collect())
+    }
+
+    /// Filters `ids` with the query, keeping their order.
+    ///
+    /// This is much cheaper than `search` for narrowing down a small set of
+    /// entries, such as the results of a previous search.
+    /// Sorting options, the limit, and the preferred directory of the query
+    /// are ignored.
+    pub fn filter_ids(
         &self,
+        ids: &[EntryId],
         query: &Query,
         abort_signal: &Arc<AtomicBool>,
     ) -> Result<Vec<EntryId>> {
-        let ctx = FilterContext::new(self, abort_signal, query.regex());
+        let xattr = self.xattr_filter(query)?;
+        if query.is_empty()
+            && xattr.is_none()
+            && !query.ignores_hidden()
+            && !query.limits_basename_len()
+            && !query.limits_statuses()
+        {
+            return Ok(ids.to_vec());
+        }
 
-        let mut hits = if let Some(ids) = self.sorted_ids[query.sort_by()].as_ref() {
-            match query.sort_order() {
-                SortOrder::Ascending => F::ordered(&ctx, ids.into_par_iter().copied())?,
-                SortOrder::Descending => F::ordered(&ctx, ids.into_par_iter().rev().copied())?,
+        let hits = ids
+            .par_iter()
+            .map(|id| {
+                if abort_signal.load(Ordering::Relaxed) {
+                    return Err(Error::SearchAbort);
+                }
+                let is_hit =
+                    self.satisfies_filters(query, xattr, id.0) && query.is_match(&self.entry(*id));
+                Ok(is_hit.then_some(*id))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(hits.into_iter().flatten().collect())
+    }
+
+    /// Returns true if the entry satisfies the conditions of the query other
+    /// than the pattern.
+    fn satisfies_filters(&self, query: &Query, xattr: Option<&[bool]>, id: u32) -> bool {
+        let has_xattr = match xattr {
+            Some(xattr) => xattr[id as usize],
+            None => true,
+        };
+        let is_visible = !query.ignores_hidden() || !self.is_in_hidden_tree(id);
+        let entry = self.entry(EntryId(id));
+        has_xattr
+            && is_visible
+            && query.accepts_basename_len(entry.basename().len())
+            && query.accepts_statuses(&entry)
+    }
+
+    /// Returns the indexed directory at `Query::preferred_dir`, if any.
+    pub fn preferred_dir(&self, query: &Query) -> Option<EntryId> {
+        let id = self.get_by_path(query.preferred_dir()?)?;
+        self.nodes[id.0 as usize].is_dir.then_some(id)
+    }
+
+    /// Returns presence of the extended attribute if the query requires it.
+    fn xattr_filter(&self, query: &Query) -> Result<Option<&[bool]>> {
+        if !query.requires_xattr() {
+            return Ok(None);
+        }
+        match &self.xattr {
+            Some(xattr) => Ok(Some(&xattr.values)),
+            None => Err(Error::InvalidOption(
+                "No extended attribute is indexed.".to_string(),
+            )),
+        }
+    }
+
+    /// Returns, for each chunk of entries, whether it can contain basenames
+    /// matching the query, if the basename bloom filters can tell.
+    fn candidate_chunks(&self, query: &Query) -> Option<Vec<bool>> {
+        let bloom = self.basename_bloom.as_ref()?;
+        if query.match_path() || query.trigrams().is_empty() {
+            return None;
+        }
+        Some(bloom.candidate_chunks(query.trigrams()))
+    }
+
+    /// Returns case-folded basenames and the case-folded pattern, if the query
+    /// can be matched against them.
+    fn folded_names_for<'q>(&self, query: &'q Query) -> Option<(&FoldedNames, &'q str)> {
+        let folded_names = self.folded_names.as_ref()?;
+        if query.match_path() || query.match_extension() {
+            return None;
+        }
+        Some((folded_names, query.folded_literal()?))
+    }
+
+    fn filter_and_sort<F: Filter>(
+        &self,
+        query: &Query,
+        abort_signal: &Arc<AtomicBool>,
+        deadline_signal: Option<&AtomicBool>,
+    ) -> Result<PartialHits> {
+        // signals are checked only once in a while during filtering
+        if abort_signal.load(Ordering::Relaxed) {
+            return Err(Error::SearchAbort);
+        }
+
+        let mut stopwatch = Stopwatch::start("search");
+        let xattr = self.xattr_filter(query)?;
+        let ctx = FilterContext::new(self, abort_signal, query.regex())
+            .with_candidate_chunks(self.candidate_chunks(query))
+            .with_folded_names(self.folded_names_for(query))
+            .with_deadline_signal(deadline_signal.filter(|_| !query.is_inverted()));
+
+        // With an inverted query, entries are matched normally first
+        // and the complement of them is taken.
+        let excluded = if query.is_inverted() && !query.is_empty() {
+            let mut excluded = vec![false; self.nodes.len()];
+            for id in F::unordered(&ctx)? {
+                excluded[id as usize] = true;
+            }
+            if abort_signal.load(Ordering::Relaxed) {
+                return Err(Error::SearchAbort);
             }
+            Some(excluded)
         } else {
-            let mut hits = F::unordered(&ctx)?;
+            None
+        };
+
+        let (sorted_ids, dirs_sorted) = match self.fast_sorted_ids(query) {
+            Some((ids, dirs_sorted)) => (Some(ids), dirs_sorted),
+            None => (None, false),
+        };
+
+        let mut hits = if let Some(ids) = sorted_ids {
+            let hits = match (query.sort_order(), &excluded) {
+                (SortOrder::Ascending, None) => F::ordered(&ctx, ids.into_par_iter().copied())?,
+                (SortOrder::Descending, None) => {
+                    F::ordered(&ctx, ids.into_par_iter().rev().copied())?
+                }
+                (SortOrder::Ascending, Some(excluded)) => ids
+                    .into_par_iter()
+                    .copied()
+                    .filter(|id| !excluded[*id as usize])
+                    .collect(),
+                (SortOrder::Descending, Some(excluded)) => ids
+                    .into_par_iter()
+                    .rev()
+                    .copied()
+                    .filter(|id| !excluded[*id as usize])
+                    .collect(),
+            };
+            // ids are filtered in an already sorted order
+            stopwatch.lap("filter");
+            hits
+        } else {
+            let mut hits = match &excluded {
+                Some(excluded) => (0..self.nodes.len() as u32)
+                    .into_par_iter()
+                    .filter(|id| !excluded[*id as usize])
+                    .collect(),
+                None => F::unordered(&ctx)?,
+            };
 
             if abort_signal.load(Ordering::Relaxed) {
                 return Err(Error::SearchAbort);
             }
+            stopwatch.lap("filter");
 
-            let compare_func = util::get_compare_func(query.sort_by());
+            let compare_func = util::get_compare_func(
+                query.sort_by(),
+                query.tiebreak(),
+                query.case_insensitive_sort(),
+            );
             let slice = hits.as_parallel_slice_mut();
             match query.sort_order() {
                 SortOrder::Ascending => slice.par_sort_unstable_by(|a, b| {
@@ -68,15 +417,42 @@ impl Database {
                     compare_func(&self.entry(EntryId(*b)), &self.entry(EntryId(*a)))
                 }),
             };
+            stopwatch.lap("sort");
 
             hits
         };
 
-        if query.sort_dirs_before_files() {
+        if let Some(xattr) = xattr {
+            hits.retain(|id| xattr[*id as usize]);
+        }
+        if query.limits_basename_len() {
+            hits = hits
+                .into_par_iter()
+                .filter(|id| {
+                    let basename_len = self.entry(EntryId(*id)).basename().len();
+                    query.accepts_basename_len(basename_len)
+                })
+                .collect();
+        }
+        if query.limits_statuses() {
+            hits = hits
+                .into_par_iter()
+                .filter(|id| query.accepts_statuses(&self.entry(EntryId(*id))))
+                .collect();
+        }
+        if query.ignores_hidden() {
+            hits = hits
+                .into_par_iter()
+                .filter(|id| !self.is_in_hidden_tree(*id))
+                .collect();
+        }
+
+        if query.sort_dirs_before_files() && !dirs_sorted {
             if abort_signal.load(Ordering::Relaxed) {
                 return Err(Error::SearchAbort);
             }
 
+            // par_sort_by is stable, so the order within directories and files is kept
             let slice = hits.as_parallel_slice_mut();
             match query.sort_order() {
                 SortOrder::Ascending => slice.par_sort_by(|a, b| {
@@ -94,6 +470,1226 @@ impl Database {
             }
         }
 
-        Ok(hits.into_iter().map(EntryId).collect())
+        if let Some(dir) = self.preferred_dir(query) {
+            match hits.iter().position(|id| *id == dir.0) {
+                Some(i) => hits[..=i].rotate_right(1),
+                None if !query.is_inverted() && self.satisfies_filters(query, xattr, dir.0) => {
+                    hits.insert(0, dir.0)
+                }
+                None => (),
+            }
+        }
+
+        if let Some(limit) = query.limit() {
+            hits.truncate(limit);
+        }
+        stopwatch.lap("post-process");
+        stopwatch.finish();
+
+        Ok(PartialHits {
+            hits: hits.into_iter().map(EntryId).collect(),
+            truncated: ctx.is_truncated(),
+        })
+    }
+}
+
+/// Hits of a search which may have been cut short by a deadline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartialHits {
+    pub hits: Vec<EntryId>,
+    /// true if some entries were skipped because the deadline passed
+    pub truncated: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::*, query::*, Error};
+    use itertools::Itertools;
+    use std::{
+        fs,
+        io::Write,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::{Duration, Instant, SystemTime},
+    };
+
+    #[test]
+    fn filter_ids() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = tmpdir.path();
+        for name in ["foo", "foobar", "bar", "baz", "foobaz"] {
+            fs::write(path.join(name), "").unwrap();
+        }
+
+        let database = DatabaseBuilder::new().add_dir(path).build().unwrap();
+        let abort_signal = Arc::new(AtomicBool::new(false));
+        let basenames = |ids: &[EntryId]| {
+            ids.iter()
+                .map(|id| database.entry(*id).basename().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let query = QueryBuilder::new("^foo").regex(true).build().unwrap();
+        let hits = database.search_all(&query).unwrap();
+        assert_eq!(basenames(&hits), ["foo", "foobar", "foobaz"]);
+
+        let refined_query = QueryBuilder::new("ba").build().unwrap();
+        let refined = database
+            .filter_ids(&hits, &refined_query, &abort_signal)
+            .unwrap();
+        assert_eq!(basenames(&refined), ["foobar", "foobaz"]);
+
+        // order of the given ids is kept
+        let reversed = hits.iter().rev().copied().collect::<Vec<_>>();
+        let refined = database
+            .filter_ids(&reversed, &refined_query, &abort_signal)
+            .unwrap();
+        assert_eq!(basenames(&refined), ["foobaz", "foobar"]);
+
+        let empty_query = QueryBuilder::new("").build().unwrap();
+        assert_eq!(
+            database
+                .filter_ids(&hits, &empty_query, &abort_signal)
+                .unwrap(),
+            hits
+        );
+        assert!(database
+            .filter_ids(&[], &refined_query, &abort_signal)
+            .unwrap()
+            .is_empty());
+
+        abort_signal.store(true, Ordering::Relaxed);
+        assert!(matches!(
+            database.filter_ids(&hits, &refined_query, &abort_signal),
+            Err(Error::SearchAbort)
+        ));
+    }
+
+    #[test]
+    fn basename_bloom() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = tmpdir.path();
+        for dir in ["alpha", "beta", "gamma", "delta"] {
+            fs::create_dir(path.join(dir)).unwrap();
+            for i in 0..1000 {
+                fs::write(path.join(dir).join(format!("{}_{:04}.txt", dir, i)), "").unwrap();
+            }
+        }
+        for name in ["Kelvin", "\u{212a}elvin", "STRASSE", "stra\u{df}e"] {
+            fs::write(path.join("delta").join(name), "").unwrap();
+        }
+
+        let database = DatabaseBuilder::new().add_dir(path).build().unwrap();
+        let bloom_database = DatabaseBuilder::new()
+            .add_dir(path)
+            .basename_bloom(true)
+            .build()
+            .unwrap();
+
+        let query = QueryBuilder::new("alpha_").build().unwrap();
+        let candidates = bloom_database.candidate_chunks(&query).unwrap();
+        assert!(candidates.iter().any(|candidate| *candidate));
+        assert!(candidates.iter().any(|candidate| !candidate));
+
+        for pattern in [
+            "alpha",
+            "ALPHA",
+            "ta_09",
+            "a_0999",
+            "elvin",
+            "kelvin",
+            "KELVIN",
+            "strasse",
+            "stra\u{df}e",
+            "txt",
+            "zzz",
+            "ta",
+            "",
+        ] {
+            for case_sensitivity in [CaseSensitivity::Smart, CaseSensitivity::Insensitive] {
+                for sort_by in [StatusKind::Basename, StatusKind::Path] {
+                    for invert in [false, true] {
+                        let query = QueryBuilder::new(pattern)
+                            .case_sensitivity(case_sensitivity)
+                            .sort_by(sort_by)
+                            .invert(invert)
+                            .build()
+                            .unwrap();
+                        assert_eq!(
+                            bloom_database.search_all(&query).unwrap(),
+                            database.search_all(&query).unwrap()
+                        );
+                    }
+                }
+            }
+        }
+
+        let query = QueryBuilder::new("kelvin")
+            .case_sensitivity(CaseSensitivity::Insensitive)
+            .build()
+            .unwrap();
+        assert_eq!(bloom_database.search_all(&query).unwrap().len(), 2);
+
+        let mut appended = DatabaseBuilder::new()
+            .add_dir(path.join("alpha"))
+            .basename_bloom(true)
+            .build()
+            .unwrap();
+        appended
+            .append(
+                DatabaseBuilder::new()
+                    .add_dir(path.join("delta"))
+                    .basename_bloom(true)
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap();
+        assert!(appended.basename_bloom.is_some());
+        let query = QueryBuilder::new("delta_").build().unwrap();
+        assert_eq!(appended.search_all(&query).unwrap().len(), 1000);
+    }
+
+    #[test]
+    fn case_fold_index() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = tmpdir.path();
+        let names = [
+            "Kelvin",
+            "\u{212a}elvin",
+            "STRASSE",
+            "stra\u{df}e",
+            "STRA\u{1e9e}E",
+            "\u{17f}ilent",
+            "\u{3a3}\u{3a3}\u{3c3}\u{3c2}",
+            "\u{130}stanbul",
+            "\u{131}zmir",
+            "\u{c5}ngstr\u{f6}m",
+            "\u{212b}ngstr\u{d6}m",
+            "Foo.TXT",
+            "foo.txt",
+            "\u{1f600}.png",
+        ];
+        fs::create_dir(path.join("dir")).unwrap();
+        for name in names {
+            fs::write(path.join("dir").join(name), "").unwrap();
+        }
+
+        let database = DatabaseBuilder::new().add_dir(path).build().unwrap();
+        let folded_database = DatabaseBuilder::new()
+            .add_dir(path)
+            .case_fold_index(true)
+            .build()
+            .unwrap();
+        let location = path.join("database");
+        folded_database.save(&location).unwrap();
+        let loaded_database = Database::open(&location).unwrap();
+        assert!(loaded_database.folded_names.is_some());
+
+        let query = QueryBuilder::new("kelvin").build().unwrap();
+        assert!(folded_database.folded_names_for(&query).is_some());
+        let query = QueryBuilder::new("Kelvin").build().unwrap();
+        assert!(folded_database.folded_names_for(&query).is_none());
+
+        for pattern in [
+            "kelvin",
+            "\u{212a}ELVIN",
+            "strasse",
+            "stra\u{df}e",
+            "\u{1e9e}",
+            "silent",
+            "\u{3c3}\u{3c3}",
+            "istanbul",
+            "i\u{307}stanbul",
+            "izmir",
+            "\u{e5}ngstr\u{f6}m",
+            "foo.txt",
+            ".png",
+            "dir",
+            "zzz",
+        ] {
+            for case_sensitivity in [CaseSensitivity::Smart, CaseSensitivity::Insensitive] {
+                for invert in [false, true] {
+                    let query = QueryBuilder::new(pattern)
+                        .case_sensitivity(case_sensitivity)
+                        .invert(invert)
+                        .build()
+                        .unwrap();
+                    let expected = database.search_all(&query).unwrap();
+                    assert_eq!(folded_database.search_all(&query).unwrap(), expected);
+                    assert_eq!(loaded_database.search_all(&query).unwrap(), expected);
+                }
+            }
+        }
+
+        let query = QueryBuilder::new("kelvin").build().unwrap();
+        assert_eq!(folded_database.search_all(&query).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn abort_during_filtering() {
+        use super::filters::{self, Filter, FilterContext};
+        use regex::Regex;
+
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = tmpdir.path().join("root");
+        for i in 0..20 {
+            let dir = path.join(format!("dir{}", i));
+            fs::create_dir_all(&dir).unwrap();
+            for j in 0..500 {
+                fs::write(dir.join(format!("file{}", j)), "").unwrap();
+            }
+        }
+        let database = DatabaseBuilder::new().add_dir(&path).build().unwrap();
+
+        let abort_signal = Arc::new(AtomicBool::new(true));
+        let regex = Regex::new("file").unwrap();
+        for interval in [1, 64] {
+            let ctx = FilterContext::new(&database, &abort_signal, &regex)
+                .with_stop_check_interval(interval);
+            for result in [
+                filters::BasenameFilter::unordered(&ctx),
+                filters::RegexPathFilter::unordered(&ctx),
+                filters::ComponentWisePathFilter::unordered(&ctx),
+                filters::FullPathFilter::unordered(&ctx),
+            ] {
+                assert!(matches!(result, Err(Error::SearchAbort)));
+            }
+        }
+
+        // searches aborted before they start fail regardless of the interval
+        let query = QueryBuilder::new("file1").build().unwrap();
+        assert!(matches!(
+            database.search(&query, &abort_signal),
+            Err(Error::SearchAbort)
+        ));
+    }
+
+    #[test]
+    fn deadline() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = tmpdir.path();
+        for dir in ["foo", "bar"] {
+            fs::create_dir(path.join(dir)).unwrap();
+            for name in ["foo", "foobar", "baz"] {
+                fs::write(path.join(dir).join(name), "").unwrap();
+            }
+        }
+
+        let database = DatabaseBuilder::new().add_dir(path).build().unwrap();
+        let queries = [
+            QueryBuilder::new("foo").build().unwrap(),
+            QueryBuilder::new("fo+").regex(true).build().unwrap(),
+            QueryBuilder::new("foo")
+                .match_path_mode(MatchPathMode::Always)
+                .build()
+                .unwrap(),
+            QueryBuilder::new("foo/foo")
+                .match_path_mode(MatchPathMode::Always)
+                .build()
+                .unwrap(),
+        ];
+
+        for query in &queries {
+            let hits = database.search_all(query).unwrap();
+            assert!(!hits.is_empty());
+
+            let partial = database
+                .search_with_deadline(query, Instant::now() + Duration::from_secs(60))
+                .unwrap();
+            assert!(!partial.truncated);
+            assert_eq!(partial.hits, hits);
+
+            let partial = database
+                .search_with_deadline(query, Instant::now())
+                .unwrap();
+            assert!(partial.hits.iter().all(|id| hits.contains(id)));
+
+            // deadline has passed before filtering starts
+            let abort_signal = Arc::new(AtomicBool::new(false));
+            let deadline_signal = AtomicBool::new(true);
+            let partial = database
+                .search_until(query, &abort_signal, Some(&deadline_signal))
+                .unwrap();
+            assert!(partial.truncated);
+            assert!(partial.hits.is_empty());
+
+            // aborting still fails the search
+            abort_signal.store(true, Ordering::Relaxed);
+            assert!(matches!(
+                database.search_until(query, &abort_signal, Some(&deadline_signal)),
+                Err(Error::SearchAbort)
+            ));
+        }
+
+        // inverted queries are not truncated
+        let query = QueryBuilder::new("foo").invert(true).build().unwrap();
+        let abort_signal = Arc::new(AtomicBool::new(false));
+        let deadline_signal = AtomicBool::new(true);
+        let partial = database
+            .search_until(&query, &abort_signal, Some(&deadline_signal))
+            .unwrap();
+        assert!(!partial.truncated);
+        assert_eq!(partial.hits, database.search_all(&query).unwrap());
+    }
+
+    #[test]
+    fn ignore_hidden() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = tmpdir.path();
+        fs::create_dir_all(path.join(".git").join("objects")).unwrap();
+        fs::create_dir(path.join("src")).unwrap();
+        for file in [
+            ".gitignore",
+            ".git/config",
+            "src/.hidden",
+            "src/main.rs",
+            "README",
+        ] {
+            fs::write(path.join(file), "").unwrap();
+        }
+
+        let database = DatabaseBuilder::new().add_dir(path).build().unwrap();
+        let abort_signal = Arc::new(AtomicBool::new(false));
+        let basenames = |ids: &[EntryId]| {
+            let mut basenames = ids
+                .iter()
+                .map(|id| database.entry(*id).basename().to_string())
+                .collect::<Vec<_>>();
+            basenames.sort_unstable();
+            basenames
+        };
+
+        let query = QueryBuilder::new("^[^.]").regex(true).build().unwrap();
+        let hits = database.search_all(&query).unwrap();
+        assert_eq!(
+            basenames(&hits),
+            ["README", "config", "main.rs", "objects", "src"]
+        );
+
+        let hidden_query = QueryBuilder::new("^[^.]")
+            .regex(true)
+            .ignore_hidden(true)
+            .build()
+            .unwrap();
+        let visible = database.search_all(&hidden_query).unwrap();
+        assert_eq!(basenames(&visible), ["README", "main.rs", "src"]);
+        assert_eq!(
+            database
+                .filter_ids(&hits, &hidden_query, &abort_signal)
+                .unwrap(),
+            visible
+        );
+
+        // the root directory is kept even though its name starts with "."
+        let empty_query = QueryBuilder::new("").ignore_hidden(true).build().unwrap();
+        assert_eq!(database.search_all(&empty_query).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn matching_root_paths() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = tmpdir.path();
+        let mut builder = DatabaseBuilder::new();
+        for i in 0..20 {
+            let root = path.join(format!("root_{}_", i));
+            fs::create_dir_all(root.join("child")).unwrap();
+            builder.add_dir(root);
+        }
+        let database = builder.build().unwrap();
+
+        // root_3_ itself matches, so do all of its descendants but nothing else
+        for pattern in ["root_3_", "/root_3_"] {
+            let query = QueryBuilder::new(pattern)
+                .match_path_mode(MatchPathMode::Always)
+                .sort_by(StatusKind::Path)
+                .build()
+                .unwrap();
+            let paths = database
+                .search_all(&query)
+                .unwrap()
+                .into_iter()
+                .map(|id| database.entry(id).path())
+                .collect::<Vec<_>>();
+            let root = dunce::canonicalize(path.join("root_3_")).unwrap();
+            assert_eq!(paths, [root.clone(), root.join("child")]);
+        }
+    }
+
+    #[test]
+    fn tiebreak() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = tmpdir.path();
+        let now = SystemTime::now();
+        for (name, content, age) in [
+            ("a", "xx", 1),
+            ("b", "x", 3),
+            ("c", "xx", 2),
+            ("d", "x", 1),
+            ("e", "xx", 3),
+        ] {
+            let mut file = fs::File::create(path.join(name)).unwrap();
+            file.write_all(content.as_bytes()).unwrap();
+            file.set_modified(now - Duration::from_secs(age * 60))
+                .unwrap();
+        }
+
+        for fast_sort in [false, true] {
+            let mut builder = DatabaseBuilder::new();
+            builder
+                .add_dir(path)
+                .index(StatusKind::Size)
+                .index(StatusKind::Modified);
+            if fast_sort {
+                builder.fast_sort(StatusKind::Size);
+            }
+            let database = builder.build().unwrap();
+
+            for (tiebreak, order, expected) in [
+                (
+                    StatusKind::Basename,
+                    SortOrder::Ascending,
+                    ["b", "d", "a", "c", "e"],
+                ),
+                (
+                    StatusKind::Modified,
+                    SortOrder::Ascending,
+                    ["b", "d", "e", "c", "a"],
+                ),
+                (
+                    StatusKind::Modified,
+                    SortOrder::Descending,
+                    ["a", "c", "e", "d", "b"],
+                ),
+            ] {
+                let query = QueryBuilder::new("^[a-e]$")
+                    .regex(true)
+                    .sort_by(StatusKind::Size)
+                    .tiebreak(tiebreak)
+                    .sort_order(order)
+                    .build()
+                    .unwrap();
+                let basenames = database
+                    .search_all(&query)
+                    .unwrap()
+                    .into_iter()
+                    .map(|id| database.entry(id).basename().to_string())
+                    .collect::<Vec<_>>();
+                assert_eq!(basenames, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn sort_dirs_before_files() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = tmpdir.path();
+        for name in ["b", "d", "f"] {
+            fs::create_dir(path.join(name)).unwrap();
+        }
+        for name in ["a", "c", "e", "g"] {
+            fs::write(path.join(name), "").unwrap();
+        }
+
+        let database = DatabaseBuilder::new().add_dir(path).build().unwrap();
+
+        for (order, expected) in [
+            (SortOrder::Ascending, ["b", "d", "f", "a", "c", "e", "g"]),
+            (SortOrder::Descending, ["g", "e", "c", "a", "f", "d", "b"]),
+        ] {
+            let query = QueryBuilder::new("")
+                .sort_by(StatusKind::Basename)
+                .sort_order(order)
+                .sort_dirs_before_files(true)
+                .build()
+                .unwrap();
+            let basenames = database
+                .search_all(&query)
+                .unwrap()
+                .into_iter()
+                .map(|id| database.entry(id).basename().to_string())
+                .filter(|basename| basename.len() == 1)
+                .collect::<Vec<_>>();
+            assert_eq!(basenames, expected);
+        }
+    }
+
+    #[test]
+    fn fast_sort_by() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = tmpdir.path().join("root");
+        fs::create_dir(&path).unwrap();
+        let now = SystemTime::now();
+        for (name, content, age) in [
+            ("a", "xx", 1),
+            ("b", "x", 3),
+            ("c", "xx", 2),
+            ("d", "x", 1),
+            ("e", "xx", 3),
+        ] {
+            let mut file = fs::File::create(path.join(name)).unwrap();
+            file.write_all(content.as_bytes()).unwrap();
+            file.set_modified(now - Duration::from_secs(age * 60))
+                .unwrap();
+        }
+        for name in ["f", "g"] {
+            fs::create_dir(path.join(name)).unwrap();
+        }
+
+        let mut builder = DatabaseBuilder::new();
+        builder
+            .add_dir(&path)
+            .index(StatusKind::Size)
+            .index(StatusKind::Modified);
+        let on_the_fly = builder.build().unwrap();
+        let keys = [
+            SortKey::Then(StatusKind::Size, StatusKind::Modified),
+            SortKey::DirsFirst(StatusKind::Basename),
+            SortKey::DirsFirst(StatusKind::Modified),
+        ];
+        for key in keys {
+            builder.fast_sort_by(key);
+        }
+        let precomputed = builder.build().unwrap();
+        assert_eq!(precomputed.fast_sort_keys().collect::<Vec<_>>(), keys);
+        assert!(!on_the_fly.is_fast_sortable_by(keys[0]));
+
+        for (sort_by, tiebreak, dirs_first) in [
+            (StatusKind::Size, StatusKind::Modified, false),
+            (StatusKind::Modified, StatusKind::Basename, true),
+            (StatusKind::Basename, StatusKind::Basename, true),
+        ] {
+            for order in [SortOrder::Ascending, SortOrder::Descending] {
+                let query = QueryBuilder::new("")
+                    .sort_by(sort_by)
+                    .tiebreak(tiebreak)
+                    .sort_order(order)
+                    .sort_dirs_before_files(dirs_first)
+                    .build()
+                    .unwrap();
+                assert!(precomputed.fast_sorted_ids(&query).is_some());
+
+                let search = |database: &Database| {
+                    database
+                        .search_all(&query)
+                        .unwrap()
+                        .into_iter()
+                        .map(|id| database.entry(id).path())
+                        .collect::<Vec<_>>()
+                };
+                assert_eq!(search(&precomputed), search(&on_the_fly));
+            }
+        }
+
+        // composite orders are kept only if all of the merged databases have them
+        let merged = Database::merge(vec![precomputed, on_the_fly]).unwrap();
+        assert_eq!(merged.fast_sort_keys().count(), 0);
+    }
+
+    #[test]
+    fn prefer_dirs_matching_prefix() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = dunce::canonicalize(tmpdir.path()).unwrap().join("root");
+        fs::create_dir_all(path.join("foo").join("bar")).unwrap();
+        fs::create_dir_all(path.join("zoo").join("foo")).unwrap();
+        fs::write(path.join("foo.txt"), "").unwrap();
+        fs::write(path.join("foo").join("baz"), "").unwrap();
+        let database = DatabaseBuilder::new().add_dir(&path).build().unwrap();
+
+        let search = |pattern: &str, sort_order, limit| {
+            let mut builder = QueryBuilder::new(pattern);
+            builder
+                .match_path_mode(MatchPathMode::Auto)
+                .sort_order(sort_order)
+                .prefer_dirs_matching_prefix(true);
+            if let Some(limit) = limit {
+                builder.limit(limit);
+            }
+            database
+                .search_all(&builder.build().unwrap())
+                .unwrap()
+                .into_iter()
+                .map(|id| database.entry(id).path())
+                .collect::<Vec<_>>()
+        };
+
+        let dir = path.join("foo");
+        let pattern = dir.to_str().unwrap();
+        assert_eq!(
+            search(pattern, SortOrder::Ascending, None),
+            vec![
+                dir.clone(),
+                dir.join("bar"),
+                dir.join("baz"),
+                path.join("foo.txt")
+            ]
+        );
+        assert_eq!(
+            search(pattern, SortOrder::Descending, None),
+            vec![
+                dir.clone(),
+                path.join("foo.txt"),
+                dir.join("baz"),
+                dir.join("bar")
+            ]
+        );
+        assert_eq!(
+            search(pattern, SortOrder::Descending, Some(1)),
+            vec![dir.clone()]
+        );
+
+        // trailing separators, `.`, and `..` are resolved
+        let pattern = format!("{}{}", pattern, std::path::MAIN_SEPARATOR);
+        assert_eq!(
+            search(&pattern, SortOrder::Ascending, None),
+            vec![dir.clone(), dir.join("bar"), dir.join("baz")]
+        );
+        let pattern = path.join("zoo").join("..").join(".").join("foo");
+        assert_eq!(
+            database.preferred_dir(
+                &QueryBuilder::new(pattern.to_str().unwrap())
+                    .prefer_dirs_matching_prefix(true)
+                    .build()
+                    .unwrap()
+            ),
+            database.get_by_path(&dir)
+        );
+
+        // files are not preferred
+        let file = path.join("foo.txt");
+        assert_eq!(
+            search(file.to_str().unwrap(), SortOrder::Descending, None),
+            vec![file]
+        );
+
+        // the directory is a hit even if the pattern does not match it,
+        // unless the query excludes it otherwise
+        let bar_path = dir.join("bar");
+        let mut builder = QueryBuilder::new(bar_path.to_str().unwrap());
+        builder.prefer_dirs_matching_prefix(true);
+        let bar = database.get_by_path(dir.join("bar")).unwrap();
+        assert_eq!(
+            database.search_all(&builder.build().unwrap()).unwrap(),
+            [bar]
+        );
+        builder.max_basename_len(2);
+        assert!(database
+            .search_all(&builder.build().unwrap())
+            .unwrap()
+            .is_empty());
+        let query = QueryBuilder::new(dir.to_str().unwrap())
+            .match_path_mode(MatchPathMode::Auto)
+            .invert(true)
+            .prefer_dirs_matching_prefix(true)
+            .build()
+            .unwrap();
+        let hits = database.search_all(&query).unwrap();
+        assert!(!hits.contains(&database.get_by_path(&dir).unwrap()));
+        assert!(hits.contains(&database.get_by_path(path.join("zoo")).unwrap()));
+    }
+
+    #[test]
+    fn limit() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = tmpdir.path().join("root");
+        fs::create_dir(&path).unwrap();
+        let now = SystemTime::now();
+        for (i, name) in ["a", "b", "c", ".d", "e"].iter().enumerate() {
+            let file = fs::File::create(path.join(name)).unwrap();
+            file.set_modified(now - Duration::from_secs(i as u64 * 60))
+                .unwrap();
+        }
+        fs::create_dir(path.join("f")).unwrap();
+
+        let mut builder = DatabaseBuilder::new();
+        builder.add_dir(&path).index(StatusKind::Modified);
+        let on_the_fly = builder.build().unwrap();
+        builder.fast_sort(StatusKind::Modified);
+        let precomputed = builder.build().unwrap();
+
+        for pattern in ["", "e", "[a-c]"] {
+            for (sort_order, dirs_first, ignore_hidden) in [
+                (SortOrder::Ascending, false, false),
+                (SortOrder::Descending, false, false),
+                (SortOrder::Descending, true, false),
+                (SortOrder::Descending, false, true),
+            ] {
+                let mut builder = QueryBuilder::new(pattern);
+                builder
+                    .regex(true)
+                    .sort_by(StatusKind::Modified)
+                    .sort_order(sort_order)
+                    .sort_dirs_before_files(dirs_first)
+                    .ignore_hidden(ignore_hidden);
+                let unlimited = builder.build().unwrap();
+                let limited = builder.limit(2).build().unwrap();
+
+                let search = |database: &Database, query: &Query| {
+                    database
+                        .search_all(query)
+                        .unwrap()
+                        .into_iter()
+                        .map(|id| database.entry(id).path())
+                        .collect::<Vec<_>>()
+                };
+                let mut expected = search(&on_the_fly, &unlimited);
+                expected.truncate(2);
+                assert_eq!(search(&on_the_fly, &limited), expected);
+                assert_eq!(search(&precomputed, &limited), expected);
+            }
+        }
+
+        let query = QueryBuilder::new("")
+            .sort_by(StatusKind::Modified)
+            .limit(2)
+            .build()
+            .unwrap();
+        assert_eq!(
+            precomputed.first_sorted_ids(&query).unwrap(),
+            precomputed.search_all(&query).unwrap()
+        );
+        assert!(on_the_fly.first_sorted_ids(&query).is_none());
+        let hits = precomputed.search_all(&query).unwrap();
+        let basenames: Vec<_> = hits
+            .into_iter()
+            .map(|id| precomputed.entry(id).basename().to_owned())
+            .collect();
+        assert_eq!(basenames, ["e", ".d"]);
+    }
+
+    #[test]
+    fn match_extension() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = tmpdir.path();
+        fs::create_dir(path.join("rs")).unwrap();
+        for name in ["main.rs", "lib.RS", "rsync.txt", "cars", "rs/mod.rs"] {
+            fs::write(path.join(name), "").unwrap();
+        }
+
+        for case_fold_index in [false, true] {
+            let database = DatabaseBuilder::new()
+                .add_dir(path)
+                .case_fold_index(case_fold_index)
+                .build()
+                .unwrap();
+            let search = |pattern: &str, invert: bool| {
+                let query = QueryBuilder::new(pattern)
+                    .match_extension(true)
+                    .match_path_mode(MatchPathMode::Always)
+                    .invert(invert)
+                    .build()
+                    .unwrap();
+                assert!(!query.match_path());
+                database
+                    .search_all(&query)
+                    .unwrap()
+                    .into_iter()
+                    .map(|id| database.entry(id))
+                    .filter(|entry| entry.path().starts_with(path))
+                    .map(|entry| entry.basename().to_owned())
+                    .collect::<Vec<_>>()
+            };
+
+            assert_eq!(search("rs", false), ["lib.RS", "main.rs", "mod.rs"]);
+            assert_eq!(search("txt", false), ["rsync.txt"]);
+            assert!(search("sync", false).is_empty());
+            assert!(!search("rs", true).contains(&"main.rs".to_owned()));
+            assert!(search("rs", true).contains(&"rsync.txt".to_owned()));
+        }
+
+        let database = DatabaseBuilder::new().add_dir(path).build().unwrap();
+        let query = QueryBuilder::new("rs")
+            .match_extension(true)
+            .build()
+            .unwrap();
+        let entry = database
+            .search_all(&query)
+            .unwrap()
+            .into_iter()
+            .map(|id| database.entry(id))
+            .find(|entry| entry.basename() == "main.rs")
+            .unwrap();
+        assert!(query.is_match(&entry));
+        let matches = query.basename_matches(&entry);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0], 5..7);
+        let path_len = entry.path().as_str().len();
+        let matches = query.path_matches(&entry);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0], path_len - 2..path_len);
+    }
+
+    #[test]
+    fn basename_len() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = tmpdir.path();
+        fs::create_dir(path.join("dir")).unwrap();
+        for name in ["a", "ab", "abc", "abcdefgh", "x"] {
+            fs::write(path.join("dir").join(name), "").unwrap();
+        }
+        let database = DatabaseBuilder::new().add_dir(path).build().unwrap();
+        let dir = database.root_entries().next().unwrap().path().join("dir");
+
+        let search = |pattern: &str, min: Option<usize>, max: Option<usize>| {
+            let mut builder = QueryBuilder::new(pattern);
+            if let Some(min) = min {
+                builder.min_basename_len(min);
+            }
+            if let Some(max) = max {
+                builder.max_basename_len(max);
+            }
+            let query = builder.build().unwrap();
+            let hits = database.search_all(&query).unwrap();
+
+            let all_ids: Vec<_> = database
+                .search_all(&QueryBuilder::new("").build().unwrap())
+                .unwrap();
+            let abort_signal = Arc::new(AtomicBool::new(false));
+            assert_eq!(
+                database
+                    .filter_ids(&all_ids, &query, &abort_signal)
+                    .unwrap(),
+                hits
+            );
+
+            hits.into_iter()
+                .map(|id| database.entry(id))
+                .filter(|entry| entry.path().parent() == Some(dir.as_path()))
+                .map(|entry| entry.basename().to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(search("a", None, None), ["a", "ab", "abc", "abcdefgh"]);
+        assert_eq!(search("a", Some(2), None), ["ab", "abc", "abcdefgh"]);
+        assert_eq!(search("a", None, Some(2)), ["a", "ab"]);
+        assert_eq!(search("a", Some(2), Some(3)), ["ab", "abc"]);
+        assert_eq!(search("b", Some(4), None), ["abcdefgh"]);
+        assert!(search("a", Some(3), Some(2)).is_empty());
+        assert_eq!(search("", None, Some(1)), ["a", "x"]);
+    }
+
+    #[test]
+    fn time_ranges() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = tmpdir.path().join("root");
+        fs::create_dir(&path).unwrap();
+        let now = SystemTime::now();
+        let hour = Duration::from_secs(60 * 60);
+        for (name, age) in [("a", 1), ("b", 5), ("c", 24), ("d", 48)] {
+            let file = fs::File::create(path.join(name)).unwrap();
+            file.set_modified(now - hour * age).unwrap();
+        }
+
+        for indexed in [false, true] {
+            let mut builder = DatabaseBuilder::new();
+            builder.add_dir(&path);
+            if indexed {
+                builder.index(StatusKind::Modified);
+            }
+            let database = builder.build().unwrap();
+
+            let search = |after: Option<u32>, before: Option<u32>| {
+                let mut builder = QueryBuilder::new("[a-d]");
+                builder.regex(true);
+                if let Some(age) = after {
+                    builder.modified_after(now - hour * age);
+                }
+                if let Some(age) = before {
+                    builder.modified_before(now - hour * age);
+                }
+                let query = builder.build().unwrap();
+                let hits = database.search_all(&query).unwrap();
+
+                let all_ids = database.search_all(&QueryBuilder::new("").build().unwrap());
+                let abort_signal = Arc::new(AtomicBool::new(false));
+                assert_eq!(
+                    database
+                        .filter_ids(&all_ids.unwrap(), &query, &abort_signal)
+                        .unwrap(),
+                    hits
+                );
+
+                hits.into_iter()
+                    .map(|id| database.entry(id).basename().to_owned())
+                    .collect::<Vec<_>>()
+            };
+
+            assert_eq!(search(None, None), ["a", "b", "c", "d"]);
+            assert_eq!(search(Some(6), None), ["a", "b"]);
+            assert_eq!(search(None, Some(6)), ["c", "d"]);
+            assert_eq!(search(Some(30), Some(2)), ["b", "c"]);
+            assert!(search(Some(5), Some(5)).is_empty());
+            assert!(search(Some(2), Some(30)).is_empty());
+        }
+    }
+
+    #[test]
+    fn size_bounds() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = tmpdir.path().join("root");
+        fs::create_dir(&path).unwrap();
+        for (name, len) in [("a", 0), ("b", 10), ("c", 100), ("d", 1000)] {
+            fs::write(path.join(name), vec![b'x'; len]).unwrap();
+        }
+        fs::create_dir(path.join("e")).unwrap();
+
+        for indexed in [false, true] {
+            let mut builder = DatabaseBuilder::new();
+            builder.add_dir(&path);
+            if indexed {
+                builder.index(StatusKind::Size);
+            }
+            let database = builder.build().unwrap();
+
+            let search = |min: Option<u64>, max: Option<u64>| {
+                let mut builder = QueryBuilder::new("^[a-e]$");
+                builder.regex(true);
+                if let Some(min) = min {
+                    builder.size_min(min);
+                }
+                if let Some(max) = max {
+                    builder.size_max(max);
+                }
+                let query = builder.build().unwrap();
+                let hits = database.search_all(&query).unwrap();
+
+                let all_ids = database.search_all(&QueryBuilder::new("").build().unwrap());
+                let abort_signal = Arc::new(AtomicBool::new(false));
+                assert_eq!(
+                    database
+                        .filter_ids(&all_ids.unwrap(), &query, &abort_signal)
+                        .unwrap(),
+                    hits
+                );
+
+                hits.into_iter()
+                    .map(|id| database.entry(id).basename().to_owned())
+                    .collect::<Vec<_>>()
+            };
+
+            assert_eq!(search(None, None), ["a", "b", "c", "d", "e"]);
+            assert_eq!(search(Some(10), None), ["b", "c", "d"]);
+            assert_eq!(search(None, Some(10)), ["a", "b"]);
+            assert_eq!(search(Some(10), Some(100)), ["b", "c"]);
+            assert_eq!(search(Some(0), None), ["a", "b", "c", "d"]);
+            assert!(search(Some(101), Some(999)).is_empty());
+        }
+    }
+
+    #[test]
+    fn glob() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = tmpdir.path();
+        for dir in ["src/bin/ix", "tests"] {
+            fs::create_dir_all(path.join(dir)).unwrap();
+        }
+        for file in [
+            "src/main.rs",
+            "src/lib.rs",
+            "src/bin/ix/main.rs",
+            "src/rs.txt",
+            "tests/a.rs",
+            "tests/ab.rs",
+            "README.md",
+        ] {
+            fs::write(path.join(file), "").unwrap();
+        }
+        let database = DatabaseBuilder::new().add_dir(path).build().unwrap();
+        let root = database.root_entries().next().unwrap().path();
+
+        let search = |pattern: &str, match_path_mode: MatchPathMode| {
+            let query = QueryBuilder::new(pattern)
+                .glob(true)
+                .match_path_mode(match_path_mode)
+                .build()
+                .unwrap();
+            assert!(query.is_glob());
+            database
+                .search_all(&query)
+                .unwrap()
+                .into_iter()
+                .map(|id| {
+                    let path = database.entry(id).path();
+                    let relative = path.strip_prefix(&root).unwrap();
+                    relative.iter().join("/")
+                })
+                .sorted()
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            search("*.rs", MatchPathMode::Auto),
+            [
+                "src/bin/ix/main.rs",
+                "src/lib.rs",
+                "src/main.rs",
+                "tests/a.rs",
+                "tests/ab.rs"
+            ]
+        );
+        assert_eq!(search("?.rs", MatchPathMode::Auto), ["tests/a.rs"]);
+        assert_eq!(
+            search("[ab]*.rs", MatchPathMode::Auto),
+            ["tests/a.rs", "tests/ab.rs"]
+        );
+        assert_eq!(search("readme.*", MatchPathMode::Auto), ["README.md"]);
+        assert!(search("rs", MatchPathMode::Auto).is_empty());
+        assert_eq!(
+            search("src/**/main.rs", MatchPathMode::Auto),
+            ["src/bin/ix/main.rs", "src/main.rs"]
+        );
+        assert_eq!(
+            search("src/*.rs", MatchPathMode::Auto),
+            ["src/lib.rs", "src/main.rs"]
+        );
+        assert_eq!(
+            search("bin/*/*.rs", MatchPathMode::Auto),
+            ["src/bin/ix/main.rs"]
+        );
+        assert!(search("rc/*.rs", MatchPathMode::Auto).is_empty());
+        assert_eq!(search("*.md", MatchPathMode::Always), ["README.md"]);
+
+        let query = QueryBuilder::new("*.rs").glob(true).build().unwrap();
+        assert!(!query.is_literal());
+        assert!(!query.is_regex_enabled());
+        let query = QueryBuilder::new("").glob(true).build().unwrap();
+        assert!(query.is_empty());
+    }
+
+    #[test]
+    fn search_par() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = tmpdir.path();
+        fs::create_dir(path.join("dir")).unwrap();
+        for (i, name) in ["a.txt", "b.txt", "c.rs", "dir/d.txt"].iter().enumerate() {
+            fs::write(path.join(name), "x".repeat(i * 10 + 1)).unwrap();
+        }
+        let database = DatabaseBuilder::new()
+            .add_dir(path)
+            .index(StatusKind::Size)
+            .build()
+            .unwrap();
+        let abort_signal = Arc::new(AtomicBool::new(false));
+
+        let query = QueryBuilder::new(".txt").build().unwrap();
+        let sequential: u64 = database
+            .search_all(&query)
+            .unwrap()
+            .into_iter()
+            .map(|id| database.entry(id).size().unwrap())
+            .sum();
+        let parallel: u64 = database
+            .search_par(&query, &abort_signal)
+            .unwrap()
+            .map(|entry| entry.size().unwrap())
+            .sum();
+        assert_eq!(parallel, 1 + 11 + 31);
+        assert_eq!(parallel, sequential);
+
+        let basenames: Vec<_> = database
+            .search_par(&query, &abort_signal)
+            .unwrap()
+            .map(|entry| entry.basename().to_owned())
+            .collect();
+        assert_eq!(basenames, ["a.txt", "b.txt", "d.txt"]);
+
+        abort_signal.store(true, Ordering::Relaxed);
+        assert!(matches!(
+            database.search_par(&query, &abort_signal),
+            Err(Error::SearchAbort)
+        ));
+    }
+
+    #[test]
+    fn case_insensitive_sort() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = tmpdir.path();
+        for dir in ["B", "a"] {
+            fs::create_dir(path.join(dir)).unwrap();
+            for name in ["Zebra.txt", "apple.TXT", "Mango.md", "banana.txt"] {
+                fs::write(path.join(dir).join(name), "").unwrap();
+            }
+        }
+        let database = DatabaseBuilder::new()
+            .add_dir(path)
+            .fast_sort(StatusKind::Path)
+            .fast_sort(StatusKind::Extension)
+            .index(StatusKind::Extension)
+            .build()
+            .unwrap();
+
+        let search = |sort_by, case_insensitive_sort| {
+            let query = QueryBuilder::new("a")
+                .sort_by(sort_by)
+                .case_insensitive_sort(case_insensitive_sort)
+                .build()
+                .unwrap();
+            assert_eq!(query.case_insensitive_sort(), case_insensitive_sort);
+            let hits = database.search_all(&query).unwrap();
+            let entries: Vec<_> = hits.iter().map(|id| database.entry(*id)).collect();
+            assert!(entries
+                .windows(2)
+                .all(|w| query.compare(&w[0], &w[1]) == std::cmp::Ordering::Less));
+            entries
+                .into_iter()
+                .filter(|entry| !entry.is_dir())
+                .map(|entry| {
+                    let parent = entry
+                        .path()
+                        .parent()
+                        .unwrap()
+                        .file_name()
+                        .unwrap()
+                        .to_owned();
+                    format!("{}/{}", parent, entry.basename())
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            search(StatusKind::Basename, false)[..4],
+            ["B/Mango.md", "a/Mango.md", "B/Zebra.txt", "a/Zebra.txt"]
+        );
+        assert_eq!(
+            search(StatusKind::Basename, true),
+            [
+                "B/apple.TXT",
+                "a/apple.TXT",
+                "B/banana.txt",
+                "a/banana.txt",
+                "B/Mango.md",
+                "a/Mango.md",
+                "B/Zebra.txt",
+                "a/Zebra.txt",
+            ]
+        );
+        assert_eq!(
+            search(StatusKind::Path, true),
+            [
+                "a/apple.TXT",
+                "a/banana.txt",
+                "a/Mango.md",
+                "a/Zebra.txt",
+                "B/apple.TXT",
+                "B/banana.txt",
+                "B/Mango.md",
+                "B/Zebra.txt",
+            ]
+        );
+        // remaining ties are broken by paths compared case-sensitively
+        assert_eq!(
+            search(StatusKind::Extension, true),
+            [
+                "B/Mango.md",
+                "a/Mango.md",
+                "B/apple.TXT",
+                "a/apple.TXT",
+                "B/banana.txt",
+                "a/banana.txt",
+                "B/Zebra.txt",
+                "a/Zebra.txt",
+            ]
+        );
     }
 }
@@ -1,21 +1,24 @@
 mod basename;
 mod component_wise_path;
+mod extension;
 mod full_path;
 mod passthrough;
 mod regex_path;
 
 pub use basename::BasenameFilter;
 pub use component_wise_path::ComponentWisePathFilter;
+pub use extension::ExtensionFilter;
 pub use full_path::FullPathFilter;
 pub use passthrough::PassthroughFilter;
 pub use regex_path::RegexPathFilter;
 
 use crate::{
-    database::{Database, EntryNode},
+    database::{bloom, fold::FoldedNames, Database, EntryNode},
     Error, Result,
 };
 
-use rayon::prelude::*;
+use memchr::memmem::Finder;
+use rayon::{iter::Either, prelude::*};
 use regex::Regex;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
@@ -23,15 +26,37 @@ use std::sync::{
 };
 use thread_local::ThreadLocal;
 
+/// Interval of entry ids at which the abort and deadline signals are checked.
+/// Must be a power of two.
+///
+/// Loading the signals for every entry adds up over millions of entries,
+/// while a few dozen entries take only microseconds to visit.
+const STOP_CHECK_INTERVAL: u32 = 64;
+
 pub(crate) struct FilterContext<'d, 'a, 'r> {
     database: &'d Database,
     abort_signal: &'a Arc<AtomicBool>,
     regex: &'r Regex,
 
+    // When set, remaining entries are skipped and hits found so far are kept.
+    deadline_signal: Option<&'a AtomicBool>,
+    // whether any entry was skipped because of deadline_signal
+    truncated: AtomicBool,
+    // should_stop checks the signals only for ids with these bits cleared
+    stop_check_mask: u32,
+
     // Since rust-lang/regex@e040c1b, regex library stopped using thread_local,
     // which had a performance impact on indexa.
     // We mitigate it by putting Regex in thread local storage.
     regex_tls: ThreadLocal<Regex>,
+
+    // For each chunk of entries, whether it can contain matching basenames.
+    // None means all chunks have to be scanned.
+    candidate_chunks: Option<Vec<bool>>,
+
+    // When set, basenames are matched by searching case-folded basenames
+    // for the case-folded pattern instead of using the regex.
+    folded: Option<(&'d FoldedNames, Finder<'r>)>,
 }
 
 impl<'d, 'a, 'r> FilterContext<'d, 'a, 'r> {
@@ -44,13 +69,100 @@ impl<'d, 'a, 'r> FilterContext<'d, 'a, 'r> {
             database,
             abort_signal,
             regex,
+            deadline_signal: None,
+            truncated: AtomicBool::new(false),
+            stop_check_mask: STOP_CHECK_INTERVAL - 1,
             regex_tls: ThreadLocal::with_capacity(rayon::current_num_threads() + 1),
+            candidate_chunks: None,
+            folded: None,
+        }
+    }
+
+    pub fn with_candidate_chunks(mut self, candidate_chunks: Option<Vec<bool>>) -> Self {
+        self.candidate_chunks = candidate_chunks;
+        self
+    }
+
+    pub fn with_folded_names(mut self, folded: Option<(&'d FoldedNames, &'r str)>) -> Self {
+        self.folded = folded.map(|(names, literal)| (names, Finder::new(literal)));
+        self
+    }
+
+    pub fn with_deadline_signal(mut self, deadline_signal: Option<&'a AtomicBool>) -> Self {
+        // the deadline may have passed before filtering starts
+        if matches!(deadline_signal, Some(signal) if signal.load(Ordering::Relaxed)) {
+            self.truncated.store(true, Ordering::Relaxed);
+        }
+        self.deadline_signal = deadline_signal;
+        self
+    }
+
+    #[cfg(test)]
+    pub fn with_stop_check_interval(mut self, interval: u32) -> Self {
+        assert!(interval.is_power_of_two());
+        self.stop_check_mask = interval - 1;
+        self
+    }
+
+    /// Returns true if some entries were skipped because the deadline passed.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated.load(Ordering::Relaxed)
+    }
+
+    /// Returns `Err(Error::SearchAbort)` if the search was aborted,
+    /// and `Ok(true)` if the remaining entries should be skipped
+    /// because the deadline passed.
+    ///
+    /// The signals are actually checked only when `id` is a multiple of
+    /// `STOP_CHECK_INTERVAL`. For other ids, the search keeps going unless
+    /// the deadline is already known to have passed.
+    #[inline]
+    fn should_stop(&self, id: u32) -> Result<bool> {
+        if id & self.stop_check_mask != 0 {
+            return Ok(self.deadline_signal.is_some() && self.is_truncated());
+        }
+
+        if self.abort_signal.load(Ordering::Relaxed) {
+            return Err(Error::SearchAbort);
+        }
+        match self.deadline_signal {
+            Some(signal) if signal.load(Ordering::Relaxed) => {
+                self.truncated.store(true, Ordering::Relaxed);
+                Ok(true)
+            }
+            _ => Ok(false),
         }
     }
 
     fn thread_local_regex(&self) -> &Regex {
         self.regex_tls.get_or(|| self.regex.clone())
     }
+
+    /// Returns ids of entries in chunks which can contain matching basenames.
+    fn candidate_ids(&self) -> impl ParallelIterator<Item = u32> + '_ {
+        let num_entries = self.database.nodes.len();
+        match &self.candidate_chunks {
+            Some(candidates) => Either::Left(
+                candidates
+                    .par_iter()
+                    .enumerate()
+                    .filter(|(_, candidate)| **candidate)
+                    .flat_map(move |(i, _)| {
+                        let start = i * bloom::CHUNK_SIZE;
+                        let end = num_entries.min(start + bloom::CHUNK_SIZE);
+                        (start as u32..end as u32).into_par_iter()
+                    }),
+            ),
+            None => Either::Right((0..num_entries as u32).into_par_iter()),
+        }
+    }
+
+    fn is_candidate(&self, id: u32) -> bool {
+        match &self.candidate_chunks {
+            Some(candidates) => candidates[id as usize / bloom::CHUNK_SIZE],
+            None => true,
+        }
+    }
 }
 
 // Filters can choose to directly implement `Filter` or
@@ -103,12 +215,13 @@ fn match_all_descendants(
     let children_range = node.child_start as usize..node.child_end as usize;
     (
         &ctx.database.nodes[children_range.clone()],
-        &matched[children_range],
+        &matched[children_range.clone()],
     )
         .into_par_iter()
-        .try_for_each(|(node, m)| {
-            if ctx.abort_signal.load(Ordering::Relaxed) {
-                return Err(Error::SearchAbort);
+        .enumerate()
+        .try_for_each(|(i, (node, m))| {
+            if ctx.should_stop(children_range.start as u32 + i as u32)? {
+                return Ok(());
             }
 
             m.store(true, Ordering::Relaxed);
@@ -1,5 +1,5 @@
 use super::{FilterContext, MatchEntries};
-use crate::{database::EntryNode, Error, Result};
+use crate::{database::EntryNode, Result};
 
 use rayon::prelude::*;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -24,7 +24,9 @@ impl MatchEntries for ComponentWisePathFilter {
                 }
             } else {
                 let root_node = &nodes[*root_id as usize];
-                traverse_tree(ctx, matched, root_node)?;
+                if root_node.has_any_child() {
+                    traverse_tree(ctx, matched, root_node)?;
+                }
             }
         }
 
@@ -38,12 +40,13 @@ fn traverse_tree(ctx: &FilterContext, matched: &[AtomicBool], node: &EntryNode)
     let children_range = node.child_start as usize..node.child_end as usize;
     (
         &ctx.database.nodes[children_range.clone()],
-        &matched[children_range],
+        &matched[children_range.clone()],
     )
         .into_par_iter()
-        .try_for_each(|(node, m)| {
-            if ctx.abort_signal.load(Ordering::Relaxed) {
-                return Err(Error::SearchAbort);
+        .enumerate()
+        .try_for_each(|(i, (node, m))| {
+            if ctx.should_stop(children_range.start as u32 + i as u32)? {
+                return Ok(());
             }
 
             if regex.is_match(ctx.database.basename_from_node(node)) {
@@ -0,0 +1,39 @@
+use super::{Filter, FilterContext};
+use crate::Result;
+
+use rayon::prelude::*;
+
+pub enum ExtensionFilter {}
+
+impl Filter for ExtensionFilter {
+    fn ordered(ctx: &FilterContext, ids: impl ParallelIterator<Item = u32>) -> Result<Vec<u32>> {
+        ids.filter(|id| ctx.is_candidate(*id))
+            .filter_map(|id| match_extension(ctx, id))
+            .collect()
+    }
+
+    fn unordered(ctx: &FilterContext) -> Result<Vec<u32>> {
+        ctx.candidate_ids()
+            .filter_map(|id| match_extension(ctx, id))
+            .collect()
+    }
+}
+
+#[inline]
+fn match_extension(ctx: &FilterContext, id: u32) -> Option<Result<u32>> {
+    match ctx.should_stop(id) {
+        Ok(false) => (),
+        Ok(true) => return None,
+        Err(err) => return Some(Err(err)),
+    }
+
+    let node = &ctx.database.nodes[id as usize];
+    if node.is_dir {
+        return None;
+    }
+    let basename = ctx.database.basename_from_node(node);
+    let (_, extension) = basename.rsplit_once('.')?;
+    ctx.thread_local_regex()
+        .is_match(extension)
+        .then_some(Ok(id))
+}
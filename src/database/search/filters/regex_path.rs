@@ -1,5 +1,5 @@
 use super::{FilterContext, MatchEntries};
-use crate::{database::EntryNode, Error, Result};
+use crate::{database::EntryNode, Result};
 
 use camino::Utf8Path;
 use rayon::prelude::*;
@@ -17,7 +17,9 @@ impl MatchEntries for RegexPathFilter {
             }
 
             let root_node = &nodes[*root_id as usize];
-            traverse_tree(ctx, matched, root_node, root_path)?;
+            if root_node.has_any_child() {
+                traverse_tree(ctx, matched, root_node, root_path)?;
+            }
         }
 
         Ok(())
@@ -35,12 +37,13 @@ fn traverse_tree(
     let children_range = node.child_start as usize..node.child_end as usize;
     (
         &ctx.database.nodes[children_range.clone()],
-        &matched[children_range],
+        &matched[children_range.clone()],
     )
         .into_par_iter()
-        .try_for_each(|(node, m)| {
-            if ctx.abort_signal.load(Ordering::Relaxed) {
-                return Err(Error::SearchAbort);
+        .enumerate()
+        .try_for_each(|(i, (node, m))| {
+            if ctx.should_stop(children_range.start as u32 + i as u32)? {
+                return Ok(());
             }
 
             let child_path = path.join(&ctx.database.basename_from_node(node));
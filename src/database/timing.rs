@@ -0,0 +1,99 @@
+use log::Level;
+use std::time::Instant;
+
+/// Target of the `log` records which report how long loading and searching
+/// databases took.
+const TARGET: &str = "indexa::timing";
+
+/// Returns true if a logger accepts timings.
+fn is_enabled() -> bool {
+    log::log_enabled!(target: TARGET, Level::Debug)
+}
+
+/// Measures stages of an operation, logging how long each of them took.
+///
+/// When no logger accepts timings, this reads no clock.
+pub(crate) struct Stopwatch {
+    operation: &'static str,
+    start: Option<Instant>,
+    lap_start: Option<Instant>,
+}
+
+impl Stopwatch {
+    pub fn start(operation: &'static str) -> Self {
+        Self::start_if(is_enabled(), operation)
+    }
+
+    fn start_if(enabled: bool, operation: &'static str) -> Self {
+        let now = enabled.then(Instant::now);
+        Self {
+            operation,
+            start: now,
+            lap_start: now,
+        }
+    }
+
+    /// Logs the time elapsed since the start or the previous lap as `stage`.
+    pub fn lap(&mut self, stage: &str) {
+        if let Some(lap_start) = &mut self.lap_start {
+            let now = Instant::now();
+            log::debug!(
+                target: TARGET,
+                "{} {}: {:?}",
+                self.operation,
+                stage,
+                now - *lap_start
+            );
+            *lap_start = now;
+        }
+    }
+
+    /// Logs the time elapsed since the start.
+    pub fn finish(self) {
+        if let Some(start) = self.start {
+            log::debug!(target: TARGET, "{}: {:?}", self.operation, start.elapsed());
+        }
+    }
+}
+
+/// Runs `f`, logging how long it took as `operation`.
+pub(crate) fn time<T, F: FnOnce() -> T>(operation: &'static str, f: F) -> T {
+    time_if(is_enabled(), operation, f)
+}
+
+fn time_if<T, F: FnOnce() -> T>(enabled: bool, operation: &'static str, f: F) -> T {
+    let stopwatch = Stopwatch::start_if(enabled, operation);
+    let value = f();
+    stopwatch.finish();
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{database::DatabaseBuilder, query::QueryBuilder};
+
+    #[test]
+    fn same_results() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        for name in ["foo", "bar", "foobar"] {
+            std::fs::write(tmpdir.path().join(name), name).unwrap();
+        }
+        let database = DatabaseBuilder::new()
+            .add_dir(tmpdir.path())
+            .build()
+            .unwrap();
+        let query = QueryBuilder::new("foo").build().unwrap();
+
+        let expected = database.search_all(&query).unwrap();
+        assert_eq!(expected.len(), 2);
+        for enabled in [false, true] {
+            let hits = time_if(enabled, "search", || database.search_all(&query)).unwrap();
+            assert_eq!(hits, expected);
+        }
+
+        let mut stopwatch = Stopwatch::start_if(false, "search");
+        stopwatch.lap("filter");
+        assert!(stopwatch.start.is_none() && stopwatch.lap_start.is_none());
+    }
+}
@@ -1,68 +1,217 @@
-use super::{Entry, StatusKind};
+use super::{Entry, StatusKind, Warning};
 use crate::{Error, Result};
 
 use camino::Utf8Path;
 use std::{
+    borrow::Cow,
     cmp::Ordering,
+    io,
     path::{Path, PathBuf},
     time::SystemTime,
 };
 
 /// Canonicalize all paths and remove all redundant subdirectories
-pub fn canonicalize_dirs<P>(dirs: &[P]) -> Result<Vec<PathBuf>>
+///
+/// If `skip_missing` is true, nonexistent paths are dropped and reported
+/// to `warnings` instead of failing.
+pub fn canonicalize_dirs<P>(
+    dirs: &[P],
+    skip_missing: bool,
+    warnings: &mut Vec<Warning>,
+) -> Result<Vec<PathBuf>>
 where
     P: AsRef<Path>,
 {
-    let mut dirs = dirs
-        .iter()
-        .map(|path| {
-            let canonicalized = dunce::canonicalize(path)?;
-            let path_str = canonicalized
-                .to_str()
-                .ok_or(Error::NonUtf8Path)?
-                .to_string();
-            Ok((canonicalized, path_str))
-        })
-        .collect::<Result<Vec<_>>>()?;
-
-    // we use str::starts_with, because Path::starts_with doesn't work well for Windows paths
-    dirs.sort_unstable_by(|(_, a), (_, b)| a.cmp(b));
-    dirs.dedup_by(|(_, a), (_, b)| a.starts_with(b.as_str()));
+    let mut canonicalized_dirs = Vec::with_capacity(dirs.len());
+    for path in dirs {
+        let canonicalized = match dunce::canonicalize(path) {
+            Ok(canonicalized) => canonicalized,
+            Err(err) if skip_missing && err.kind() == io::ErrorKind::NotFound => {
+                warnings.push(Warning::new(path.as_ref().to_path_buf(), err));
+                continue;
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let canonicalized = canonicalized.to_str().ok_or(Error::NonUtf8Path)?;
+        let canonicalized = strip_verbatim_prefix(canonicalized);
+        let path_str = comparison_key(&canonicalized);
+        canonicalized_dirs.push((PathBuf::from(canonicalized.into_owned()), path_str));
+    }
+    let mut dirs = canonicalized_dirs;
+
+    // we compare strings, because Path::starts_with doesn't work well for Windows paths.
+    // Sorting component-wise keeps subdirectories right after their ancestors
+    // (e.g. /foo, /foo/bar, /foo-bar rather than /foo, /foo-bar, /foo/bar).
+    dirs.sort_unstable_by(|(_, a), (_, b)| {
+        a.split(std::path::MAIN_SEPARATOR)
+            .cmp(b.split(std::path::MAIN_SEPARATOR))
+    });
+    dirs.dedup_by(|(_, a), (_, b)| is_same_or_subdir(a, b));
 
     Ok(dirs.into_iter().map(|(path, _)| path).collect())
 }
 
+/// Windows paths are case-insensitive, so they are compared in lowercase.
+/// They are also compared without extended-length prefixes (`\\?\`).
+pub fn comparison_key(path: &str) -> String {
+    if cfg!(windows) {
+        strip_verbatim_prefix(path).to_lowercase()
+    } else {
+        path.to_string()
+    }
+}
+
+/// Removes the extended-length prefix from a Windows path if the path means
+/// the same without it, e.g. `\\?\C:\foo` to `C:\foo` and
+/// `\\?\UNC\server\share\foo` to `\\server\share\foo`.
+///
+/// `dunce` keeps the prefix on paths longer than `MAX_PATH`, but the standard
+/// library adds it back when accessing such paths, so they can be stripped as
+/// well. Without the prefix, paths can be joined and compared with the ones
+/// users give.
+pub fn strip_verbatim_prefix(path: &str) -> Cow<'_, str> {
+    if !cfg!(windows) {
+        return path.into();
+    }
+
+    let (stripped, rest) = if let Some(rest) = path.strip_prefix(r"\\?\UNC\") {
+        (format!(r"\\{}", rest), rest)
+    } else if let Some(rest) = path.strip_prefix(r"\\?\") {
+        let bytes = rest.as_bytes();
+        let is_absolute = bytes.len() >= 3
+            && bytes[0].is_ascii_alphabetic()
+            && bytes[1] == b':'
+            && bytes[2] == b'\\';
+        if !is_absolute {
+            return path.into();
+        }
+        (rest.to_owned(), &rest[2..])
+    } else {
+        return path.into();
+    };
+
+    // Without the prefix, `/` becomes a separator, `.` and `..` get resolved,
+    // trailing dots and spaces get trimmed, and device names get special meanings.
+    let is_plain_name = |name: &str| {
+        let stem = name.split('.').next().unwrap_or(name).trim_end();
+        let is_device = match stem.to_ascii_uppercase().as_str() {
+            "CON" | "PRN" | "AUX" | "NUL" => true,
+            s => {
+                (s.starts_with("COM") || s.starts_with("LPT"))
+                    && s.len() == 4
+                    && matches!(s.as_bytes()[3], b'1'..=b'9')
+            }
+        };
+        !name.contains('/') && !name.ends_with(['.', ' ']) && !is_device
+    };
+    if rest
+        .split('\\')
+        .filter(|name| !name.is_empty())
+        .all(is_plain_name)
+    {
+        stripped.into()
+    } else {
+        path.into()
+    }
+}
+
+/// Returns true if `path` is `dir` itself or lies under `dir`.
+pub fn is_same_or_subdir(path: &str, dir: &str) -> bool {
+    match path.strip_prefix(dir) {
+        Some(rest) => {
+            rest.is_empty()
+                || rest.starts_with(std::path::MAIN_SEPARATOR)
+                || dir.ends_with(std::path::MAIN_SEPARATOR)
+        }
+        None => false,
+    }
+}
+
 pub fn get_basename(path: &Utf8Path) -> &str {
     path.file_name().unwrap_or_else(|| path.as_str())
 }
 
-pub fn get_compare_func(kind: StatusKind) -> fn(&Entry, &Entry) -> Ordering {
-    #[inline]
+/// Returns a function comparing entries by `kind`, then by `tiebreak`.
+///
+/// With `ignore_case`, basenames, paths, and extensions are compared ignoring
+/// case. Remaining ties are broken by path (case-sensitively), which is unique
+/// to each entry, so the function is a total order.
+pub fn get_compare_func(
+    kind: StatusKind,
+    tiebreak: StatusKind,
+    ignore_case: bool,
+) -> impl Fn(&Entry, &Entry) -> Ordering + Copy + Send + Sync {
+    let primary = get_key_compare_func(kind, ignore_case);
+    let secondary = get_key_compare_func(tiebreak, ignore_case);
+    move |a, b| {
+        primary(a, b)
+            .then_with(|| secondary(a, b))
+            .then_with(|| Entry::cmp_by_path(a, b))
+    }
+}
+
+/// Returns a function comparing entries by `kind` alone.
+fn get_key_compare_func(kind: StatusKind, ignore_case: bool) -> fn(&Entry, &Entry) -> Ordering {
     fn cmp_by_basename(a: &Entry, b: &Entry) -> Ordering {
-        Ord::cmp(a.basename(), b.basename()).then_with(|| Entry::cmp_by_path(a, b))
+        Ord::cmp(a.basename(), b.basename())
     }
     fn cmp_by_path(a: &Entry, b: &Entry) -> Ordering {
         Entry::cmp_by_path(a, b)
     }
     fn cmp_by_extension(a: &Entry, b: &Entry) -> Ordering {
-        Entry::cmp_by_extension(a, b).then_with(|| cmp_by_basename(a, b))
+        Entry::cmp_by_extension(a, b)
     }
     fn cmp_by_size(a: &Entry, b: &Entry) -> Ordering {
-        Ord::cmp(&a.size().ok(), &b.size().ok()).then_with(|| cmp_by_basename(a, b))
+        Ord::cmp(&a.size().ok(), &b.size().ok())
     }
     fn cmp_by_mode(a: &Entry, b: &Entry) -> Ordering {
-        Ord::cmp(&a.mode().ok(), &b.mode().ok()).then_with(|| cmp_by_basename(a, b))
+        Ord::cmp(&a.mode().ok(), &b.mode().ok())
     }
     fn cmp_by_created(a: &Entry, b: &Entry) -> Ordering {
-        Ord::cmp(&a.created().ok(), &b.created().ok()).then_with(|| cmp_by_basename(a, b))
+        Ord::cmp(&a.created().ok(), &b.created().ok())
     }
     fn cmp_by_modified(a: &Entry, b: &Entry) -> Ordering {
-        Ord::cmp(&a.modified().ok(), &b.modified().ok()).then_with(|| cmp_by_basename(a, b))
+        Ord::cmp(&a.modified().ok(), &b.modified().ok())
     }
     fn cmp_by_accessed(a: &Entry, b: &Entry) -> Ordering {
-        Ord::cmp(&a.accessed().ok(), &b.accessed().ok()).then_with(|| cmp_by_basename(a, b))
+        Ord::cmp(&a.accessed().ok(), &b.accessed().ok())
+    }
+    fn cmp_by_owner(a: &Entry, b: &Entry) -> Ordering {
+        Ord::cmp(&a.owner().ok(), &b.owner().ok())
+    }
+    fn cmp_by_basename_ignore_case(a: &Entry, b: &Entry) -> Ordering {
+        cmp_ignore_case(a.basename(), b.basename())
+    }
+    fn cmp_by_path_ignore_case(a: &Entry, b: &Entry) -> Ordering {
+        // compared component-wise, so that /a/b comes before /a.b as in cmp_by_path
+        let (path_a, path_b) = (a.path(), b.path());
+        let (mut a, mut b) = (path_a.iter(), path_b.iter());
+        loop {
+            match (a.next(), b.next()) {
+                (Some(a), Some(b)) => match cmp_ignore_case(a, b) {
+                    Ordering::Equal => continue,
+                    ordering => return ordering,
+                },
+                (a, b) => return Ord::cmp(&a.is_some(), &b.is_some()),
+            }
+        }
+    }
+    fn cmp_by_extension_ignore_case(a: &Entry, b: &Entry) -> Ordering {
+        if a.is_dir() && b.is_dir() {
+            return Ordering::Equal;
+        }
+        match (a.extension(), b.extension()) {
+            (Some(a), Some(b)) => cmp_ignore_case(a, b),
+            (a, b) => Ord::cmp(&a.is_some(), &b.is_some()),
+        }
     }
 
+    match (kind, ignore_case) {
+        (StatusKind::Basename, true) => return cmp_by_basename_ignore_case,
+        (StatusKind::Path, true) => return cmp_by_path_ignore_case,
+        (StatusKind::Extension, true) => return cmp_by_extension_ignore_case,
+        _ => (),
+    }
     match kind {
         StatusKind::Basename => cmp_by_basename,
         StatusKind::Path => cmp_by_path,
@@ -72,9 +221,17 @@ pub fn get_compare_func(kind: StatusKind) -> fn(&Entry, &Entry) -> Ordering {
         StatusKind::Created => cmp_by_created,
         StatusKind::Modified => cmp_by_modified,
         StatusKind::Accessed => cmp_by_accessed,
+        StatusKind::Owner => cmp_by_owner,
     }
 }
 
+/// Compares strings by their lowercase forms.
+fn cmp_ignore_case(a: &str, b: &str) -> Ordering {
+    a.chars()
+        .flat_map(char::to_lowercase)
+        .cmp(b.chars().flat_map(char::to_lowercase))
+}
+
 /// check for invalid SystemTime (e.g. older than unix epoch) and fix them
 pub fn sanitize_system_time(time: &SystemTime) -> SystemTime {
     if let Ok(duration) = time.duration_since(SystemTime::UNIX_EPOCH) {
@@ -114,6 +271,78 @@ pub fn is_hidden(dent: &std::fs::DirEntry) -> bool {
         .unwrap_or(false)
 }
 
+/// Same as `is_hidden`, but for a path and its metadata.
+#[cfg(unix)]
+pub fn is_hidden_path(path: &Path, _metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+
+    path.file_name()
+        .map(|filename| filename.as_bytes().first() == Some(&b'.'))
+        .unwrap_or(false)
+}
+
+/// Same as `is_hidden`, but for a path and its metadata.
+#[cfg(windows)]
+pub fn is_hidden_path(path: &Path, metadata: &std::fs::Metadata) -> bool {
+    use crate::mode::Mode;
+
+    Mode::from(metadata).is_hidden()
+        || path
+            .file_name()
+            .and_then(|filename| filename.to_str())
+            .map(|s| s.starts_with('.'))
+            .unwrap_or(false)
+}
+
+/// Whether extended attributes can be read on this platform.
+#[cfg(unix)]
+pub const XATTR_SUPPORTED: bool = xattr::SUPPORTED_PLATFORM;
+#[cfg(not(unix))]
+pub const XATTR_SUPPORTED: bool = false;
+
+/// Whether owners of files can be read on this platform.
+pub const OWNER_SUPPORTED: bool = cfg!(unix);
+
+/// Returns the user id of the owner of the file.
+#[cfg(unix)]
+pub fn owner(metadata: &std::fs::Metadata) -> io::Result<u32> {
+    use std::os::unix::fs::MetadataExt;
+    Ok(metadata.uid())
+}
+
+#[cfg(not(unix))]
+pub fn owner(_metadata: &std::fs::Metadata) -> io::Result<u32> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Returns the host name of this machine, if it can be determined.
+pub fn hostname() -> Option<String> {
+    let from_file = || {
+        ["/proc/sys/kernel/hostname", "/etc/hostname"]
+            .iter()
+            .find_map(|path| std::fs::read_to_string(path).ok())
+    };
+    let hostname = if cfg!(windows) {
+        std::env::var("COMPUTERNAME").ok()
+    } else {
+        from_file().or_else(|| std::env::var("HOSTNAME").ok())
+    };
+    hostname
+        .map(|hostname| hostname.trim().to_owned())
+        .filter(|hostname| !hostname.is_empty())
+}
+
+/// Returns true if the file (not the target of a symlink) has the extended attribute.
+#[cfg(unix)]
+pub fn has_xattr<P: AsRef<Path>>(path: P, name: &str) -> io::Result<bool> {
+    xattr::get(path, name).map(|value| value.is_some())
+}
+
+#[cfg(not(unix))]
+pub fn has_xattr<P: AsRef<Path>>(_path: P, _name: &str) -> io::Result<bool> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -139,30 +368,81 @@ mod tests {
         }
 
         assert_eq!(
-            canonicalize_dirs(&dirs).unwrap(),
+            canonicalize_dirs(&dirs, false, &mut Vec::new()).unwrap(),
             vec![path.join("a"), path.join("b/c"), path.join("e")]
                 .iter()
                 .map(|p| dunce::canonicalize(p).unwrap())
                 .collect::<Vec<_>>()
         );
 
-        assert!(canonicalize_dirs::<PathBuf>(&[]).unwrap().is_empty());
+        assert!(canonicalize_dirs::<PathBuf>(&[], false, &mut Vec::new())
+            .unwrap()
+            .is_empty());
 
         let tmpdir = tempfile::tempdir().unwrap();
         let path = tmpdir.path();
         std::env::set_current_dir(path).unwrap();
         assert_eq!(
-            canonicalize_dirs(&[Path::new(".")]).unwrap(),
+            canonicalize_dirs(&[Path::new(".")], false, &mut Vec::new()).unwrap(),
             vec![dunce::canonicalize(path).unwrap()]
         );
     }
 
+    #[test]
+    fn canonicalize_sibling_dirs() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = tmpdir.path();
+
+        let dirs = vec![
+            path.join("foobar"),
+            path.join("foo/bar"),
+            path.join("foo-bar"),
+            path.join("foo"),
+        ];
+        for dir in &dirs {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+
+        assert_eq!(
+            canonicalize_dirs(&dirs, false, &mut Vec::new()).unwrap(),
+            [path.join("foo"), path.join("foo-bar"), path.join("foobar")]
+                .iter()
+                .map(|p| dunce::canonicalize(p).unwrap())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_is_same_or_subdir() {
+        assert!(is_same_or_subdir("/foo", "/foo"));
+        assert!(is_same_or_subdir("/foo/bar", "/foo"));
+        assert!(is_same_or_subdir("/foo", "/"));
+        assert!(!is_same_or_subdir("/foobar", "/foo"));
+        assert!(!is_same_or_subdir("/foo", "/foo/bar"));
+    }
+
     #[test]
     #[should_panic]
     fn canonicalize_non_existent_dir() {
         let tmpdir = tempfile::tempdir().unwrap();
         let dir = tmpdir.path().join("xxxx");
-        canonicalize_dirs(&[dir]).unwrap();
+        canonicalize_dirs(&[dir], false, &mut Vec::new()).unwrap();
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_is_same_or_subdir() {
+        let is_subdir = |path, dir| is_same_or_subdir(&comparison_key(path), &comparison_key(dir));
+
+        assert!(is_subdir(r"C:\Foo", r"C:\Foo"));
+        assert!(is_subdir(r"c:\foo", r"C:\Foo"));
+        assert!(is_subdir(r"c:\foo\bar", r"C:\Foo"));
+        assert!(is_subdir(r"C:\FOO\Bar\baz", r"c:\foo\bar"));
+        assert!(!is_subdir(r"C:\Foobar", r"C:\Foo"));
+        assert!(!is_subdir(r"C:\Foo", r"C:\Foo\bar"));
+        assert!(!is_subdir(r"D:\Foo", r"C:\Foo"));
+        assert!(is_subdir(r"C:\Foo", r"c:\"));
     }
 
     #[cfg(unix)]
@@ -184,5 +464,46 @@ mod tests {
             get_basename(Utf8Path::new(r"\\server\share\"))
         );
         assert_eq!("foo", get_basename(Utf8Path::new(r"\\server\share\foo")));
+        assert_eq!(r"\\?\C:\", get_basename(Utf8Path::new(r"\\?\C:\")));
+        assert_eq!("bar", get_basename(Utf8Path::new(r"\\?\C:\foo\bar")));
+        assert_eq!(
+            "foo",
+            get_basename(Utf8Path::new(r"\\?\UNC\server\share\foo"))
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_strip_verbatim_prefix() {
+        assert_eq!(strip_verbatim_prefix(r"\\?\C:\"), r"C:\");
+        assert_eq!(strip_verbatim_prefix(r"\\?\C:\foo\bar"), r"C:\foo\bar");
+        assert_eq!(
+            strip_verbatim_prefix(r"\\?\UNC\server\share\foo"),
+            r"\\server\share\foo"
+        );
+        assert_eq!(strip_verbatim_prefix(r"C:\foo"), r"C:\foo");
+        assert_eq!(
+            strip_verbatim_prefix(r"\\server\share\foo"),
+            r"\\server\share\foo"
+        );
+
+        let long = format!(r"\\?\C:{}", r"\long_directory_name".repeat(20));
+        assert_eq!(strip_verbatim_prefix(&long), &long[4..]);
+
+        for kept in [
+            r"\\?\Volume{00000000-0000-0000-0000-000000000000}\foo",
+            r"\\?\C:",
+            r"\\?\C:\foo.",
+            r"\\?\C:\foo \bar",
+            r"\\?\C:\foo\..\bar",
+            r"\\?\C:\a/b",
+            r"\\?\C:\nul",
+            r"\\?\C:\foo\COM1.txt",
+            r"\\?\UNC\server\share\con",
+        ] {
+            assert_eq!(strip_verbatim_prefix(kept), kept);
+        }
+
+        assert_eq!(comparison_key(r"\\?\C:\Foo"), comparison_key(r"c:\foo"));
     }
 }
@@ -6,11 +6,21 @@ pub enum Error {
     #[error(transparent)]
     Io(#[from] io::Error),
     #[error(transparent)]
+    Bincode(#[from] bincode::Error),
+    #[error(transparent)]
+    ThreadPool(#[from] rayon::ThreadPoolBuildError),
+    #[error(transparent)]
     Regex(#[from] regex::Error),
     #[error(transparent)]
     RegexSyntax(#[from] regex_syntax::Error),
     #[error("{0}")]
     InvalidOption(String),
+    #[error("Not a database file or saved in an unknown format")]
+    UnknownDatabaseFormat,
+    #[error("Database is corrupted")]
+    CorruptDatabase,
+    #[error("Names of entries exceed {} bytes in total", u32::MAX)]
+    TooManyNames,
     #[error("Encountered non-UTF-8 path")]
     NonUtf8Path,
     #[error("Search aborted")]
@@ -0,0 +1,102 @@
+/// Translates a shell-style glob into a regex matching the whole of a path
+/// whose components are separated by `separator`.
+///
+/// `*` and `?` do not match the separator, while `**` as a whole component
+/// matches any number of components. `[...]` is a character class, which is
+/// negated with a leading `!` or `^`, and a `]` right after the opening `[`
+/// or the negation is a literal. Unless the separator is `\`, a backslash
+/// escapes the following character.
+/// The result is not anchored.
+pub(crate) fn to_regex(glob: &str, separator: char) -> String {
+    let sep = regex::escape(&separator.to_string());
+    let escapes = separator != '\\';
+
+    let mut regex = String::new();
+    let mut chars = glob.chars().peekable();
+    let mut at_component_start = true;
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') && at_component_start => {
+                chars.next();
+                match chars.peek() {
+                    // "**/" matches zero or more directories
+                    Some(c) if *c == separator => {
+                        chars.next();
+                        regex.push_str(&format!("(?:.*{})?", sep));
+                    }
+                    // trailing "**" matches everything inside
+                    None => regex.push_str(".*"),
+                    // otherwise "**" is the same as "*"
+                    Some(_) => regex.push_str(&format!("[^{}]*", sep)),
+                }
+            }
+            '*' => regex.push_str(&format!("[^{}]*", sep)),
+            '?' => regex.push_str(&format!("[^{}]", sep)),
+            '[' => {
+                let mut class = String::from("[");
+                let mut raw = String::from("[");
+                if let Some(c) = chars.next_if(|c| *c == '!' || *c == '^') {
+                    class.push('^');
+                    raw.push(c);
+                }
+                let body_start = raw.len();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == ']' && raw.len() > body_start {
+                        closed = true;
+                        break;
+                    }
+                    if matches!(c, '\\' | '[' | ']' | '&' | '~') {
+                        class.push('\\');
+                    }
+                    class.push(c);
+                    raw.push(c);
+                }
+                if closed {
+                    regex.push_str(&class);
+                    regex.push(']');
+                } else {
+                    regex.push_str(&regex::escape(&raw));
+                }
+            }
+            '\\' if escapes => {
+                if let Some(c) = chars.next() {
+                    regex.push_str(&regex::escape(&c.to_string()));
+                }
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+        at_component_start = regex.ends_with(&sep);
+    }
+    regex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translate() {
+        assert_eq!(to_regex("foo", '/'), "foo");
+        assert_eq!(to_regex("*.rs", '/'), r"[^/]*\.rs");
+        assert_eq!(to_regex("file?.txt", '/'), r"file[^/]\.txt");
+        assert_eq!(to_regex("src/**/main.rs", '/'), r"src/(?:.*/)?main\.rs");
+        assert_eq!(to_regex("a/**", '/'), "a/.*");
+        assert_eq!(to_regex("a**b", '/'), "a[^/]*[^/]*b");
+        assert_eq!(to_regex("[!abc]", '/'), "[^abc]");
+        assert_eq!(to_regex("[a-c]x", '/'), "[a-c]x");
+        assert_eq!(to_regex("[abc", '/'), r"\[abc");
+        assert_eq!(to_regex("[]a]", '/'), r"[\]a]");
+        assert_eq!(to_regex("[!]]*", '/'), r"[^\]][^/]*");
+        assert_eq!(to_regex("[^]]*", '/'), r"[^\]][^/]*");
+        assert_eq!(to_regex("[!]", '/'), r"\[!\]");
+        for glob in ["[!]]*", "[^]]*"] {
+            let regex = regex::Regex::new(&format!("^{}$", to_regex(glob, '/'))).unwrap();
+            assert!(regex.is_match("a]"));
+            assert!(!regex.is_match("]a"));
+        }
+        assert_eq!(to_regex(r"\*", '/'), r"\*");
+        assert_eq!(to_regex(r"a\b\*", '\\'), r"a\\b\\[^\\]*");
+        assert_eq!(to_regex(r"**\x", '\\'), r"(?:.*\\)?x");
+    }
+}
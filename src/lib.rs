@@ -4,6 +4,7 @@ pub use strum;
 
 pub mod database;
 mod error;
+mod glob;
 pub mod mode;
 pub mod query;
 
@@ -15,6 +15,35 @@ impl From<u32> for Mode {
     }
 }
 
+/// Type of a file, as told by its mode.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FileType {
+    Directory,
+    Symlink,
+    Executable,
+    Regular,
+    Fifo,
+    Socket,
+    BlockDevice,
+    CharDevice,
+}
+
+impl FileType {
+    /// Returns a short label, e.g. "dir" for a directory.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Directory => "dir",
+            Self::Symlink => "link",
+            Self::Executable => "exec",
+            Self::Regular => "file",
+            Self::Fifo => "fifo",
+            Self::Socket => "sock",
+            Self::BlockDevice => "blk",
+            Self::CharDevice => "chr",
+        }
+    }
+}
+
 trait HasFlag: Copy {
     fn has_flag(&self, other: Self) -> bool;
 }
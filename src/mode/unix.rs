@@ -1,4 +1,4 @@
-use super::{HasFlag, Mode};
+use super::{FileType, HasFlag, Mode};
 use std::{
     fmt::{self, Write},
     fs::Metadata,
@@ -37,6 +37,25 @@ impl From<&Metadata> for Mode {
 }
 
 impl Mode {
+    pub fn is_symlink(&self) -> bool {
+        self.0 & S_IFMT == S_IFLNK
+    }
+
+    /// Returns the file type. Regular files with any of execute bits set
+    /// are considered executable.
+    pub fn file_type(&self) -> FileType {
+        match self.0 & S_IFMT {
+            S_IFIFO => FileType::Fifo,
+            S_IFCHR => FileType::CharDevice,
+            S_IFDIR => FileType::Directory,
+            S_IFBLK => FileType::BlockDevice,
+            S_IFLNK => FileType::Symlink,
+            S_IFSOCK => FileType::Socket,
+            _ if self.0 & (S_IXUSR | S_IXGRP | S_IXOTH) != 0 => FileType::Executable,
+            _ => FileType::Regular,
+        }
+    }
+
     pub fn display_octal(&self) -> DisplayOctal {
         DisplayOctal(self.0)
     }
@@ -131,4 +150,13 @@ mod tests {
         check(0o100664, "0664", "-rw-rw-r--");
         check(0o120755, "0755", "lrwxr-xr-x");
     }
+
+    #[test]
+    fn file_type() {
+        assert_eq!(Mode::from(0o040755).file_type(), FileType::Directory);
+        assert_eq!(Mode::from(0o120777).file_type(), FileType::Symlink);
+        assert_eq!(Mode::from(0o100744).file_type(), FileType::Executable);
+        assert_eq!(Mode::from(0o100644).file_type(), FileType::Regular);
+        assert_eq!(Mode::from(0o010644).file_type(), FileType::Fifo);
+    }
 }
@@ -1,4 +1,4 @@
-use super::{HasFlag, Mode};
+use super::{FileType, HasFlag, Mode};
 use std::{
     fmt::{self, Write},
     fs::Metadata,
@@ -10,7 +10,10 @@ const FILE_ATTRIBUTE_HIDDEN: u32 = 0x00000002;
 const FILE_ATTRIBUTE_SYSTEM: u32 = 0x00000004;
 const FILE_ATTRIBUTE_DIRECTORY: u32 = 0x00000010;
 const FILE_ATTRIBUTE_ARCHIVE: u32 = 0x00000020;
+const FILE_ATTRIBUTE_SPARSE_FILE: u32 = 0x00000200;
 const FILE_ATTRIBUTE_REPARSE_POINT: u32 = 0x00000400;
+const FILE_ATTRIBUTE_COMPRESSED: u32 = 0x00000800;
+const FILE_ATTRIBUTE_ENCRYPTED: u32 = 0x00004000;
 
 const ATTRIBUTE_CHARS: [char; 21] = [
     'R', 'H', 'S', 'V', 'D', 'A', 'X', 'N', 'T', 'P', 'L', 'C', 'O', 'I', 'E', 'V', '\0', 'X',
@@ -28,6 +31,22 @@ impl Mode {
         self.0.has_flag(FILE_ATTRIBUTE_HIDDEN)
     }
 
+    pub fn is_symlink(&self) -> bool {
+        self.0.has_flag(FILE_ATTRIBUTE_REPARSE_POINT)
+    }
+
+    /// Returns the file type. Executables are not distinguished from
+    /// regular files, as attributes do not tell them.
+    pub fn file_type(&self) -> FileType {
+        if self.is_symlink() {
+            FileType::Symlink
+        } else if self.0.has_flag(FILE_ATTRIBUTE_DIRECTORY) {
+            FileType::Directory
+        } else {
+            FileType::Regular
+        }
+    }
+
     pub fn display_traditional(&self) -> DisplayTraditional {
         DisplayTraditional(self.0)
     }
@@ -81,6 +100,21 @@ impl fmt::Display for DisplayPowerShell {
         } else {
             '-'
         })?;
+        f.write_char(if self.0.has_flag(FILE_ATTRIBUTE_COMPRESSED) {
+            'c'
+        } else {
+            '-'
+        })?;
+        f.write_char(if self.0.has_flag(FILE_ATTRIBUTE_ENCRYPTED) {
+            'e'
+        } else {
+            '-'
+        })?;
+        f.write_char(if self.0.has_flag(FILE_ATTRIBUTE_SPARSE_FILE) {
+            'p'
+        } else {
+            '-'
+        })?;
         Ok(())
     }
 }
@@ -97,26 +131,28 @@ mod tests {
 
     #[test]
     fn check_both() {
-        check(0x0000, "-----", "");
-        check(0x0010, "d----", "D");
-        check(0x0020, "-a---", "A");
-        check(0x0021, "-ar--", "RA");
-        check(0x0023, "-arh-", "RHA");
-        check(0x0024, "-a--s", "SA");
-        check(0x0027, "-arhs", "RHSA");
-        check(0x0122, "-a-h-", "HAT");
-        check(0x0220, "-a---", "AP");
-        check(0x0410, "l----", "DL");
-        check(0x0420, "la---", "AL");
-        check(0x0820, "-a---", "AC");
-        check(0x1010, "d----", "DO");
-        check(0x1224, "-a--s", "SAPO");
-        check(0x1326, "-a-hs", "HSATPO");
-        check(0x2004, "----s", "SI");
-        check(0x2020, "-a---", "AI");
-        check(0x2024, "-a--s", "SAI");
-        check(0x2026, "-a-hs", "HSAI");
-        check(0x2920, "-a---", "ATCI");
-        check(0x200000 - 1, "larhs", "RHSVDAXNTPLCOIEVXPU");
+        check(0x0000, "--------", "");
+        check(0x0010, "d-------", "D");
+        check(0x0020, "-a------", "A");
+        check(0x0021, "-ar-----", "RA");
+        check(0x0023, "-arh----", "RHA");
+        check(0x0024, "-a--s---", "SA");
+        check(0x0027, "-arhs---", "RHSA");
+        check(0x0122, "-a-h----", "HAT");
+        check(0x0220, "-a-----p", "AP");
+        check(0x0410, "l-------", "DL");
+        check(0x0420, "la------", "AL");
+        check(0x0820, "-a---c--", "AC");
+        check(0x1010, "d-------", "DO");
+        check(0x1224, "-a--s--p", "SAPO");
+        check(0x1326, "-a-hs--p", "HSATPO");
+        check(0x2004, "----s---", "SI");
+        check(0x2020, "-a------", "AI");
+        check(0x2024, "-a--s---", "SAI");
+        check(0x2026, "-a-hs---", "HSAI");
+        check(0x2920, "-a---c--", "ATCI");
+        check(0x4020, "-a----e-", "AE");
+        check(0x4820, "-a---ce-", "ACE");
+        check(0x200000 - 1, "larhscep", "RHSVDAXNTPLCOIEVXPU");
     }
 }
@@ -1,22 +1,51 @@
 mod regex_helper;
 
 use crate::{
-    database::{Entry, StatusKind},
-    Result,
+    database::{self, Entry, StatusKind},
+    glob, Result,
 };
 use regex::{Regex, RegexBuilder};
-use serde::Deserialize;
-use std::{borrow::Cow, ops::Range};
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    ops::Range,
+    path::{self, Component, Path, PathBuf, MAIN_SEPARATOR},
+    time::SystemTime,
+};
 
 #[derive(Clone)]
 pub struct Query {
     regex: Regex,
+    is_regex_enabled: bool,
+    is_glob: bool,
+    case_sensitive: bool,
     match_path: bool,
+    match_extension: bool,
     sort_by: StatusKind,
+    tiebreak: StatusKind,
     sort_order: SortOrder,
     sort_dirs_before_files: bool,
+    case_insensitive_sort: bool,
+    invert: bool,
+    require_xattr: bool,
+    ignore_hidden: bool,
+    min_basename_len: Option<usize>,
+    max_basename_len: Option<usize>,
+    size_min: Option<u64>,
+    size_max: Option<u64>,
+    modified_range: TimeRange,
+    created_range: TimeRange,
+    accessed_range: TimeRange,
+    limit: Option<usize>,
+    /// absolute path the pattern denotes, whose directory is put first among hits
+    preferred_dir: Option<PathBuf>,
     is_literal: bool,
     has_path_separator: bool,
+    /// trigrams which basenames matching a non-regex pattern always contain
+    trigrams: Vec<[u8; 3]>,
+    /// case-folded pattern if it is a case-insensitive non-regex one
+    folded_literal: Option<String>,
 }
 
 impl Query {
@@ -25,16 +54,57 @@ impl Query {
         &self.regex
     }
 
+    /// Returns the source of the compiled regex.
+    ///
+    /// Unless regex or glob is enabled, this is the pattern with regex
+    /// metacharacters escaped.
+    #[inline]
+    pub fn pattern(&self) -> &str {
+        self.regex.as_str()
+    }
+
+    /// Returns true if the pattern was interpreted as a regex.
+    #[inline]
+    pub fn is_regex_enabled(&self) -> bool {
+        self.is_regex_enabled
+    }
+
+    /// Returns true if the pattern was interpreted as a glob.
+    #[inline]
+    pub fn is_glob(&self) -> bool {
+        self.is_glob
+    }
+
+    /// Returns true if the pattern is matched case-sensitively,
+    /// which smart case decides from the pattern.
+    #[inline]
+    pub fn case_sensitive(&self) -> bool {
+        self.case_sensitive
+    }
+
     #[inline]
     pub fn match_path(&self) -> bool {
         self.match_path
     }
 
+    /// Returns true if the pattern is matched against whole extensions
+    /// instead of basenames or paths.
+    #[inline]
+    pub fn match_extension(&self) -> bool {
+        self.match_extension
+    }
+
     #[inline]
     pub fn sort_by(&self) -> StatusKind {
         self.sort_by
     }
 
+    /// Returns the status used to order entries which are equal in `sort_by`.
+    #[inline]
+    pub fn tiebreak(&self) -> StatusKind {
+        self.tiebreak
+    }
+
     #[inline]
     pub fn sort_order(&self) -> SortOrder {
         self.sort_order
@@ -45,37 +115,221 @@ impl Query {
         self.sort_dirs_before_files
     }
 
+    /// Returns true if basenames, paths, and extensions are sorted ignoring case.
+    #[inline]
+    pub fn case_insensitive_sort(&self) -> bool {
+        self.case_insensitive_sort
+    }
+
+    /// Returns true if the query selects entries which do not match the pattern.
+    #[inline]
+    pub fn is_inverted(&self) -> bool {
+        self.invert
+    }
+
+    /// Returns true if the query selects only entries having the indexed extended attribute.
+    #[inline]
+    pub fn requires_xattr(&self) -> bool {
+        self.require_xattr
+    }
+
+    /// Returns true if the query excludes hidden entries and entries in hidden directories.
+    #[inline]
+    pub fn ignores_hidden(&self) -> bool {
+        self.ignore_hidden
+    }
+
+    /// Returns the minimum length of basenames of matching entries in bytes.
+    #[inline]
+    pub fn min_basename_len(&self) -> Option<usize> {
+        self.min_basename_len
+    }
+
+    /// Returns the maximum length of basenames of matching entries in bytes.
+    #[inline]
+    pub fn max_basename_len(&self) -> Option<usize> {
+        self.max_basename_len
+    }
+
+    /// Returns the minimum size of matching files in bytes.
+    #[inline]
+    pub fn size_min(&self) -> Option<u64> {
+        self.size_min
+    }
+
+    /// Returns the maximum size of matching files in bytes.
+    #[inline]
+    pub fn size_max(&self) -> Option<u64> {
+        self.size_max
+    }
+
+    /// Returns true if the query limits sizes, or created, modified,
+    /// or accessed times.
+    #[inline]
+    pub(crate) fn limits_statuses(&self) -> bool {
+        self.size_min.is_some()
+            || self.size_max.is_some()
+            || !self.modified_range.is_unbounded()
+            || !self.created_range.is_unbounded()
+            || !self.accessed_range.is_unbounded()
+    }
+
+    /// Returns true if the size and times of the entry lie in the ranges of
+    /// the query.
+    ///
+    /// Entries whose statuses are unavailable are rejected by limited ranges.
+    pub(crate) fn accepts_statuses(&self, entry: &Entry) -> bool {
+        self.accepts_size(entry)
+            && self.modified_range.accepts(|| entry.modified())
+            && self.created_range.accepts(|| entry.created())
+            && self.accessed_range.accepts(|| entry.accessed())
+    }
+
+    fn accepts_size(&self, entry: &Entry) -> bool {
+        if self.size_min.is_none() && self.size_max.is_none() {
+            return true;
+        }
+        if entry.size_is_item_count() {
+            return false;
+        }
+        match entry.size() {
+            Ok(size) => {
+                self.size_min.unwrap_or(0) <= size && size <= self.size_max.unwrap_or(u64::MAX)
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Returns the maximum number of hits.
+    #[inline]
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    /// Returns the path the pattern denotes if the directory at the path is
+    /// put first among hits. See `QueryBuilder::prefer_dirs_matching_prefix`.
+    #[inline]
+    pub fn preferred_dir(&self) -> Option<&Path> {
+        self.preferred_dir.as_deref()
+    }
+
+    /// Returns true if the query limits lengths of basenames.
+    #[inline]
+    pub(crate) fn limits_basename_len(&self) -> bool {
+        self.min_basename_len.is_some() || self.max_basename_len.is_some()
+    }
+
+    /// Returns true if a basename of `len` bytes satisfies the length limits.
+    #[inline]
+    pub(crate) fn accepts_basename_len(&self, len: usize) -> bool {
+        self.min_basename_len.unwrap_or(0) <= len
+            && len <= self.max_basename_len.unwrap_or(usize::MAX)
+    }
+
     #[inline]
     pub fn is_empty(&self) -> bool {
         self.regex.as_str().is_empty()
     }
 
+    /// Returns true if entries matching the query are a subset of those
+    /// matching `previous` and are sorted in the same order, so that its hits
+    /// can be found by filtering hits of `previous` with
+    /// `Database::filter_ids` instead of searching the whole database.
+    ///
+    /// This holds when a non-regex pattern extends that of `previous`
+    /// with the other options unchanged.
+    pub fn narrows(&self, previous: &Query) -> bool {
+        let is_substring_search = |query: &Query| {
+            !query.is_regex_enabled && !query.is_glob && !query.invert && !query.is_empty()
+        };
+
+        is_substring_search(self)
+            && is_substring_search(previous)
+            && self.pattern().starts_with(previous.pattern())
+            // a case-insensitive pattern can match more than a case-sensitive one
+            && (self.case_sensitive || !previous.case_sensitive)
+            && self.match_path == previous.match_path
+            && self.match_extension == previous.match_extension
+            && self.sort_by == previous.sort_by
+            && self.tiebreak == previous.tiebreak
+            && self.sort_order == previous.sort_order
+            && self.sort_dirs_before_files == previous.sort_dirs_before_files
+            && self.case_insensitive_sort == previous.case_insensitive_sort
+            && self.require_xattr == previous.require_xattr
+            && self.ignore_hidden == previous.ignore_hidden
+            && self.min_basename_len == previous.min_basename_len
+            && self.max_basename_len == previous.max_basename_len
+            && self.size_min == previous.size_min
+            && self.size_max == previous.size_max
+            && self.modified_range == previous.modified_range
+            && self.created_range == previous.created_range
+            && self.accessed_range == previous.accessed_range
+            // hits of a limited query may lack some of the matching entries
+            && self.limit.is_none()
+            && previous.limit.is_none()
+            // the preferred directory changes along with the pattern
+            && self.preferred_dir.is_none()
+            && previous.preferred_dir.is_none()
+    }
+
     #[inline]
     pub fn is_match(&self, entry: &Entry) -> bool {
-        if self.match_path {
+        if self.is_empty() {
+            // empty query selects everything even when inverted
+            return true;
+        }
+
+        let is_match = if self.match_extension {
+            matches!(entry.extension(), Some(extension) if self.regex.is_match(extension))
+        } else if self.match_path {
             self.regex.is_match(entry.path().as_str())
         } else {
             self.regex.is_match(entry.basename())
+        };
+        is_match != self.invert
+    }
+
+    /// Compares entries in the order in which hits of the query are sorted.
+    ///
+    /// The entries may come from different databases, which allows merging
+    /// hits of searches in multiple databases.
+    pub fn compare(&self, a: &Entry, b: &Entry) -> Ordering {
+        let compare_func =
+            database::get_compare_func(self.sort_by, self.tiebreak, self.case_insensitive_sort);
+        let ordering = if self.sort_dirs_before_files {
+            Ord::cmp(&b.is_dir(), &a.is_dir()).then_with(|| compare_func(a, b))
+        } else {
+            compare_func(a, b)
+        };
+        match self.sort_order {
+            SortOrder::Ascending => ordering,
+            SortOrder::Descending => ordering.reverse(),
         }
     }
 
     pub fn basename_matches(&self, entry: &Entry) -> Vec<Range<usize>> {
-        if self.is_empty() {
+        if self.is_empty() || self.invert {
             return Vec::new();
         }
 
         let basename = entry.basename();
 
-        if self.match_path {
+        if self.match_extension {
+            let offset = basename.len() - entry.extension().unwrap_or("").len();
+            self.extension_matches(entry, offset)
+        } else if self.match_path {
             let path = entry.path();
             let path_str = path.as_str();
+            let basename_start = path_str.len() - basename.len();
 
+            // matches crossing the last separator are highlighted
+            // from the start of the basename
             self.regex
                 .find_iter(path_str)
-                .filter(|m| path_str.len() - m.end() < basename.len())
+                .filter(|m| m.end() > basename_start)
                 .map(|m| Range {
-                    start: basename.len().saturating_sub(path_str.len() - m.start()),
-                    end: basename.len() - (path_str.len() - m.end()),
+                    start: m.start().max(basename_start) - basename_start,
+                    end: m.end() - basename_start,
                 })
                 .collect()
         } else {
@@ -84,28 +338,51 @@ impl Query {
     }
 
     pub fn path_matches(&self, entry: &Entry) -> Vec<Range<usize>> {
-        if self.is_empty() {
+        if self.is_empty() || self.invert {
             return Vec::new();
         }
 
         let path = entry.path();
         let path_str = path.as_str();
 
-        if self.match_path {
+        if self.match_extension {
+            let offset = path_str.len() - entry.extension().unwrap_or("").len();
+            self.extension_matches(entry, offset)
+        } else if self.match_path {
             self.regex.find_iter(path_str).map(|m| m.range()).collect()
         } else {
-            let basename = entry.basename();
+            let basename_start = path_str.len() - entry.basename().len();
 
             self.regex
-                .find_iter(basename)
+                .find_iter(entry.basename())
                 .map(|m| Range {
-                    start: path_str.len() - basename.len() + m.start(),
-                    end: path_str.len() - basename.len() + m.end(),
+                    start: basename_start + m.start(),
+                    end: basename_start + m.end(),
                 })
                 .collect()
         }
     }
 
+    /// Returns ranges of the path components containing matches in the path.
+    ///
+    /// Components of a match spanning path separators are covered by
+    /// a single range.
+    pub fn path_match_components(&self, entry: &Entry) -> Vec<Range<usize>> {
+        enclosing_components(entry.path().as_str(), &self.path_matches(entry))
+    }
+
+    /// Returns ranges of matches in the extension, shifted by `offset`.
+    fn extension_matches(&self, entry: &Entry, offset: usize) -> Vec<Range<usize>> {
+        match entry.extension() {
+            Some(extension) => self
+                .regex
+                .find_iter(extension)
+                .map(|m| offset + m.start()..offset + m.end())
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
     #[inline]
     pub(crate) fn is_literal(&self) -> bool {
         self.is_literal
@@ -115,9 +392,19 @@ impl Query {
     pub(crate) fn has_path_separator(&self) -> bool {
         self.has_path_separator
     }
+
+    #[inline]
+    pub(crate) fn trigrams(&self) -> &[[u8; 3]] {
+        &self.trigrams
+    }
+
+    #[inline]
+    pub(crate) fn folded_literal(&self) -> Option<&str> {
+        self.folded_literal.as_deref()
+    }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum MatchPathMode {
     #[serde(alias = "yes")]
@@ -127,14 +414,15 @@ pub enum MatchPathMode {
     Auto,
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum CaseSensitivity {
     Sensitive,
     Insensitive,
     Smart,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum SortOrder {
     #[serde(alias = "asc")]
@@ -146,11 +434,27 @@ pub enum SortOrder {
 pub struct QueryBuilder<'a> {
     pattern: Cow<'a, str>,
     match_path_mode: MatchPathMode,
+    match_extension: bool,
     case_sensitivity: CaseSensitivity,
     is_regex_enabled: bool,
+    is_glob: bool,
     sort_by: StatusKind,
+    tiebreak: StatusKind,
     sort_order: SortOrder,
     sort_dirs_before_files: bool,
+    case_insensitive_sort: bool,
+    invert: bool,
+    require_xattr: bool,
+    ignore_hidden: bool,
+    min_basename_len: Option<usize>,
+    max_basename_len: Option<usize>,
+    size_min: Option<u64>,
+    size_max: Option<u64>,
+    modified_range: TimeRange,
+    created_range: TimeRange,
+    accessed_range: TimeRange,
+    limit: Option<usize>,
+    prefer_dirs_matching_prefix: bool,
 }
 
 impl<'a> QueryBuilder<'a> {
@@ -161,11 +465,27 @@ impl<'a> QueryBuilder<'a> {
         Self {
             pattern: pattern.into(),
             match_path_mode: MatchPathMode::Never,
+            match_extension: false,
             case_sensitivity: CaseSensitivity::Smart,
             is_regex_enabled: false,
+            is_glob: false,
             sort_by: StatusKind::Basename,
+            tiebreak: StatusKind::Basename,
             sort_order: SortOrder::Ascending,
             sort_dirs_before_files: false,
+            case_insensitive_sort: false,
+            invert: false,
+            require_xattr: false,
+            ignore_hidden: false,
+            min_basename_len: None,
+            max_basename_len: None,
+            size_min: None,
+            size_max: None,
+            modified_range: TimeRange::default(),
+            created_range: TimeRange::default(),
+            accessed_range: TimeRange::default(),
+            limit: None,
+            prefer_dirs_matching_prefix: false,
         }
     }
 
@@ -174,6 +494,15 @@ impl<'a> QueryBuilder<'a> {
         self
     }
 
+    /// Matches the pattern against extensions of entries instead of their
+    /// basenames or paths. Entries without an extension never match.
+    ///
+    /// This takes precedence over `match_path_mode`.
+    pub fn match_extension(&mut self, yes: bool) -> &mut Self {
+        self.match_extension = yes;
+        self
+    }
+
     pub fn case_sensitivity(&mut self, case_sensitivity: CaseSensitivity) -> &mut Self {
         self.case_sensitivity = case_sensitivity;
         self
@@ -184,11 +513,31 @@ impl<'a> QueryBuilder<'a> {
         self
     }
 
+    /// Interprets the pattern as a shell-style glob such as `*.rs` or
+    /// `src/**/main.rs`, which takes precedence over `regex`.
+    ///
+    /// A glob has to match a whole basename, or whole trailing components
+    /// of a path when matching paths. `*` and `?` do not match path
+    /// separators, and `**` as a whole component matches any number of
+    /// components.
+    pub fn glob(&mut self, yes: bool) -> &mut Self {
+        self.is_glob = yes;
+        self
+    }
+
     pub fn sort_by(&mut self, kind: StatusKind) -> &mut Self {
         self.sort_by = kind;
         self
     }
 
+    /// Sets the status to order entries which are equal in `sort_by`.
+    ///
+    /// Defaults to basename. Entries which are still equal are ordered by path.
+    pub fn tiebreak(&mut self, kind: StatusKind) -> &mut Self {
+        self.tiebreak = kind;
+        self
+    }
+
     pub fn sort_order(&mut self, order: SortOrder) -> &mut Self {
         self.sort_order = order;
         self
@@ -199,11 +548,148 @@ impl<'a> QueryBuilder<'a> {
         self
     }
 
+    /// Sorts by basenames, paths, and extensions ignoring case, so that
+    /// `apple` comes before `Zebra`.
+    ///
+    /// Fast-sorted ids cannot be used for such queries, so sorting takes longer.
+    pub fn case_insensitive_sort(&mut self, yes: bool) -> &mut Self {
+        self.case_insensitive_sort = yes;
+        self
+    }
+
+    /// Selects entries which do not match the pattern, like `grep -v`.
+    ///
+    /// An empty pattern still selects all entries.
+    pub fn invert(&mut self, yes: bool) -> &mut Self {
+        self.invert = yes;
+        self
+    }
+
+    /// Selects only entries having the extended attribute indexed with
+    /// `DatabaseBuilder::index_xattr`.
+    ///
+    /// Searching fails if no extended attribute is indexed.
+    pub fn require_xattr(&mut self, yes: bool) -> &mut Self {
+        self.require_xattr = yes;
+        self
+    }
+
+    /// Excludes hidden entries and entries in hidden directories from results.
+    ///
+    /// This is independent of `DatabaseBuilder::ignore_hidden`, so hidden
+    /// entries can be indexed and still be hidden in some searches.
+    pub fn ignore_hidden(&mut self, yes: bool) -> &mut Self {
+        self.ignore_hidden = yes;
+        self
+    }
+
+    /// Selects only entries whose basenames are at least `len` bytes long.
+    ///
+    /// This is useful for cutting down noise from short patterns
+    /// matching almost everything.
+    pub fn min_basename_len(&mut self, len: usize) -> &mut Self {
+        self.min_basename_len = Some(len);
+        self
+    }
+
+    /// Selects only entries whose basenames are at most `len` bytes long.
+    pub fn max_basename_len(&mut self, len: usize) -> &mut Self {
+        self.max_basename_len = Some(len);
+        self
+    }
+
+    /// Selects only files which are at least `bytes` large.
+    ///
+    /// Directories are excluded by size bounds unless the database has
+    /// recursive directory sizes.
+    pub fn size_min(&mut self, bytes: u64) -> &mut Self {
+        self.size_min = Some(bytes);
+        self
+    }
+
+    /// Selects only files which are at most `bytes` large.
+    ///
+    /// Directories are excluded by size bounds unless the database has
+    /// recursive directory sizes.
+    pub fn size_max(&mut self, bytes: u64) -> &mut Self {
+        self.size_max = Some(bytes);
+        self
+    }
+
+    /// Selects only entries modified at or after `time`.
+    pub fn modified_after(&mut self, time: SystemTime) -> &mut Self {
+        self.modified_range.after = Some(time);
+        self
+    }
+
+    /// Selects only entries modified before `time`.
+    pub fn modified_before(&mut self, time: SystemTime) -> &mut Self {
+        self.modified_range.before = Some(time);
+        self
+    }
+
+    /// Selects only entries created at or after `time`.
+    pub fn created_after(&mut self, time: SystemTime) -> &mut Self {
+        self.created_range.after = Some(time);
+        self
+    }
+
+    /// Selects only entries created before `time`.
+    pub fn created_before(&mut self, time: SystemTime) -> &mut Self {
+        self.created_range.before = Some(time);
+        self
+    }
+
+    /// Selects only entries accessed at or after `time`.
+    pub fn accessed_after(&mut self, time: SystemTime) -> &mut Self {
+        self.accessed_range.after = Some(time);
+        self
+    }
+
+    /// Selects only entries accessed before `time`.
+    pub fn accessed_before(&mut self, time: SystemTime) -> &mut Self {
+        self.accessed_range.before = Some(time);
+        self
+    }
+
+    /// Returns only the first `limit` hits in the order of the query.
+    ///
+    /// With an empty pattern, the hits are taken directly from fast-sorted
+    /// ids when possible, so that browsing a huge database stays cheap.
+    pub fn limit(&mut self, limit: usize) -> &mut Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Interprets the pattern also as a path, and puts the directory at the
+    /// path first among hits if the directory is indexed.
+    ///
+    /// The directory is a hit even if the pattern does not match it (e.g.
+    /// with a trailing separator), as long as the other conditions of the
+    /// query hold. Inverted queries do not add the directory.
+    ///
+    /// Relative paths are resolved against the current directory when the
+    /// query is built. `.` and `..` are resolved without following symlinks.
+    pub fn prefer_dirs_matching_prefix(&mut self, yes: bool) -> &mut Self {
+        self.prefer_dirs_matching_prefix = yes;
+        self
+    }
+
     pub fn build(&self) -> Result<Query> {
-        let escaped_pattern = if self.is_regex_enabled {
+        // Paths are stored with \ on Windows, where / is also a separator.
+        let is_regex_enabled = self.is_regex_enabled && !self.is_glob;
+        let pattern = if cfg!(windows) && !is_regex_enabled {
+            self.pattern.replace('/', "\\").into()
+        } else {
             self.pattern.clone()
+        };
+
+        let escaped_pattern = if self.is_glob {
+            glob::to_regex(&pattern, MAIN_SEPARATOR).into()
+        } else if is_regex_enabled {
+            pattern.clone()
         } else {
-            regex::escape(&self.pattern).into()
+            regex::escape(&pattern).into()
         };
 
         let mut parser = regex_syntax::ParserBuilder::new()
@@ -214,25 +700,103 @@ impl<'a> QueryBuilder<'a> {
         let has_uppercase_char = regex_helper::hir_has_uppercase_char(&hir);
         let case_sensitive = should_be_case_sensitive(self.case_sensitivity, has_uppercase_char);
 
+        // the translated glob excludes the separator with negated classes,
+        // which contain / on Windows, so the glob itself is checked instead
+        let has_path_separator = if self.is_glob {
+            pattern.contains(MAIN_SEPARATOR)
+        } else {
+            regex_helper::hir_has_path_separator(&hir)
+        };
+        let match_path =
+            !self.match_extension && should_match_path(self.match_path_mode, has_path_separator);
+
+        // globs match whole basenames or whole trailing components of paths
+        let escaped_pattern = match escaped_pattern {
+            glob if self.is_glob && !glob.is_empty() => {
+                if match_path {
+                    let separator = regex::escape(MAIN_SEPARATOR.encode_utf8(&mut [0; 4]));
+                    format!("(?:^|{})(?:{})$", separator, glob).into()
+                } else {
+                    format!("^(?:{})$", glob).into()
+                }
+            }
+            escaped_pattern => escaped_pattern,
+        };
+
         let regex = RegexBuilder::new(&escaped_pattern)
             .case_insensitive(!case_sensitive)
             .build()?;
 
-        let has_path_separator = regex_helper::hir_has_path_separator(&hir);
-        let match_path = should_match_path(self.match_path_mode, has_path_separator);
-
         Ok(Query {
             regex,
+            is_regex_enabled,
+            is_glob: self.is_glob,
+            case_sensitive,
             match_path,
+            match_extension: self.match_extension,
             sort_by: self.sort_by,
+            tiebreak: self.tiebreak,
             sort_order: self.sort_order,
             sort_dirs_before_files: self.sort_dirs_before_files,
-            is_literal: hir.is_literal(),
+            case_insensitive_sort: self.case_insensitive_sort,
+            invert: self.invert,
+            require_xattr: self.require_xattr,
+            ignore_hidden: self.ignore_hidden,
+            min_basename_len: self.min_basename_len,
+            max_basename_len: self.max_basename_len,
+            size_min: self.size_min,
+            size_max: self.size_max,
+            modified_range: self.modified_range,
+            created_range: self.created_range,
+            accessed_range: self.accessed_range,
+            limit: self.limit,
+            preferred_dir: if self.prefer_dirs_matching_prefix && !self.pattern.is_empty() {
+                absolute_path(Path::new(self.pattern.as_ref()))
+            } else {
+                None
+            },
+            is_literal: hir.is_literal() && !self.is_glob,
             has_path_separator,
+            trigrams: if is_regex_enabled || self.is_glob {
+                Vec::new()
+            } else {
+                database::query_trigrams(&pattern, case_sensitive)
+            },
+            folded_literal: (!is_regex_enabled && !self.is_glob && !case_sensitive)
+                .then(|| database::fold_case(&pattern)),
         })
     }
 }
 
+/// Range of times with an inclusive lower bound and an exclusive upper bound.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct TimeRange {
+    after: Option<SystemTime>,
+    before: Option<SystemTime>,
+}
+
+impl TimeRange {
+    fn is_unbounded(&self) -> bool {
+        self.after.is_none() && self.before.is_none()
+    }
+
+    fn accepts<F>(&self, time: F) -> bool
+    where
+        F: FnOnce() -> Result<SystemTime>,
+    {
+        if self.is_unbounded() {
+            return true;
+        }
+        match time() {
+            Ok(time) => {
+                self.after.iter().all(|after| time >= *after)
+                    && self.before.iter().all(|before| time < *before)
+            }
+            Err(_) => false,
+        }
+    }
+}
+
 fn should_match_path(match_path_mode: MatchPathMode, has_path_separator: bool) -> bool {
     match match_path_mode {
         MatchPathMode::Always => true,
@@ -249,6 +813,46 @@ fn should_be_case_sensitive(case_sensitivity: CaseSensitivity, has_uppercase_cha
     }
 }
 
+/// Resolves `path` against the current directory, removing `.` and `..`.
+fn absolute_path(path: &Path) -> Option<PathBuf> {
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir().ok()?.join(path)
+    };
+
+    let mut resolved = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            component => resolved.push(component),
+        }
+    }
+    Some(resolved)
+}
+
+/// Expands sorted `ranges` in `path` to the components enclosing them,
+/// merging the ones sharing a component.
+fn enclosing_components(path: &str, ranges: &[Range<usize>]) -> Vec<Range<usize>> {
+    let mut components: Vec<Range<usize>> = Vec::new();
+    for range in ranges {
+        let start = path[..range.start]
+            .rfind(path::is_separator)
+            .map_or(0, |i| i + 1);
+        let end = path[range.end..]
+            .find(path::is_separator)
+            .map_or(path.len(), |i| range.end + i);
+        match components.last_mut() {
+            Some(last) if start < last.end => last.end = last.end.max(end),
+            _ => components.push(start..end),
+        }
+    }
+    components
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -269,6 +873,45 @@ mod tests {
         parser.parse(&escaped_pattern).unwrap()
     }
 
+    #[test]
+    fn path_separators() {
+        let match_path = |pattern| {
+            let hir = parse_pattern(pattern, false);
+            let has_path_separator = regex_helper::hir_has_path_separator(&hir);
+            should_match_path(MatchPathMode::Auto, has_path_separator)
+        };
+
+        assert!(match_path("foo/bar"));
+        assert!(!match_path("foo"));
+        if cfg!(windows) {
+            assert!(match_path(r"foo\bar"));
+        } else {
+            assert!(!match_path(r"foo\bar"));
+        }
+
+        let hir = parse_pattern("[.-0]", true);
+        assert!(regex_helper::hir_has_path_separator(&hir));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn glob_path_separators() {
+        let match_path = |pattern| {
+            QueryBuilder::new(pattern)
+                .glob(true)
+                .build()
+                .unwrap()
+                .match_path()
+        };
+
+        for pattern in ["*.rs", "file?.txt", "[!a]*"] {
+            assert!(!match_path(pattern), "{}", pattern);
+        }
+        for pattern in [r"src\*.rs", "src/*.rs", "src/**/main.rs"] {
+            assert!(match_path(pattern), "{}", pattern);
+        }
+    }
+
     #[test]
     fn match_path() {
         use std::path::MAIN_SEPARATOR;
@@ -361,6 +1004,35 @@ mod tests {
         assert!(!is_case_sensitive(CaseSensitivity::Smart, true, "[a-z]x"));
     }
 
+    #[test]
+    fn accessors() {
+        let query = QueryBuilder::new("a.b").build().unwrap();
+        assert_eq!(query.pattern(), r"a\.b");
+        assert!(!query.is_regex_enabled());
+        assert!(!query.case_sensitive());
+
+        let query = QueryBuilder::new("A.b").regex(true).build().unwrap();
+        assert_eq!(query.pattern(), "A.b");
+        assert!(query.is_regex_enabled());
+        assert!(query.case_sensitive());
+
+        let query = QueryBuilder::new("A.b")
+            .case_sensitivity(CaseSensitivity::Insensitive)
+            .build()
+            .unwrap();
+        assert!(!query.case_sensitive());
+
+        let query = QueryBuilder::new("a")
+            .case_sensitivity(CaseSensitivity::Sensitive)
+            .build()
+            .unwrap();
+        assert!(query.case_sensitive());
+
+        let query = QueryBuilder::new("").build().unwrap();
+        assert_eq!(query.pattern(), "");
+        assert!(query.is_empty());
+    }
+
     #[test]
     fn literal() {
         fn is_literal(is_regex_enabled: bool, pattern: &str) -> bool {
@@ -417,7 +1089,7 @@ mod tests {
 
         let search = |query| {
             database
-                .search(query)
+                .search_all(query)
                 .unwrap()
                 .into_iter()
                 .map(|id| database.entry(id))
@@ -480,4 +1152,246 @@ mod tests {
             vec![prefix_len..prefix_len + 4, prefix_len + 13..prefix_len + 16]
         );
     }
+
+    #[test]
+    fn matches_crossing_separators() {
+        let tmpdir = create_dir_structure(&[Path::new("home/r")]);
+        let root = dunce::canonicalize(tmpdir.path()).unwrap();
+        let path = root.join("home").join("r").join("main.rs");
+        fs::write(&path, "").unwrap();
+        let database = DatabaseBuilder::new().add_dir(&root).build().unwrap();
+        let entry = database.entry(database.get_by_path(&path).unwrap());
+        let prefix_len = root.to_str().unwrap().len();
+
+        let to_tuples = |ranges: Vec<Range<usize>>| {
+            ranges
+                .into_iter()
+                .map(|range| (range.start, range.end))
+                .collect::<Vec<_>>()
+        };
+        let matches = |pattern: &str, regex| {
+            let query = QueryBuilder::new(pattern)
+                .match_path_mode(MatchPathMode::Always)
+                .regex(regex)
+                .build()
+                .unwrap();
+            // matches in the root path are not of interest
+            let path_matches = query
+                .path_matches(&entry)
+                .into_iter()
+                .filter(|range| prefix_len <= range.start)
+                .map(|range| range.start - prefix_len..range.end - prefix_len)
+                .collect();
+            (
+                to_tuples(query.basename_matches(&entry)),
+                to_tuples(path_matches),
+            )
+        };
+
+        // relative to the root, the path is "/home/r/main.rs",
+        // where the basename starts at 8
+        let sep = std::path::MAIN_SEPARATOR;
+        assert_eq!(
+            matches(&format!("r{}main", sep), false),
+            (vec![(0, 4)], vec![(6, 12)])
+        );
+        assert_eq!(
+            matches(&format!("home{0}r{0}m", sep), false),
+            (vec![(0, 1)], vec![(1, 9)])
+        );
+        assert_eq!(
+            matches(&format!("{}main.rs", sep), false),
+            (vec![(0, 7)], vec![(7, 15)])
+        );
+        // matches ending at the last separator are not in the basename
+        assert_eq!(matches(&format!("r{}", sep), false), (vec![], vec![(6, 8)]));
+        assert_eq!(
+            matches(&format!("e{}r|rs", regex::escape(&sep.to_string())), true),
+            (vec![(5, 7)], vec![(4, 7), (13, 15)])
+        );
+    }
+
+    #[test]
+    fn components() {
+        let components = |path, ranges: &[(usize, usize)]| {
+            let ranges = ranges
+                .iter()
+                .map(|&(start, end)| start..end)
+                .collect::<Vec<_>>();
+            enclosing_components(path, &ranges)
+                .into_iter()
+                .map(|range| (range.start, range.end))
+                .collect::<Vec<_>>()
+        };
+
+        assert!(components("/foo/bar", &[]).is_empty());
+        assert_eq!(components("/foo/bar", &[(2, 3)]), [(1, 4)]);
+        assert_eq!(components("/foo/bar", &[(1, 4)]), [(1, 4)]);
+        assert_eq!(components("/foo/bar", &[(5, 6), (7, 8)]), [(5, 8)]);
+        assert_eq!(
+            components("/foo/bar/baz", &[(1, 2), (9, 10)]),
+            [(1, 4), (9, 12)]
+        );
+        // a match spanning a separator covers both components
+        assert_eq!(components("/foo/bar/baz", &[(3, 6)]), [(1, 8)]);
+        assert_eq!(components("/foo/bar", &[(4, 5)]), [(1, 8)]);
+        assert_eq!(components("foo", &[(0, 1)]), [(0, 3)]);
+        assert_eq!(components("/foo/", &[(2, 2)]), [(1, 4)]);
+
+        let tmpdir = tempfile::tempdir().unwrap();
+        fs::create_dir(tmpdir.path().join("abc")).unwrap();
+        fs::write(tmpdir.path().join("abc").join("xbz"), "").unwrap();
+        let database = DatabaseBuilder::new()
+            .add_dir(tmpdir.path())
+            .build()
+            .unwrap();
+        let query = QueryBuilder::new("b")
+            .match_path_mode(MatchPathMode::Always)
+            .build()
+            .unwrap();
+        let entry = database
+            .search_all(&query)
+            .unwrap()
+            .into_iter()
+            .map(|id| database.entry(id))
+            .find(|entry| entry.basename() == "xbz")
+            .unwrap();
+        let path_len = entry.path().as_str().len();
+        // the root path may also contain matches
+        let components = query.path_match_components(&entry);
+        assert_eq!(
+            components[components.len() - 2..],
+            [path_len - 7..path_len - 4, path_len - 3..path_len]
+        );
+    }
+
+    #[test]
+    fn invert() {
+        let tmpdir = create_dir_structure(&[
+            Path::new("aaa/foobarbaz/barbaz"),
+            Path::new("0042bar/a/foo123bar"),
+        ]);
+        fs::write(tmpdir.path().join("aaa").join("qux"), "").unwrap();
+
+        let database = DatabaseBuilder::new()
+            .add_dir(tmpdir.path())
+            .index(StatusKind::Size)
+            .build()
+            .unwrap();
+        let all = database
+            .search_all(&QueryBuilder::new("").build().unwrap())
+            .unwrap();
+
+        for (pattern, match_path_mode, sort_by) in [
+            ("bar", MatchPathMode::Never, StatusKind::Basename),
+            ("bar", MatchPathMode::Never, StatusKind::Size),
+            ("ba", MatchPathMode::Always, StatusKind::Basename),
+            ("aaa/", MatchPathMode::Auto, StatusKind::Path),
+            ("^a", MatchPathMode::Never, StatusKind::Basename),
+        ] {
+            let mut builder = QueryBuilder::new(pattern);
+            builder
+                .match_path_mode(match_path_mode)
+                .regex(pattern.starts_with('^'))
+                .sort_by(sort_by);
+            let query = builder.build().unwrap();
+            let inverted_query = builder.invert(true).build().unwrap();
+            assert!(inverted_query.is_inverted());
+
+            let hits = database.search_all(&query).unwrap();
+            let inverted = database.search_all(&inverted_query).unwrap();
+            assert!(!inverted.is_empty());
+
+            let mut expected = all
+                .iter()
+                .filter(|id| !hits.contains(id))
+                .copied()
+                .collect::<Vec<_>>();
+            let sorted_by_query = database
+                .search_all(&QueryBuilder::new("").sort_by(sort_by).build().unwrap())
+                .unwrap();
+            expected.sort_by_key(|id| sorted_by_query.iter().position(|x| x == id));
+            assert_eq!(inverted, expected);
+
+            for id in inverted {
+                let entry = database.entry(id);
+                assert!(inverted_query.is_match(&entry));
+                assert!(!query.is_match(&entry));
+                assert!(inverted_query.basename_matches(&entry).is_empty());
+                assert!(inverted_query.path_matches(&entry).is_empty());
+            }
+        }
+
+        let query = QueryBuilder::new("").invert(true).build().unwrap();
+        assert_eq!(database.search_all(&query).unwrap(), all);
+    }
+
+    #[test]
+    fn narrows() {
+        let narrows = |query: &mut QueryBuilder, previous: &mut QueryBuilder| {
+            query.build().unwrap().narrows(&previous.build().unwrap())
+        };
+
+        assert!(narrows(
+            &mut QueryBuilder::new("rep"),
+            &mut QueryBuilder::new("re")
+        ));
+        assert!(narrows(
+            &mut QueryBuilder::new("re"),
+            &mut QueryBuilder::new("re")
+        ));
+        assert!(narrows(
+            &mut QueryBuilder::new("a.b"),
+            &mut QueryBuilder::new("a.")
+        ));
+        let dir = std::env::temp_dir().join("re");
+        let dir = dir.to_str().unwrap();
+        assert!(!narrows(
+            QueryBuilder::new(format!("{}p", dir)).prefer_dirs_matching_prefix(true),
+            QueryBuilder::new(dir).prefer_dirs_matching_prefix(true)
+        ));
+        assert!(!narrows(
+            &mut QueryBuilder::new("re"),
+            &mut QueryBuilder::new("rep")
+        ));
+        assert!(!narrows(
+            &mut QueryBuilder::new("are"),
+            &mut QueryBuilder::new("re")
+        ));
+        assert!(!narrows(
+            &mut QueryBuilder::new("re"),
+            &mut QueryBuilder::new("")
+        ));
+
+        // smart case turns case-sensitive, which matches fewer entries
+        assert!(narrows(
+            &mut QueryBuilder::new("reP"),
+            &mut QueryBuilder::new("re")
+        ));
+        assert!(!narrows(
+            &mut QueryBuilder::new("rep"),
+            QueryBuilder::new("re").case_sensitivity(CaseSensitivity::Sensitive)
+        ));
+
+        assert!(!narrows(
+            QueryBuilder::new("rep").regex(true),
+            QueryBuilder::new("re").regex(true)
+        ));
+        assert!(!narrows(
+            QueryBuilder::new("rep").glob(true),
+            QueryBuilder::new("re").glob(true)
+        ));
+        assert!(!narrows(
+            QueryBuilder::new("rep").invert(true),
+            QueryBuilder::new("re").invert(true)
+        ));
+        assert!(!narrows(
+            QueryBuilder::new("re/").match_path_mode(MatchPathMode::Auto),
+            QueryBuilder::new("re").match_path_mode(MatchPathMode::Auto)
+        ));
+        assert!(!narrows(
+            QueryBuilder::new("rep").sort_order(SortOrder::Descending),
+            &mut QueryBuilder::new("re")
+        ));
+    }
 }
@@ -2,18 +2,26 @@
 
 use regex_syntax::hir::{Class, Group, Hir, HirKind, Literal, Repetition};
 
+/// Characters accepted as path separators. Windows accepts `/` as well as `\`.
+const PATH_SEPARATORS: &[char] = if cfg!(windows) {
+    &[std::path::MAIN_SEPARATOR, '/']
+} else {
+    &[std::path::MAIN_SEPARATOR]
+};
+
 pub fn hir_has_path_separator(hir: &Hir) -> bool {
-    use std::path::MAIN_SEPARATOR;
+    let in_range =
+        |start: char, end: char| PATH_SEPARATORS.iter().any(|c| (start..=end).contains(c));
 
     match hir.kind() {
-        HirKind::Literal(Literal::Unicode(c)) => *c == MAIN_SEPARATOR,
-        HirKind::Literal(Literal::Byte(b)) => char::from(*b) == MAIN_SEPARATOR,
-        HirKind::Class(Class::Unicode(ranges)) => ranges
+        HirKind::Literal(Literal::Unicode(c)) => PATH_SEPARATORS.contains(c),
+        HirKind::Literal(Literal::Byte(b)) => PATH_SEPARATORS.contains(&char::from(*b)),
+        HirKind::Class(Class::Unicode(ranges)) => {
+            ranges.iter().any(|r| in_range(r.start(), r.end()))
+        }
+        HirKind::Class(Class::Bytes(ranges)) => ranges
             .iter()
-            .any(|r| r.start() <= MAIN_SEPARATOR && MAIN_SEPARATOR <= r.end()),
-        HirKind::Class(Class::Bytes(ranges)) => ranges.iter().any(|r| {
-            char::from(r.start()) <= MAIN_SEPARATOR && MAIN_SEPARATOR <= char::from(r.end())
-        }),
+            .any(|r| in_range(char::from(r.start()), char::from(r.end()))),
         HirKind::Group(Group { hir, .. }) | HirKind::Repetition(Repetition { hir, .. }) => {
             hir_has_path_separator(hir)
         }